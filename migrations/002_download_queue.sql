@@ -0,0 +1,43 @@
+-- Migration: Persistent Download Queue
+-- Stores download requests so pending and in-flight downloads survive restarts
+-- and a queryable download history is kept per user
+
+-- ============================================================================
+-- DOWNLOAD TABLES
+-- ============================================================================
+
+-- Download requests and their latest known state
+CREATE TABLE download_requests (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    artist_name TEXT NOT NULL,
+    track_title TEXT NOT NULL,
+    album_title TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    source_url TEXT,
+    magnet_url TEXT,
+    torrent_hash TEXT, -- Info hash once known to the torrent client
+    download_path TEXT,
+    file_size INTEGER,
+    progress REAL,
+    download_speed INTEGER,
+    upload_speed INTEGER,
+    seeds INTEGER,
+    peers INTEGER,
+    error_message TEXT,
+    requested_at INTEGER NOT NULL,
+    started_at INTEGER,
+    completed_at INTEGER,
+    processed_at INTEGER,
+    track_id TEXT, -- Library track created by the import
+    updated_at INTEGER DEFAULT (strftime('%s', 'now'))
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_download_requests_user_id ON download_requests(user_id);
+CREATE INDEX idx_download_requests_status ON download_requests(status);
+CREATE INDEX idx_download_requests_torrent_hash ON download_requests(torrent_hash);
+CREATE INDEX idx_download_requests_requested_at ON download_requests(requested_at);
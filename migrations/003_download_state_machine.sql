@@ -0,0 +1,37 @@
+-- Migration: Download State Machine
+-- Normalizes free-form download statuses to the typed lifecycle states and
+-- records every state transition with a timestamp and reason
+
+-- ============================================================================
+-- STATUS NORMALIZATION
+-- ============================================================================
+
+-- Torrent client states were previously copied into the request status
+UPDATE download_requests
+SET status = 'downloading'
+WHERE status IN ('stopped', 'check_pending', 'checking', 'download_pending',
+                 'seed_pending', 'seeding', 'unknown');
+
+UPDATE download_requests SET status = 'failed' WHERE status = 'error';
+
+-- Processed downloads were marked 'completed'; they are now 'imported'
+UPDATE download_requests
+SET status = 'imported'
+WHERE status = 'completed' AND processed_at IS NOT NULL;
+
+-- ============================================================================
+-- TRANSITION LOG
+-- ============================================================================
+
+CREATE TABLE download_transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id TEXT NOT NULL,
+    from_status TEXT NOT NULL,
+    to_status TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    transitioned_at INTEGER NOT NULL,
+    FOREIGN KEY (request_id) REFERENCES download_requests (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_download_transitions_request_id ON download_transitions(request_id);
+CREATE INDEX idx_download_transitions_transitioned_at ON download_transitions(transitioned_at);
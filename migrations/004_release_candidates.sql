@@ -0,0 +1,33 @@
+-- Migration: Release Candidates
+-- Records every indexer release considered for a download request, the score
+-- it got from the quality profile and why it was or was not selected
+
+-- ============================================================================
+-- CANDIDATE TABLES
+-- ============================================================================
+
+CREATE TABLE download_candidates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id TEXT NOT NULL,
+    indexer TEXT NOT NULL,
+    title TEXT NOT NULL,
+    guid TEXT,
+    info_hash TEXT,
+    magnet_url TEXT,
+    size INTEGER,
+    seeders INTEGER,
+    format TEXT,
+    bitrate INTEGER,
+    source TEXT NOT NULL,
+    score REAL NOT NULL,
+    selected BOOLEAN NOT NULL DEFAULT FALSE,
+    rejection_reasons TEXT NOT NULL DEFAULT '[]', -- JSON array of RejectionReason
+    evaluated_at INTEGER NOT NULL,
+    FOREIGN KEY (request_id) REFERENCES download_requests (id) ON DELETE CASCADE
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_download_candidates_request_id ON download_candidates(request_id);
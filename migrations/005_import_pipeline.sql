@@ -0,0 +1,27 @@
+-- Migration: Import Pipeline
+-- Downloads that could not be imported automatically are parked here for
+-- manual review instead of leaving half-copied folders in the library
+
+-- ============================================================================
+-- MANUAL IMPORT TABLES
+-- ============================================================================
+
+CREATE TABLE manual_imports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id TEXT,
+    torrent_hash TEXT,
+    source_path TEXT NOT NULL,
+    stage TEXT NOT NULL, -- verify, match, tag, organize
+    reason TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    resolved_at INTEGER,
+    resolution TEXT, -- imported, dismissed
+    FOREIGN KEY (request_id) REFERENCES download_requests (id) ON DELETE SET NULL
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_manual_imports_unresolved ON manual_imports(resolved_at);
+CREATE INDEX idx_manual_imports_request_id ON manual_imports(request_id);
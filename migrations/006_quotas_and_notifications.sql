@@ -0,0 +1,25 @@
+-- Migration: Quotas and Notifications
+-- Download requests are attributed to real users and checked against
+-- per-role quotas; users are notified when an admin approves or rejects them
+
+-- ============================================================================
+-- NOTIFICATION TABLES
+-- ============================================================================
+
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    title TEXT NOT NULL,
+    message TEXT NOT NULL,
+    data TEXT NOT NULL DEFAULT '{}', -- JSON payload for the client
+    created_at INTEGER NOT NULL,
+    read_at INTEGER
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_notifications_user_id ON notifications(user_id, created_at);
+CREATE INDEX idx_download_requests_user_requested ON download_requests(user_id, requested_at);
@@ -0,0 +1,24 @@
+-- Migration: Lidarr Webhooks
+-- Downloads Lidarr grabs and imports are mirrored into the download history,
+-- and users who asked Lidarr to monitor an artist are remembered so they can
+-- be told when an album lands
+
+-- ============================================================================
+-- LIDARR TABLES
+-- ============================================================================
+
+CREATE TABLE lidarr_artist_requests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    foreign_artist_id TEXT NOT NULL, -- MusicBrainz artist ID
+    artist_name TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    requested_at INTEGER NOT NULL,
+    UNIQUE(foreign_artist_id, user_id)
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_lidarr_artist_requests_name ON lidarr_artist_requests(artist_name COLLATE NOCASE);
+CREATE INDEX idx_download_requests_source_url ON download_requests(source_url);
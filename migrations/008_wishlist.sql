@@ -0,0 +1,57 @@
+-- Migration: Wishlist Autopilot
+-- Opt-in acquisition of highly recommended music that is not in the library,
+-- limited by a weekly budget per user and recorded in an audit trail
+
+-- ============================================================================
+-- WISHLIST TABLES
+-- ============================================================================
+
+CREATE TABLE wishlist_settings (
+    user_id TEXT PRIMARY KEY,
+    username TEXT NOT NULL,
+    role TEXT NOT NULL, -- Role at opt-in, used for quota checks on automatic requests
+    enabled BOOLEAN NOT NULL DEFAULT 0,
+    auto_queue BOOLEAN NOT NULL DEFAULT 1,
+    weekly_budget INTEGER NOT NULL,
+    min_score REAL NOT NULL,
+    last_run_at INTEGER,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE wishlist_candidates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    dedupe_key TEXT NOT NULL, -- MBID, or lowercased name when unknown
+    kind TEXT NOT NULL, -- 'artist' or 'recording'
+    source TEXT NOT NULL, -- 'listenbrainz' or 'musicbrainz'
+    artist_name TEXT NOT NULL,
+    artist_mbid TEXT,
+    title TEXT,
+    recording_mbid TEXT,
+    score REAL NOT NULL,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'proposed',
+    target TEXT, -- Download request ID or Lidarr artist once queued
+    created_at INTEGER NOT NULL,
+    decided_at INTEGER,
+    UNIQUE(user_id, dedupe_key)
+);
+
+CREATE TABLE wishlist_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    candidate_id INTEGER,
+    action TEXT NOT NULL,
+    actor TEXT NOT NULL, -- 'autopilot' or the username that acted
+    detail TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (candidate_id) REFERENCES wishlist_candidates (id) ON DELETE SET NULL
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_wishlist_candidates_user_status ON wishlist_candidates(user_id, status, score);
+CREATE INDEX idx_wishlist_audit_user_created ON wishlist_audit(user_id, created_at);
+CREATE INDEX idx_wishlist_audit_action ON wishlist_audit(user_id, action, created_at);
@@ -0,0 +1,26 @@
+-- Migration: Release Blocklist
+-- Releases that stalled or errored repeatedly are blocklisted so neither the
+-- fallback to the next-best candidate nor a later search picks them again
+
+-- ============================================================================
+-- BLOCKLIST TABLES
+-- ============================================================================
+
+CREATE TABLE release_blocklist (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    info_hash TEXT,
+    guid TEXT,
+    indexer TEXT,
+    title TEXT NOT NULL,
+    request_id TEXT,
+    reason TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (request_id) REFERENCES download_requests (id) ON DELETE SET NULL
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_release_blocklist_info_hash ON release_blocklist(info_hash);
+CREATE INDEX idx_release_blocklist_guid ON release_blocklist(guid);
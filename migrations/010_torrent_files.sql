@@ -0,0 +1,21 @@
+-- Migration: Torrent Files
+-- Uploaded or fetched .torrent files are kept until their request finishes so
+-- a request can be added to Transmission again after a restart
+
+-- ============================================================================
+-- TORRENT FILE TABLES
+-- ============================================================================
+
+-- No foreign key: the file is stored before the request is first persisted
+CREATE TABLE torrent_files (
+    request_id TEXT PRIMARY KEY,
+    info_hash TEXT NOT NULL,
+    data BLOB NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_torrent_files_info_hash ON torrent_files(info_hash);
@@ -0,0 +1,15 @@
+-- Migration: Download Priority
+-- Low-priority requests (autopilot and other background work) are paused by
+-- the bandwidth scheduler while anyone is streaming
+
+-- ============================================================================
+-- DOWNLOAD REQUEST COLUMNS
+-- ============================================================================
+
+ALTER TABLE download_requests ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal';
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_download_requests_priority ON download_requests(priority);
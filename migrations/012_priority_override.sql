@@ -0,0 +1,9 @@
+-- Migration: Priority Override
+-- Users order their own requests with `priority`; an admin override moves a
+-- request ahead of (or behind) other users' requests in the download queue
+
+-- ============================================================================
+-- DOWNLOAD REQUEST COLUMNS
+-- ============================================================================
+
+ALTER TABLE download_requests ADD COLUMN priority_override TEXT;
@@ -0,0 +1,16 @@
+-- Migration: Track Placements
+-- Library tracks popular enough to have a verified copy on the hot (NVMe)
+-- tier; the cold library stays the canonical copy
+
+-- ============================================================================
+-- TIER PLACEMENT TABLES
+-- ============================================================================
+
+CREATE TABLE track_placements (
+    track_path TEXT PRIMARY KEY, -- Relative to the library root, as in listening_sessions
+    hot_path TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    sha256 TEXT,
+    plays INTEGER NOT NULL DEFAULT 0, -- Plays in the popularity window when promoted
+    promoted_at INTEGER NOT NULL
+);
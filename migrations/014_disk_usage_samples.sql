@@ -0,0 +1,22 @@
+-- Migration: Disk Usage Samples
+-- Free space per storage tier over time, used to forecast growth and warn
+-- before a tier runs out
+
+-- ============================================================================
+-- DISK USAGE TABLES
+-- ============================================================================
+
+CREATE TABLE disk_usage_samples (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tier TEXT NOT NULL,
+    sampled_at INTEGER NOT NULL,
+    total_bytes INTEGER NOT NULL,
+    free_bytes INTEGER NOT NULL,
+    used_bytes INTEGER NOT NULL
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_disk_usage_samples_tier_time ON disk_usage_samples(tier, sampled_at);
@@ -0,0 +1,27 @@
+-- Migration: Recycle Bin
+-- Files and directories moved aside instead of being deleted, so they can be
+-- restored until they expire or are purged
+
+-- ============================================================================
+-- RECYCLE BIN TABLES
+-- ============================================================================
+
+CREATE TABLE trash_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    original_path TEXT NOT NULL,
+    trash_path TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL DEFAULT 0,
+    is_dir BOOLEAN NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'trashed', -- 'trashed', 'restored', 'purged'
+    trashed_at INTEGER NOT NULL,
+    resolved_at INTEGER,
+    resolved_by TEXT
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_trash_items_status ON trash_items(status, trashed_at);
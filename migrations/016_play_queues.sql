@@ -0,0 +1,18 @@
+-- Migration: Play Queues
+-- Server-side play queue per user, so playback can resume on another device
+
+-- ============================================================================
+-- PLAY QUEUE TABLES
+-- ============================================================================
+
+CREATE TABLE play_queues (
+    user_id TEXT PRIMARY KEY,
+    tracks TEXT NOT NULL DEFAULT '[]', -- JSON array of queued tracks, in queue order
+    current_index INTEGER NOT NULL DEFAULT 0,
+    position_ms INTEGER NOT NULL DEFAULT 0,
+    shuffle_order TEXT, -- JSON array of track indexes, NULL when not shuffled
+    repeat_mode TEXT NOT NULL DEFAULT 'Off',
+    is_playing BOOLEAN NOT NULL DEFAULT 0,
+    updated_by_device TEXT,
+    updated_at INTEGER NOT NULL
+);
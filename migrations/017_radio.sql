@@ -0,0 +1,30 @@
+-- Migration: Radio
+-- Endless radio stations, one per user, extending their play queue from a
+-- seed; early skips are kept as negative feedback
+
+-- ============================================================================
+-- RADIO TABLES
+-- ============================================================================
+
+CREATE TABLE radio_stations (
+    user_id TEXT PRIMARY KEY,
+    seed_kind TEXT NOT NULL, -- 'track', 'artist', 'album', 'playlist', 'genre'
+    seed_value TEXT NOT NULL,
+    seed_genres TEXT NOT NULL DEFAULT '[]', -- JSON array of genres found in the seed
+    discovery_mode TEXT NOT NULL,
+    started_at INTEGER NOT NULL
+);
+
+CREATE TABLE track_skips (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    track_id TEXT NOT NULL,
+    artist TEXT,
+    skipped_at INTEGER NOT NULL
+);
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_track_skips_user_time ON track_skips(user_id, skipped_at);
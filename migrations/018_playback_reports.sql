@@ -0,0 +1,19 @@
+-- Migration: Playback Reports
+-- Play reports from clients: what is playing, how far it got and how it ended
+
+-- ============================================================================
+-- LISTENING SESSION COLUMNS
+-- ============================================================================
+
+ALTER TABLE listening_sessions ADD COLUMN track_id TEXT; -- Navidrome song ID
+ALTER TABLE listening_sessions ADD COLUMN track_json TEXT; -- Title, artist, album and duration as reported
+ALTER TABLE listening_sessions ADD COLUMN position_ms INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE listening_sessions ADD COLUMN played_ms INTEGER NOT NULL DEFAULT 0; -- duration_played, to the millisecond
+ALTER TABLE listening_sessions ADD COLUMN last_report_at INTEGER;
+ALTER TABLE listening_sessions ADD COLUMN end_reason TEXT; -- 'ended', 'skipped', 'replaced'
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_listening_sessions_open ON listening_sessions(user_id, ended_at);
@@ -0,0 +1,40 @@
+-- Migration: Scrobble Queue
+-- Outbound scrobbles, kept until each service has accepted them, and the
+-- last submission error per integration
+
+-- ============================================================================
+-- SCROBBLE TABLES
+-- ============================================================================
+
+CREATE TABLE scrobble_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    service TEXT NOT NULL, -- 'listenbrainz', 'navidrome'
+    session_id INTEGER, -- listening_sessions row the listen came from
+    track_id TEXT NOT NULL,
+    recording_key TEXT NOT NULL, -- Normalised artist and title
+    track_json TEXT NOT NULL,
+    listened_at INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending', -- 'pending', 'sent', 'failed'
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL,
+    last_error TEXT,
+    created_at INTEGER NOT NULL,
+    sent_at INTEGER,
+    UNIQUE(user_id, service, recording_key, listened_at)
+);
+
+-- ============================================================================
+-- USER INTEGRATION COLUMNS
+-- ============================================================================
+
+-- Why the last submission to an integration failed; cleared on success
+ALTER TABLE user_integrations ADD COLUMN last_error TEXT;
+ALTER TABLE user_integrations ADD COLUMN last_error_at INTEGER;
+
+-- ============================================================================
+-- PERFORMANCE INDEXES
+-- ============================================================================
+
+CREATE INDEX idx_scrobble_queue_due ON scrobble_queue(status, next_attempt_at);
+CREATE INDEX idx_scrobble_queue_user ON scrobble_queue(user_id, service, status);
@@ -0,0 +1,8 @@
+-- Migration: Track Placement Source
+-- The library file a hot copy was taken from, to spot copies that went stale
+
+-- ============================================================================
+-- TRACK PLACEMENT COLUMNS
+-- ============================================================================
+
+ALTER TABLE track_placements ADD COLUMN source_mtime INTEGER; -- Library file mtime when promoted, unix seconds
@@ -265,7 +265,9 @@ pub async fn optional_auth_middleware(
     next: Next,
 ) -> Response {
     // Try to extract and validate token
-    if let Some(token) = AuthService::extract_bearer_token(request.headers()) {
+    let token = AuthService::extract_bearer_token(request.headers())
+        .or_else(|| event_stream_token(request.uri()));
+    if let Some(token) = token {
         if let Ok(user) = auth_service.validate_token(&token).await {
             if user.is_active {
                 request.extensions_mut().insert(user);
@@ -276,6 +278,37 @@ pub async fn optional_auth_middleware(
     next.run(request).await
 }
 
+/// Routes opened with EventSource or WebSocket, where `*` matches one path segment
+const QUERY_TOKEN_ROUTES: &[&str] = &[
+    "/api/v1/events",
+    "/api/v1/events/ws",
+    "/api/v1/player/ws",
+    "/api/v1/parties/*/ws",
+];
+
+/// Browsers cannot set headers on EventSource or WebSocket connections, so
+/// the streaming endpoints in `QUERY_TOKEN_ROUTES` also accept the token as
+/// `?access_token=`
+fn event_stream_token(uri: &axum::http::Uri) -> Option<String> {
+    let path = uri.path().trim_end_matches('/');
+    let accepts_query_token = QUERY_TOKEN_ROUTES.iter().any(|route| {
+        let mut segments = path.split('/');
+        route.split('/').all(|expected| {
+            segments
+                .next()
+                .is_some_and(|segment| expected == "*" || segment == expected)
+        }) && segments.next().is_none()
+    });
+    if !accepts_query_token {
+        return None;
+    }
+
+    url::form_urlencoded::parse(uri.query()?.as_bytes())
+        .find(|(key, _)| key == "access_token")
+        .map(|(_, token)| token.into_owned())
+        .filter(|token| !token.is_empty())
+}
+
 /// Extract authenticated user from request
 pub fn get_authenticated_user(request: &Request) -> Option<&AuthenticatedUser> {
     request.extensions().get::<AuthenticatedUser>()
@@ -498,4 +531,79 @@ mod tests {
         assert!(!token.is_empty());
         assert!(token.contains('.'));
     }
+
+    #[test]
+    fn test_event_stream_token() {
+        let uri: axum::http::Uri = "/api/v1/events?topics=download&access_token=a%2Eb"
+            .parse()
+            .unwrap();
+        assert_eq!(event_stream_token(&uri).as_deref(), Some("a.b"));
+
+        let uri: axum::http::Uri = "/api/v1/download/active?access_token=abc".parse().unwrap();
+        assert_eq!(event_stream_token(&uri), None);
+
+        let uri: axum::http::Uri = "/api/v1/player/ws?access_token=abc".parse().unwrap();
+        assert_eq!(event_stream_token(&uri).as_deref(), Some("abc"));
+        let uri: axum::http::Uri = "/api/v1/events/ws?access_token=abc".parse().unwrap();
+        assert_eq!(event_stream_token(&uri).as_deref(), Some("abc"));
+        let uri: axum::http::Uri = "/api/v1/events/other?access_token=abc".parse().unwrap();
+        assert_eq!(event_stream_token(&uri), None);
+        let uri: axum::http::Uri = "/api/v1/parties/abc/ws?access_token=abc".parse().unwrap();
+        assert_eq!(event_stream_token(&uri).as_deref(), Some("abc"));
+        let uri: axum::http::Uri = "/api/v1/parties/abc?access_token=abc".parse().unwrap();
+        assert_eq!(event_stream_token(&uri), None);
+    }
+
+    #[tokio::test]
+    async fn test_party_socket_accepts_query_token() {
+        use crate::database::Database;
+        use axum::{body::Body, routing::get, Extension, Router};
+        use tower::ServiceExt;
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let auth_service = Arc::new(
+            AuthService::new(
+                AuthConfig::development("party-secret".to_string()),
+                Arc::new(UserService::new(database)),
+            )
+            .unwrap(),
+        );
+
+        let app = Router::new()
+            .route(
+                "/api/v1/parties/:party_id/ws",
+                get(|user: Option<Extension<AuthenticatedUser>>| async move {
+                    user.map_or_else(String::new, |Extension(user)| user.username)
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                auth_service,
+                optional_auth_middleware,
+            ));
+
+        let token = utils::generate_test_token(
+            "party-guest",
+            "guest",
+            "guest@example.com",
+            vec!["user".to_string()],
+            "party-secret",
+        )
+        .unwrap();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/v1/parties/p1/ws?access_token={}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"guest");
+    }
 }
@@ -219,6 +219,93 @@ pub struct SearchResult {
     pub album: Option<Album>,
 }
 
+/// One page of a paged Lidarr resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub page: u32,
+    pub page_size: u32,
+    pub total_records: u32,
+    pub records: Vec<T>,
+}
+
+/// Warning attached to a queue item by Lidarr
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueStatusMessage {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<String>,
+}
+
+/// Download in Lidarr's queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueItem {
+    pub id: u32,
+    pub artist_id: Option<u32>,
+    pub album_id: Option<u32>,
+    pub artist: Option<Artist>,
+    pub album: Option<Album>,
+    pub title: Option<String>,
+    pub size: Option<f64>,
+    pub sizeleft: Option<f64>,
+    pub timeleft: Option<String>,
+    pub estimated_completion_time: Option<DateTime<Utc>>,
+    pub status: Option<String>,
+    pub tracked_download_status: Option<String>,
+    pub tracked_download_state: Option<String>,
+    #[serde(default)]
+    pub status_messages: Vec<QueueStatusMessage>,
+    pub error_message: Option<String>,
+    pub download_id: Option<String>,
+    pub protocol: Option<String>,
+    pub download_client: Option<String>,
+    pub indexer: Option<String>,
+    pub output_path: Option<String>,
+}
+
+impl QueueItem {
+    /// Fraction downloaded, between 0 and 1
+    pub fn progress(&self) -> f64 {
+        match (self.size, self.sizeleft) {
+            (Some(size), Some(left)) if size > 0.0 => ((size - left) / size).clamp(0.0, 1.0),
+            _ => 0.0,
+        }
+    }
+
+    /// Whether the item needs attention: Lidarr flagged it (e.g. an import
+    /// it cannot complete on its own) or the download client failed it
+    pub fn is_stuck(&self) -> bool {
+        let flagged = self
+            .tracked_download_status
+            .as_deref()
+            .is_some_and(|status| !status.eq_ignore_ascii_case("ok"));
+        let failed = self.status.as_deref().is_some_and(|status| {
+            status.eq_ignore_ascii_case("failed") || status.eq_ignore_ascii_case("warning")
+        });
+
+        flagged || failed || self.error_message.is_some()
+    }
+}
+
+/// Entry in Lidarr's grab and import history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRecord {
+    pub id: u32,
+    pub artist_id: Option<u32>,
+    pub album_id: Option<u32>,
+    pub artist: Option<Artist>,
+    pub album: Option<Album>,
+    pub source_title: Option<String>,
+    pub event_type: String,
+    pub date: Option<DateTime<Utc>>,
+    pub download_id: Option<String>,
+    #[serde(default)]
+    pub data: HashMap<String, Option<String>>,
+}
+
 impl LidarrClient {
     /// Create a new Lidarr client
     pub fn new(base_url: &str, api_key: &str) -> Result<Self> {
@@ -494,6 +581,186 @@ impl LidarrClient {
         }
     }
 
+    /// Get an album by ID
+    pub async fn get_album(&self, album_id: u32) -> Result<Option<Album>> {
+        let url = format!("{}/api/v1/album/{}", self.base_url, album_id);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("X-Api-Key", &self.api_key)
+            .send()
+            .await
+            .context("Failed to get album")?;
+
+        match response.status() {
+            status if status.is_success() => {
+                let album: Album = response.json().await.context("Failed to parse album")?;
+                Ok(Some(album))
+            }
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            _ => anyhow::bail!("Failed to get album: {}", response.status()),
+        }
+    }
+
+    /// Turn monitoring on or off for individual albums
+    pub async fn set_albums_monitored(&self, album_ids: &[u32], monitored: bool) -> Result<()> {
+        let url = format!("{}/api/v1/album/monitor", self.base_url);
+
+        let body = serde_json::json!({
+            "albumIds": album_ids,
+            "monitored": monitored
+        });
+
+        let response = self
+            .client
+            .put(&url)
+            .header("X-Api-Key", &self.api_key)
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to update album monitoring")?;
+
+        if response.status().is_success() {
+            info!("Set monitored={} for albums {:?}", monitored, album_ids);
+            Ok(())
+        } else {
+            anyhow::bail!("Failed to update album monitoring: {}", response.status());
+        }
+    }
+
+    /// Get monitored albums that have no files yet, oldest release first
+    pub async fn get_missing(&self, page: u32, page_size: u32) -> Result<Page<Album>> {
+        let response = self
+            .missing_request(page, page_size)
+            .send()
+            .await
+            .context("Failed to get missing albums")?;
+
+        if response.status().is_success() {
+            response
+                .json()
+                .await
+                .context("Failed to parse missing albums")
+        } else {
+            anyhow::bail!("Failed to get missing albums: {}", response.status());
+        }
+    }
+
+    fn missing_request(&self, page: u32, page_size: u32) -> reqwest::RequestBuilder {
+        self.client
+            .get(format!("{}/api/v1/wanted/missing", self.base_url))
+            .header("X-Api-Key", &self.api_key)
+            .query(&[
+                ("page", page.to_string()),
+                ("pageSize", page_size.to_string()),
+                ("sortKey", "releaseDate".to_string()),
+                ("sortDirection", "ascending".to_string()),
+                ("includeArtist", "true".to_string()),
+                ("monitored", "true".to_string()),
+            ])
+    }
+
+    /// Get the download queue
+    pub async fn get_queue(&self, page: u32, page_size: u32) -> Result<Page<QueueItem>> {
+        let url = format!("{}/api/v1/queue", self.base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("X-Api-Key", &self.api_key)
+            .query(&[
+                ("page", page.to_string()),
+                ("pageSize", page_size.to_string()),
+                ("includeArtist", "true".to_string()),
+                ("includeAlbum", "true".to_string()),
+            ])
+            .send()
+            .await
+            .context("Failed to get queue")?;
+
+        if response.status().is_success() {
+            response.json().await.context("Failed to parse queue")
+        } else {
+            anyhow::bail!("Failed to get queue: {}", response.status());
+        }
+    }
+
+    /// Get every item in the download queue, page by page
+    pub async fn get_full_queue(&self) -> Result<Vec<QueueItem>> {
+        let mut items = Vec::new();
+        for page in 1.. {
+            let queue = self.get_queue(page, 200).await?;
+            let done = queue.records.is_empty();
+            items.extend(queue.records);
+            if done || items.len() >= queue.total_records as usize {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Remove an item from the queue, optionally deleting it from the
+    /// download client and blocklisting the release so it is not grabbed again
+    pub async fn remove_queue_item(
+        &self,
+        queue_id: u32,
+        remove_from_client: bool,
+        blocklist: bool,
+    ) -> Result<()> {
+        let url = format!("{}/api/v1/queue/{}", self.base_url, queue_id);
+
+        let response = self
+            .client
+            .delete(&url)
+            .header("X-Api-Key", &self.api_key)
+            .query(&[
+                ("removeFromClient", remove_from_client.to_string()),
+                ("blocklist", blocklist.to_string()),
+            ])
+            .send()
+            .await
+            .context("Failed to remove queue item")?;
+
+        match response.status() {
+            status if status.is_success() => {
+                info!("Removed queue item {}", queue_id);
+                Ok(())
+            }
+            reqwest::StatusCode::NOT_FOUND => {
+                anyhow::bail!("Queue item {} not found", queue_id)
+            }
+            status => anyhow::bail!("Failed to remove queue item: {}", status),
+        }
+    }
+
+    /// Get grab and import history, newest first
+    pub async fn get_history(&self, page: u32, page_size: u32) -> Result<Page<HistoryRecord>> {
+        let url = format!("{}/api/v1/history", self.base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("X-Api-Key", &self.api_key)
+            .query(&[
+                ("page", page.to_string()),
+                ("pageSize", page_size.to_string()),
+                ("sortKey", "date".to_string()),
+                ("sortDirection", "descending".to_string()),
+                ("includeArtist", "true".to_string()),
+                ("includeAlbum", "true".to_string()),
+            ])
+            .send()
+            .await
+            .context("Failed to get history")?;
+
+        if response.status().is_success() {
+            response.json().await.context("Failed to parse history")
+        } else {
+            anyhow::bail!("Failed to get history: {}", response.status());
+        }
+    }
+
     /// Trigger automatic search for missing albums
     pub async fn search_missing(&self) -> Result<()> {
         let url = format!("{}/api/v1/command", self.base_url);
@@ -633,6 +900,21 @@ mod tests {
         assert_eq!(client.api_key, "test_api_key");
     }
 
+    #[test]
+    fn test_missing_query_oldest_first() {
+        let client = LidarrClient::new("http://localhost:8686", "test_key").unwrap();
+        let request = client.missing_request(2, 50).build().unwrap();
+
+        assert_eq!(request.url().path(), "/api/v1/wanted/missing");
+        assert_eq!(
+            request.url().query(),
+            Some(
+                "page=2&pageSize=50&sortKey=releaseDate&sortDirection=ascending\
+                 &includeArtist=true&monitored=true"
+            )
+        );
+    }
+
     #[test]
     fn test_create_artist_for_monitoring() {
         let client = LidarrClient::new("http://localhost:8686", "test_key").unwrap();
@@ -650,4 +932,47 @@ mod tests {
         assert_eq!(artist.quality_profile_id, Some(1));
         assert_eq!(artist.monitored, Some(true));
     }
+
+    #[test]
+    fn test_parse_queue() {
+        let page: Page<QueueItem> = serde_json::from_str(
+            r#"{
+                "page": 1,
+                "pageSize": 20,
+                "totalRecords": 2,
+                "records": [
+                    {
+                        "id": 1,
+                        "albumId": 10,
+                        "title": "Artist - Album [FLAC]",
+                        "size": 400.0,
+                        "sizeleft": 100.0,
+                        "timeleft": "00:05:00",
+                        "status": "downloading",
+                        "trackedDownloadStatus": "ok",
+                        "trackedDownloadState": "downloading",
+                        "statusMessages": []
+                    },
+                    {
+                        "id": 2,
+                        "size": 100.0,
+                        "sizeleft": 0.0,
+                        "status": "completed",
+                        "trackedDownloadStatus": "warning",
+                        "trackedDownloadState": "importPending",
+                        "statusMessages": [{"title": "Album.flac", "messages": ["No files found are eligible for import"]}]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(page.total_records, 2);
+        let (downloading, blocked) = (&page.records[0], &page.records[1]);
+        assert_eq!(downloading.progress(), 0.75);
+        assert!(!downloading.is_stuck());
+        assert_eq!(blocked.progress(), 1.0);
+        assert!(blocked.is_stuck());
+        assert_eq!(blocked.status_messages[0].messages.len(), 1);
+    }
 }
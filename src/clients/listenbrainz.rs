@@ -7,6 +7,7 @@ use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 use tracing::{debug, info, warn};
 
@@ -42,6 +43,16 @@ pub struct AdditionalInfo {
     pub release_mbid: Option<String>,
 }
 
+/// A batch submission that stopped part way
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Submitted {submitted} of {total} listens before failing: {message}")]
+pub struct BatchSubmitError {
+    /// Listens accepted before the failing batch, counted from the start
+    pub submitted: usize,
+    pub total: usize,
+    pub message: String,
+}
+
 /// Recommendation from ListenBrainz
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recommendation {
@@ -82,6 +93,97 @@ pub struct RecordingStats {
     pub listen_count: u64,
 }
 
+/// Response of the collaborative filtering recommendation endpoint
+#[derive(Debug, Deserialize)]
+struct RecommendationResponse {
+    payload: RecommendationPayload,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecommendationPayload {
+    #[serde(default)]
+    mbids: Vec<RecommendedRecording>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecommendedRecording {
+    recording_mbid: String,
+    score: f64,
+}
+
+/// Entry of the recording metadata lookup, keyed by recording MBID
+#[derive(Debug, Deserialize)]
+struct RecordingMetadata {
+    recording: Option<MetadataRecording>,
+    artist: Option<MetadataArtist>,
+    release: Option<MetadataRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataRecording {
+    name: String,
+    length: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataArtist {
+    name: String,
+    #[serde(default)]
+    artists: Vec<MetadataArtistCredit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataArtistCredit {
+    artist_mbid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataRelease {
+    name: Option<String>,
+    mbid: Option<String>,
+}
+
+impl RecordingMetadata {
+    fn into_track_metadata(self, recording_mbid: &str) -> Option<TrackMetadata> {
+        let recording = self.recording?;
+        let artist = self.artist?;
+        Some(TrackMetadata {
+            artist_name: artist.name,
+            track_name: recording.name,
+            release_name: self.release.as_ref().and_then(|r| r.name.clone()),
+            additional_info: Some(AdditionalInfo {
+                duration_ms: recording.length,
+                recording_mbid: Some(recording_mbid.to_string()),
+                artist_mbid: artist
+                    .artists
+                    .into_iter()
+                    .find_map(|credit| credit.artist_mbid),
+                release_mbid: self.release.and_then(|r| r.mbid),
+            }),
+        })
+    }
+}
+
+/// Response of the top artists statistics endpoint
+#[derive(Debug, Deserialize)]
+struct ArtistStatsResponse {
+    payload: ArtistStatsPayload,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArtistStatsPayload {
+    #[serde(default)]
+    artists: Vec<TopArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopArtist {
+    artist_name: String,
+    #[serde(default)]
+    artist_mbids: Vec<String>,
+    listen_count: u64,
+}
+
 impl ListenBrainzClient {
     /// Create a new ListenBrainz client
     pub fn new(base_url: &str, user_token: Option<&str>) -> Result<Self> {
@@ -184,13 +286,37 @@ impl ListenBrainzClient {
             .await
             .context("Failed to get user stats")?;
 
-        if response.status().is_success() {
-            // For now, return empty stats - would need actual API parsing
-            Ok(ListeningStats {
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            // Statistics have not been calculated for this user yet
+            return Ok(ListeningStats {
                 total_listen_count: 0,
                 artists: Vec::new(),
                 releases: Vec::new(),
                 recordings: Vec::new(),
+            });
+        }
+
+        if response.status().is_success() {
+            let stats: ArtistStatsResponse = response
+                .json()
+                .await
+                .context("Failed to parse user stats")?;
+            let artists: Vec<ArtistStats> = stats
+                .payload
+                .artists
+                .into_iter()
+                .map(|artist| ArtistStats {
+                    artist_name: artist.artist_name,
+                    artist_mbid: artist.artist_mbids.into_iter().next(),
+                    listen_count: artist.listen_count,
+                })
+                .collect();
+
+            Ok(ListeningStats {
+                total_listen_count: artists.iter().map(|a| a.listen_count).sum(),
+                artists,
+                releases: Vec::new(),
+                recordings: Vec::new(),
             })
         } else {
             anyhow::bail!("Failed to get user stats: {}", response.status());
@@ -211,9 +337,45 @@ impl ListenBrainzClient {
             .await
             .context("Failed to get recommendations")?;
 
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            debug!("No recommendations generated for {} yet", user_name);
+            return Ok(Vec::new());
+        }
+
         if response.status().is_success() {
-            // For now, return empty recommendations - would need actual API parsing
-            Ok(Vec::new())
+            let recommendations: RecommendationResponse = response
+                .json()
+                .await
+                .context("Failed to parse recommendations")?;
+            let mbids: Vec<String> = recommendations
+                .payload
+                .mbids
+                .iter()
+                .map(|r| r.recording_mbid.clone())
+                .collect();
+
+            // Recommendations only carry MBIDs; without names they cannot be used
+            let metadata = self
+                .get_recording_metadata(&mbids)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to resolve recommendation metadata: {}", e);
+                    HashMap::new()
+                });
+
+            Ok(recommendations
+                .payload
+                .mbids
+                .into_iter()
+                .filter_map(|r| {
+                    let track_metadata = metadata.get(&r.recording_mbid)?.clone();
+                    Some(Recommendation {
+                        recording_mbid: r.recording_mbid,
+                        score: r.score,
+                        track_metadata,
+                    })
+                })
+                .collect())
         } else {
             warn!(
                 "Failed to get recommendations for user {}: {}",
@@ -224,6 +386,43 @@ impl ListenBrainzClient {
         }
     }
 
+    /// Look up artist, title and release for recordings, keyed by MBID
+    pub async fn get_recording_metadata(
+        &self,
+        recording_mbids: &[String],
+    ) -> Result<HashMap<String, TrackMetadata>> {
+        let mut metadata = HashMap::new();
+
+        for chunk in recording_mbids.chunks(50) {
+            let url = format!("{}/1/metadata/recording/", self.base_url);
+            let response = self
+                .client
+                .get(&url)
+                .query(&[
+                    ("recording_mbids", chunk.join(",")),
+                    ("inc", "artist release".to_string()),
+                ])
+                .send()
+                .await
+                .context("Failed to get recording metadata")?;
+
+            if !response.status().is_success() {
+                anyhow::bail!("Failed to get recording metadata: {}", response.status());
+            }
+
+            let entries: HashMap<String, RecordingMetadata> = response
+                .json()
+                .await
+                .context("Failed to parse recording metadata")?;
+            metadata.extend(entries.into_iter().filter_map(|(mbid, entry)| {
+                let track = entry.into_track_metadata(&mbid)?;
+                Some((mbid, track))
+            }));
+        }
+
+        Ok(metadata)
+    }
+
     /// Get similar users based on listening history
     pub async fn get_similar_users(&self, user_name: &str) -> Result<Vec<String>> {
         let url = format!("{}/1/user/{}/similar-users", self.base_url, user_name);
@@ -339,15 +538,18 @@ impl ListenBrainzClient {
         }
     }
 
-    /// Batch submit multiple listens efficiently
+    /// Batch submit multiple listens efficiently.
+    ///
+    /// Batches go in order and submission stops at the first one that fails,
+    /// so the error tells how many listens made it.
     pub async fn batch_submit_listens(
         &self,
         user_name: &str,
         listens: Vec<Listen>,
         batch_size: usize,
-    ) -> Result<()> {
+    ) -> Result<usize, BatchSubmitError> {
         if listens.is_empty() {
-            return Ok(());
+            return Ok(0);
         }
 
         info!(
@@ -357,17 +559,25 @@ impl ListenBrainzClient {
             batch_size
         );
 
-        for chunk in listens.chunks(batch_size) {
+        let mut submitted = 0;
+        for (i, chunk) in listens.chunks(batch_size.max(1)).enumerate() {
+            if i > 0 {
+                // Small delay between batches to be respectful to the API
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+
             if let Err(e) = self.submit_listens(user_name, chunk.to_vec()).await {
                 warn!("Failed to submit batch of {} listens: {}", chunk.len(), e);
-                // Continue with next batch instead of failing completely
+                return Err(BatchSubmitError {
+                    submitted,
+                    total: listens.len(),
+                    message: e.to_string(),
+                });
             }
-
-            // Small delay between batches to be respectful to the API
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            submitted += chunk.len();
         }
 
-        Ok(())
+        Ok(submitted)
     }
 }
 
@@ -404,4 +614,36 @@ mod tests {
         );
         assert_eq!(listen.listened_at, Some(now.timestamp()));
     }
+
+    #[test]
+    fn test_parse_recommendations_and_metadata() {
+        let response: RecommendationResponse = serde_json::from_str(
+            r#"{"payload": {"count": 2, "entity": "recording", "mbids": [
+                {"recording_mbid": "rec-1", "score": 0.9},
+                {"recording_mbid": "rec-2", "score": 0.4}
+            ], "user_name": "alice"}}"#,
+        )
+        .unwrap();
+        assert_eq!(response.payload.mbids.len(), 2);
+        assert_eq!(response.payload.mbids[0].score, 0.9);
+
+        let metadata: HashMap<String, RecordingMetadata> = serde_json::from_str(
+            r#"{"rec-1": {
+                "recording": {"name": "Roygbiv", "length": 151000},
+                "artist": {"name": "Boards of Canada", "artists": [{"artist_mbid": "art-1", "name": "Boards of Canada"}]},
+                "release": {"name": "Music Has the Right to Children", "mbid": "rel-1"}
+            }}"#,
+        )
+        .unwrap();
+        let track = metadata
+            .into_iter()
+            .next()
+            .and_then(|(mbid, entry)| entry.into_track_metadata(&mbid))
+            .unwrap();
+        assert_eq!(track.artist_name, "Boards of Canada");
+        assert_eq!(track.track_name, "Roygbiv");
+        let info = track.additional_info.unwrap();
+        assert_eq!(info.artist_mbid.as_deref(), Some("art-1"));
+        assert_eq!(info.recording_mbid.as_deref(), Some("rec-1"));
+    }
 }
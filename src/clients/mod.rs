@@ -1,12 +1,13 @@
 //! API clients for external services
 //!
 //! This module contains clients for interacting with various external music services
-//! including Navidrome, ListenBrainz, Lidarr, and MusicBrainz.
+//! including Navidrome, ListenBrainz, Lidarr, MusicBrainz, and Torznab indexers.
 
 pub mod lidarr;
 pub mod listenbrainz;
 pub mod musicbrainz;
 pub mod navidrome;
+pub mod torznab;
 pub mod transmission;
 
 // Re-export for convenience
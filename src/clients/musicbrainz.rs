@@ -66,6 +66,23 @@ pub struct Release {
     pub genres: Option<Vec<Genre>>,
 }
 
+impl Release {
+    /// Tracks across all media, when MusicBrainz listed them
+    pub fn track_count(&self) -> Option<u32> {
+        let count = self
+            .media
+            .iter()
+            .flatten()
+            .filter_map(|medium| {
+                medium
+                    .track_count
+                    .or_else(|| medium.tracks.as_ref().map(|tracks| tracks.len() as u32))
+            })
+            .sum();
+        (count > 0).then_some(count)
+    }
+}
+
 /// MusicBrainz recording entity
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recording {
@@ -152,6 +169,7 @@ pub struct Tag {
 /// Relation information (links between entities)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Relation {
+    #[serde(alias = "type")]
     pub relation_type: String,
     pub direction: Option<String>,
     pub target: Option<String>,
@@ -185,6 +203,7 @@ pub struct Medium {
     pub title: Option<String>,
     pub format: Option<String>,
     pub position: Option<u32>,
+    #[serde(alias = "track-count")]
     pub track_count: Option<u32>,
     pub tracks: Option<Vec<Track>>,
 }
@@ -756,6 +775,21 @@ mod tests {
         assert_eq!(client.user_agent, "StepheyBot-Music/1.0");
     }
 
+    #[test]
+    fn test_release_track_count() {
+        let release: Release = serde_json::from_value(serde_json::json!({
+            "id": "r1",
+            "title": "Album",
+            "media": [{"position": 1, "track-count": 10}, {"position": 2, "track-count": 8}]
+        }))
+        .unwrap();
+        assert_eq!(release.track_count(), Some(18));
+
+        let release: Release =
+            serde_json::from_value(serde_json::json!({"id": "r2", "title": "Album"})).unwrap();
+        assert_eq!(release.track_count(), None);
+    }
+
     #[test]
     fn test_parse_release_date() {
         let client = MusicBrainzClient::new("Test/1.0").unwrap();
@@ -258,6 +258,13 @@ pub struct SongsResponse {
     pub directory: Option<Directory>,
     #[serde(rename = "randomSongs")]
     pub random_songs: Option<RandomSongs>,
+    #[serde(rename = "similarSongs")]
+    pub similar_songs: Option<SongList>,
+    #[serde(rename = "topSongs")]
+    pub top_songs: Option<SongList>,
+    pub song: Option<NavidromeSong>,
+    pub album: Option<AlbumWithSongs>,
+    pub playlist: Option<NavidromePlaylist>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -277,6 +284,35 @@ pub struct RandomSongs {
     pub song: Vec<NavidromeSong>,
 }
 
+/// Song lists that may be omitted when empty
+#[derive(Debug, Deserialize)]
+pub struct SongList {
+    #[serde(default)]
+    pub song: Vec<NavidromeSong>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumWithSongs {
+    #[serde(default)]
+    pub song: Vec<NavidromeSong>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanStatus {
+    pub scanning: bool,
+    pub count: Option<u64>,
+    #[serde(rename = "folderCount")]
+    pub folder_count: Option<u64>,
+    #[serde(rename = "lastScan")]
+    pub last_scan: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScanStatusResponse {
+    #[serde(rename = "scanStatus")]
+    pub scan_status: ScanStatus,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PlaylistsResponse {
     pub playlists: Playlists,
@@ -473,6 +509,69 @@ impl NavidromeClient {
         }
     }
 
+    /// Get a single song by ID
+    pub async fn get_song(&self, song_id: &str) -> Result<Option<NavidromeSong>> {
+        let response: SubsonicResponse<SongsResponse> =
+            self.make_request("getSong", &[("id", song_id)]).await?;
+        Ok(response.subsonic_response.data.and_then(|d| d.song))
+    }
+
+    /// Get the songs of an album
+    pub async fn get_album_songs(&self, album_id: &str) -> Result<Vec<NavidromeSong>> {
+        let response: SubsonicResponse<SongsResponse> =
+            self.make_request("getAlbum", &[("id", album_id)]).await?;
+        Ok(response
+            .subsonic_response
+            .data
+            .and_then(|d| d.album)
+            .map(|album| album.song)
+            .unwrap_or_default())
+    }
+
+    /// Get the songs of a playlist
+    pub async fn get_playlist_songs(&self, playlist_id: &str) -> Result<Vec<NavidromeSong>> {
+        let response: SubsonicResponse<SongsResponse> = self
+            .make_request("getPlaylist", &[("id", playlist_id)])
+            .await?;
+        Ok(response
+            .subsonic_response
+            .data
+            .and_then(|d| d.playlist)
+            .and_then(|playlist| playlist.entry)
+            .unwrap_or_default())
+    }
+
+    /// Get songs similar to a song, album or artist, as found by Navidrome's agents
+    pub async fn get_similar_songs(&self, id: &str, count: u32) -> Result<Vec<NavidromeSong>> {
+        let count = count.to_string();
+        let response: SubsonicResponse<SongsResponse> = self
+            .make_request("getSimilarSongs", &[("id", id), ("count", count.as_str())])
+            .await?;
+        Ok(response
+            .subsonic_response
+            .data
+            .and_then(|d| d.similar_songs)
+            .map(|list| list.song)
+            .unwrap_or_default())
+    }
+
+    /// Get the most popular songs of an artist, by name
+    pub async fn get_top_songs(&self, artist: &str, count: u32) -> Result<Vec<NavidromeSong>> {
+        let count = count.to_string();
+        let response: SubsonicResponse<SongsResponse> = self
+            .make_request(
+                "getTopSongs",
+                &[("artist", artist), ("count", count.as_str())],
+            )
+            .await?;
+        Ok(response
+            .subsonic_response
+            .data
+            .and_then(|d| d.top_songs)
+            .map(|list| list.song)
+            .unwrap_or_default())
+    }
+
     /// Search for music
     pub async fn search(
         &self,
@@ -516,6 +615,39 @@ impl NavidromeClient {
         }
     }
 
+    /// Start a library scan, picking up new and changed files
+    pub async fn start_scan(&self, full_scan: bool) -> Result<ScanStatus> {
+        let mut params = Vec::new();
+        if full_scan {
+            params.push(("fullScan", "true"));
+        }
+
+        let response: SubsonicResponse<ScanStatusResponse> =
+            self.make_request("startScan", &params).await?;
+
+        match response.subsonic_response.data {
+            Some(data) => Ok(data.scan_status),
+            None => Err(anyhow::anyhow!(
+                "Failed to start scan: {:?}",
+                response.subsonic_response.error
+            )),
+        }
+    }
+
+    /// Get the status of the current or last library scan
+    pub async fn get_scan_status(&self) -> Result<ScanStatus> {
+        let response: SubsonicResponse<ScanStatusResponse> =
+            self.make_request("getScanStatus", &[]).await?;
+
+        match response.subsonic_response.data {
+            Some(data) => Ok(data.scan_status),
+            None => Err(anyhow::anyhow!(
+                "Failed to get scan status: {:?}",
+                response.subsonic_response.error
+            )),
+        }
+    }
+
     /// Get user playlists
     pub async fn get_playlists(&self, username: Option<&str>) -> Result<Vec<NavidromePlaylist>> {
         let mut params = Vec::new();
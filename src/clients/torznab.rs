@@ -0,0 +1,757 @@
+//! Torznab/Newznab indexer client for StepheyBot Music
+//!
+//! This module queries Torznab-compatible indexers (Jackett, Prowlarr, or any
+//! tracker exposing the API directly) so releases can be found and grabbed
+//! without routing every request through Lidarr.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::utils::{normalize_music_name, parse_audio_info_from_filename, AudioQuality};
+
+/// Newznab "Audio" parent category
+pub const AUDIO_CATEGORY: u32 = 3000;
+
+/// Default number of candidates returned by a search
+const DEFAULT_RESULT_LIMIT: usize = 50;
+
+/// Configuration for a single Torznab indexer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexerConfig {
+    pub name: String,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub api_key: String,
+    pub categories: Vec<u32>,
+    pub enabled: bool,
+    /// Seeding ratio required by this indexer, overriding the global limit
+    pub seed_ratio: Option<f64>,
+    /// Seeding time required by this indexer, overriding the global limit
+    pub seed_time: Option<Duration>,
+}
+
+impl IndexerConfig {
+    /// Create an enabled indexer searching the audio category
+    pub fn new(name: &str, url: &str, api_key: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            categories: vec![AUDIO_CATEGORY],
+            enabled: true,
+            seed_ratio: None,
+            seed_time: None,
+        }
+    }
+
+    /// Load indexers from numbered environment variables
+    ///
+    /// Each indexer is configured with `STEPHEYBOT__INDEXERS__<N>__URL` and
+    /// `STEPHEYBOT__INDEXERS__<N>__API_KEY`, plus optional `__NAME`,
+    /// `__CATEGORIES` (comma separated), `__ENABLED` and the seeding overrides
+    /// `__SEED_RATIO` and `__SEED_TIME_HOURS`, starting at `N = 0`.
+    pub fn from_env() -> Vec<Self> {
+        let mut indexers = Vec::new();
+
+        for index in 0.. {
+            let prefix = format!("STEPHEYBOT__INDEXERS__{}", index);
+            let Ok(url) = env::var(format!("{}__URL", prefix)) else {
+                break;
+            };
+
+            let api_key = env::var(format!("{}__API_KEY", prefix)).unwrap_or_default();
+            let name = env::var(format!("{}__NAME", prefix))
+                .unwrap_or_else(|_| format!("indexer-{}", index));
+
+            let mut indexer = Self::new(&name, &url, &api_key);
+
+            if let Ok(categories) = env::var(format!("{}__CATEGORIES", prefix)) {
+                let parsed: Vec<u32> = categories
+                    .split(',')
+                    .filter_map(|c| c.trim().parse().ok())
+                    .collect();
+                if !parsed.is_empty() {
+                    indexer.categories = parsed;
+                }
+            }
+
+            if let Ok(enabled) = env::var(format!("{}__ENABLED", prefix)) {
+                indexer.enabled = enabled.parse().unwrap_or(true);
+            }
+
+            indexer.seed_ratio = env::var(format!("{}__SEED_RATIO", prefix))
+                .ok()
+                .and_then(|ratio| ratio.parse::<f64>().ok())
+                .filter(|ratio| ratio.is_finite() && *ratio >= 0.0);
+            indexer.seed_time = env::var(format!("{}__SEED_TIME_HOURS", prefix))
+                .ok()
+                .and_then(|hours| hours.parse::<f64>().ok())
+                .filter(|hours| hours.is_finite() && *hours >= 0.0)
+                .map(|hours| Duration::from_secs_f64(hours * 3600.0));
+
+            indexers.push(indexer);
+        }
+
+        indexers
+    }
+
+    /// Build the Torznab API endpoint for this indexer
+    fn api_url(&self) -> Result<Url> {
+        let base = if self.url.ends_with("/api") {
+            self.url.clone()
+        } else {
+            format!("{}/api", self.url)
+        };
+
+        Url::parse(&base).with_context(|| format!("Invalid indexer URL: {}", self.url))
+    }
+}
+
+/// Search parameters for an indexer query
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TorznabQuery {
+    pub query: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl TorznabQuery {
+    /// Search for a specific album by an artist
+    pub fn album(artist: &str, album: &str) -> Self {
+        Self {
+            artist: Some(artist.to_string()),
+            album: Some(album.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Free-text search terms sent to the indexer
+    ///
+    /// Uses the generic `t=search` function rather than `t=music`, since not
+    /// every indexer implements the music search capability.
+    fn search_terms(&self) -> String {
+        [&self.artist, &self.album, &self.query]
+            .iter()
+            .filter_map(|part| part.as_deref())
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A release returned by an indexer, normalized across indexers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseCandidate {
+    pub indexer: String,
+    pub title: String,
+    pub guid: String,
+    pub download_url: Option<String>,
+    pub magnet_url: Option<String>,
+    pub info_hash: Option<String>,
+    pub size: u64,
+    pub seeders: Option<u32>,
+    pub peers: Option<u32>,
+    pub grabs: Option<u32>,
+    pub categories: Vec<u32>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub format: Option<String>,
+    pub bitrate: Option<u32>,
+    pub score: f64,
+}
+
+impl ReleaseCandidate {
+    fn empty(indexer: &str) -> Self {
+        Self {
+            indexer: indexer.to_string(),
+            title: String::new(),
+            guid: String::new(),
+            download_url: None,
+            magnet_url: None,
+            info_hash: None,
+            size: 0,
+            seeders: None,
+            peers: None,
+            grabs: None,
+            categories: Vec::new(),
+            published_at: None,
+            format: None,
+            bitrate: None,
+            score: 0.0,
+        }
+    }
+
+    /// Best magnet link for this release, if one can be determined
+    pub fn magnet(&self) -> Option<String> {
+        if let Some(magnet) = &self.magnet_url {
+            return Some(magnet.clone());
+        }
+
+        if let Some(url) = self
+            .download_url
+            .as_ref()
+            .filter(|u| u.starts_with("magnet:"))
+        {
+            return Some(url.clone());
+        }
+
+        self.info_hash.as_ref().map(|hash| {
+            format!(
+                "magnet:?xt=urn:btih:{}&dn={}",
+                hash,
+                urlencoding::encode(&self.title)
+            )
+        })
+    }
+
+    /// Audio quality inferred from the release title
+    pub fn audio_quality(&self) -> AudioQuality {
+        AudioQuality {
+            bitrate: self.bitrate,
+            sample_rate: None,
+            channels: None,
+            format: self.format.clone(),
+        }
+    }
+
+    /// Whether the release is in a lossless format
+    pub fn is_lossless(&self) -> bool {
+        matches!(
+            self.format.as_deref(),
+            Some("flac") | Some("alac") | Some("ape") | Some("wav") | Some("aiff")
+        )
+    }
+
+    /// Fill in format and bitrate from the title and compute the ranking score
+    fn normalize(&mut self) {
+        let title_lower = self.title.to_lowercase();
+        let quality = parse_audio_info_from_filename(&self.title);
+
+        // Release titles rarely end in a real file extension, so fall back to
+        // looking for well-known format tags anywhere in the title
+        self.format = quality
+            .format
+            .filter(|format| KNOWN_FORMATS.contains(&format.as_str()))
+            .or_else(|| detect_format_from_title(&title_lower));
+        self.bitrate = quality
+            .bitrate
+            .or_else(|| detect_bitrate_from_title(&title_lower));
+
+        if let Some(hash) = &self.info_hash {
+            self.info_hash = Some(hash.to_lowercase());
+        }
+
+        self.score = self.compute_score();
+    }
+
+    /// Rank by audio quality first, then by how well seeded the release is
+    fn compute_score(&self) -> f64 {
+        let mut quality = self.audio_quality();
+        if self.is_lossless() && quality.bitrate.is_none() {
+            // Lossless releases rarely state a bitrate; score them at CD rate
+            quality.bitrate = Some(1411);
+        }
+
+        let availability = match self.seeders {
+            Some(0) => 0.0,
+            Some(seeders) => ((seeders as f64 + 1.0).ln() / 101f64.ln()).min(1.0),
+            // Usenet results have no seeders, availability is not a concern
+            None if self.magnet().is_none() => 0.5,
+            None => 0.1,
+        };
+
+        let score = quality.quality_score() * 0.6 + availability * 0.4;
+        (score * 1000.0).round() / 1000.0
+    }
+}
+
+/// Audio formats recognised in release titles
+const KNOWN_FORMATS: &[&str] = &[
+    "flac", "alac", "ape", "wav", "aiff", "mp3", "aac", "m4a", "ogg", "opus", "wma",
+];
+
+fn detect_format_from_title(title_lower: &str) -> Option<String> {
+    let tokens: Vec<&str> = title_lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    KNOWN_FORMATS
+        .iter()
+        .find(|format| tokens.contains(format))
+        .map(|format| match *format {
+            "m4a" => "aac".to_string(),
+            other => other.to_string(),
+        })
+}
+
+fn detect_bitrate_from_title(title_lower: &str) -> Option<u32> {
+    let tokens: Vec<&str> = title_lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    // LAME VBR presets, by their typical average bitrate
+    if tokens.contains(&"v0") {
+        return Some(245);
+    }
+    if tokens.contains(&"v2") {
+        return Some(190);
+    }
+
+    ["320", "256", "192", "128"]
+        .iter()
+        .find(|rate| tokens.contains(rate))
+        .and_then(|rate| rate.parse().ok())
+}
+
+/// Client that searches all configured Torznab indexers
+#[derive(Clone)]
+pub struct TorznabClient {
+    client: Client,
+    indexers: Vec<IndexerConfig>,
+}
+
+impl TorznabClient {
+    /// Create a new Torznab client for the given indexers
+    pub fn new(indexers: Vec<IndexerConfig>) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent("StepheyBot-Music/1.0")
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self { client, indexers })
+    }
+
+    /// Create a client for the indexers configured in the environment
+    pub fn from_env() -> Result<Self> {
+        Self::new(IndexerConfig::from_env())
+    }
+
+    /// Configured indexers
+    pub fn indexers(&self) -> &[IndexerConfig] {
+        &self.indexers
+    }
+
+    /// Whether at least one indexer is enabled
+    pub fn is_configured(&self) -> bool {
+        self.indexers.iter().any(|indexer| indexer.enabled)
+    }
+
+    /// Search every enabled indexer in parallel and return ranked candidates
+    ///
+    /// Indexers that fail are logged and skipped; an error is only returned
+    /// when no indexer could be queried at all.
+    pub async fn search(&self, query: &TorznabQuery) -> Result<Vec<ReleaseCandidate>> {
+        let enabled: Vec<&IndexerConfig> = self.indexers.iter().filter(|i| i.enabled).collect();
+        if enabled.is_empty() {
+            anyhow::bail!("No indexers configured");
+        }
+
+        info!(
+            "Searching {} indexer(s) for '{}'",
+            enabled.len(),
+            query.search_terms()
+        );
+
+        let results = join_all(
+            enabled
+                .iter()
+                .map(|indexer| self.search_indexer(indexer, query)),
+        )
+        .await;
+
+        let mut candidates = Vec::new();
+        let mut failures = Vec::new();
+
+        for (indexer, result) in enabled.iter().zip(results) {
+            match result {
+                Ok(found) => {
+                    debug!("Indexer {} returned {} results", indexer.name, found.len());
+                    candidates.extend(found);
+                }
+                Err(e) => {
+                    warn!("Indexer {} search failed: {}", indexer.name, e);
+                    failures.push(format!("{}: {}", indexer.name, e));
+                }
+            }
+        }
+
+        if failures.len() == enabled.len() {
+            anyhow::bail!("All indexers failed: {}", failures.join("; "));
+        }
+
+        Ok(rank_candidates(candidates, query))
+    }
+
+    /// Query a single indexer
+    async fn search_indexer(
+        &self,
+        indexer: &IndexerConfig,
+        query: &TorznabQuery,
+    ) -> Result<Vec<ReleaseCandidate>> {
+        let mut url = indexer.api_url()?;
+        {
+            let mut pairs = url.query_pairs_mut();
+            pairs.append_pair("t", "search");
+            pairs.append_pair("apikey", &indexer.api_key);
+            pairs.append_pair("q", &query.search_terms());
+            if !indexer.categories.is_empty() {
+                let categories: Vec<String> =
+                    indexer.categories.iter().map(|c| c.to_string()).collect();
+                pairs.append_pair("cat", &categories.join(","));
+            }
+        }
+
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to indexer {}", indexer.name))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Indexer returned HTTP {}", response.status());
+        }
+
+        let body = response
+            .text()
+            .await
+            .context("Failed to read indexer response")?;
+
+        parse_torznab_response(&body, &indexer.name)
+    }
+}
+
+/// Filter out unrelated results, collapse duplicates and sort by score
+fn rank_candidates(
+    candidates: Vec<ReleaseCandidate>,
+    query: &TorznabQuery,
+) -> Vec<ReleaseCandidate> {
+    let wanted_artist = query
+        .artist
+        .as_deref()
+        .map(normalize_music_name)
+        .filter(|artist| !artist.is_empty());
+
+    // The same torrent is often listed by several indexers; keep the copy
+    // with the most seeders
+    let mut by_key: HashMap<String, ReleaseCandidate> = HashMap::new();
+    for candidate in candidates {
+        if let Some(artist) = &wanted_artist {
+            if !normalize_music_name(&candidate.title).contains(artist.as_str()) {
+                continue;
+            }
+        }
+
+        let key = candidate
+            .info_hash
+            .clone()
+            .unwrap_or_else(|| format!("{}:{}", candidate.indexer, candidate.guid));
+
+        match by_key.get(&key) {
+            Some(existing) if existing.seeders >= candidate.seeders => {}
+            _ => {
+                by_key.insert(key, candidate);
+            }
+        }
+    }
+
+    let mut ranked: Vec<ReleaseCandidate> = by_key.into_values().collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| b.seeders.cmp(&a.seeders))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    ranked.truncate(query.limit.unwrap_or(DEFAULT_RESULT_LIMIT));
+    ranked
+}
+
+/// Parse a Torznab/Newznab RSS response into normalized candidates
+pub fn parse_torznab_response(xml: &str, indexer: &str) -> Result<Vec<ReleaseCandidate>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut candidates = Vec::new();
+    let mut current: Option<ReleaseCandidate> = None;
+    let mut element = String::new();
+
+    loop {
+        match reader
+            .read_event()
+            .context("Failed to parse indexer response")?
+        {
+            Event::Start(e) => {
+                let name = local_name(&e);
+                if name == "item" {
+                    current = Some(ReleaseCandidate::empty(indexer));
+                }
+                element = name;
+            }
+            Event::Empty(e) => {
+                let name = local_name(&e);
+                let attrs = attributes(&e);
+
+                if name == "error" {
+                    anyhow::bail!(
+                        "Indexer error {}: {}",
+                        attrs.get("code").map(String::as_str).unwrap_or("?"),
+                        attrs
+                            .get("description")
+                            .map(String::as_str)
+                            .unwrap_or("unknown")
+                    );
+                }
+
+                if let Some(candidate) = current.as_mut() {
+                    match name.as_str() {
+                        "attr" => apply_attr(candidate, &attrs),
+                        "enclosure" => {
+                            if let Some(url) = attrs.get("url") {
+                                candidate.download_url.get_or_insert(url.clone());
+                            }
+                            if candidate.size == 0 {
+                                if let Some(length) = attrs.get("length") {
+                                    candidate.size = length.parse().unwrap_or(0);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Text(e) => {
+                if let Some(candidate) = current.as_mut() {
+                    let text = e.unescape().context("Invalid text in indexer response")?;
+                    apply_text(candidate, &element, &text);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(candidate) = current.as_mut() {
+                    let text = String::from_utf8_lossy(&e.into_inner()).into_owned();
+                    apply_text(candidate, &element, &text);
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"item" {
+                    if let Some(mut candidate) = current.take() {
+                        if !candidate.title.is_empty() {
+                            candidate.normalize();
+                            candidates.push(candidate);
+                        }
+                    }
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(candidates)
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn attributes(e: &BytesStart) -> HashMap<String, String> {
+    e.attributes()
+        .flatten()
+        .filter_map(|attr| {
+            let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+            attr.unescape_value()
+                .ok()
+                .map(|value| (key, value.into_owned()))
+        })
+        .collect()
+}
+
+fn apply_text(candidate: &mut ReleaseCandidate, element: &str, text: &str) {
+    match element {
+        "title" => candidate.title.push_str(text),
+        "guid" => candidate.guid.push_str(text),
+        "link" => {
+            candidate.download_url = Some(text.to_string());
+        }
+        "size" => candidate.size = text.trim().parse().unwrap_or(candidate.size),
+        "pubDate" => {
+            candidate.published_at = DateTime::parse_from_rfc2822(text.trim())
+                .ok()
+                .map(|date| date.with_timezone(&Utc));
+        }
+        "category" => {
+            if let Ok(category) = text.trim().parse() {
+                if !candidate.categories.contains(&category) {
+                    candidate.categories.push(category);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Apply a `torznab:attr` / `newznab:attr` name/value pair
+fn apply_attr(candidate: &mut ReleaseCandidate, attrs: &HashMap<String, String>) {
+    let (Some(name), Some(value)) = (attrs.get("name"), attrs.get("value")) else {
+        return;
+    };
+
+    match name.as_str() {
+        "seeders" => candidate.seeders = value.parse().ok(),
+        "peers" => candidate.peers = value.parse().ok(),
+        "grabs" => candidate.grabs = value.parse().ok(),
+        "infohash" => candidate.info_hash = Some(value.clone()),
+        "magneturl" => candidate.magnet_url = Some(value.clone()),
+        "size" if candidate.size == 0 => candidate.size = value.parse().unwrap_or(0),
+        "category" => {
+            if let Ok(category) = value.parse() {
+                if !candidate.categories.contains(&category) {
+                    candidate.categories.push(category);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Query, routing::get, Router};
+
+    const SAMPLE_FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+  <channel>
+    <title>Mock Indexer</title>
+    <item>
+      <title>Boards of Canada - Geogaddi (2002) [MP3 320]</title>
+      <guid>https://tracker.example/details/1</guid>
+      <link>https://tracker.example/download/1.torrent</link>
+      <pubDate>Tue, 18 Feb 2020 10:00:00 +0000</pubDate>
+      <size>160000000</size>
+      <category>3010</category>
+      <torznab:attr name="seeders" value="40"/>
+      <torznab:attr name="peers" value="45"/>
+      <torznab:attr name="infohash" value="AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"/>
+    </item>
+    <item>
+      <title><![CDATA[Boards of Canada - Geogaddi (2002) [FLAC]]]></title>
+      <guid>https://tracker.example/details/2</guid>
+      <enclosure url="https://tracker.example/download/2.torrent" length="480000000" type="application/x-bittorrent"/>
+      <torznab:attr name="category" value="3040"/>
+      <torznab:attr name="seeders" value="25"/>
+      <torznab:attr name="peers" value="30"/>
+      <torznab:attr name="magneturl" value="magnet:?xt=urn:btih:bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb&amp;dn=Geogaddi"/>
+    </item>
+    <item>
+      <title>Boards of Canada - Geogaddi [FLAC] (dead)</title>
+      <guid>https://tracker.example/details/3</guid>
+      <size>470000000</size>
+      <torznab:attr name="seeders" value="0"/>
+      <torznab:attr name="infohash" value="cccccccccccccccccccccccccccccccccccccccc"/>
+    </item>
+  </channel>
+</rss>"#;
+
+    #[test]
+    fn test_parse_torznab_response() {
+        let candidates = parse_torznab_response(SAMPLE_FEED, "mock").unwrap();
+        assert_eq!(candidates.len(), 3);
+
+        let mp3 = &candidates[0];
+        assert_eq!(mp3.indexer, "mock");
+        assert_eq!(mp3.format.as_deref(), Some("mp3"));
+        assert_eq!(mp3.bitrate, Some(320));
+        assert_eq!(mp3.seeders, Some(40));
+        assert_eq!(mp3.size, 160_000_000);
+        assert_eq!(mp3.categories, vec![3010]);
+        assert!(mp3.published_at.is_some());
+        assert_eq!(
+            mp3.info_hash.as_deref(),
+            Some("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")
+        );
+        assert!(mp3
+            .magnet()
+            .unwrap()
+            .starts_with("magnet:?xt=urn:btih:aaaa"));
+
+        let flac = &candidates[1];
+        assert_eq!(flac.title, "Boards of Canada - Geogaddi (2002) [FLAC]");
+        assert_eq!(flac.format.as_deref(), Some("flac"));
+        assert_eq!(flac.size, 480_000_000);
+        assert!(flac.magnet_url.as_deref().unwrap().contains("&dn=Geogaddi"));
+        assert!(flac.score > mp3.score);
+
+        assert!(candidates[2].score < mp3.score);
+    }
+
+    #[test]
+    fn test_parse_torznab_error() {
+        let xml =
+            r#"<?xml version="1.0"?><error code="100" description="Incorrect user credentials"/>"#;
+        let err = parse_torznab_response(xml, "mock").unwrap_err();
+        assert!(err.to_string().contains("Incorrect user credentials"));
+    }
+
+    #[tokio::test]
+    async fn test_search_mock_indexers() {
+        let app = Router::new()
+            .route(
+                "/good/api",
+                get(|Query(params): Query<HashMap<String, String>>| async move {
+                    assert_eq!(params.get("t").map(String::as_str), Some("search"));
+                    assert_eq!(params.get("cat").map(String::as_str), Some("3000"));
+                    assert_eq!(
+                        params.get("q").map(String::as_str),
+                        Some("Boards of Canada Geogaddi")
+                    );
+                    SAMPLE_FEED
+                }),
+            )
+            .route(
+                "/bad/api",
+                get(|| async { (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "boom") }),
+            );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = TorznabClient::new(vec![
+            IndexerConfig::new("good", &format!("http://{}/good", addr), "key"),
+            IndexerConfig::new("bad", &format!("http://{}/bad/", addr), "key"),
+        ])
+        .unwrap();
+
+        let mut query = TorznabQuery::album("Boards of Canada", "Geogaddi");
+        query.limit = Some(2);
+
+        let candidates = client.search(&query).await.unwrap();
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].format.as_deref(), Some("flac"));
+        assert_eq!(candidates[1].format.as_deref(), Some("mp3"));
+
+        let all_bad = TorznabClient::new(vec![IndexerConfig::new(
+            "bad",
+            &format!("http://{}/bad", addr),
+            "key",
+        )])
+        .unwrap();
+        assert!(all_bad.search(&query).await.is_err());
+    }
+}
@@ -45,6 +45,20 @@ pub struct TorrentInfo {
     pub eta: i64,
     #[serde(rename = "uploadRatio")]
     pub ratio: f64,
+    #[serde(rename = "secondsSeeding", default)]
+    pub seconds_seeding: u64,
+}
+
+/// Speed limits of the Transmission session, speeds in KB/s
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SpeedSettings {
+    pub speed_limit_down: u64,
+    pub speed_limit_down_enabled: bool,
+    pub speed_limit_up: u64,
+    pub speed_limit_up_enabled: bool,
+    /// Turtle mode, using the alternative limits configured in Transmission
+    pub alt_speed_enabled: bool,
 }
 
 impl TorrentInfo {
@@ -250,6 +264,38 @@ impl TransmissionClient {
             serde_json::Value::String(magnet_url.to_string()),
         );
 
+        self.add_torrent(arguments, download_dir, paused).await
+    }
+
+    /// Add a torrent from the contents of a `.torrent` file
+    pub async fn add_torrent_file(
+        &mut self,
+        torrent: &[u8],
+        download_dir: Option<&str>,
+        paused: Option<bool>,
+    ) -> Result<String> {
+        use base64::Engine;
+
+        info!(
+            "📄 Adding torrent file to Transmission ({} bytes)",
+            torrent.len()
+        );
+
+        let mut arguments = serde_json::Map::new();
+        arguments.insert(
+            "metainfo".to_string(),
+            serde_json::Value::String(base64::engine::general_purpose::STANDARD.encode(torrent)),
+        );
+
+        self.add_torrent(arguments, download_dir, paused).await
+    }
+
+    async fn add_torrent(
+        &mut self,
+        mut arguments: serde_json::Map<String, serde_json::Value>,
+        download_dir: Option<&str>,
+        paused: Option<bool>,
+    ) -> Result<String> {
         if let Some(dir) = download_dir {
             arguments.insert(
                 "download-dir".to_string(),
@@ -304,6 +350,7 @@ impl TransmissionClient {
             "errorString",
             "eta",
             "uploadRatio",
+            "secondsSeeding",
         ];
 
         let mut arguments = serde_json::Map::new();
@@ -418,6 +465,46 @@ impl TransmissionClient {
         .context("Failed to get session stats")
     }
 
+    /// Get the session's speed limits
+    pub async fn get_speed_settings(&mut self) -> Result<SpeedSettings> {
+        let fields = [
+            "speed-limit-down",
+            "speed-limit-down-enabled",
+            "speed-limit-up",
+            "speed-limit-up-enabled",
+            "alt-speed-enabled",
+        ];
+        let response = self
+            .rpc_request("session-get", serde_json::json!({ "fields": fields }))
+            .await
+            .context("Failed to get session speed limits")?;
+
+        serde_json::from_value(response).context("Failed to parse session speed limits")
+    }
+
+    /// Replace the session's speed limits
+    pub async fn set_speed_settings(&mut self, settings: &SpeedSettings) -> Result<()> {
+        self.rpc_request("session-set", serde_json::to_value(settings)?)
+            .await
+            .context("Failed to set session speed limits")?;
+
+        info!(
+            "🚦 Transmission speed limits: down {} up {} alt-speed {}",
+            if settings.speed_limit_down_enabled {
+                format!("{} KB/s", settings.speed_limit_down)
+            } else {
+                "unlimited".to_string()
+            },
+            if settings.speed_limit_up_enabled {
+                format!("{} KB/s", settings.speed_limit_up)
+            } else {
+                "unlimited".to_string()
+            },
+            settings.alt_speed_enabled
+        );
+        Ok(())
+    }
+
     /// Get the current download directory
     pub async fn get_download_dir(&mut self) -> Result<String> {
         let response = self
@@ -453,6 +540,7 @@ mod tests {
             error: "".to_string(),
             eta: 3600,
             ratio: 0.5,
+            seconds_seeding: 0,
         };
 
         assert_eq!(torrent.status_string(), "downloading");
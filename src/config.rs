@@ -263,9 +263,9 @@ impl Config {
             .set_default("server.port", 8080)?
             .set_default("server.enable_admin_api", true)?
             .set_default("server.request_timeout", 30)?
-            .set_default("database.url", "sqlite:data/stepheybot-music.db")?
-            .set_default("database.max_connections", 10)?
-            .set_default("database.connection_timeout", 30)?
+            .set_default("database.url", default_database_url())?
+            .set_default("database.max_connections", default_max_connections())?
+            .set_default("database.connection_timeout", default_db_timeout())?
             .set_default("listenbrainz.url", "https://api.listenbrainz.org")?
             .set_default("listenbrainz.timeout", 30)?
             .set_default("listenbrainz.rate_limit", 60)?
@@ -544,8 +544,8 @@ mod tests {
             },
             navidrome: NavidromeConfig {
                 url: "http://localhost:4533".to_string(),
-                admin_user: "admin".to_string(),
-                admin_password: "password".to_string(),
+                username: "admin".to_string(),
+                password: "password".to_string(),
                 timeout: 30,
                 verify_ssl: true,
             },
@@ -13,32 +13,72 @@ mod navidrome_addon;
 mod services;
 mod utils;
 
+use crate::auth::{optional_auth_middleware, AuthConfig, AuthService};
+use crate::clients::lidarr::{LidarrClient, Page, QueueItem};
+use crate::clients::navidrome::NavidromeClient;
+use crate::clients::torznab::{TorznabClient, TorznabQuery};
+use crate::clients::RetryConfig;
+use crate::database::Database;
 use crate::lidarr_addon::is_lidarr_configured;
-use crate::models::entities::DownloadRequest;
+use crate::models::entities::{DownloadPriority, DownloadRequest, DownloadStatus};
+use crate::models::user::AuthenticatedUser;
+use crate::services::bandwidth::BandwidthConfig;
+use crate::services::disk_space::{DiskSpaceConfig, InsufficientSpace, SpaceLevel};
 use crate::services::download_service::{DownloadConfig, DownloadService};
+use crate::services::download_store::DownloadHistoryFilter;
+use crate::services::events::{EventFilter, EventTopic, LiveEvent, Subscription};
+use crate::services::import::DEFAULT_LIBRARY_TEMPLATE;
+use crate::services::lidarr_webhook::LidarrWebhookPayload;
+#[cfg(feature = "social")]
+use crate::services::listening_party::ListeningParties;
+use crate::services::play_queue::{PlayQueue, PlayQueueService, SavePlayQueue};
+use crate::services::playback::{
+    EndReport, ListeningSession, NowPlayingReport, PlaybackService, ProgressReport,
+};
+use crate::services::quality_profile::QualityProfile;
+use crate::services::quota::{QuotaExceeded, QuotaPolicy, Requester};
+use crate::services::radio::{RadioConfig, RadioPick, RadioService, RadioSource, StartRadio};
+use crate::services::recycle_bin::{RecycleBinConfig, TrashStatus, TRASH_DIR_NAME};
+use crate::services::remote_control::{
+    DeviceConnection, DeviceMessage, PlayerCommand, RemoteControl, ServerMessage,
+};
+use crate::services::scrobble::{ScrobbleConfig, Scrobbler};
+use crate::services::stall::StallPolicy;
+use crate::services::tiering::{Tier, TieringConfig, TieringService};
+use crate::services::torrent_file::{self, TorrentMetainfo};
+use crate::services::user_service::UserService;
+use crate::services::wishlist::{CandidateStatus, SettingsUpdate, WishlistConfig, WishlistService};
 
 use anyhow::Result;
 use axum::{
-    extract::{Json as ExtractJson, Path, Query, State},
-    http::{header, StatusCode},
-    response::{Html, Json, Response},
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        FromRequest, Json as ExtractJson, Multipart, Path, Query, Request, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        Html, Json, Response,
+    },
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
 use chrono::Utc;
 use lidarr_addon::{
     create_lidarr_addon, get_lidarr_connection_status, test_lidarr_integration, LidarrSearchResult,
 };
 use navidrome_addon::{create_navidrome_addon, get_connection_status, test_navidrome_integration};
-use rand::random;
 use serde_json::{json, Value};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::timeout;
-use tower::ServiceBuilder;
+use tower::{ServiceBuilder, ServiceExt};
 use tower_http::{
-    compression::CompressionLayer, cors::CorsLayer, services::ServeDir, trace::TraceLayer,
+    compression::CompressionLayer,
+    cors::CorsLayer,
+    services::{ServeDir, ServeFile},
+    trace::TraceLayer,
 };
 use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -88,8 +128,39 @@ async fn main() -> Result<()> {
     info!("✅ Lidarr integration test completed: {:?}", lidarr_test);
     info!("🚀 Lidarr integration setup complete");
 
+    // Initialize database
+    info!("🗄️ Initializing database...");
+    let database_url = std::env::var("STEPHEYBOT__DATABASE__URL")
+        .unwrap_or_else(|_| "sqlite:/data/stepheybot-music.db".to_string());
+    let database = Arc::new(Database::new(&database_url).await?);
+    database.migrate().await?;
+    info!("✅ Database ready");
+
+    // Initialize authentication; without a JWT secret requests stay anonymous
+    let auth_service = match std::env::var("STEPHEYBOT__AUTH__JWT_SECRET")
+        .or_else(|_| std::env::var("JWT_SECRET"))
+    {
+        Ok(secret) => {
+            let auth_config = match (
+                std::env::var("STEPHEYBOT__AUTH__KEYCLOAK_REALM_URL"),
+                std::env::var("STEPHEYBOT__AUTH__KEYCLOAK_CLIENT_ID"),
+            ) {
+                (Ok(realm_url), Ok(client_id)) => AuthConfig::new(realm_url, client_id, secret),
+                _ => AuthConfig::development(secret),
+            };
+            let user_service = Arc::new(UserService::new(database.clone()));
+            info!("🔐 Authentication enabled, downloads are attributed to users");
+            Some(Arc::new(AuthService::new(auth_config, user_service)?))
+        }
+        Err(_) => {
+            warn!("⚠️ Authentication not configured, downloads are attributed to 'system'");
+            None
+        }
+    };
+
     // Initialize Download Service
     info!("🔧 Initializing Download Service...");
+    let download_path = std::path::PathBuf::from("/hot_downloads");
     let download_config = DownloadConfig {
         transmission_url: std::env::var("STEPHEYBOT__TRANSMISSION__URL")
             .unwrap_or_else(|_| "http://stepheybot_music_vpn:9091".to_string()),
@@ -97,14 +168,58 @@ async fn main() -> Result<()> {
             .unwrap_or_else(|_| "admin".to_string()),
         transmission_password: std::env::var("STEPHEYBOT__TRANSMISSION__PASSWORD")
             .unwrap_or_else(|_| "adminadmin".to_string()),
-        download_path: std::path::PathBuf::from("/hot_downloads"),
+        trash: RecycleBinConfig::from_env(&download_path.join(TRASH_DIR_NAME)),
+        download_path,
         processing_path: std::path::PathBuf::from("/processing"),
         final_library_path: std::path::PathBuf::from("/final_library"),
         category: "stepheybot-music".to_string(),
+        indexers: crate::clients::torznab::IndexerConfig::from_env(),
+        quality_profile: QualityProfile::from_env(),
+        library_template: std::env::var("STEPHEYBOT__IMPORT__TEMPLATE")
+            .unwrap_or_else(|_| DEFAULT_LIBRARY_TEMPLATE.to_string()),
+        navidrome_url: navidrome_addon.enabled.then(|| navidrome_addon.url.clone()),
+        navidrome_username: navidrome_addon.username.clone(),
+        navidrome_password: navidrome_addon.password.clone(),
+        seed_ratio_limit: std::env::var("STEPHEYBOT__SEEDING__RATIO_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2.0),
+        seed_time_limit: std::env::var("STEPHEYBOT__SEEDING__TIME_LIMIT_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|hours| hours.is_finite() && *hours >= 0.0)
+            .map(|hours| Duration::from_secs_f64(hours * 3600.0))
+            .unwrap_or(Duration::from_secs(86400)),
+        quotas: QuotaPolicy {
+            allow_anonymous: auth_service.is_none(),
+            ..QuotaPolicy::from_env()
+        },
+        lidarr_webhook_secret: std::env::var("STEPHEYBOT__LIDARR__WEBHOOK_SECRET").ok(),
+        stall_policy: StallPolicy {
+            timeout: std::env::var("STEPHEYBOT__DOWNLOADS__STALL_TIMEOUT_MINUTES")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|minutes| *minutes > 0)
+                .map(|minutes| Duration::from_secs(minutes * 60))
+                .unwrap_or(StallPolicy::default().timeout),
+            max_restarts: std::env::var("STEPHEYBOT__DOWNLOADS__STALL_RESTARTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(StallPolicy::default().max_restarts),
+        },
+        retry: RetryConfig {
+            max_retries: std::env::var("STEPHEYBOT__DOWNLOADS__RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(RetryConfig::default().max_retries),
+            ..RetryConfig::default()
+        },
+        bandwidth: BandwidthConfig::from_env(),
+        disk_space: DiskSpaceConfig::from_env(),
         ..Default::default()
     };
 
-    let download_service = Arc::new(DownloadService::new(download_config));
+    let download_service = Arc::new(DownloadService::new(download_config, database.clone())?);
 
     // Start the download service
     if let Err(e) = download_service.start().await {
@@ -113,6 +228,80 @@ async fn main() -> Result<()> {
         info!("✅ Download service started successfully");
     }
 
+    // Initialize the wishlist autopilot; users opt in individually
+    let wishlist_config = WishlistConfig {
+        navidrome_url: navidrome_addon.enabled.then(|| navidrome_addon.url.clone()),
+        navidrome_username: navidrome_addon.username.clone(),
+        navidrome_password: navidrome_addon.password.clone(),
+        ..WishlistConfig::from_env()
+    };
+    let wishlist_service = Arc::new(WishlistService::new(
+        wishlist_config,
+        database.clone(),
+        download_service.clone(),
+    )?);
+    wishlist_service.start();
+    info!("✅ Wishlist autopilot ready");
+
+    // Keep frequently played tracks on the hot tier
+    let tiering_service = Arc::new(TieringService::new(
+        TieringConfig::from_env(),
+        database.clone(),
+    ));
+    tiering_service.start();
+
+    // Play queues follow users from device to device
+    let play_queue_service = Arc::new(PlayQueueService::new(database.clone()));
+
+    // Radio, play reports and scrobbles all talk to Navidrome
+    let navidrome_client = navidrome_addon
+        .enabled
+        .then(|| {
+            NavidromeClient::new(
+                &navidrome_addon.url,
+                &navidrome_addon.username,
+                &navidrome_addon.password,
+            )
+        })
+        .and_then(|client| {
+            client
+                .map_err(|e| warn!("⚠️ Radio and scrobbling to Navidrome disabled: {}", e))
+                .ok()
+        });
+    let radio_source = navidrome_client
+        .clone()
+        .map(|client| Arc::new(client) as Arc<dyn RadioSource>);
+    let radio_service = Arc::new(RadioService::new(
+        RadioConfig::from_env(),
+        database.clone(),
+        play_queue_service.clone(),
+        radio_source,
+    ));
+    let remote_control =
+        Arc::new(RemoteControl::new(play_queue_service.clone()).with_radio(radio_service.clone()));
+
+    // Players report what they play; finished plays are scrobbled
+    let scrobbler = Arc::new(Scrobbler::new(
+        ScrobbleConfig::from_env(),
+        database.clone(),
+        navidrome_client.clone(),
+    ));
+    scrobbler.start();
+    let playback_service = Arc::new(PlaybackService::new(
+        database.clone(),
+        radio_service.clone(),
+        scrobbler.clone(),
+        navidrome_client,
+    ));
+
+    // Hosts and their guests listen along in sync
+    #[cfg(feature = "social")]
+    let listening_parties = {
+        let parties = Arc::new(ListeningParties::new(database.clone()));
+        parties.start();
+        parties
+    };
+
     // Create router
     let app = Router::new()
         // Health check endpoints
@@ -155,6 +344,25 @@ async fn main() -> Result<()> {
         .route("/api/v1/lidarr/artists", get(lidarr_artists))
         .route("/api/v1/lidarr/search/:query", get(lidarr_search))
         .route("/api/v1/lidarr/add", post(lidarr_add_artist))
+        .route("/api/v1/lidarr/webhook", post(lidarr_webhook))
+        .route("/api/v1/lidarr/wanted/missing", get(lidarr_missing))
+        .route(
+            "/api/v1/lidarr/albums/:album_id/monitor",
+            post(lidarr_set_album_monitored),
+        )
+        .route(
+            "/api/v1/lidarr/albums/:album_id/search",
+            post(lidarr_search_album),
+        )
+        .route("/api/v1/lidarr/queue", get(lidarr_queue))
+        .route(
+            "/api/v1/lidarr/queue/:queue_id/remove",
+            post(lidarr_remove_queue_item),
+        )
+        .route("/api/v1/lidarr/history", get(lidarr_history))
+        // Indexer search endpoints
+        .route("/api/v1/indexers", get(list_indexers))
+        .route("/api/v1/indexers/search", get(indexer_search))
         // Download integration endpoints
         .route(
             "/api/v1/download/musicbrainz/:mbid",
@@ -175,6 +383,31 @@ async fn main() -> Result<()> {
         .route("/api/v1/player/pause", post(pause_playback))
         .route("/api/v1/player/next", post(next_track))
         .route("/api/v1/player/previous", post(previous_track))
+        .route("/api/v1/player/devices", get(list_player_devices))
+        .route(
+            "/api/v1/player/devices/:device_id/transfer",
+            post(transfer_playback),
+        )
+        .route(
+            "/api/v1/player/devices/:device_id/command",
+            post(command_player_device),
+        )
+        .route("/api/v1/player/ws", get(player_ws_endpoint))
+        .route("/api/v1/radio", get(get_radio_station))
+        .route("/api/v1/radio/start", post(start_radio))
+        .route("/api/v1/radio/stop", post(stop_radio))
+        .route("/api/v1/playback/now-playing", post(report_now_playing))
+        .route("/api/v1/playback/sessions", get(list_listening_sessions))
+        .route("/api/v1/scrobbles", get(get_scrobble_status))
+        .route("/api/v1/scrobbles/retry", post(retry_scrobbles))
+        .route(
+            "/api/v1/playback/sessions/:session_id/progress",
+            post(report_playback_progress),
+        )
+        .route(
+            "/api/v1/playback/sessions/:session_id/end",
+            post(report_playback_end),
+        )
         // Global search endpoints
         .route("/api/v1/search/global/:query", get(global_search))
         .route("/api/v1/search/external/:query", get(external_search))
@@ -189,6 +422,74 @@ async fn main() -> Result<()> {
             get(get_active_downloads_endpoint),
         )
         .route("/api/v1/download/stats", get(get_download_stats_endpoint))
+        .route(
+            "/api/v1/download/history",
+            get(get_download_history_endpoint),
+        )
+        .route("/api/v1/download/quota", get(get_download_quota_endpoint))
+        .route("/api/v1/download/seeding", get(get_seeding_status_endpoint))
+        .route(
+            "/api/v1/download/approvals",
+            get(list_pending_approvals_endpoint),
+        )
+        .route(
+            "/api/v1/download/approvals/:request_id/approve",
+            post(approve_download_endpoint),
+        )
+        .route(
+            "/api/v1/download/approvals/:request_id/reject",
+            post(reject_download_endpoint),
+        )
+        .route("/api/v1/notifications", get(list_notifications_endpoint))
+        .route("/api/v1/events", get(events_sse_endpoint))
+        .route("/api/v1/events/ws", get(events_ws_endpoint))
+        .route(
+            "/api/v1/notifications/:id/read",
+            post(mark_notification_read_endpoint),
+        )
+        .route(
+            "/api/v1/wishlist/settings",
+            get(get_wishlist_settings_endpoint).post(update_wishlist_settings_endpoint),
+        )
+        .route("/api/v1/wishlist/run", post(run_wishlist_endpoint))
+        .route(
+            "/api/v1/wishlist/candidates",
+            get(list_wishlist_candidates_endpoint),
+        )
+        .route(
+            "/api/v1/wishlist/candidates/:id/approve",
+            post(approve_wishlist_candidate_endpoint),
+        )
+        .route(
+            "/api/v1/wishlist/candidates/:id/reject",
+            post(reject_wishlist_candidate_endpoint),
+        )
+        .route("/api/v1/wishlist/audit", get(wishlist_audit_endpoint))
+        .route("/api/v1/download/blocklist", get(get_blocklist_endpoint))
+        .route("/api/v1/download/bandwidth", get(get_bandwidth_endpoint))
+        .route("/api/v1/storage/tiering", get(get_tiering_endpoint))
+        .route("/api/v1/storage/disk", get(get_disk_space_endpoint))
+        .route("/api/v1/storage/tiering/run", post(run_tiering_endpoint))
+        .route("/api/v1/trash", get(list_trash_endpoint))
+        .route("/api/v1/trash/:id/restore", post(restore_trash_endpoint))
+        .route("/api/v1/trash/:id/purge", post(purge_trash_endpoint))
+        .route("/api/v1/download/queue", get(get_download_queue_endpoint))
+        .route(
+            "/api/v1/download/priority/:request_id",
+            post(set_download_priority_endpoint),
+        )
+        .route(
+            "/api/v1/download/manual-imports",
+            get(list_manual_imports_endpoint),
+        )
+        .route(
+            "/api/v1/download/manual-imports/:id/retry",
+            post(retry_manual_import_endpoint),
+        )
+        .route(
+            "/api/v1/download/manual-imports/:id/dismiss",
+            post(dismiss_manual_import_endpoint),
+        )
         .route(
             "/api/v1/download/pause/:hash",
             post(pause_download_endpoint),
@@ -210,16 +511,40 @@ async fn main() -> Result<()> {
         .nest_service("/_app", ServeDir::new("/app/frontend/_app"))
         .route("/favicon.svg", get(serve_favicon))
         // Root route - serve the frontend
-        .route("/", get(serve_frontend))
+        .route("/", get(serve_frontend));
+
+    #[cfg(feature = "social")]
+    let app = app
+        .merge(parties_api::routes())
+        .layer(Extension(listening_parties));
+
+    let app = app
         // Smart fallback - API routes get 404 JSON, others get frontend for SPA routing
         .fallback(smart_fallback)
         .with_state(download_service.clone())
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CompressionLayer::new())
-                .layer(CorsLayer::permissive()),
-        );
+        .layer(Extension(wishlist_service))
+        .layer(Extension(tiering_service))
+        .layer(Extension(play_queue_service))
+        .layer(Extension(remote_control))
+        .layer(Extension(radio_service))
+        .layer(Extension(playback_service))
+        .layer(Extension(scrobbler));
+
+    // Identify the caller where a token is presented
+    let app = match auth_service {
+        Some(auth_service) => app.layer(axum::middleware::from_fn_with_state(
+            auth_service,
+            optional_auth_middleware,
+        )),
+        None => app,
+    };
+
+    let app = app.layer(
+        ServiceBuilder::new()
+            .layer(TraceLayer::new_for_http())
+            .layer(CompressionLayer::new())
+            .layer(CorsLayer::permissive()),
+    );
 
     // Get port from environment or use default
     let port = std::env::var("STEPHEYBOT__SERVER__PORT")
@@ -304,11 +629,18 @@ async fn serve_favicon() -> Result<Response, StatusCode> {
 }
 
 /// Basic health check
-async fn health_check() -> Result<Json<Value>, StatusCode> {
+async fn health_check(
+    State(download_service): State<Arc<DownloadService>>,
+) -> Result<Json<Value>, StatusCode> {
+    let disk = download_service.disk_space_status().await;
+
     Ok(Json(json!({
-        "status": "healthy",
+        // Low disk space needs attention but the service itself is still up
+        "status": if disk.level == SpaceLevel::Ok { "healthy" } else { "degraded" },
         "service": "stepheybot-music",
         "version": env!("CARGO_PKG_VERSION"),
+        "disk_space": disk.level,
+        "alerts": disk.alerts(),
         "timestamp": chrono::Utc::now().to_rfc3339()
     })))
 }
@@ -372,45 +704,31 @@ async fn trigger_sync() -> Result<Json<Value>, StatusCode> {
     })))
 }
 
-/// Get recommendations for a user with real tracks from Navidrome
-async fn get_recommendations(Path(user_id): Path<String>) -> Result<Json<Value>, StatusCode> {
+/// Get recommendations for a user from their radio station, or from what they are playing
+async fn get_recommendations(
+    Extension(radio): Extension<Arc<RadioService>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
     info!("Fetching recommendations for user: {}", user_id);
 
-    let addon = create_navidrome_addon();
-
-    // Try to get real tracks from Navidrome
-    match addon.get_random_tracks(10).await {
-        Ok(real_tracks) => {
-            let recommendations: Vec<Value> = real_tracks
+    match radio.recommend(&user_id, 10).await {
+        Ok(picks) => {
+            let recommendations: Vec<Value> = picks
                 .into_iter()
                 .enumerate()
-                .map(|(i, track)| {
-                    let recommendation_types = ["collaborative", "content", "popular"];
-                    let reasons = [
-                        "Based on your listening history and similar users",
-                        "Similar musical characteristics to your favorites",
-                        "Trending in your music community",
-                        "Discovered through advanced AI analysis",
-                        "Perfect match for your current mood",
-                        "Recommended by music experts",
-                    ];
-
-                    // Generate a realistic score between 0.75 and 0.95
-                    let score = 0.75 + (i as f64 * 0.02) + (random::<f64>() * 0.15);
-
+                .map(|(i, pick)| {
                     json!({
                         "id": format!("rec_{}", i + 1),
-                        "track_id": track.id.clone(),
-                        "title": track.title,
-                        "artist": track.artist,
-                        "album": track.album,
-                        "duration": track.duration,
-                        "year": track.year,
-                        "score": (score * 100.0).round() / 100.0, // Round to 2 decimal places
-                        "recommendation_type": recommendation_types[i % recommendation_types.len()],
-                        "reason": reasons[i % reasons.len()],
-                        "genres": track.genre.map(|g| vec![g]).unwrap_or_else(|| vec!["Unknown".to_string()]),
-                        "stream_url": format!("/api/v1/stream/{}", track.id),
+                        "track_id": pick.track.id,
+                        "title": pick.track.title,
+                        "artist": pick.track.artist,
+                        "album": pick.track.album,
+                        "duration": pick.track.duration_secs,
+                        "score": (pick.score * 100.0).round() / 100.0, // Round to 2 decimal places
+                        "recommendation_type": pick.origin,
+                        "reason": pick.origin.reason(),
+                        "genres": pick.track.genre.clone().map(|g| vec![g]).unwrap_or_else(|| vec!["Unknown".to_string()]),
+                        "stream_url": format!("/api/v1/stream/{}", pick.track.id),
                         "added_at": chrono::Utc::now().to_rfc3339()
                     })
                 })
@@ -421,13 +739,13 @@ async fn get_recommendations(Path(user_id): Path<String>) -> Result<Json<Value>,
                 "recommendations": recommendations,
                 "total": recommendations.len(),
                 "generated_at": chrono::Utc::now().to_rfc3339(),
-                "algorithm_version": "1.0.0",
-                "source": "navidrome_random",
-                "note": "Using real tracks from your Navidrome library"
+                "algorithm_version": "2.0.0",
+                "source": "radio",
+                "note": "Picked from your Navidrome library by the radio engine"
             })))
         }
         Err(e) => {
-            warn!("Failed to get tracks from Navidrome, using fallback: {}", e);
+            warn!("Failed to get recommendations, using fallback: {}", e);
 
             // Fallback to a few sample recommendations if Navidrome fails
             let fallback_recommendations = vec![json!({
@@ -450,9 +768,9 @@ async fn get_recommendations(Path(user_id): Path<String>) -> Result<Json<Value>,
                 "recommendations": fallback_recommendations,
                 "total": fallback_recommendations.len(),
                 "generated_at": chrono::Utc::now().to_rfc3339(),
-                "algorithm_version": "1.0.0",
+                "algorithm_version": "2.0.0",
                 "source": "fallback",
-                "error": e,
+                "error": e.to_string(),
                 "note": "Failed to connect to Navidrome - showing fallback message"
             })))
         }
@@ -878,8 +1196,11 @@ async fn lidarr_search(Path(query): Path<String>) -> Result<Json<Value>, StatusC
 
 /// Add artist to Lidarr monitoring
 async fn lidarr_add_artist(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
     ExtractJson(payload): ExtractJson<Value>,
 ) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
     let addon = create_lidarr_addon();
 
     if !addon.enabled {
@@ -962,6 +1283,15 @@ async fn lidarr_add_artist(
                 "Successfully added artist '{}' to Lidarr monitoring",
                 artist_name
             );
+            if let Err(e) = download_service
+                .record_artist_request(foreign_artist_id, artist_name, &requester)
+                .await
+            {
+                warn!(
+                    "Failed to record artist request for '{}': {}",
+                    artist_name, e
+                );
+            }
             Ok(Json(json!({
                 "success": true,
                 "message": format!("Added '{}' to monitoring", artist_name),
@@ -981,10 +1311,381 @@ async fn lidarr_add_artist(
     }
 }
 
+/// Receive grab, import, upgrade, rename and delete events from Lidarr
+///
+/// Lidarr authenticates with the shared webhook secret, sent either as an
+/// `X-Api-Key` header or a `token` query parameter.
+async fn lidarr_webhook(
+    State(download_service): State<Arc<DownloadService>>,
+    headers: HeaderMap,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+    ExtractJson(payload): ExtractJson<LidarrWebhookPayload>,
+) -> Result<Json<Value>, StatusCode> {
+    if !download_service.lidarr_webhook_enabled() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    let secret = headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| params.get("token").map(|s| s.as_str()))
+        .unwrap_or_default();
+    if !download_service.verify_lidarr_webhook(secret) {
+        warn!("Rejected Lidarr webhook with invalid secret");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    match download_service.handle_lidarr_event(&payload).await {
+        Ok(request) => Ok(Json(json!({
+            "success": true,
+            "event": payload.event(),
+            "request": request,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => {
+            error!(
+                "Failed to handle Lidarr {} event: {}",
+                payload.event_type, e
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Build a Lidarr API client, or the response to send when Lidarr is not
+/// configured
+fn lidarr_client() -> Result<LidarrClient, Json<Value>> {
+    let addon = create_lidarr_addon();
+    if !addon.enabled {
+        return Err(Json(json!({
+            "success": false,
+            "error": "Lidarr not configured",
+            "timestamp": Utc::now()
+        })));
+    }
+
+    LidarrClient::new(&addon.url, &addon.api_key).map_err(|e| {
+        Json(json!({
+            "success": false,
+            "error": format!("Failed to create Lidarr client: {}", e),
+            "timestamp": Utc::now()
+        }))
+    })
+}
+
+/// Read `page` and `page_size` query parameters for paged Lidarr resources
+fn lidarr_paging(params: &std::collections::HashMap<String, String>) -> (u32, u32) {
+    let page = params
+        .get("page")
+        .and_then(|v| v.parse().ok())
+        .filter(|page| *page > 0)
+        .unwrap_or(1);
+    let page_size = params
+        .get("page_size")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(20u32)
+        .clamp(1, 200);
+    (page, page_size)
+}
+
+/// List monitored albums Lidarr has not found yet
+async fn lidarr_missing(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    requester_for(&download_service, user)?;
+    let client = match lidarr_client() {
+        Ok(client) => client,
+        Err(response) => return Ok(response),
+    };
+    let (page, page_size) = lidarr_paging(&params);
+
+    match client.get_missing(page, page_size).await {
+        Ok(missing) => Ok(Json(json!({
+            "success": true,
+            "albums": missing.records,
+            "page": missing.page,
+            "page_size": missing.page_size,
+            "total": missing.total_records,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load missing albums: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Turn monitoring on or off for a single album
+async fn lidarr_set_album_monitored(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(album_id): Path<u32>,
+    ExtractJson(payload): ExtractJson<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+    let monitored = payload
+        .get("monitored")
+        .and_then(|v| v.as_bool())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let client = match lidarr_client() {
+        Ok(client) => client,
+        Err(response) => return Ok(response),
+    };
+
+    match client.set_albums_monitored(&[album_id], monitored).await {
+        Ok(()) => Ok(Json(json!({
+            "success": true,
+            "album_id": album_id,
+            "monitored": monitored,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to update album monitoring: {}", e),
+            "album_id": album_id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Ask Lidarr to search its indexers for one album
+async fn lidarr_search_album(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(album_id): Path<u32>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+    let client = match lidarr_client() {
+        Ok(client) => client,
+        Err(response) => return Ok(response),
+    };
+
+    match client.get_album(album_id).await {
+        Ok(Some(album)) => match client.search_album(album_id).await {
+            Ok(()) => Ok(Json(json!({
+                "success": true,
+                "message": format!("Searching for '{}'", album.title),
+                "album_id": album_id,
+                "timestamp": Utc::now()
+            }))),
+            Err(e) => Ok(Json(json!({
+                "success": false,
+                "error": format!("Failed to start album search: {}", e),
+                "album_id": album_id,
+                "timestamp": Utc::now()
+            }))),
+        },
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load album: {}", e),
+            "album_id": album_id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Show Lidarr's download queue with progress; `?stuck=true` lists only
+/// items that need attention
+async fn lidarr_queue(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    requester_for(&download_service, user)?;
+    let client = match lidarr_client() {
+        Ok(client) => client,
+        Err(response) => return Ok(response),
+    };
+    let (page, page_size) = lidarr_paging(&params);
+    let stuck_only = params.get("stuck").is_some_and(|v| v == "true" || v == "1");
+
+    // Stuck items can be on any page, so filter the whole queue and page the result
+    let result = if stuck_only {
+        client.get_full_queue().await.map(|records| {
+            let stuck: Vec<QueueItem> =
+                records.into_iter().filter(|item| item.is_stuck()).collect();
+            let total = stuck.len() as u32;
+            let records = stuck
+                .into_iter()
+                .skip(((page - 1) * page_size) as usize)
+                .take(page_size as usize)
+                .collect();
+            Page {
+                page,
+                page_size,
+                total_records: total,
+                records,
+            }
+        })
+    } else {
+        client.get_queue(page, page_size).await
+    };
+
+    match result {
+        Ok(queue) => {
+            let items: Vec<Value> = queue
+                .records
+                .iter()
+                .map(|item| {
+                    let mut value = serde_json::to_value(item).unwrap_or_default();
+                    value["progress"] = json!(item.progress());
+                    value["stuck"] = json!(item.is_stuck());
+                    value
+                })
+                .collect();
+
+            Ok(Json(json!({
+                "success": true,
+                "queue": items,
+                "page": queue.page,
+                "page_size": queue.page_size,
+                "total": queue.total_records,
+                "timestamp": Utc::now()
+            })))
+        }
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load Lidarr queue: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Remove an item from Lidarr's queue; by default it is also removed from
+/// the download client, `?blocklist=true` stops Lidarr grabbing it again
+async fn lidarr_remove_queue_item(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(queue_id): Path<u32>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+    let remove_from_client = params
+        .get("remove_from_client")
+        .is_none_or(|v| v == "true" || v == "1");
+    let blocklist = params
+        .get("blocklist")
+        .is_some_and(|v| v == "true" || v == "1");
+    let client = match lidarr_client() {
+        Ok(client) => client,
+        Err(response) => return Ok(response),
+    };
+
+    match client
+        .remove_queue_item(queue_id, remove_from_client, blocklist)
+        .await
+    {
+        Ok(()) => Ok(Json(json!({
+            "success": true,
+            "message": format!("Queue item {} removed", queue_id),
+            "queue_id": queue_id,
+            "removed_from_client": remove_from_client,
+            "blocklisted": blocklist,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to remove queue item: {}", e),
+            "queue_id": queue_id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Show Lidarr's grab and import history
+async fn lidarr_history(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    requester_for(&download_service, user)?;
+    let client = match lidarr_client() {
+        Ok(client) => client,
+        Err(response) => return Ok(response),
+    };
+    let (page, page_size) = lidarr_paging(&params);
+
+    match client.get_history(page, page_size).await {
+        Ok(history) => Ok(Json(json!({
+            "success": true,
+            "history": history.records,
+            "page": history.page,
+            "page_size": history.page_size,
+            "total": history.total_records,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load Lidarr history: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
 /// Stream track (proxy to Navidrome)
-async fn stream_track(Path(track_id): Path<String>) -> Result<Response, StatusCode> {
+async fn stream_track(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(tiering): Extension<Arc<TieringService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(track_id): Path<String>,
+    request: Request,
+) -> Result<Response, StatusCode> {
     let addon = create_navidrome_addon();
 
+    // Low-priority downloads make way while anyone is listening
+    let listener = user.map_or_else(
+        || "anonymous".to_string(),
+        |Extension(user)| user.id.to_string(),
+    );
+    download_service.record_stream(&listener);
+
+    // Tracks promoted to the hot tier are served straight from it; Navidrome is
+    // only asked for the path the first time a track id is streamed
+    if addon.enabled && tiering.is_enabled() {
+        let track_path = match tiering.track_path(&track_id).await {
+            Some(track_path) => Some(track_path),
+            None => match addon.get_track_path(&track_id).await {
+                Ok(Some(track_path)) => {
+                    tiering.remember_track_path(&track_id, &track_path).await;
+                    Some(track_path)
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Could not resolve the path of track {}: {}", track_id, e);
+                    None
+                }
+            },
+        };
+        let location = match track_path {
+            Some(track_path) => tiering.locate(&track_path).await,
+            None => None,
+        };
+        if let Some(location) = location.filter(|location| location.tier == Tier::Hot) {
+            let response = ServeFile::new(&location.path)
+                .oneshot(request)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let (mut parts, body) = response.into_parts();
+            parts.headers.insert(
+                "Access-Control-Allow-Origin",
+                header::HeaderValue::from_static("*"),
+            );
+            parts.headers.insert(
+                "Access-Control-Allow-Methods",
+                header::HeaderValue::from_static("GET, HEAD, OPTIONS"),
+            );
+            parts.headers.insert(
+                "Access-Control-Allow-Headers",
+                header::HeaderValue::from_static("Range, Content-Type"),
+            );
+            return Ok(Response::from_parts(parts, axum::body::Body::new(body)));
+        }
+    }
+
     if !addon.enabled {
         return Ok(Response::builder()
             .status(StatusCode::SERVICE_UNAVAILABLE)
@@ -1132,29 +1833,21 @@ async fn search_tracks(Path(query): Path<String>) -> Result<Json<Value>, StatusC
     }
 }
 
-/// Discover new music
-async fn discover_music() -> Result<Json<Value>, StatusCode> {
-    let navidrome_addon = create_navidrome_addon();
+/// Discover new music picked for the caller
+async fn discover_music(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(radio): Extension<Arc<RadioService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
     let lidarr_addon = create_lidarr_addon();
 
-    // Get some random tracks for discovery
-    let discovery_tracks = match navidrome_addon.get_random_tracks(20).await {
-        Ok(tracks) => tracks
-            .into_iter()
-            .map(|track| {
-                json!({
-                    "id": track.id.clone(),
-                    "title": track.title,
-                    "artist": track.artist,
-                    "album": track.album,
-                    "duration": track.duration,
-                    "year": track.year,
-                    "genre": track.genre,
-                    "stream_url": format!("/api/v1/stream/{}", track.id)
-                })
-            })
-            .collect::<Vec<_>>(),
-        Err(_) => vec![],
+    let discovery_tracks = match radio.recommend(&requester.user_id, 20).await {
+        Ok(picks) => picks.into_iter().map(radio_pick_json).collect::<Vec<_>>(),
+        Err(e) => {
+            warn!("No discovery tracks for {}: {}", requester.username, e);
+            vec![]
+        }
     };
 
     // Get trending artists from Lidarr (if available)
@@ -1168,95 +1861,583 @@ async fn discover_music() -> Result<Json<Value>, StatusCode> {
         "discovery": {
             "tracks": discovery_tracks,
             "trending_artists": trending_artists,
-            "recommendations": "Based on your library and trending music"
+            "recommendations": "Based on your radio station, what you play and what you skip"
         },
         "timestamp": Utc::now()
     })))
 }
 
-/// Get current player queue
-async fn get_player_queue() -> Result<Json<Value>, StatusCode> {
-    // Use the nvme stream directory for queue storage
-    let queue_dir = "/queue";
+fn radio_pick_json(pick: RadioPick) -> Value {
+    json!({
+        "id": pick.track.id.clone(),
+        "title": pick.track.title,
+        "artist": pick.track.artist,
+        "album": pick.track.album,
+        "duration": pick.track.duration_secs,
+        "genre": pick.track.genre,
+        "origin": pick.origin,
+        "score": (pick.score * 100.0).round() / 100.0,
+        "stream_url": format!("/api/v1/stream/{}", pick.track.id)
+    })
+}
 
-    // In a real implementation, this would read from the queue directory
-    // For now, return empty queue but with proper structure
-    Ok(Json(json!({
-        "success": true,
-        "queue": [],
-        "current_index": 0,
-        "queue_directory": queue_dir,
-        "timestamp": Utc::now()
-    })))
+/// The caller's running radio station, if any
+async fn get_radio_station(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(radio): Extension<Arc<RadioService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match radio.station(&requester.user_id).await {
+        Ok(station) => Ok(Json(json!({
+            "success": true,
+            "station": station,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load radio station: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
 }
 
-/// Update player queue
-async fn update_player_queue() -> Result<Json<Value>, StatusCode> {
-    // In a real implementation, this would accept a JSON body with tracks
-    // and store them in /queue directory (mounted from /mnt/nvme/stream)
-    let queue_dir = "/queue";
+/// Start endless radio from a seed, replacing the caller's queue
+async fn start_radio(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(radio): Extension<Arc<RadioService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    ExtractJson(start): ExtractJson<StartRadio>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let result = radio
+        .start(
+            &requester.user_id,
+            start.seed,
+            start.discovery_mode,
+            start.device.as_deref(),
+        )
+        .await;
+    match result {
+        Ok((station, queue)) => {
+            remote.broadcast_state(&requester.user_id).await;
+            Ok(Json(json!({
+                "success": true,
+                "station": station,
+                "current_track": queue.current(),
+                "queue": queue,
+                "timestamp": Utc::now()
+            })))
+        }
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to start radio: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
 
-    Ok(Json(json!({
-        "success": true,
-        "message": "Queue updated",
-        "queue_directory": queue_dir,
-        "note": "Queue files will be stored in /mnt/nvme/stream",
-        "timestamp": Utc::now()
-    })))
+/// Stop the caller's radio; the queue is left as it is
+async fn stop_radio(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(radio): Extension<Arc<RadioService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match radio.stop(&requester.user_id).await {
+        Ok(stopped) => Ok(Json(json!({
+            "success": true,
+            "stopped": stopped,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to stop radio: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
 }
 
-/// Get current playing track
-async fn get_current_track() -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "success": true,
-        "current_track": null,
-        "is_playing": false,
-        "position": 0,
-        "timestamp": Utc::now()
-    })))
+/// Report the track a player started; returns the session to send progress to
+async fn report_now_playing(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(playback): Extension<Arc<PlaybackService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    headers: HeaderMap,
+    ExtractJson(mut report): ExtractJson<NowPlayingReport>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    if !report.client.is_object() {
+        report.client = json!({});
+    }
+    if let (Some(client), Some(user_agent)) = (
+        report.client.as_object_mut(),
+        headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok()),
+    ) {
+        client
+            .entry("user_agent")
+            .or_insert_with(|| json!(user_agent));
+    }
+
+    match playback.now_playing(&requester.user_id, report).await {
+        Ok(session) => Ok(Json(json!({
+            "success": true,
+            "session": session,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to record now playing: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
 }
 
-/// Play specific track
-async fn play_track(Path(track_id): Path<String>) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "success": true,
-        "action": "play",
-        "track_id": track_id,
-        "message": "Track playback started",
-        "timestamp": Utc::now()
-    })))
+/// Heartbeat with the current position of an open session
+async fn report_playback_progress(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(playback): Extension<Arc<PlaybackService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(session_id): Path<i64>,
+    ExtractJson(report): ExtractJson<ProgressReport>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let result = playback
+        .progress(&requester.user_id, session_id, report.position_ms)
+        .await;
+    session_response(result, "record progress")
 }
 
-/// Pause playback
-async fn pause_playback() -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "success": true,
-        "action": "pause",
-        "message": "Playback paused",
-        "timestamp": Utc::now()
-    })))
+/// Report that a session's track ended or was skipped
+async fn report_playback_end(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(playback): Extension<Arc<PlaybackService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(session_id): Path<i64>,
+    ExtractJson(report): ExtractJson<EndReport>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let result = playback.end(&requester.user_id, session_id, report).await;
+    session_response(result, "end session")
 }
 
-/// Next track
-async fn next_track() -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "success": true,
-        "action": "next",
-        "message": "Skipped to next track",
-        "timestamp": Utc::now()
-    })))
+/// Unknown and already closed sessions are 404s
+fn session_response(
+    result: anyhow::Result<Option<ListeningSession>>,
+    action: &str,
+) -> Result<Json<Value>, StatusCode> {
+    match result {
+        Ok(Some(session)) => Ok(Json(json!({
+            "success": true,
+            "session": session,
+            "timestamp": Utc::now()
+        }))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to {}: {}", action, e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// The caller's recent listening sessions (?limit=, default 50)
+async fn list_listening_sessions(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(playback): Extension<Arc<PlaybackService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(50)
+        .clamp(1, 500);
+    match playback.recent(&requester.user_id, limit).await {
+        Ok(sessions) => Ok(Json(json!({
+            "success": true,
+            "total": sessions.len(),
+            "sessions": sessions,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load listening sessions: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Scrobble queue health per integration: last success, last error and backlog
+async fn get_scrobble_status(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(scrobbler): Extension<Arc<Scrobbler>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match scrobbler.status(&requester.user_id).await {
+        Ok(integrations) => Ok(Json(json!({
+            "success": true,
+            "integrations": integrations,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load scrobble status: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Retry the caller's waiting and given-up scrobbles now (?service= for one integration)
+async fn retry_scrobbles(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(scrobbler): Extension<Arc<Scrobbler>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let service = params.get("service").map(String::as_str);
+    match scrobbler.retry(&requester.user_id, service).await {
+        Ok(count) => Ok(Json(json!({
+            "success": true,
+            "retrying": count,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to retry scrobbles: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Get the caller's play queue, as last saved from any device
+async fn get_player_queue(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(play_queues): Extension<Arc<PlayQueueService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match play_queues.get(&requester.user_id).await {
+        Ok(queue) => Ok(Json(json!({
+            "success": true,
+            "current_track": queue.current(),
+            "play_order": queue.play_order(),
+            "queue": queue,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load play queue: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Replace the caller's play queue, like Subsonic's savePlayQueue
+async fn update_player_queue(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(play_queues): Extension<Arc<PlayQueueService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    ExtractJson(save): ExtractJson<SavePlayQueue>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let result = play_queues
+        .update(&requester.user_id, save.device.as_deref(), |queue| {
+            if let Some(repeat_mode) = save.repeat_mode {
+                queue.set_repeat(repeat_mode);
+            }
+            if let Some(shuffle) = save.shuffle {
+                queue.set_shuffle(shuffle);
+            }
+            queue.replace(save.tracks, save.current_index, save.position_ms);
+        })
+        .await;
+    remote.broadcast_state(&requester.user_id).await;
+    player_response(result, "queue", None)
+}
+
+/// Get the caller's current track and playback position
+async fn get_current_track(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(play_queues): Extension<Arc<PlayQueueService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match play_queues.get(&requester.user_id).await {
+        Ok(queue) => Ok(Json(json!({
+            "success": true,
+            "current_track": queue.current(),
+            "current_index": queue.current_index,
+            "is_playing": queue.is_playing,
+            "position_ms": queue.position_ms,
+            "updated_by_device": queue.updated_by_device,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load play queue: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Play a track on the active device, queueing it after the current one if needed
+async fn play_track(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(track_id): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let command = PlayerCommand::Play {
+        track_id: Some(track_id),
+    };
+    let result = remote
+        .execute(
+            &requester.user_id,
+            device_param(&params),
+            target_param(&params),
+            command,
+        )
+        .await;
+    player_response(result, "play", Some("Track playback started"))
+}
+
+/// Pause playback, remembering `?position_ms=` so another device can resume
+async fn pause_playback(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let position_ms = match params.get("position_ms") {
+        Some(value) => Some(value.parse().map_err(|_| StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    let result = remote
+        .execute(
+            &requester.user_id,
+            device_param(&params),
+            target_param(&params),
+            PlayerCommand::Pause { position_ms },
+        )
+        .await;
+    player_response(result, "pause", Some("Playback paused"))
+}
+
+/// Next track, honouring the repeat mode
+async fn next_track(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let result = remote
+        .execute(
+            &requester.user_id,
+            device_param(&params),
+            target_param(&params),
+            PlayerCommand::Next,
+        )
+        .await;
+    player_response(result, "next", Some("Skipped to next track"))
+}
+
+/// Previous track, or back to the start of the current one
+async fn previous_track(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let result = remote
+        .execute(
+            &requester.user_id,
+            device_param(&params),
+            target_param(&params),
+            PlayerCommand::Previous,
+        )
+        .await;
+    player_response(result, "previous", Some("Skipped to previous track"))
 }
 
-/// Previous track
-async fn previous_track() -> Result<Json<Value>, StatusCode> {
+/// The caller's connected devices and which one is playing
+async fn list_player_devices(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let (devices, active_device) = remote.devices(&requester.user_id);
     Ok(Json(json!({
         "success": true,
-        "action": "previous",
-        "message": "Skipped to previous track",
+        "devices": devices,
+        "active_device": active_device,
         "timestamp": Utc::now()
     })))
 }
 
+/// Move playback to another device (`{"play": false}` to transfer paused)
+async fn transfer_playback(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(device_id): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+    payload: Option<ExtractJson<Value>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let play = payload
+        .and_then(|ExtractJson(payload)| payload.get("play").and_then(Value::as_bool))
+        .unwrap_or(true);
+    let result = remote
+        .transfer(&requester.user_id, device_param(&params), &device_id, play)
+        .await;
+    player_response(result, "transfer", Some("Playback transferred"))
+}
+
+/// Send a command (play, pause, seek, next, previous, volume) to one device
+async fn command_player_device(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(device_id): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+    ExtractJson(command): ExtractJson<PlayerCommand>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let action = command.name();
+    let result = remote
+        .execute(
+            &requester.user_id,
+            device_param(&params),
+            Some(&device_id),
+            command,
+        )
+        .await;
+    player_response(result, action, None)
+}
+
+/// Register the caller's player as a device (`?device_id=&name=`) and
+/// exchange commands and state with it
+async fn player_ws_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(remote): Extension<Arc<RemoteControl>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let device_id = params
+        .get("device_id")
+        .filter(|id| !id.is_empty())
+        .cloned()
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let name = params
+        .get("name")
+        .cloned()
+        .unwrap_or_else(|| device_id.clone());
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        let connection = remote.connect(&requester.user_id, &device_id, &name).await;
+        run_device_socket(socket, &remote, connection).await;
+    }))
+}
+
+async fn run_device_socket(
+    mut socket: WebSocket,
+    remote: &RemoteControl,
+    mut connection: DeviceConnection,
+) {
+    loop {
+        tokio::select! {
+            message = connection.recv() => {
+                let Some(message) = message else { break };
+                let Ok(text) = serde_json::to_string(&message) else { continue };
+                if socket.send(WsMessage::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(WsMessage::Text(text))) => {
+                    let handled = match serde_json::from_str::<DeviceMessage>(&text) {
+                        Ok(message) => remote
+                            .handle(&connection.user_id, &connection.device_id, message)
+                            .await
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(format!("Invalid message: {}", e)),
+                    };
+                    if let Err(message) = handled {
+                        let error = ServerMessage::Error { message };
+                        let Ok(text) = serde_json::to_string(&error) else { continue };
+                        if socket.send(WsMessage::Text(text)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+    remote.disconnect(&connection).await;
+}
+
+/// The `?device=` a player control came from
+fn device_param(params: &std::collections::HashMap<String, String>) -> Option<&str> {
+    params
+        .get("device")
+        .map(String::as_str)
+        .filter(|device| !device.is_empty())
+}
+
+/// The `?target=` device a player control is meant for, by default the active one
+fn target_param(params: &std::collections::HashMap<String, String>) -> Option<&str> {
+    params
+        .get("target")
+        .map(String::as_str)
+        .filter(|target| !target.is_empty())
+}
+
+fn player_response(
+    result: anyhow::Result<PlayQueue>,
+    action: &str,
+    message: Option<&str>,
+) -> Result<Json<Value>, StatusCode> {
+    match result {
+        Ok(queue) => Ok(Json(json!({
+            "success": true,
+            "action": action,
+            "message": message,
+            "current_track": queue.current(),
+            "is_playing": queue.is_playing,
+            "position_ms": queue.position_ms,
+            "queue": queue,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => {
+            error!("Player {} failed: {}", action, e);
+            Ok(Json(json!({
+                "success": false,
+                "action": action,
+                "error": format!("Failed to update play queue: {}", e),
+                "timestamp": Utc::now()
+            })))
+        }
+    }
+}
+
 /// Global search combining local library and external sources
 async fn global_search(
     Path(query): Path<String>,
@@ -1461,10 +2642,31 @@ async fn external_search(Path(query): Path<String>) -> Result<Json<Value>, Statu
 }
 
 /// Request download of a track via Download Service
+///
+/// Takes JSON, or a multipart form with a `.torrent` file in its `torrent`
+/// field and the artist, title, album and source as text fields.
 async fn request_download(
     State(download_service): State<Arc<DownloadService>>,
-    ExtractJson(payload): ExtractJson<Value>,
+    user: Option<Extension<AuthenticatedUser>>,
+    request: Request,
 ) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+    if is_multipart {
+        let multipart = Multipart::from_request(request, &())
+            .await
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        return upload_torrent_download(&download_service, &requester, multipart).await;
+    }
+
+    let ExtractJson(payload) = ExtractJson::<Value>::from_request(request, &())
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
     info!("Download request received: {:?}", payload);
 
     let track_title = payload
@@ -1484,11 +2686,79 @@ async fn request_download(
     let external_id = payload
         .get("external_id")
         .and_then(|v| v.as_str())
-        .unwrap_or("");
+        .unwrap_or("")
+        .to_string();
     let source = payload
         .get("source")
         .and_then(|v| v.as_str())
         .unwrap_or("unknown");
+    let priority = match payload.get("priority").and_then(|v| v.as_str()) {
+        Some(priority) => priority
+            .parse::<DownloadPriority>()
+            .map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => DownloadPriority::default(),
+    };
+
+    // Torrent URLs are fetched by the download service, after the quota checks,
+    // so the torrent can be checked before it is added
+    let torrent_url = payload
+        .get("torrent_url")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .or_else(|| {
+            (external_id.starts_with("http://") || external_id.starts_with("https://"))
+                .then(|| external_id.clone())
+        });
+    if let Some(url) = torrent_url {
+        let mut download_request = DownloadRequest::new(
+            requester.user_id.clone(),
+            artist_name.to_string(),
+            track_title.to_string(),
+        );
+        download_request.album_title = album_title;
+        download_request.priority = priority;
+
+        return match download_service
+            .submit_torrent_url(download_request, &url, &requester)
+            .await
+        {
+            Ok(request) => {
+                info!(
+                    "Successfully submitted torrent URL download: {}",
+                    request.id
+                );
+                Ok(Json(json!({
+                    "success": true,
+                    "message": format!("Download {} for {} by {} (torrent URL)", submitted_verb(&request), track_title, artist_name),
+                    "request_id": request.id,
+                    "artist_added": false,
+                    "artist_name": artist_name,
+                    "track_title": track_title,
+                    "status": request.status,
+                    "download_method": if request.magnet_url.is_some() { "magnet" } else { "torrent_file" },
+                    "torrent_hash": request.torrent_hash,
+                    "file_size": request.file_size,
+                    "source": source,
+                    "timestamp": Utc::now()
+                })))
+            }
+            Err(e) => {
+                warn!("Failed to queue torrent from {}: {}", url, e);
+                Ok(Json(json!({
+                    "success": false,
+                    "message": format!("Failed to queue download: {}", e),
+                    "artist_name": artist_name,
+                    "track_title": track_title,
+                    "status": "failed",
+                    "error": e.to_string(),
+                    "quota_exceeded": e.downcast_ref::<QuotaExceeded>(),
+                    "insufficient_space": e.downcast_ref::<InsufficientSpace>(),
+                    "timestamp": Utc::now()
+                })))
+            }
+        };
+    }
+    let external_id = external_id.as_str();
 
     // Check if this is a direct download (magnet link)
     let is_magnet_link = external_id.starts_with("magnet:");
@@ -1517,26 +2787,30 @@ async fn request_download(
 
         // Create download request
         let mut download_request = DownloadRequest::new_with_magnet(
-            "system".to_string(),
+            requester.user_id.clone(),
             artist_name.to_string(),
             track_title.to_string(),
             magnet_url.to_string(),
             None,
         );
         download_request.album_title = album_title;
+        download_request.priority = priority;
 
         // Submit to download service
-        match download_service.add_download(download_request).await {
-            Ok(request_id) => {
-                info!("Successfully queued download: {}", request_id);
+        match download_service
+            .submit_download(download_request, &requester)
+            .await
+        {
+            Ok(request) => {
+                info!("Successfully submitted download: {}", request.id);
                 return Ok(Json(json!({
                     "success": true,
-                    "message": format!("Download queued for {} by {} (direct download)", track_title, artist_name),
-                    "request_id": request_id,
+                    "message": format!("Download {} for {} by {} (direct download)", submitted_verb(&request), track_title, artist_name),
+                    "request_id": request.id,
                     "artist_added": false,
                     "artist_name": artist_name,
                     "track_title": track_title,
-                    "status": "queued",
+                    "status": request.status,
                     "download_method": "magnet",
                     "magnet_url": magnet_url,
                     "external_id": external_id,
@@ -1553,15 +2827,63 @@ async fn request_download(
                     "track_title": track_title,
                     "status": "failed",
                     "error": e.to_string(),
+                    "quota_exceeded": e.downcast_ref::<QuotaExceeded>(),
+                    "insufficient_space": e.downcast_ref::<InsufficientSpace>(),
                     "timestamp": Utc::now()
                 })));
             }
         }
     }
 
-    // If not a magnet link, try to add artist to Lidarr monitoring (fallback behavior)
-    let lidarr_addon = create_lidarr_addon();
-
+    // Without a magnet link, let the download service pick a release from the indexers
+    if download_service.can_search_indexers() {
+        let mut download_request = DownloadRequest::new(
+            requester.user_id.clone(),
+            artist_name.to_string(),
+            track_title.to_string(),
+        );
+        download_request.album_title = album_title;
+        download_request.priority = priority;
+
+        return match download_service
+            .submit_download(download_request, &requester)
+            .await
+        {
+            Ok(request) => {
+                info!("Submitted indexer search for download: {}", request.id);
+                Ok(Json(json!({
+                    "success": true,
+                    "message": format!("Indexer search {} for {} by {}", submitted_verb(&request), track_title, artist_name),
+                    "request_id": request.id,
+                    "artist_added": false,
+                    "artist_name": artist_name,
+                    "track_title": track_title,
+                    "status": request.status,
+                    "download_method": "indexer",
+                    "source": source,
+                    "timestamp": Utc::now()
+                })))
+            }
+            Err(e) => {
+                error!("Failed to queue indexer search: {}", e);
+                Ok(Json(json!({
+                    "success": false,
+                    "message": format!("Failed to queue download: {}", e),
+                    "artist_name": artist_name,
+                    "track_title": track_title,
+                    "status": "failed",
+                    "error": e.to_string(),
+                    "quota_exceeded": e.downcast_ref::<QuotaExceeded>(),
+                    "insufficient_space": e.downcast_ref::<InsufficientSpace>(),
+                    "timestamp": Utc::now()
+                })))
+            }
+        };
+    }
+
+    // If not a magnet link, try to add artist to Lidarr monitoring (fallback behavior)
+    let lidarr_addon = create_lidarr_addon();
+
     // First, try to add the artist to Lidarr monitoring with timeout
     let search_result = timeout(
         Duration::from_secs(10),
@@ -1583,6 +2905,19 @@ async fn request_download(
                 match add_result {
                     Ok(Ok(_)) => {
                         info!("Artist {} added to Lidarr monitoring", artist.artist_name);
+                        if let Err(e) = download_service
+                            .record_artist_request(
+                                &artist.foreign_artist_id,
+                                &artist.artist_name,
+                                &requester,
+                            )
+                            .await
+                        {
+                            warn!(
+                                "Failed to record artist request for '{}': {}",
+                                artist.artist_name, e
+                            );
+                        }
                         Ok(Json(json!({
                             "success": true,
                             "message": format!("Download request submitted for {} by {}", track_title, artist_name),
@@ -2505,6 +3840,47 @@ async fn search_downloadable_releases(
     entity_name: &str,
     artist_name: &str,
 ) -> Result<Vec<Value>, String> {
+    // Prefer native indexers when configured, they can find a single album
+    // without the artist being monitored in Lidarr
+    if let Ok(indexers) = TorznabClient::from_env() {
+        if indexers.is_configured() {
+            let mut query = TorznabQuery::album(artist_name, entity_name);
+            query.limit = Some(5);
+
+            match indexers.search(&query).await {
+                Ok(candidates) if !candidates.is_empty() => {
+                    info!(
+                        "Found {} indexer releases for {} by {}",
+                        candidates.len(),
+                        entity_name,
+                        artist_name
+                    );
+                    return Ok(candidates
+                        .iter()
+                        .map(|candidate| {
+                            json!({
+                                "id": candidate.guid,
+                                "title": candidate.title,
+                                "artist": artist_name,
+                                "quality": candidate.audio_quality().description(),
+                                "size_mb": candidate.size / 1024 / 1024,
+                                "seeders": candidate.seeders.unwrap_or(0),
+                                "indexer": candidate.indexer,
+                                "magnet_url": candidate.magnet(),
+                                "score": candidate.score,
+                                "can_download": candidate.magnet().is_some(),
+                                "storage_tier": "hot",
+                                "estimated_download_time": "2-5 minutes"
+                            })
+                        })
+                        .collect());
+                }
+                Ok(_) => info!("No indexer releases found for {}, trying Lidarr", mbid),
+                Err(e) => warn!("Indexer search failed for {}: {}", mbid, e),
+            }
+        }
+    }
+
     let lidarr_addon = create_lidarr_addon();
 
     if !lidarr_addon.enabled {
@@ -2574,132 +3950,1213 @@ async fn search_downloadable_releases(
                 Err("Failed to parse Lidarr response".to_string())
             }
         }
-        Err(e) => Err(format!("Failed to query Lidarr: {}", e)),
+        Err(e) => Err(format!("Failed to query Lidarr: {}", e)),
+    }
+}
+
+/// List configured Torznab indexers
+async fn list_indexers() -> Result<Json<Value>, StatusCode> {
+    let client = TorznabClient::from_env().map_err(|e| {
+        error!("Failed to create indexer client: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "indexers": client.indexers(),
+        "total": client.indexers().len(),
+        "configured": client.is_configured(),
+        "quality_profile": QualityProfile::from_env(),
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Search all configured indexers for ranked release candidates
+async fn indexer_search(Query(query): Query<TorznabQuery>) -> Result<Json<Value>, StatusCode> {
+    let client = TorznabClient::from_env().map_err(|e| {
+        error!("Failed to create indexer client: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match client.search(&query).await {
+        Ok(candidates) => Ok(Json(json!({
+            "success": true,
+            "query": query,
+            "candidates": candidates,
+            "total": candidates.len(),
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": e.to_string(),
+            "query": query,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Smart fallback handler - returns 404 JSON for API routes, frontend for others
+async fn smart_fallback(uri: axum::http::Uri) -> Result<Response, StatusCode> {
+    let path = uri.path();
+
+    if path.starts_with("/api/") {
+        // Return JSON 404 for API routes
+        let json_response = Json(json!({
+            "error": "Not Found",
+            "message": format!("API endpoint {} not found", path),
+            "timestamp": Utc::now()
+        }));
+
+        Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(&json_response.0).unwrap().into())
+            .unwrap())
+    } else {
+        // Serve frontend for non-API routes (SPA routing)
+        match serve_frontend().await {
+            Ok(html) => Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "text/html")
+                .body(html.0.into())
+                .unwrap()),
+            Err(status) => Err(status),
+        }
+    }
+}
+
+/// Get download status by request ID; users only see their own requests
+async fn get_download_status_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(request_id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let Some(request) = download_service.get_download_status(&request_id).await else {
+        return Ok(Json(json!({
+            "success": false,
+            "error": "Download request not found",
+            "request_id": request_id,
+            "timestamp": Utc::now()
+        })));
+    };
+    if request.user_id != requester.user_id && !requester.is_admin() {
+        warn!(
+            "User {} may not view download {}",
+            requester.username, request_id
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let transitions = download_service
+        .get_transitions(&request_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to load transitions for {}: {}", request_id, e);
+            Vec::new()
+        });
+    let candidates = download_service
+        .get_candidates(&request_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to load release candidates for {}: {}",
+                request_id, e
+            );
+            Vec::new()
+        });
+    let estimate = download_service.queue_estimate(&request_id).await;
+
+    Ok(Json(json!({
+        "success": true,
+        "request_id": request.id,
+        "status": request.status,
+        "priority": request.priority,
+        "priority_override": request.priority_override,
+        "queue_position": estimate.as_ref().map(|e| e.position),
+        "estimated_start": estimate.as_ref().map(|e| e.estimated_start),
+        "artist_name": request.artist_name,
+        "track_title": request.track_title,
+        "album_title": request.album_title,
+        "progress": request.progress.unwrap_or(0.0),
+        "file_size": request.file_size,
+        "download_speed": request.download_speed,
+        "seeds": request.seeds,
+        "peers": request.peers,
+        "torrent_hash": request.torrent_hash,
+        "requested_at": request.requested_at,
+        "started_at": request.started_at,
+        "completed_at": request.completed_at,
+        "processed_at": request.processed_at,
+        "error_message": request.error_message,
+        "transitions": transitions,
+        "candidates": candidates,
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Get all active downloads
+async fn get_active_downloads_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+) -> Result<Json<Value>, StatusCode> {
+    let active_downloads = download_service.get_active_downloads().await;
+
+    Ok(Json(json!({
+        "success": true,
+        "active_downloads": active_downloads.len(),
+        "downloads": active_downloads.iter().map(|d| json!({
+            "id": d.id,
+            "request_id": d.download_request_id,
+            "torrent_hash": d.torrent_hash,
+            "name": d.name,
+            "status": d.status,
+            "progress": d.progress,
+            "size": d.size,
+            "downloaded": d.downloaded,
+            "download_speed": d.download_speed,
+            "upload_speed": d.upload_speed,
+            "seeds": d.seeds,
+            "peers": d.peers,
+            "eta": d.eta,
+            "added_at": d.added_at,
+            "completed_at": d.completed_at
+        })).collect::<Vec<_>>(),
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Get download statistics
+async fn get_download_stats_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+) -> Result<Json<Value>, StatusCode> {
+    let stats = download_service.get_stats().await;
+
+    Ok(Json(json!({
+        "success": true,
+        "stats": {
+            "total_downloads": stats.total_downloads,
+            "completed_downloads": stats.completed_downloads,
+            "failed_downloads": stats.failed_downloads,
+            "cancelled_downloads": stats.cancelled_downloads,
+            "active_downloads": stats.active_downloads,
+            "queued_downloads": stats.queued_downloads,
+            "total_downloaded_bytes": stats.total_downloaded_bytes,
+            "total_uploaded_bytes": stats.total_uploaded_bytes,
+            "average_download_speed": stats.average_download_speed,
+            "by_status": stats.by_status,
+            "last_updated": stats.last_updated
+        },
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Get the persisted download history, optionally filtered by user or status;
+/// only admins can see other users' downloads
+async fn get_download_history_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(mut filter): Query<DownloadHistoryFilter>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    if !requester.is_admin() {
+        filter.user_id = Some(requester.user_id);
+    }
+    match download_service.get_history(&filter).await {
+        Ok(history) => Ok(Json(json!({
+            "success": true,
+            "total": history.len(),
+            "downloads": history,
+            "filter": filter,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => {
+            error!("Failed to load download history: {}", e);
+            Ok(Json(json!({
+                "success": false,
+                "error": format!("Failed to load download history: {}", e),
+                "timestamp": Utc::now()
+            })))
+        }
+    }
+}
+
+/// Work out who is making a request; without authentication configured,
+/// anonymous requests are attributed to "system"
+fn requester_for(
+    download_service: &DownloadService,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Requester, StatusCode> {
+    match user {
+        Some(Extension(user)) => Ok(Requester::from(&user)),
+        None if download_service.allows_anonymous_requests() => Ok(Requester::system()),
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Like [`requester_for`], but only admins get through
+fn admin_for(
+    download_service: &DownloadService,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Requester, StatusCode> {
+    let requester = requester_for(download_service, user)?;
+    if requester.is_admin() {
+        Ok(requester)
+    } else {
+        warn!("Admin access denied for user: {}", requester.username);
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Queue a download for a `.torrent` file sent as multipart form data
+async fn upload_torrent_download(
+    download_service: &DownloadService,
+    requester: &Requester,
+    mut multipart: Multipart,
+) -> Result<Json<Value>, StatusCode> {
+    let mut fields = std::collections::HashMap::new();
+    let mut torrent = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "torrent" || name == "file" {
+            let bytes = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+            if bytes.len() > torrent_file::MAX_TORRENT_SIZE {
+                return Err(StatusCode::PAYLOAD_TOO_LARGE);
+            }
+            torrent = Some(bytes.to_vec());
+        } else {
+            let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+            fields.insert(name, value);
+        }
+    }
+
+    let Some(bytes) = torrent else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let metainfo = match TorrentMetainfo::parse(&bytes) {
+        Ok(metainfo) => metainfo,
+        Err(e) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": format!("Failed to queue download: {}", e),
+                "status": "failed",
+                "error": e.to_string(),
+                "timestamp": Utc::now()
+            })))
+        }
+    };
+    info!(
+        "Torrent upload received: {} ({})",
+        metainfo.name, metainfo.info_hash
+    );
+
+    let mut download_request = DownloadRequest::new(
+        requester.user_id.clone(),
+        fields
+            .remove("artist")
+            .unwrap_or_else(|| "Unknown".to_string()),
+        fields
+            .remove("title")
+            .unwrap_or_else(|| metainfo.name.clone()),
+    );
+    download_request.album_title = fields.remove("album");
+    if let Some(priority) = fields.remove("priority") {
+        download_request.priority = priority.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    }
+    let source = fields
+        .remove("source")
+        .unwrap_or_else(|| "upload".to_string());
+
+    Ok(submit_torrent_download(
+        download_service,
+        requester,
+        download_request,
+        &metainfo,
+        &bytes,
+        &source,
+    )
+    .await)
+}
+
+/// Submit a request for a parsed `.torrent` file and describe the outcome
+async fn submit_torrent_download(
+    download_service: &DownloadService,
+    requester: &Requester,
+    download_request: DownloadRequest,
+    metainfo: &TorrentMetainfo,
+    bytes: &[u8],
+    source: &str,
+) -> Json<Value> {
+    let artist_name = download_request.artist_name.clone();
+    let track_title = download_request.track_title.clone();
+
+    match download_service
+        .submit_torrent_file(download_request, metainfo, bytes, requester)
+        .await
+    {
+        Ok(request) => {
+            info!(
+                "Successfully submitted torrent file download: {}",
+                request.id
+            );
+            Json(json!({
+                "success": true,
+                "message": format!("Download {} for {} by {} (torrent file)", submitted_verb(&request), track_title, artist_name),
+                "request_id": request.id,
+                "artist_added": false,
+                "artist_name": artist_name,
+                "track_title": track_title,
+                "status": request.status,
+                "download_method": "torrent_file",
+                "torrent_hash": metainfo.info_hash,
+                "torrent_name": metainfo.name,
+                "file_size": metainfo.total_size,
+                "audio_files": metainfo.audio_files().count(),
+                "source": source,
+                "timestamp": Utc::now()
+            }))
+        }
+        Err(e) => {
+            error!("Failed to queue torrent file download: {}", e);
+            Json(json!({
+                "success": false,
+                "message": format!("Failed to queue download: {}", e),
+                "artist_name": artist_name,
+                "track_title": track_title,
+                "status": "failed",
+                "error": e.to_string(),
+                "quota_exceeded": e.downcast_ref::<QuotaExceeded>(),
+                    "insufficient_space": e.downcast_ref::<InsufficientSpace>(),
+                "timestamp": Utc::now()
+            }))
+        }
+    }
+}
+
+fn submitted_verb(request: &DownloadRequest) -> &'static str {
+    if request.status == DownloadStatus::AwaitingApproval {
+        "awaiting approval"
+    } else {
+        "queued"
+    }
+}
+
+/// Get the caller's download quota and current usage
+async fn get_download_quota_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match download_service.get_quota(&requester).await {
+        Ok((limits, usage)) => Ok(Json(json!({
+            "success": true,
+            "user_id": requester.user_id,
+            "role": requester.role,
+            "limits": limits,
+            "usage": usage,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load quota: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// List download requests waiting for admin approval, oldest first
+async fn list_pending_approvals_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(50);
+    let offset = params
+        .get("offset")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+
+    match download_service.pending_approvals(limit, offset).await {
+        Ok(pending) => Ok(Json(json!({
+            "success": true,
+            "total": pending.len(),
+            "limit": limit,
+            "offset": offset,
+            "downloads": pending,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load pending approvals: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Approve a download request and queue it
+async fn approve_download_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(request_id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let admin = admin_for(&download_service, user)?;
+    match download_service.approve_download(&request_id, &admin).await {
+        Ok(request) => Ok(Json(json!({
+            "success": true,
+            "message": format!("Download approved: {}", request.full_description()),
+            "request_id": request_id,
+            "status": request.status,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to approve download: {}", e),
+            "request_id": request_id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Reject a download request; the body must contain a `reason`
+async fn reject_download_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(request_id): Path<String>,
+    ExtractJson(payload): ExtractJson<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let admin = admin_for(&download_service, user)?;
+    let reason = payload
+        .get("reason")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    match download_service
+        .reject_download(&request_id, &admin, reason)
+        .await
+    {
+        Ok(request) => Ok(Json(json!({
+            "success": true,
+            "message": format!("Download rejected: {}", request.full_description()),
+            "request_id": request_id,
+            "status": request.status,
+            "reason": reason,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to reject download: {}", e),
+            "request_id": request_id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// List the caller's notifications (?unread=true&limit=)
+async fn list_notifications_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let unread_only = params
+        .get("unread")
+        .is_some_and(|v| v == "true" || v == "1");
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(50);
+
+    match download_service
+        .notifications()
+        .list(&requester.user_id, unread_only, limit)
+        .await
+    {
+        Ok(notifications) => Ok(Json(json!({
+            "success": true,
+            "total": notifications.len(),
+            "notifications": notifications,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load notifications: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Mark one of the caller's notifications as read
+async fn mark_notification_read_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match download_service
+        .notifications()
+        .mark_read(&requester.user_id, id)
+        .await
+    {
+        Ok(true) => Ok(Json(json!({
+            "success": true,
+            "notification_id": id,
+            "timestamp": Utc::now()
+        }))),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to update notification: {}", e),
+            "notification_id": id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Subscribe the caller to live events, resuming after the `Last-Event-ID`
+/// header or `?last_event_id=` and limited to `?topics=download,scan`
+fn subscribe_events(
+    download_service: &DownloadService,
+    user: Option<Extension<AuthenticatedUser>>,
+    headers: &HeaderMap,
+    params: &std::collections::HashMap<String, String>,
+) -> Result<Subscription, StatusCode> {
+    let requester = requester_for(download_service, user)?;
+    let topics = params
+        .get("topics")
+        .map(|topics| {
+            topics
+                .split(',')
+                .filter(|topic| !topic.is_empty())
+                .map(|topic| topic.trim().parse::<EventTopic>())
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .unwrap_or_default();
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| params.get("last_event_id").map(String::as_str))
+        .and_then(|id| id.trim().parse().ok());
+
+    Ok(download_service
+        .events()
+        .subscribe(EventFilter::new(&requester, topics), last_event_id))
+}
+
+/// Live download and scan events as Server-Sent Events
+async fn events_sse_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    headers: HeaderMap,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Sse<impl futures::Stream<Item = Result<SseEvent, std::convert::Infallible>>>, StatusCode>
+{
+    let subscription = subscribe_events(&download_service, user, &headers, &params)?;
+    let stream = futures::stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+        let sse = SseEvent::default()
+            .id(event.id.to_string())
+            .event(event.kind.clone())
+            .json_data(&event)
+            .unwrap_or_default();
+        Some((Ok(sse), subscription))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Live download and scan events over a WebSocket, one JSON message per event
+async fn events_ws_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    headers: HeaderMap,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    let subscription = subscribe_events(&download_service, user, &headers, &params)?;
+    Ok(ws.on_upgrade(move |socket| forward_events(socket, subscription)))
+}
+
+async fn forward_events(mut socket: WebSocket, mut subscription: Subscription) {
+    loop {
+        tokio::select! {
+            event = subscription.next() => {
+                let Some(event) = event else { break };
+                let Ok(text) = serde_json::to_string::<LiveEvent>(&event) else { continue };
+                if socket.send(WsMessage::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            // Only watch for the client going away; the channel is push-only
+            message = socket.recv() => match message {
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+/// The caller's wishlist autopilot settings and remaining weekly budget
+async fn get_wishlist_settings_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(wishlist): Extension<Arc<WishlistService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let settings = match wishlist.settings(&requester).await {
+        Ok(settings) => settings,
+        Err(e) => {
+            return Ok(Json(json!({
+                "success": false,
+                "error": format!("Failed to load wishlist settings: {}", e),
+                "timestamp": Utc::now()
+            })))
+        }
+    };
+
+    Ok(Json(json!({
+        "success": true,
+        "budget_remaining": wishlist.budget_remaining(&settings).await.ok(),
+        "settings": settings,
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Opt in or out of the wishlist autopilot and tune its budget
+async fn update_wishlist_settings_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(wishlist): Extension<Arc<WishlistService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    ExtractJson(update): ExtractJson<SettingsUpdate>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match wishlist.update_settings(&requester, &update).await {
+        Ok(settings) => Ok(Json(json!({
+            "success": true,
+            "settings": settings,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to update wishlist settings: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Run the caller's wishlist autopilot now instead of waiting for the schedule
+async fn run_wishlist_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(wishlist): Extension<Arc<WishlistService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match wishlist.run_for_user(&requester).await {
+        Ok(summary) => Ok(Json(json!({
+            "success": true,
+            "summary": summary,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Wishlist run failed: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// The caller's wishlist candidates for the review screen
+async fn list_wishlist_candidates_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(wishlist): Extension<Arc<WishlistService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let status = match params.get("status").map(|s| s.parse::<CandidateStatus>()) {
+        Some(Ok(status)) => Some(status),
+        Some(Err(_)) => return Err(StatusCode::BAD_REQUEST),
+        None => None,
+    };
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(100);
+
+    match wishlist.candidates(&requester.user_id, status, limit).await {
+        Ok(candidates) => Ok(Json(json!({
+            "success": true,
+            "total": candidates.len(),
+            "candidates": candidates,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load wishlist candidates: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Queue a proposed wishlist candidate
+async fn approve_wishlist_candidate_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(wishlist): Extension<Arc<WishlistService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    match wishlist.approve(&requester, id).await {
+        Ok(candidate) => Ok(Json(json!({
+            "success": true,
+            "candidate": candidate,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to queue wishlist candidate: {}", e),
+            "candidate_id": id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Reject a proposed wishlist candidate so it is not suggested again
+async fn reject_wishlist_candidate_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(wishlist): Extension<Arc<WishlistService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(id): Path<i64>,
+    body: Option<ExtractJson<Value>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let reason = body
+        .as_ref()
+        .and_then(|ExtractJson(body)| body.get("reason"))
+        .and_then(|reason| reason.as_str());
+
+    match wishlist.reject(&requester, id, reason).await {
+        Ok(candidate) => Ok(Json(json!({
+            "success": true,
+            "candidate": candidate,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to reject wishlist candidate: {}", e),
+            "candidate_id": id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Everything the wishlist autopilot and the caller decided, newest first
+async fn wishlist_audit_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(wishlist): Extension<Arc<WishlistService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(100);
+
+    match wishlist.audit(&requester.user_id, limit).await {
+        Ok(entries) => Ok(Json(json!({
+            "success": true,
+            "total": entries.len(),
+            "audit": entries,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load wishlist audit trail: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Seeding progress and removal decision for each completed torrent
+async fn get_seeding_status_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+) -> Result<Json<Value>, StatusCode> {
+    match download_service.get_seeding_status().await {
+        Ok(torrents) => Ok(Json(json!({
+            "success": true,
+            "total": torrents.len(),
+            "torrents": torrents,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to evaluate seeding policy: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Releases blocklisted after stalling or erroring, newest first
+async fn get_blocklist_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(100);
+
+    match download_service.get_blocklist(limit).await {
+        Ok(blocklist) => Ok(Json(json!({
+            "success": true,
+            "total": blocklist.len(),
+            "blocklist": blocklist,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to load release blocklist: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
+
+/// Current bandwidth schedule window and downloads paused for streaming
+async fn get_bandwidth_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let status = download_service.bandwidth_status().await;
+
+    Ok(Json(json!({
+        "success": true,
+        "plan": status.plan,
+        "applied_at": status.applied_at,
+        "streaming": !status.listeners.is_empty(),
+        "listener_count": status.listeners.len(),
+        // Who is listening is only shown to admins
+        "listeners": requester.is_admin().then_some(&status.listeners),
+        "paused_for_streaming": status.paused_for_streaming,
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Queued downloads in start order; users only see their own requests
+async fn get_download_queue_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let estimates = download_service.queue_estimates().await;
+    let queue_length = estimates.len();
+    let queue: Vec<_> = estimates
+        .into_iter()
+        .filter(|estimate| requester.is_admin() || estimate.user_id == requester.user_id)
+        .collect();
+
+    Ok(Json(json!({
+        "success": true,
+        "queue_length": queue_length,
+        "queue": queue,
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Set a request's priority, or with `override` (admins only) its rank
+/// against other users' requests
+async fn set_download_priority_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(request_id): Path<String>,
+    Json(payload): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let requester = requester_for(&download_service, user)?;
+    let parse = |value: &Value| -> Result<DownloadPriority, StatusCode> {
+        value
+            .as_str()
+            .and_then(|priority| priority.parse().ok())
+            .ok_or(StatusCode::BAD_REQUEST)
+    };
+
+    let result = match (payload.get("priority"), payload.get("override")) {
+        (Some(priority), None) => {
+            let priority = parse(priority)?;
+            download_service
+                .set_priority(&request_id, priority, &requester)
+                .await
+        }
+        (None, Some(priority_override)) => {
+            if !requester.is_admin() {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            let priority_override = match priority_override {
+                Value::Null => None,
+                value => Some(parse(value)?),
+            };
+            download_service
+                .override_priority(&request_id, priority_override, &requester)
+                .await
+        }
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    match result {
+        Ok(request) => {
+            let estimate = download_service.queue_estimate(&request.id).await;
+            Ok(Json(json!({
+                "success": true,
+                "request_id": request.id,
+                "priority": request.priority,
+                "priority_override": request.priority_override,
+                "queue_position": estimate.as_ref().map(|e| e.position),
+                "estimated_start": estimate.as_ref().map(|e| e.estimated_start),
+                "timestamp": Utc::now()
+            })))
+        }
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to change priority: {}", e),
+            "request_id": request_id,
+            "timestamp": Utc::now()
+        }))),
     }
 }
 
-/// Smart fallback handler - returns 404 JSON for API routes, frontend for others
-async fn smart_fallback(uri: axum::http::Uri) -> Result<Response, StatusCode> {
-    let path = uri.path();
+/// Free space, growth forecast and safeguards per storage tier
+async fn get_disk_space_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+) -> Result<Json<Value>, StatusCode> {
+    let status = download_service.disk_space_status().await;
 
-    if path.starts_with("/api/") {
-        // Return JSON 404 for API routes
-        let json_response = Json(json!({
-            "error": "Not Found",
-            "message": format!("API endpoint {} not found", path),
+    Ok(Json(json!({
+        "success": true,
+        "level": status.level,
+        "accepting_downloads": status.level == SpaceLevel::Ok,
+        "tiers": status.tiers,
+        "alerts": status.alerts(),
+        "paused_for_space": status.paused_for_space,
+        "checked_at": status.checked_at,
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Result of the last tier placement run
+async fn get_tiering_endpoint(
+    Extension(tiering): Extension<Arc<TieringService>>,
+) -> Result<Json<Value>, StatusCode> {
+    Ok(Json(json!({
+        "success": true,
+        "enabled": tiering.is_enabled(),
+        "last_run": tiering.last_report().await,
+        "timestamp": Utc::now()
+    })))
+}
+
+/// Re-plan tier placement now (admin)
+async fn run_tiering_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    Extension(tiering): Extension<Arc<TieringService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+
+    match tiering.run().await {
+        Ok(report) => Ok(Json(json!({
+            "success": true,
+            "report": report,
             "timestamp": Utc::now()
-        }));
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Tier placement failed: {}", e),
+            "timestamp": Utc::now()
+        }))),
+    }
+}
 
-        Ok(Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(serde_json::to_string(&json_response.0).unwrap().into())
-            .unwrap())
-    } else {
-        // Serve frontend for non-API routes (SPA routing)
-        match serve_frontend().await {
-            Ok(html) => Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, "text/html")
-                .body(html.0.into())
-                .unwrap()),
-            Err(status) => Err(status),
+/// List recycle bin items, by default those still restorable
+async fn list_trash_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+    let status = match params.get("status").map(String::as_str) {
+        None => Some(TrashStatus::Trashed),
+        Some("all") => None,
+        Some(status) => Some(status.parse().map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(100);
+
+    match download_service.recycle_bin().list(status, limit).await {
+        Ok(items) => Ok(Json(json!({
+            "success": true,
+            "total": items.len(),
+            "items": items,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => {
+            error!("Failed to list the recycle bin: {}", e);
+            Ok(Json(json!({
+                "success": false,
+                "error": format!("Failed to list the recycle bin: {}", e),
+                "timestamp": Utc::now()
+            })))
         }
     }
 }
 
-/// Get download status by request ID
-async fn get_download_status_endpoint(
+/// Move a recycle bin item back to its original path
+async fn restore_trash_endpoint(
     State(download_service): State<Arc<DownloadService>>,
-    Path(request_id): Path<String>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(id): Path<i64>,
 ) -> Result<Json<Value>, StatusCode> {
-    match download_service.get_download_status(&request_id).await {
-        Some(request) => Ok(Json(json!({
+    let admin = admin_for(&download_service, user)?;
+    match download_service
+        .recycle_bin()
+        .restore(id, &admin.username)
+        .await
+    {
+        Ok(item) => Ok(Json(json!({
             "success": true,
-            "request_id": request.id,
-            "status": request.status,
-            "artist_name": request.artist_name,
-            "track_title": request.track_title,
-            "album_title": request.album_title,
-            "progress": request.progress.unwrap_or(0.0),
-            "file_size": request.file_size,
-            "download_speed": request.download_speed,
-            "seeds": request.seeds,
-            "peers": request.peers,
-            "torrent_hash": request.torrent_hash,
-            "requested_at": request.requested_at,
-            "started_at": request.started_at,
-            "completed_at": request.completed_at,
-            "error_message": request.error_message,
-            "timestamp": Utc::now()
-        }))),
-        None => Ok(Json(json!({
+            "message": format!("Restored {}", item.original_path.display()),
+            "item": item,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
             "success": false,
-            "error": "Download request not found",
-            "request_id": request_id,
+            "error": format!("Failed to restore trash item: {}", e),
+            "trash_item_id": id,
             "timestamp": Utc::now()
         }))),
     }
 }
 
-/// Get all active downloads
-async fn get_active_downloads_endpoint(
+/// Permanently delete a recycle bin item
+async fn purge_trash_endpoint(
     State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(id): Path<i64>,
 ) -> Result<Json<Value>, StatusCode> {
-    let active_downloads = download_service.get_active_downloads().await;
+    let admin = admin_for(&download_service, user)?;
+    match download_service
+        .recycle_bin()
+        .purge(id, &admin.username)
+        .await
+    {
+        Ok(item) => Ok(Json(json!({
+            "success": true,
+            "message": format!("Purged {}", item.original_path.display()),
+            "item": item,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to purge trash item: {}", e),
+            "trash_item_id": id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
 
-    Ok(Json(json!({
-        "success": true,
-        "active_downloads": active_downloads.len(),
-        "downloads": active_downloads.iter().map(|d| json!({
-            "id": d.id,
-            "request_id": d.download_request_id,
-            "torrent_hash": d.torrent_hash,
-            "name": d.name,
-            "status": d.status,
-            "progress": d.progress,
-            "size": d.size,
-            "downloaded": d.downloaded,
-            "download_speed": d.download_speed,
-            "upload_speed": d.upload_speed,
-            "seeds": d.seeds,
-            "peers": d.peers,
-            "eta": d.eta,
-            "added_at": d.added_at,
-            "completed_at": d.completed_at
-        })).collect::<Vec<_>>(),
-        "timestamp": Utc::now()
-    })))
+/// List completed downloads that could not be imported automatically
+async fn list_manual_imports_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+    match download_service.list_manual_imports().await {
+        Ok(imports) => Ok(Json(json!({
+            "success": true,
+            "total": imports.len(),
+            "manual_imports": imports,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => {
+            error!("Failed to load manual imports: {}", e);
+            Ok(Json(json!({
+                "success": false,
+                "error": format!("Failed to load manual imports: {}", e),
+                "timestamp": Utc::now()
+            })))
+        }
+    }
 }
 
-/// Get download statistics
-async fn get_download_stats_endpoint(
+/// Retry a manual import, with `force` skipping the release match check
+async fn retry_manual_import_endpoint(
     State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(id): Path<i64>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
 ) -> Result<Json<Value>, StatusCode> {
-    let stats = download_service.get_stats().await;
+    admin_for(&download_service, user)?;
+    let force = params.get("force").is_some_and(|v| v == "true" || v == "1");
+    match download_service.retry_manual_import(id, force).await {
+        Ok(request) => Ok(Json(json!({
+            "success": request.status == DownloadStatus::Imported,
+            "manual_import_id": id,
+            "status": request.status,
+            "message": request.error_message,
+            "download": request,
+            "timestamp": Utc::now()
+        }))),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to retry import: {}", e),
+            "manual_import_id": id,
+            "timestamp": Utc::now()
+        }))),
+    }
+}
 
-    Ok(Json(json!({
-        "success": true,
-        "stats": {
-            "total_downloads": stats.total_downloads,
-            "completed_downloads": stats.completed_downloads,
-            "failed_downloads": stats.failed_downloads,
-            "active_downloads": stats.active_downloads,
-            "queued_downloads": stats.queued_downloads,
-            "total_downloaded_bytes": stats.total_downloaded_bytes,
-            "total_uploaded_bytes": stats.total_uploaded_bytes,
-            "average_download_speed": stats.average_download_speed,
-            "last_updated": stats.last_updated
-        },
-        "timestamp": Utc::now()
-    })))
+/// Drop a download from the manual-import queue
+async fn dismiss_manual_import_endpoint(
+    State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
+    match download_service.dismiss_manual_import(id).await {
+        Ok(true) => Ok(Json(json!({
+            "success": true,
+            "message": format!("Manual import {} dismissed", id),
+            "manual_import_id": id,
+            "timestamp": Utc::now()
+        }))),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(json!({
+            "success": false,
+            "error": format!("Failed to dismiss manual import: {}", e),
+            "manual_import_id": id,
+            "timestamp": Utc::now()
+        }))),
+    }
 }
 
 /// Pause a download
 async fn pause_download_endpoint(
     State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
     Path(hash): Path<String>,
 ) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
     match download_service.pause_download(&hash).await {
         Ok(_) => Ok(Json(json!({
             "success": true,
@@ -2719,8 +5176,10 @@ async fn pause_download_endpoint(
 /// Resume a download
 async fn resume_download_endpoint(
     State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
     Path(hash): Path<String>,
 ) -> Result<Json<Value>, StatusCode> {
+    admin_for(&download_service, user)?;
     match download_service.resume_download(&hash).await {
         Ok(_) => Ok(Json(json!({
             "success": true,
@@ -2993,9 +5452,25 @@ async fn browse_library() -> Result<Json<Value>, StatusCode> {
 /// Cancel a download
 async fn cancel_download_endpoint(
     State(download_service): State<Arc<DownloadService>>,
+    user: Option<Extension<AuthenticatedUser>>,
     Path(hash): Path<String>,
 ) -> Result<Json<Value>, StatusCode> {
-    match download_service.cancel_download(&hash, true).await {
+    let requester = requester_for(&download_service, user)?;
+    let owner = download_service
+        .stored_request_for(&hash)
+        .await
+        .map(|request| request.user_id);
+    if owner.as_deref() != Some(requester.user_id.as_str()) && !requester.is_admin() {
+        warn!(
+            "User {} may not cancel download {}",
+            requester.username, hash
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match download_service
+        .cancel_download(&hash, true, &requester.username)
+        .await
+    {
         Ok(_) => Ok(Json(json!({
             "success": true,
             "message": format!("Download cancelled: {}", hash),
@@ -3010,3 +5485,297 @@ async fn cancel_download_endpoint(
         }))),
     }
 }
+
+/// Listening parties, built with the `social` feature
+#[cfg(feature = "social")]
+mod parties_api {
+    use super::*;
+    use crate::services::listening_party::{
+        server_time, CreateParty, InviteMember, ListeningParties, MemberMessage, Party,
+        PartyCommand, PartyConnection, PartyError, PartyMessage,
+    };
+    use crate::services::play_queue::QueueTrack;
+
+    pub fn routes() -> Router<Arc<DownloadService>> {
+        Router::new()
+            .route("/api/v1/parties/clock", get(party_clock))
+            .route("/api/v1/parties", get(list_parties).post(create_party))
+            .route("/api/v1/parties/:party_id", get(get_party))
+            .route("/api/v1/parties/:party_id/join", post(join_party))
+            .route("/api/v1/parties/:party_id/leave", post(leave_party))
+            .route("/api/v1/parties/:party_id/invite", post(invite_to_party))
+            .route("/api/v1/parties/:party_id/queue", post(add_to_party))
+            .route("/api/v1/parties/:party_id/skip-vote", post(vote_skip))
+            .route("/api/v1/parties/:party_id/control", post(control_party))
+            .route("/api/v1/parties/:party_id/end", post(end_party))
+            .route("/api/v1/parties/:party_id/ws", get(party_ws_endpoint))
+    }
+
+    /// Server time in milliseconds, for clients to work out their clock offset
+    async fn party_clock() -> Json<Value> {
+        Json(json!({
+            "success": true,
+            "server_time": server_time(),
+            "timestamp": Utc::now()
+        }))
+    }
+
+    /// Parties the caller is allowed to see
+    async fn list_parties(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.visible(&requester.user_id).await {
+            Ok(visible) => Ok(Json(json!({
+                "success": true,
+                "parties": visible,
+                "timestamp": Utc::now()
+            }))),
+            Err(e) => party_failure("list parties", e),
+        }
+    }
+
+    /// Open a party hosted by the caller
+    async fn create_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        ExtractJson(request): ExtractJson<CreateParty>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties
+            .create(&requester.user_id, &requester.username, request)
+            .await
+        {
+            Ok(party) => party_response(party),
+            Err(e) => party_failure("create party", e),
+        }
+    }
+
+    async fn get_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.get(&party_id, &requester.user_id).await {
+            Ok(party) => party_response(party),
+            Err(e) => party_failure("load party", e),
+        }
+    }
+
+    /// Join a party; the response says where in the current track to start
+    async fn join_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties
+            .join(&party_id, &requester.user_id, &requester.username)
+            .await
+        {
+            Ok((party, cue)) => Ok(Json(json!({
+                "success": true,
+                "party": party,
+                "cue": cue,
+                "server_time": server_time(),
+                "timestamp": Utc::now()
+            }))),
+            Err(e) => party_failure("join party", e),
+        }
+    }
+
+    /// Leave a party; when the host leaves, the party ends
+    async fn leave_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.leave(&party_id, &requester.user_id) {
+            Ok(()) => Ok(Json(json!({
+                "success": true,
+                "timestamp": Utc::now()
+            }))),
+            Err(e) => party_failure("leave party", e),
+        }
+    }
+
+    async fn invite_to_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+        ExtractJson(invite): ExtractJson<InviteMember>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.invite(&party_id, &requester.user_id, &invite.user_id) {
+            Ok(party) => party_response(party),
+            Err(e) => party_failure("invite to party", e),
+        }
+    }
+
+    /// Add a track to the shared queue
+    async fn add_to_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+        ExtractJson(track): ExtractJson<QueueTrack>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.add_track(&party_id, &requester.user_id, track) {
+            Ok(party) => party_response(party),
+            Err(e) => party_failure("add to party queue", e),
+        }
+    }
+
+    async fn vote_skip(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.vote_skip(&party_id, &requester.user_id) {
+            Ok(vote) => Ok(Json(json!({
+                "success": true,
+                "vote": vote,
+                "timestamp": Utc::now()
+            }))),
+            Err(e) => party_failure("vote to skip", e),
+        }
+    }
+
+    /// Play, pause, seek or skip for everyone; host only
+    async fn control_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+        ExtractJson(command): ExtractJson<PartyCommand>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.control(&party_id, &requester.user_id, command) {
+            Ok(party) => party_response(party),
+            Err(e) => party_failure("control party", e),
+        }
+    }
+
+    async fn end_party(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        match parties.end(&party_id, &requester.user_id) {
+            Ok(()) => Ok(Json(json!({
+                "success": true,
+                "timestamp": Utc::now()
+            }))),
+            Err(e) => party_failure("end party", e),
+        }
+    }
+
+    /// Join a party and follow it live: state changes and cues come down,
+    /// pings, skip votes, queue additions and host controls go up
+    async fn party_ws_endpoint(
+        State(download_service): State<Arc<DownloadService>>,
+        Extension(parties): Extension<Arc<ListeningParties>>,
+        user: Option<Extension<AuthenticatedUser>>,
+        Path(party_id): Path<String>,
+        ws: WebSocketUpgrade,
+    ) -> Result<Response, StatusCode> {
+        let requester = requester_for(&download_service, user)?;
+        let connection = match parties
+            .join(&party_id, &requester.user_id, &requester.username)
+            .await
+            .and_then(|_| parties.connect(&party_id, &requester.user_id))
+        {
+            Ok(connection) => connection,
+            Err(e) => return Err(party_status(&e)),
+        };
+
+        Ok(ws.on_upgrade(move |socket| async move {
+            run_party_socket(socket, &parties, connection).await;
+        }))
+    }
+
+    async fn run_party_socket(
+        mut socket: WebSocket,
+        parties: &ListeningParties,
+        mut connection: PartyConnection,
+    ) {
+        loop {
+            tokio::select! {
+                message = connection.recv() => {
+                    let Some(message) = message else { break };
+                    let closed = matches!(message, PartyMessage::Closed);
+                    let Ok(text) = serde_json::to_string(&message) else { continue };
+                    if socket.send(WsMessage::Text(text)).await.is_err() || closed {
+                        break;
+                    }
+                }
+                incoming = socket.recv() => match incoming {
+                    Some(Ok(WsMessage::Text(text))) => {
+                        let handled = match serde_json::from_str::<MemberMessage>(&text) {
+                            Ok(message) => parties
+                                .handle(&connection, message)
+                                .map_err(|e| e.to_string()),
+                            Err(e) => Err(format!("Invalid message: {}", e)),
+                        };
+                        if let Err(message) = handled {
+                            let error = PartyMessage::Error { message };
+                            let Ok(text) = serde_json::to_string(&error) else { continue };
+                            if socket.send(WsMessage::Text(text)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+        parties.disconnect(&connection);
+    }
+
+    fn party_response(party: Party) -> Result<Json<Value>, StatusCode> {
+        Ok(Json(json!({
+            "success": true,
+            "party": party,
+            "cue": party.cue(server_time()),
+            "timestamp": Utc::now()
+        })))
+    }
+
+    fn party_status(e: &PartyError) -> StatusCode {
+        match e {
+            PartyError::NotFound => StatusCode::NOT_FOUND,
+            PartyError::Forbidden(_) => StatusCode::FORBIDDEN,
+            PartyError::Invalid(_) => StatusCode::BAD_REQUEST,
+            PartyError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn party_failure(action: &str, e: PartyError) -> Result<Json<Value>, StatusCode> {
+        match e {
+            PartyError::NotFound | PartyError::Forbidden(_) => Err(party_status(&e)),
+            e => {
+                error!("Failed to {}: {}", action, e);
+                Ok(Json(json!({
+                    "success": false,
+                    "error": format!("Failed to {}: {}", action, e),
+                    "timestamp": Utc::now()
+                })))
+            }
+        }
+    }
+}
@@ -655,6 +655,195 @@ impl UserTrackRating {
     }
 }
 
+/// Lifecycle state of a download request
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum DownloadStatus {
+    Pending,
+    #[serde(rename = "awaiting_approval")]
+    #[sqlx(rename = "awaiting_approval")]
+    AwaitingApproval,
+    Searching,
+    Queued,
+    Downloading,
+    Stalled,
+    Completed,
+    Verifying,
+    Importing,
+    Imported,
+    Failed,
+    Cancelled,
+    Rejected,
+}
+
+impl DownloadStatus {
+    /// Every status, in lifecycle order
+    pub const ALL: [DownloadStatus; 13] = [
+        DownloadStatus::Pending,
+        DownloadStatus::AwaitingApproval,
+        DownloadStatus::Searching,
+        DownloadStatus::Queued,
+        DownloadStatus::Downloading,
+        DownloadStatus::Stalled,
+        DownloadStatus::Completed,
+        DownloadStatus::Verifying,
+        DownloadStatus::Importing,
+        DownloadStatus::Imported,
+        DownloadStatus::Failed,
+        DownloadStatus::Cancelled,
+        DownloadStatus::Rejected,
+    ];
+
+    /// Database and API representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadStatus::Pending => "pending",
+            DownloadStatus::AwaitingApproval => "awaiting_approval",
+            DownloadStatus::Searching => "searching",
+            DownloadStatus::Queued => "queued",
+            DownloadStatus::Downloading => "downloading",
+            DownloadStatus::Stalled => "stalled",
+            DownloadStatus::Completed => "completed",
+            DownloadStatus::Verifying => "verifying",
+            DownloadStatus::Importing => "importing",
+            DownloadStatus::Imported => "imported",
+            DownloadStatus::Failed => "failed",
+            DownloadStatus::Cancelled => "cancelled",
+            DownloadStatus::Rejected => "rejected",
+        }
+    }
+
+    /// States the request will not leave on its own
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            DownloadStatus::Imported
+                | DownloadStatus::Failed
+                | DownloadStatus::Cancelled
+                | DownloadStatus::Rejected
+        )
+    }
+
+    /// Check whether moving to `next` is a legal transition
+    pub fn can_transition_to(&self, next: DownloadStatus) -> bool {
+        use DownloadStatus::*;
+
+        match self {
+            Pending => matches!(
+                next,
+                AwaitingApproval | Searching | Queued | Failed | Cancelled
+            ),
+            // Approved requests continue as if they had just been submitted
+            AwaitingApproval => matches!(next, Searching | Queued | Failed | Rejected | Cancelled),
+            Searching => matches!(next, Queued | Failed | Cancelled),
+            Queued => matches!(next, Downloading | Failed | Cancelled),
+            Downloading => matches!(next, Stalled | Completed | Queued | Failed | Cancelled),
+            Stalled => matches!(next, Downloading | Completed | Queued | Failed | Cancelled),
+            Completed => matches!(next, Verifying | Importing | Failed | Cancelled),
+            // An interrupted import starts over from Completed
+            Verifying => matches!(next, Importing | Completed | Failed),
+            Importing => matches!(next, Imported | Completed | Failed),
+            // Failed requests may be retried manually, failed imports
+            // go straight back to verification
+            Failed => matches!(next, Queued | Verifying),
+            Imported | Cancelled | Rejected => false,
+        }
+    }
+}
+
+impl std::fmt::Display for DownloadStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DownloadStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DownloadStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("Unknown download status: {}", s))
+    }
+}
+
+/// How urgently a download request should be fetched
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum DownloadPriority {
+    /// Background work such as autopilot requests; paused while anyone streams
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl DownloadPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadPriority::Low => "low",
+            DownloadPriority::Normal => "normal",
+            DownloadPriority::High => "high",
+        }
+    }
+}
+
+impl std::fmt::Display for DownloadPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DownloadPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(DownloadPriority::Low),
+            "normal" => Ok(DownloadPriority::Normal),
+            "high" => Ok(DownloadPriority::High),
+            other => Err(format!("Unknown download priority: {}", other)),
+        }
+    }
+}
+
+/// Rejected attempt to move a download request between states
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Invalid download transition for {request_id}: {from} -> {to}")]
+pub struct InvalidTransition {
+    pub request_id: String,
+    pub from: DownloadStatus,
+    pub to: DownloadStatus,
+}
+
+/// Recorded state change of a download request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadTransition {
+    pub request_id: String,
+    pub from_status: DownloadStatus,
+    pub to_status: DownloadStatus,
+    pub reason: String,
+    pub transitioned_at: DateTime<Utc>,
+}
+
 /// Download request entity
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct DownloadRequest {
@@ -663,7 +852,13 @@ pub struct DownloadRequest {
     pub artist_name: String,
     pub track_title: String,
     pub album_title: Option<String>,
-    pub status: String,
+    pub status: DownloadStatus,
+    /// Set by the requester, orders their own requests
+    #[serde(default)]
+    pub priority: DownloadPriority,
+    /// Set by an admin, takes precedence over other users' requests
+    #[serde(default)]
+    pub priority_override: Option<DownloadPriority>,
     pub source_url: Option<String>,
     pub magnet_url: Option<String>,
     pub torrent_hash: Option<String>,
@@ -691,7 +886,9 @@ impl DownloadRequest {
             artist_name,
             track_title,
             album_title: None,
-            status: "pending".to_string(),
+            status: DownloadStatus::Pending,
+            priority: DownloadPriority::Normal,
+            priority_override: None,
             source_url: None,
             magnet_url: None,
             torrent_hash: None,
@@ -722,28 +919,99 @@ impl DownloadRequest {
         let mut request = Self::new(user_id, artist_name, track_title);
         request.magnet_url = Some(magnet_url);
         request.torrent_hash = torrent_hash;
-        request.status = "queued".to_string();
         request
     }
 
+    /// The admin override if there is one, otherwise the requester's priority
+    pub fn effective_priority(&self) -> DownloadPriority {
+        self.priority_override.unwrap_or(self.priority)
+    }
+
+    /// Move the request to a new state, returning the transition to record
+    pub fn transition_to(
+        &mut self,
+        next: DownloadStatus,
+        reason: impl Into<String>,
+    ) -> Result<DownloadTransition, InvalidTransition> {
+        if !self.status.can_transition_to(next) {
+            return Err(InvalidTransition {
+                request_id: self.id.clone(),
+                from: self.status,
+                to: next,
+            });
+        }
+
+        let now = Utc::now();
+        let reason = reason.into();
+
+        match next {
+            DownloadStatus::Queued => {
+                self.error_message = None;
+            }
+            DownloadStatus::Downloading => {
+                self.started_at.get_or_insert(now);
+            }
+            DownloadStatus::Completed => {
+                self.completed_at.get_or_insert(now);
+                self.progress = Some(1.0);
+                self.download_speed = Some(0);
+            }
+            DownloadStatus::Imported => {
+                self.processed_at = Some(now);
+            }
+            DownloadStatus::Failed | DownloadStatus::Rejected => {
+                self.error_message = Some(reason.clone());
+                self.download_speed = Some(0);
+            }
+            DownloadStatus::Cancelled => {
+                self.download_speed = Some(0);
+                self.upload_speed = Some(0);
+            }
+            _ => {}
+        }
+
+        let transition = DownloadTransition {
+            request_id: self.id.clone(),
+            from_status: self.status,
+            to_status: next,
+            reason,
+            transitioned_at: now,
+        };
+        self.status = next;
+
+        Ok(transition)
+    }
+
     /// Check if download is completed
     pub fn is_completed(&self) -> bool {
-        self.status == "completed" && self.progress.unwrap_or(0.0) >= 1.0
+        matches!(
+            self.status,
+            DownloadStatus::Completed
+                | DownloadStatus::Verifying
+                | DownloadStatus::Importing
+                | DownloadStatus::Imported
+        )
     }
 
     /// Check if download failed
     pub fn is_failed(&self) -> bool {
-        self.status == "failed" || self.status == "error"
+        self.status == DownloadStatus::Failed
     }
 
     /// Check if download is in progress
     pub fn is_in_progress(&self) -> bool {
-        self.status == "downloading" || self.status == "seeding"
+        matches!(
+            self.status,
+            DownloadStatus::Downloading | DownloadStatus::Stalled
+        )
     }
 
     /// Check if download is queued
     pub fn is_queued(&self) -> bool {
-        self.status == "queued" || self.status == "pending"
+        matches!(
+            self.status,
+            DownloadStatus::Pending | DownloadStatus::Searching | DownloadStatus::Queued
+        )
     }
 
     /// Get download progress percentage
@@ -784,13 +1052,9 @@ impl DownloadRequest {
         self.file_size = Some(torrent_info.size);
         self.download_speed = Some(torrent_info.download_speed);
         self.upload_speed = Some(torrent_info.upload_speed);
-        self.status = torrent_info.status_string().to_string();
         // Transmission doesn't provide seeds/peers count in basic info
         // self.seeds and self.peers will remain unchanged
-
-        if torrent_info.progress >= 1.0 && self.completed_at.is_none() {
-            self.completed_at = Some(Utc::now());
-        }
+        // Status is owned by the download state machine, see `transition_to`
     }
 
     /// Helper function to format bytes
@@ -1054,3 +1318,96 @@ impl TrackPopularity {
             .min(1.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_status_round_trip() {
+        for status in DownloadStatus::ALL {
+            assert_eq!(status.as_str().parse::<DownloadStatus>(), Ok(status));
+        }
+        assert!("seeding".parse::<DownloadStatus>().is_err());
+    }
+
+    #[test]
+    fn test_download_transitions() {
+        let mut request = DownloadRequest::new(
+            "user".to_string(),
+            "Artist".to_string(),
+            "Track".to_string(),
+        );
+
+        let transition = request
+            .transition_to(DownloadStatus::Queued, "Queued for download")
+            .unwrap();
+        assert_eq!(transition.from_status, DownloadStatus::Pending);
+        assert_eq!(request.status, DownloadStatus::Queued);
+
+        // Cannot skip straight to the library
+        let err = request
+            .transition_to(DownloadStatus::Imported, "Too early")
+            .unwrap_err();
+        assert_eq!(err.from, DownloadStatus::Queued);
+        assert_eq!(request.status, DownloadStatus::Queued);
+
+        request
+            .transition_to(DownloadStatus::Downloading, "Started")
+            .unwrap();
+        assert!(request.started_at.is_some());
+
+        request
+            .transition_to(DownloadStatus::Failed, "Tracker unreachable")
+            .unwrap();
+        assert_eq!(
+            request.error_message.as_deref(),
+            Some("Tracker unreachable")
+        );
+        assert!(request.status.is_terminal());
+
+        // Failed requests can be retried, cancelled ones cannot
+        assert!(request.status.can_transition_to(DownloadStatus::Verifying));
+        assert!(!request.status.can_transition_to(DownloadStatus::Importing));
+        request
+            .transition_to(DownloadStatus::Queued, "Retry")
+            .unwrap();
+        assert!(request.error_message.is_none());
+        request
+            .transition_to(DownloadStatus::Cancelled, "Cancelled by user")
+            .unwrap();
+        assert!(request
+            .transition_to(DownloadStatus::Queued, "Retry")
+            .is_err());
+    }
+
+    #[test]
+    fn test_download_approval_transitions() {
+        let mut request = DownloadRequest::new(
+            "user".to_string(),
+            "Artist".to_string(),
+            "Track".to_string(),
+        );
+
+        request
+            .transition_to(DownloadStatus::AwaitingApproval, "Waiting for approval")
+            .unwrap();
+        assert!(!request.status.is_terminal());
+        assert!(!request
+            .status
+            .can_transition_to(DownloadStatus::Downloading));
+
+        request
+            .transition_to(DownloadStatus::Rejected, "Rejected by admin: duplicate")
+            .unwrap();
+        assert!(request.status.is_terminal());
+        assert_eq!(
+            request.error_message.as_deref(),
+            Some("Rejected by admin: duplicate")
+        );
+        assert_eq!(
+            "awaiting_approval".parse::<DownloadStatus>().unwrap(),
+            DownloadStatus::AwaitingApproval
+        );
+    }
+}
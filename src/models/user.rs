@@ -124,7 +124,7 @@ impl Default for UserPreferences {
 }
 
 /// Discovery mode for recommendations
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DiscoveryMode {
     Conservative, // Stick to similar music
     Balanced,     // Mix of similar and new
@@ -132,7 +132,7 @@ pub enum DiscoveryMode {
 }
 
 /// Music repeat modes
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RepeatMode {
     Off,
     Track,
@@ -152,6 +152,11 @@ pub struct UserIntegration {
     pub last_sync: Option<i64>,
     pub sync_settings_json: String,
     pub created_at: i64,
+    /// Why the last scrobble submission failed, cleared once one succeeds
+    #[sqlx(default)]
+    pub last_error: Option<String>,
+    #[sqlx(default)]
+    pub last_error_at: Option<i64>,
 }
 
 impl UserIntegration {
@@ -291,10 +296,21 @@ impl AuthenticatedUser {
     pub fn can_manage_users(&self) -> bool {
         self.is_admin() || self.has_role("user-manager")
     }
+
+    /// Highest role granted to the user
+    pub fn role(&self) -> UserRole {
+        if self.is_admin() {
+            UserRole::Admin
+        } else if self.has_role("user") {
+            UserRole::User
+        } else {
+            UserRole::Guest
+        }
+    }
 }
 
 /// User role enumeration
-#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
 pub enum UserRole {
     Admin,
     User,
@@ -386,6 +386,45 @@ impl NavidromeAddon {
         }
     }
 
+    /// Library-relative file path of a song, as Navidrome reports it
+    pub async fn get_track_path(&self, track_id: &str) -> Result<Option<String>, String> {
+        if !self.enabled {
+            return Err("Navidrome not configured".to_string());
+        }
+
+        let salt = "randomsalt";
+        let token = format!("{:x}", md5::compute(format!("{}{}", self.password, salt)));
+        let song_url = format!(
+            "{}/rest/getSong?id={}&u={}&t={}&s={}&v=1.16.1&c=StepheyBot-Music",
+            self.url,
+            urlencoding::encode(track_id),
+            self.username,
+            token,
+            salt
+        );
+
+        let response = reqwest::get(&song_url)
+            .await
+            .map_err(|e| format!("Song request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Song API Error: {}", response.status()));
+        }
+        let text = response.text().await.map_err(|e| e.to_string())?;
+
+        let song_regex =
+            Regex::new(r#"<song[^>]+>"#).map_err(|e| format!("Song regex error: {}", e))?;
+        Ok(song_regex
+            .find(&text)
+            .and_then(|song| extract_attribute(song.as_str(), " path"))
+            .map(|path| {
+                path.replace("&quot;", "\"")
+                    .replace("&apos;", "'")
+                    .replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&amp;", "&")
+            }))
+    }
+
     /// Create a simple status report
     pub async fn get_status(&self) -> serde_json::Value {
         let info = self.test_connection().await;
@@ -0,0 +1,363 @@
+//! Bandwidth scheduling for StepheyBot Music
+//!
+//! A schedule of time-of-day windows decides the torrent client's speed limits
+//! and whether its alternative ("turtle") limits are on, e.g. throttled during
+//! the working day and unlimited overnight. While anyone is streaming,
+//! low-priority downloads are paused so playback does not compete with them.
+
+use anyhow::Result;
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, NaiveDateTime, NaiveTime, Utc, Weekday,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::clients::transmission::{SpeedSettings, TransmissionClient};
+
+/// Speed limits in KB/s, `None` meaning unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpeedLimits {
+    pub download_kbps: Option<u64>,
+    pub upload_kbps: Option<u64>,
+}
+
+/// What the torrent client should be doing right now
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthPlan {
+    pub limits: SpeedLimits,
+    /// Use the client's alternative speed limits
+    pub alt_speed: bool,
+    /// The window that produced the plan, `None` outside every window
+    pub window: Option<String>,
+}
+
+/// One time-of-day window of the schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandwidthWindow {
+    /// Days the window starts on, every day if empty
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    /// May be before `start`, in which case the window runs past midnight
+    pub end: NaiveTime,
+    pub limits: SpeedLimits,
+    pub alt_speed: bool,
+    spec: String,
+}
+
+impl BandwidthWindow {
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Whether the window covers a local time
+    pub fn covers(&self, at: NaiveDateTime) -> bool {
+        let time = at.time();
+        if self.start <= self.end {
+            self.starts_on(at.weekday()) && time >= self.start && time < self.end
+        } else {
+            (self.starts_on(at.weekday()) && time >= self.start)
+                || (self.starts_on(at.weekday().pred()) && time < self.end)
+        }
+    }
+}
+
+impl std::str::FromStr for BandwidthWindow {
+    type Err = String;
+
+    /// `[days] HH:MM-HH:MM action...`, e.g. `mon-fri 09:00-18:00 down=500 up=50`
+    /// or `* 01:00-07:00 unlimited`; actions are `down=KB/s`, `up=KB/s`,
+    /// `alt` and `unlimited`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec = s.trim();
+        let mut tokens = spec.split_whitespace().peekable();
+
+        let days = match tokens.peek() {
+            Some(token) if !token.contains(':') => parse_days(tokens.next().unwrap_or_default())?,
+            _ => Vec::new(),
+        };
+
+        let range = tokens
+            .next()
+            .ok_or_else(|| format!("Missing time range in '{}'", spec))?;
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid time range '{}'", range))?;
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| format!("Invalid time '{}'", time))
+        };
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            return Err(format!("Empty time range '{}'", range));
+        }
+
+        let mut window = Self {
+            days,
+            start,
+            end,
+            limits: SpeedLimits::default(),
+            alt_speed: false,
+            spec: spec.to_string(),
+        };
+        let mut has_action = false;
+        for action in tokens {
+            has_action = true;
+            let parse_speed = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid speed '{}'", value))
+            };
+            match action.split_once('=') {
+                Some(("down", value)) => window.limits.download_kbps = Some(parse_speed(value)?),
+                Some(("up", value)) => window.limits.upload_kbps = Some(parse_speed(value)?),
+                None if action == "alt" => window.alt_speed = true,
+                None if action == "unlimited" => {}
+                _ => return Err(format!("Unknown bandwidth action '{}'", action)),
+            }
+        }
+        if !has_action {
+            return Err(format!("No action in '{}'", spec));
+        }
+
+        Ok(window)
+    }
+}
+
+fn parse_days(spec: &str) -> Result<Vec<Weekday>, String> {
+    if spec == "*" {
+        return Ok(Vec::new());
+    }
+
+    let parse_day = |day: &str| {
+        day.parse::<Weekday>()
+            .map_err(|_| format!("Invalid day '{}'", day))
+    };
+    let mut days = Vec::new();
+    for part in spec.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (mut day, last) = (parse_day(first)?, parse_day(last)?);
+                // Ranges may wrap around the week, e.g. fri-mon
+                while day != last {
+                    days.push(day);
+                    day = day.succ();
+                }
+                days.push(last);
+            }
+            None => days.push(parse_day(part)?),
+        }
+    }
+    Ok(days)
+}
+
+/// Time-of-day windows, the first matching window wins
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BandwidthSchedule {
+    pub windows: Vec<BandwidthWindow>,
+}
+
+impl BandwidthSchedule {
+    /// The plan for a local time; outside every window the client runs unlimited
+    pub fn plan_at(&self, at: NaiveDateTime) -> BandwidthPlan {
+        self.windows
+            .iter()
+            .find(|window| window.covers(at))
+            .map(|window| BandwidthPlan {
+                limits: window.limits,
+                alt_speed: window.alt_speed,
+                window: Some(window.spec.clone()),
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl std::str::FromStr for BandwidthSchedule {
+    type Err = String;
+
+    /// Windows separated by `;`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let windows = s
+            .split(';')
+            .filter(|window| !window.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { windows })
+    }
+}
+
+/// Bandwidth scheduler configuration
+#[derive(Debug, Clone)]
+pub struct BandwidthConfig {
+    pub schedule: BandwidthSchedule,
+    pub pause_low_priority_while_streaming: bool,
+    /// How long after a stream starts its listener counts as streaming
+    pub stream_grace: Duration,
+    pub interval: Duration,
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self {
+            schedule: BandwidthSchedule::default(),
+            pause_low_priority_while_streaming: true,
+            stream_grace: Duration::from_secs(10 * 60),
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl BandwidthConfig {
+    /// Read the configuration from `STEPHEYBOT__BANDWIDTH__*`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(schedule) = var("STEPHEYBOT__BANDWIDTH__SCHEDULE") {
+            match schedule.parse() {
+                Ok(schedule) => config.schedule = schedule,
+                Err(e) => warn!("Ignoring invalid bandwidth schedule: {}", e),
+            }
+        }
+        if let Some(pause) =
+            var("STEPHEYBOT__BANDWIDTH__PAUSE_WHILE_STREAMING").and_then(|v| v.parse().ok())
+        {
+            config.pause_low_priority_while_streaming = pause;
+        }
+        if let Some(minutes) = var("STEPHEYBOT__BANDWIDTH__STREAM_GRACE_MINUTES")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0)
+        {
+            config.stream_grace = Duration::from_secs(minutes * 60);
+        }
+
+        config
+    }
+}
+
+/// Who streamed what recently
+#[derive(Debug, Clone, Default)]
+pub struct StreamActivity {
+    streams: Arc<Mutex<HashMap<String, DateTime<Utc>>>>,
+}
+
+impl StreamActivity {
+    pub fn record(&self, user_id: &str, at: DateTime<Utc>) {
+        let mut streams = self.streams.lock().unwrap_or_else(|e| e.into_inner());
+        streams.insert(user_id.to_string(), at);
+    }
+
+    /// Users who started a stream within `grace` of `now`
+    pub fn active_listeners(&self, now: DateTime<Utc>, grace: Duration) -> Vec<String> {
+        let grace = ChronoDuration::from_std(grace).unwrap_or_else(|_| ChronoDuration::zero());
+        let mut streams = self.streams.lock().unwrap_or_else(|e| e.into_inner());
+        streams.retain(|_, started| now - *started < grace);
+
+        let mut listeners: Vec<String> = streams.keys().cloned().collect();
+        listeners.sort();
+        listeners
+    }
+}
+
+/// State of the scheduler, for the status endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandwidthStatus {
+    pub plan: Option<BandwidthPlan>,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub listeners: Vec<String>,
+    /// Torrents paused because someone is streaming
+    pub paused_for_streaming: Vec<String>,
+}
+
+/// A torrent client whose session speed limits can be set
+#[async_trait::async_trait]
+pub trait BandwidthControl: Send + Sync {
+    /// Apply a plan, leaving the client alone if it already matches
+    async fn apply(&self, plan: &BandwidthPlan) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl BandwidthControl for tokio::sync::Mutex<TransmissionClient> {
+    async fn apply(&self, plan: &BandwidthPlan) -> Result<()> {
+        let mut transmission = self.lock().await;
+        let current = transmission.get_speed_settings().await?;
+
+        let wanted = SpeedSettings {
+            speed_limit_down: plan
+                .limits
+                .download_kbps
+                .unwrap_or(current.speed_limit_down),
+            speed_limit_down_enabled: plan.limits.download_kbps.is_some(),
+            speed_limit_up: plan.limits.upload_kbps.unwrap_or(current.speed_limit_up),
+            speed_limit_up_enabled: plan.limits.upload_kbps.is_some(),
+            alt_speed_enabled: plan.alt_speed,
+        };
+        if wanted != current {
+            transmission.set_speed_settings(&wanted).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        // 2024-01-01 was a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn test_schedule_windows() {
+        let schedule: BandwidthSchedule =
+            "mon-fri 09:00-18:00 down=500 up=50; 23:00-07:00 unlimited; sat,sun 10:00-22:00 alt"
+                .parse()
+                .unwrap();
+        assert_eq!(schedule.windows.len(), 3);
+
+        let workday = schedule.plan_at(at(3, "10:30"));
+        assert_eq!(workday.limits.download_kbps, Some(500));
+        assert_eq!(workday.limits.upload_kbps, Some(50));
+        assert!(!workday.alt_speed);
+
+        // Overnight windows continue into the next day
+        let night = schedule.plan_at(at(6, "03:00"));
+        assert_eq!(night.window.as_deref(), Some("23:00-07:00 unlimited"));
+        assert_eq!(night.limits, SpeedLimits::default());
+
+        assert!(schedule.plan_at(at(6, "12:00")).alt_speed);
+        assert_eq!(schedule.plan_at(at(5, "19:00")), BandwidthPlan::default());
+
+        let weekend: BandwidthWindow = "fri-mon 00:00-23:59 alt".parse().unwrap();
+        assert_eq!(
+            weekend.days,
+            vec![Weekday::Fri, Weekday::Sat, Weekday::Sun, Weekday::Mon]
+        );
+
+        assert!("09:00-18:00".parse::<BandwidthWindow>().is_err());
+        assert!("mon 9-18 alt".parse::<BandwidthWindow>().is_err());
+        assert!("mon 09:00-18:00 down=fast"
+            .parse::<BandwidthWindow>()
+            .is_err());
+        assert!("".parse::<BandwidthSchedule>().unwrap().windows.is_empty());
+    }
+
+    #[test]
+    fn test_stream_activity_expires() {
+        let activity = StreamActivity::default();
+        let now = Utc::now();
+        activity.record("1", now - ChronoDuration::minutes(20));
+        activity.record("2", now - ChronoDuration::minutes(2));
+
+        let grace = Duration::from_secs(10 * 60);
+        assert_eq!(activity.active_listeners(now, grace), vec!["2".to_string()]);
+        assert!(activity
+            .active_listeners(now + ChronoDuration::minutes(9), grace)
+            .is_empty());
+    }
+}
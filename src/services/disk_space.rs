@@ -0,0 +1,449 @@
+//! Disk space monitoring for StepheyBot Music
+//!
+//! Free space is checked per storage tier and sampled into SQLite, so the
+//! growth of each tier can be extrapolated into a days-until-full forecast.
+//! Below the low-space threshold new downloads are refused; below the
+//! critical threshold active torrents are paused as well.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::database::Database;
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Capacity of the filesystem holding a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub total_bytes: u64,
+    /// Available to unprivileged processes, which is what downloads can use
+    pub free_bytes: u64,
+}
+
+impl DiskUsage {
+    pub fn used_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.free_bytes)
+    }
+}
+
+/// Query the filesystem a path lives on
+#[cfg(unix)]
+pub async fn disk_usage(path: &Path) -> std::io::Result<DiskUsage> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    tokio::task::spawn_blocking(move || {
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: `path` is NUL-terminated and `stat` is a valid out pointer
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fragment = stat.f_frsize as u64;
+        Ok(DiskUsage {
+            total_bytes: stat.f_blocks as u64 * fragment,
+            free_bytes: stat.f_bavail as u64 * fragment,
+        })
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+#[cfg(not(unix))]
+pub async fn disk_usage(_path: &Path) -> std::io::Result<DiskUsage> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "free space checks need a Unix filesystem",
+    ))
+}
+
+/// How worried to be about a tier's free space
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpaceLevel {
+    #[default]
+    Ok,
+    /// New downloads are refused
+    Low,
+    /// Active torrents are paused as well
+    Critical,
+}
+
+/// Why a download was refused
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[error("Not enough free disk space on the {tier} tier ({free_gb:.1} GB free, {min_free_gb:.1} GB required)")]
+pub struct InsufficientSpace {
+    pub tier: String,
+    pub free_gb: f64,
+    pub min_free_gb: f64,
+}
+
+/// Free-space thresholds and monitoring settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskSpaceConfig {
+    pub min_free_bytes: u64,
+    pub critical_free_bytes: u64,
+    /// How much history the forecast extrapolates from
+    pub forecast_window: Duration,
+    /// Samples older than this are dropped
+    pub retention: Duration,
+    pub interval: Duration,
+    /// Users told when space runs low, besides the live event
+    pub alert_user_ids: Vec<String>,
+}
+
+impl Default for DiskSpaceConfig {
+    fn default() -> Self {
+        Self {
+            min_free_bytes: (10.0 * GIB) as u64,
+            critical_free_bytes: (2.0 * GIB) as u64,
+            forecast_window: Duration::from_secs(14 * 86400),
+            retention: Duration::from_secs(90 * 86400),
+            interval: Duration::from_secs(15 * 60),
+            alert_user_ids: Vec::new(),
+        }
+    }
+}
+
+impl DiskSpaceConfig {
+    /// Load the configuration, overriding defaults from `STEPHEYBOT__DISK__*`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let gib = |name: &str| {
+            var(name)
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|gb| gb.is_finite() && *gb >= 0.0)
+                .map(|gb| (gb * GIB) as u64)
+        };
+
+        if let Some(bytes) = gib("STEPHEYBOT__DISK__MIN_FREE_GB") {
+            config.min_free_bytes = bytes;
+        }
+        if let Some(bytes) = gib("STEPHEYBOT__DISK__CRITICAL_FREE_GB") {
+            config.critical_free_bytes = bytes;
+        }
+        if let Some(days) = var("STEPHEYBOT__DISK__FORECAST_DAYS")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|days| *days > 0)
+        {
+            config.forecast_window = Duration::from_secs(days * 86400);
+        }
+        if let Some(minutes) = var("STEPHEYBOT__DISK__INTERVAL_MINUTES")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0)
+        {
+            config.interval = Duration::from_secs(minutes * 60);
+        }
+        if let Some(users) = var("STEPHEYBOT__DISK__ALERT_USER_IDS") {
+            config.alert_user_ids = users
+                .split(',')
+                .map(|user| user.trim().to_string())
+                .filter(|user| !user.is_empty())
+                .collect();
+        }
+
+        config
+    }
+
+    pub fn level(&self, free_bytes: u64) -> SpaceLevel {
+        if free_bytes < self.critical_free_bytes {
+            SpaceLevel::Critical
+        } else if free_bytes < self.min_free_bytes {
+            SpaceLevel::Low
+        } else {
+            SpaceLevel::Ok
+        }
+    }
+}
+
+/// Free space of one storage tier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierSpace {
+    pub tier: String,
+    pub path: PathBuf,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    pub used_percent: f64,
+    /// Bytes per day the tier is filling up by, negative when it shrinks
+    pub growth_bytes_per_day: Option<f64>,
+    pub days_until_full: Option<f64>,
+    pub level: SpaceLevel,
+}
+
+/// Latest free-space picture across tiers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskSpaceStatus {
+    pub tiers: Vec<TierSpace>,
+    /// Worst level of any tier
+    pub level: SpaceLevel,
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Torrents paused because space ran critically low
+    pub paused_for_space: Vec<String>,
+}
+
+impl DiskSpaceStatus {
+    /// Refuse new downloads while any tier is low on space
+    pub fn ensure_room(&self, config: &DiskSpaceConfig) -> Result<(), InsufficientSpace> {
+        match self.tiers.iter().find(|tier| tier.level != SpaceLevel::Ok) {
+            Some(tier) => Err(InsufficientSpace {
+                tier: tier.tier.clone(),
+                free_gb: tier.free_bytes as f64 / GIB,
+                min_free_gb: config.min_free_bytes as f64 / GIB,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Human-readable problems, for the health endpoint
+    pub fn alerts(&self) -> Vec<String> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.level != SpaceLevel::Ok)
+            .map(|tier| {
+                let severity = match tier.level {
+                    SpaceLevel::Critical => "critically low on space",
+                    _ => "low on space",
+                };
+                let mut alert = format!(
+                    "{} tier is {}: {:.1} GB free of {:.1} GB",
+                    tier.tier,
+                    severity,
+                    tier.free_bytes as f64 / GIB,
+                    tier.total_bytes as f64 / GIB
+                );
+                if let Some(days) = tier.days_until_full {
+                    alert.push_str(&format!(", full in about {:.1} days", days));
+                }
+                alert
+            })
+            .collect()
+    }
+}
+
+/// Linear trend of free space over time, in bytes per day
+///
+/// A least-squares fit rather than first-to-last, so one large import or
+/// deletion does not dominate the forecast.
+pub fn free_space_trend(samples: &[(DateTime<Utc>, u64)]) -> Option<f64> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let origin = samples[0].0;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(at, free)| ((*at - origin).num_seconds() as f64 / 86400.0, *free as f64))
+        .collect();
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    // Samples spanning less than an hour say nothing about growth
+    if variance <= 0.0 || points.last()?.0 - points[0].0 < 1.0 / 24.0 {
+        return None;
+    }
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    Some(covariance / variance)
+}
+
+/// Days until free space runs out at the current trend, if it is shrinking
+pub fn days_until_full(free_bytes: u64, trend_bytes_per_day: f64) -> Option<f64> {
+    (trend_bytes_per_day < 0.0).then(|| free_bytes as f64 / -trend_bytes_per_day)
+}
+
+/// Persisted free-space samples
+#[derive(Clone)]
+pub struct DiskUsageHistory {
+    database: Arc<Database>,
+}
+
+impl DiskUsageHistory {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+
+    pub async fn record(&self, tier: &str, usage: &DiskUsage, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO disk_usage_samples (tier, sampled_at, total_bytes, free_bytes, used_bytes)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(tier)
+        .bind(at.timestamp())
+        .bind(usage.total_bytes as i64)
+        .bind(usage.free_bytes as i64)
+        .bind(usage.used_bytes() as i64)
+        .execute(self.database.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Free bytes of a tier since a point in time, oldest first
+    pub async fn free_since(
+        &self,
+        tier: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, u64)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT sampled_at, free_bytes FROM disk_usage_samples
+            WHERE tier = ? AND sampled_at >= ?
+            ORDER BY sampled_at
+            "#,
+        )
+        .bind(tier)
+        .bind(since.timestamp())
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let at = Utc.timestamp_opt(row.get("sampled_at"), 0).single()?;
+                Some((at, row.get::<i64, _>("free_bytes").max(0) as u64))
+            })
+            .collect())
+    }
+
+    /// Drop samples older than the retention period
+    pub async fn prune(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM disk_usage_samples WHERE sampled_at < ?")
+            .bind(before.timestamp())
+            .execute(self.database.pool())
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Sample every tier and work out levels and forecasts
+    pub async fn check(
+        &self,
+        tiers: &[(&str, &Path)],
+        config: &DiskSpaceConfig,
+        now: DateTime<Utc>,
+    ) -> Vec<TierSpace> {
+        let window_start =
+            now - ChronoDuration::from_std(config.forecast_window).unwrap_or_default();
+        let mut spaces = Vec::new();
+
+        for (tier, path) in tiers {
+            let usage = match disk_usage(path).await {
+                Ok(usage) => usage,
+                Err(e) => {
+                    tracing::debug!("Cannot check free space of {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            if let Err(e) = self.record(tier, &usage, now).await {
+                tracing::warn!("Failed to record disk usage of {}: {}", tier, e);
+            }
+
+            let trend = self
+                .free_since(tier, window_start)
+                .await
+                .ok()
+                .and_then(|samples| free_space_trend(&samples));
+            spaces.push(TierSpace {
+                tier: tier.to_string(),
+                path: path.to_path_buf(),
+                total_bytes: usage.total_bytes,
+                free_bytes: usage.free_bytes,
+                used_percent: if usage.total_bytes > 0 {
+                    usage.used_bytes() as f64 * 100.0 / usage.total_bytes as f64
+                } else {
+                    0.0
+                },
+                growth_bytes_per_day: trend.map(|trend| -trend),
+                days_until_full: trend.and_then(|trend| days_until_full(usage.free_bytes, trend)),
+                level: config.level(usage.free_bytes),
+            });
+        }
+
+        spaces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_trend_and_forecast() {
+        let start = Utc::now() - ChronoDuration::days(4);
+        let gib = GIB as u64;
+        // Losing 2 GiB a day, with a one-off cleanup in the middle
+        let samples: Vec<(DateTime<Utc>, u64)> = [100, 98, 99, 94, 92]
+            .iter()
+            .enumerate()
+            .map(|(day, free)| (start + ChronoDuration::days(day as i64), free * gib))
+            .collect();
+
+        let trend = free_space_trend(&samples).unwrap();
+        assert!((trend / GIB + 2.0).abs() < 0.2, "trend {}", trend / GIB);
+        let days = days_until_full(92 * gib, trend).unwrap();
+        assert!((40.0..52.0).contains(&days), "days {}", days);
+
+        // Not shrinking, or not enough history, means no forecast
+        assert_eq!(days_until_full(gib, 5.0), None);
+        assert_eq!(free_space_trend(&samples[..1]), None);
+        let close = [(start, gib), (start + ChronoDuration::minutes(5), 0)];
+        assert_eq!(free_space_trend(&close), None);
+
+        let config = DiskSpaceConfig::default();
+        assert_eq!(config.level(20 * gib), SpaceLevel::Ok);
+        assert_eq!(config.level(5 * gib), SpaceLevel::Low);
+        assert_eq!(config.level(gib), SpaceLevel::Critical);
+    }
+
+    #[tokio::test]
+    async fn test_check_records_samples() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let history = DiskUsageHistory::new(database);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let now = Utc::now();
+        let config = DiskSpaceConfig {
+            min_free_bytes: u64::MAX,
+            ..DiskSpaceConfig::default()
+        };
+        let missing = dir.path().join("missing");
+        let spaces = history
+            .check(
+                &[("library", dir.path()), ("nowhere", missing.as_path())],
+                &config,
+                now,
+            )
+            .await;
+
+        assert_eq!(spaces.len(), 1);
+        assert!(spaces[0].total_bytes > 0);
+        assert!(spaces[0].level >= SpaceLevel::Low);
+        let samples = history
+            .free_since("library", now - ChronoDuration::minutes(1))
+            .await
+            .unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(
+            history
+                .prune(now + ChronoDuration::seconds(1))
+                .await
+                .unwrap(),
+            1
+        );
+    }
+}
@@ -4,8 +4,10 @@
 //! for automated music acquisition through Transmission.
 
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use chrono::{DateTime, Datelike, Local, TimeZone, Utc};
+use futures::FutureExt;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,8 +15,40 @@ use tokio::sync::{Mutex, RwLock};
 use tokio::time::{interval, sleep};
 use tracing::{debug, error, info, warn};
 
+use crate::clients::musicbrainz::MusicBrainzClient;
+use crate::clients::navidrome::NavidromeClient;
+use crate::clients::torznab::{IndexerConfig, TorznabClient, TorznabQuery};
 use crate::clients::transmission::{TorrentInfo, TransmissionClient};
-use crate::models::entities::{DownloadFile, DownloadRequest, TorrentDownload};
+use crate::clients::{retry_with_backoff, RetryConfig};
+use crate::database::Database;
+use crate::models::entities::{
+    DownloadPriority, DownloadRequest, DownloadStatus, DownloadTransition, TorrentDownload,
+};
+use crate::services::bandwidth::{
+    BandwidthConfig, BandwidthControl, BandwidthStatus, StreamActivity,
+};
+use crate::services::disk_space::{DiskSpaceConfig, DiskSpaceStatus, DiskUsageHistory, SpaceLevel};
+use crate::services::download_store::{
+    BlocklistEntry, CandidateRecord, DownloadHistoryFilter, DownloadStore, ManualImport,
+};
+use crate::services::events::{EventHub, EventTopic};
+use crate::services::fair_queue::{self, FairQueue, QueueEstimate};
+use crate::services::import::{
+    ImportConfig, ImportError, ImportOptions, ImportOutcome, ImportPipeline, ImportStage,
+    DEFAULT_LIBRARY_TEMPLATE,
+};
+use crate::services::lidarr_webhook::{
+    self, LidarrEvent, LidarrWebhookPayload, LIDARR_SOURCE_PREFIX, LIDARR_USER_ID,
+};
+use crate::services::notifications::{NotificationKind, NotificationService};
+use crate::services::quality_profile::{QualityProfile, SelectionContext, ESTIMATED_ALBUM_TRACKS};
+use crate::services::quota::{QuotaLimits, QuotaPolicy, QuotaUsage, Requester};
+use crate::services::recycle_bin::{
+    RecycleBin, RecycleBinConfig, TrashItem, SYSTEM_ACTOR, TRASH_DIR_NAME,
+};
+use crate::services::seeding::{self, SeedingDecision, SeedingPolicy, SeedingRule, SeedingStatus};
+use crate::services::stall::{StallAction, StallDetector, StallPolicy, TorrentHealth};
+use crate::services::torrent_file::{self, FetchedTorrent, TorrentMetainfo};
 
 /// Download service configuration
 #[derive(Debug, Clone)]
@@ -32,6 +66,22 @@ pub struct DownloadConfig {
     pub seed_ratio_limit: f64,
     pub seed_time_limit: Duration,
     pub auto_delete_completed: bool,
+    pub indexers: Vec<IndexerConfig>,
+    pub quality_profile: QualityProfile,
+    pub library_template: String,
+    pub navidrome_url: Option<String>,
+    pub navidrome_username: String,
+    pub navidrome_password: String,
+    /// Album track counts are looked up on MusicBrainz to size releases
+    pub musicbrainz_user_agent: String,
+    pub quotas: QuotaPolicy,
+    pub lidarr_webhook_secret: Option<String>,
+    pub stall_policy: StallPolicy,
+    /// Backoff for Transmission calls that fail transiently
+    pub retry: RetryConfig,
+    pub bandwidth: BandwidthConfig,
+    pub disk_space: DiskSpaceConfig,
+    pub trash: RecycleBinConfig,
 }
 
 impl Default for DownloadConfig {
@@ -50,6 +100,23 @@ impl Default for DownloadConfig {
             seed_ratio_limit: 2.0,
             seed_time_limit: Duration::from_secs(86400), // 24 hours
             auto_delete_completed: true,
+            indexers: Vec::new(),
+            quality_profile: QualityProfile::default(),
+            library_template: DEFAULT_LIBRARY_TEMPLATE.to_string(),
+            navidrome_url: None,
+            navidrome_username: String::new(),
+            navidrome_password: String::new(),
+            musicbrainz_user_agent: "StepheyBot-Music/1.0".to_string(),
+            quotas: QuotaPolicy::default(),
+            lidarr_webhook_secret: None,
+            stall_policy: StallPolicy::default(),
+            retry: RetryConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+            disk_space: DiskSpaceConfig::default(),
+            trash: RecycleBinConfig {
+                path: PathBuf::from("/downloads").join(TRASH_DIR_NAME),
+                ..RecycleBinConfig::default()
+            },
         }
     }
 }
@@ -58,58 +125,122 @@ impl Default for DownloadConfig {
 pub struct DownloadService {
     config: DownloadConfig,
     transmission: Arc<Mutex<TransmissionClient>>,
+    indexers: TorznabClient,
+    importer: ImportPipeline,
+    navidrome: Option<Arc<NavidromeClient>>,
+    musicbrainz: MusicBrainzClient,
+    seeding: SeedingPolicy,
     active_downloads: Arc<RwLock<HashMap<String, TorrentDownload>>>,
     download_queue: Arc<Mutex<Vec<DownloadRequest>>>,
     processing_queue: Arc<Mutex<Vec<String>>>, // Torrent hashes ready for processing
-    stats: Arc<RwLock<DownloadStats>>,
+    store: DownloadStore,
+    notifications: NotificationService,
+    events: EventHub,
+    stalls: Arc<Mutex<StallDetector>>,
+    streams: StreamActivity,
+    bandwidth: Arc<Mutex<BandwidthStatus>>,
+    fair_queue: Arc<Mutex<FairQueue>>,
+    disk_history: DiskUsageHistory,
+    disk_space: Arc<Mutex<DiskSpaceStatus>>,
+    recycle_bin: RecycleBin,
+    admissions: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>, // Per-user admission locks
 }
 
-/// Download statistics
+/// Download statistics, derived from the persisted request states
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct DownloadStats {
     pub total_downloads: u64,
     pub completed_downloads: u64,
     pub failed_downloads: u64,
+    pub cancelled_downloads: u64,
     pub active_downloads: u64,
     pub queued_downloads: u64,
     pub total_downloaded_bytes: u64,
     pub total_uploaded_bytes: u64,
     pub average_download_speed: u64,
+    pub by_status: BTreeMap<DownloadStatus, u64>,
     pub last_updated: DateTime<Utc>,
 }
 
 impl DownloadService {
     /// Create a new download service
-    pub fn new(config: DownloadConfig) -> Self {
+    pub fn new(config: DownloadConfig, database: Arc<Database>) -> Result<Self> {
         let transmission = Arc::new(Mutex::new(TransmissionClient::new(
             config.transmission_url.clone(),
             config.transmission_username.clone(),
             config.transmission_password.clone(),
         )));
 
-        Self {
+        let indexers = TorznabClient::new(config.indexers.clone())
+            .context("Failed to create indexer client")?;
+
+        let musicbrainz = MusicBrainzClient::new(&config.musicbrainz_user_agent)?;
+
+        let importer = ImportPipeline::new(ImportConfig {
+            processing_path: config.processing_path.clone(),
+            library_path: config.final_library_path.clone(),
+            template: config.library_template.clone(),
+        });
+
+        // Imports are picked up by a Navidrome scan when one is configured
+        let navidrome = config.navidrome_url.as_deref().and_then(|url| {
+            NavidromeClient::new(url, &config.navidrome_username, &config.navidrome_password)
+                .map(Arc::new)
+                .map_err(|e| warn!("Library index updates disabled: {}", e))
+                .ok()
+        });
+
+        // A non-positive limit disables that part of the seeding rule
+        let seeding = SeedingPolicy::new(
+            SeedingRule {
+                ratio_limit: (config.seed_ratio_limit > 0.0).then_some(config.seed_ratio_limit),
+                time_limit: (!config.seed_time_limit.is_zero()).then_some(config.seed_time_limit),
+            },
+            &config.indexers,
+        );
+        let stalls = Arc::new(Mutex::new(StallDetector::new(config.stall_policy)));
+        let recycle_bin = RecycleBin::new(config.trash.clone(), database.clone());
+
+        Ok(Self {
             config,
             transmission,
+            indexers,
+            importer,
+            navidrome,
+            musicbrainz,
+            seeding,
             active_downloads: Arc::new(RwLock::new(HashMap::new())),
             download_queue: Arc::new(Mutex::new(Vec::new())),
             processing_queue: Arc::new(Mutex::new(Vec::new())),
-            stats: Arc::new(RwLock::new(DownloadStats::default())),
-        }
+            store: DownloadStore::new(database.clone()),
+            notifications: NotificationService::new(database.clone()),
+            events: EventHub::new(),
+            stalls,
+            streams: StreamActivity::default(),
+            bandwidth: Arc::new(Mutex::new(BandwidthStatus::default())),
+            fair_queue: Arc::new(Mutex::new(FairQueue::default())),
+            disk_history: DiskUsageHistory::new(database),
+            disk_space: Arc::new(Mutex::new(DiskSpaceStatus::default())),
+            recycle_bin,
+            admissions: Arc::new(Mutex::new(HashMap::new())),
+        })
     }
 
     /// Start the download service background tasks
     pub async fn start(&self) -> Result<()> {
         info!("Starting DownloadService with config: {:?}", self.config);
 
-        // Test Transmission connection
-        self.transmission
-            .lock()
-            .await
-            .health_check()
-            .await
-            .context("Failed to connect to Transmission")?;
+        // Test Transmission connection; queued work is still restored and the
+        // background tasks retry once it comes up
+        match self.transmission.lock().await.health_check().await {
+            Ok(_) => info!("Transmission connection established"),
+            Err(e) => warn!("Failed to connect to Transmission: {}", e),
+        }
 
-        info!("Transmission connection established");
+        // Pick up where we left off before the last shutdown
+        if let Err(e) = self.restore_state().await {
+            error!("Failed to restore persisted downloads: {}", e);
+        }
 
         // Start background tasks
         self.start_background_tasks().await;
@@ -117,38 +248,558 @@ impl DownloadService {
         Ok(())
     }
 
-    /// Add a download request to the queue
-    pub async fn add_download(&self, mut request: DownloadRequest) -> Result<String> {
+    /// Submit a request on behalf of a user, enforcing their quota and
+    /// holding it for admin approval if their role requires it
+    pub async fn submit_download(
+        &self,
+        mut request: DownloadRequest,
+        requester: &Requester,
+    ) -> Result<DownloadRequest> {
+        request.user_id = requester.user_id.clone();
+
+        // Held until the request is stored, so parallel submissions from one
+        // user cannot all pass the quota check on the same usage
+        let admission = self.admission_lock(&requester.user_id).await;
+        let _admitted = admission.lock().await;
+        self.admit(requester, request.file_size).await?;
+
+        if self.needs_approval(requester) {
+            self.transition(
+                &mut request,
+                DownloadStatus::AwaitingApproval,
+                format!(
+                    "Requested by {}, waiting for admin approval",
+                    requester.username
+                ),
+            )
+            .await
+            .context("Failed to persist download request")?;
+            info!(
+                "Download request {} from {} is awaiting approval",
+                request.id, requester.username
+            );
+            return Ok(request);
+        }
+
+        let request_id = self.enqueue(request, None).await?;
+        self.store
+            .get(&request_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Download request {} vanished", request_id))
+    }
+
+    /// Submit a request for an uploaded or fetched `.torrent` file
+    ///
+    /// The file is checked for audio before anything is stored, and its info
+    /// hash and size are known up front rather than after Transmission
+    /// accepts it.
+    pub async fn submit_torrent_file(
+        &self,
+        mut request: DownloadRequest,
+        metainfo: &TorrentMetainfo,
+        torrent: &[u8],
+        requester: &Requester,
+    ) -> Result<DownloadRequest> {
+        metainfo.ensure_audio()?;
+        if self
+            .store
+            .is_blocklisted(Some(&metainfo.info_hash), "", "")
+            .await?
+        {
+            return Err(anyhow::anyhow!(
+                "Torrent '{}' is blocklisted",
+                metainfo.name
+            ));
+        }
+
+        request.magnet_url = None;
+        request.torrent_hash = Some(metainfo.info_hash.clone());
+        request.file_size = Some(metainfo.total_size);
+
+        // Stored first so the download processor never sees the request without it
+        self.store
+            .save_torrent_file(&request.id, &metainfo.info_hash, torrent)
+            .await
+            .context("Failed to store torrent file")?;
+
+        let request_id = request.id.clone();
+        let result = self.submit_download(request, requester).await;
+        if result.is_err() {
+            if let Err(e) = self.store.delete_torrent_file(&request_id).await {
+                warn!("Failed to drop torrent file of {}: {}", request_id, e);
+            }
+        }
+        result
+    }
+
+    /// Submit a request for a torrent behind an HTTP(S) URL
+    ///
+    /// Nothing is fetched for a user who is out of quota or space. When the
+    /// user needs approval, the URL is kept and only fetched once an admin
+    /// approves the request.
+    pub async fn submit_torrent_url(
+        &self,
+        mut request: DownloadRequest,
+        url: &str,
+        requester: &Requester,
+    ) -> Result<DownloadRequest> {
+        self.admit(requester, None).await?;
+        request.source_url = Some(url.to_string());
+        if self.needs_approval(requester) {
+            return self.submit_download(request, requester).await;
+        }
+
+        match torrent_file::fetch(url, &self.trusted_hosts()).await? {
+            FetchedTorrent::File { metainfo, bytes } => {
+                self.submit_torrent_file(request, &metainfo, &bytes, requester)
+                    .await
+            }
+            // The indexer redirected to a magnet link, carry on with that
+            FetchedTorrent::Magnet(magnet) => {
+                request.magnet_url = Some(magnet);
+                self.submit_download(request, requester).await
+            }
+        }
+    }
+
+    /// Refuse new requests while disk space or the user's quota has run out
+    async fn admit(&self, requester: &Requester, expected_bytes: Option<u64>) -> Result<()> {
+        self.disk_space
+            .lock()
+            .await
+            .ensure_room(&self.config.disk_space)?;
+
+        let usage = self.quota_usage(&requester.user_id).await?;
+        self.config
+            .quotas
+            .check(requester.role, &usage, expected_bytes)?;
+        Ok(())
+    }
+
+    async fn admission_lock(&self, user_id: &str) -> Arc<Mutex<()>> {
+        Arc::clone(
+            self.admissions
+                .lock()
+                .await
+                .entry(user_id.to_string())
+                .or_default(),
+        )
+    }
+
+    fn needs_approval(&self, requester: &Requester) -> bool {
+        self.config.quotas.limits(requester.role).requires_approval
+    }
+
+    /// Indexer hosts, whose download links may be on the local network
+    fn trusted_hosts(&self) -> Vec<String> {
+        torrent_file::url_hosts(
+            self.config
+                .indexers
+                .iter()
+                .map(|indexer| indexer.url.as_str()),
+        )
+    }
+
+    /// Fetch the torrent of an approved request that was submitted as a URL
+    async fn fetch_approved_torrent(&self, request: &mut DownloadRequest) -> Result<()> {
+        let Some(url) = request
+            .source_url
+            .clone()
+            .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+        else {
+            return Ok(());
+        };
+        if request.magnet_url.is_some() || self.store.has_torrent_file(&request.id).await? {
+            return Ok(());
+        }
+
+        match torrent_file::fetch(&url, &self.trusted_hosts()).await? {
+            FetchedTorrent::File { metainfo, bytes } => {
+                metainfo.ensure_audio()?;
+                request.torrent_hash = Some(metainfo.info_hash.clone());
+                request.file_size = Some(metainfo.total_size);
+                self.store
+                    .save_torrent_file(&request.id, &metainfo.info_hash, &bytes)
+                    .await
+                    .context("Failed to store torrent file")?;
+            }
+            FetchedTorrent::Magnet(magnet) => request.magnet_url = Some(magnet),
+        }
+        Ok(())
+    }
+
+    /// Get a user's quota limits and current usage
+    pub async fn get_quota(&self, requester: &Requester) -> Result<(QuotaLimits, QuotaUsage)> {
+        let usage = self.quota_usage(&requester.user_id).await?;
+        Ok((self.config.quotas.limits(requester.role).clone(), usage))
+    }
+
+    /// Whether requests without an authenticated user are accepted
+    pub fn allows_anonymous_requests(&self) -> bool {
+        self.config.quotas.allow_anonymous
+    }
+
+    /// List requests waiting for an admin decision, oldest first, a page at a time
+    pub async fn pending_approvals(&self, limit: u32, offset: u32) -> Result<Vec<DownloadRequest>> {
+        let filter = DownloadHistoryFilter {
+            status: Some(DownloadStatus::AwaitingApproval),
+            limit: Some(limit),
+            offset: Some(offset),
+            oldest_first: true,
+            ..Default::default()
+        };
+        self.store.history(&filter).await
+    }
+
+    /// Approve a waiting request and queue it
+    pub async fn approve_download(
+        &self,
+        request_id: &str,
+        admin: &Requester,
+    ) -> Result<DownloadRequest> {
+        let mut request = self.awaiting_approval(request_id).await?;
+        let user_id = request.user_id.clone();
+        let description = request.full_description();
+        self.fetch_approved_torrent(&mut request).await?;
+
+        self.enqueue(request, Some(format!("Approved by {}", admin.username)))
+            .await?;
+
+        self.notify(
+            &user_id,
+            NotificationKind::DownloadApproved,
+            "Download approved",
+            &format!("Your request for {} was approved", description),
+            request_id,
+        )
+        .await;
+
+        self.store
+            .get(request_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Download request {} vanished", request_id))
+    }
+
+    /// Reject a waiting request with a reason for the requester
+    pub async fn reject_download(
+        &self,
+        request_id: &str,
+        admin: &Requester,
+        reason: &str,
+    ) -> Result<DownloadRequest> {
+        let mut request = self.awaiting_approval(request_id).await?;
+
+        self.transition(
+            &mut request,
+            DownloadStatus::Rejected,
+            format!("Rejected by {}: {}", admin.username, reason),
+        )
+        .await?;
+
+        self.notify(
+            &request.user_id,
+            NotificationKind::DownloadRejected,
+            "Download rejected",
+            &format!(
+                "Your request for {} was rejected: {}",
+                request.full_description(),
+                reason
+            ),
+            request_id,
+        )
+        .await;
+
+        Ok(request)
+    }
+
+    /// Access the notification inbox
+    pub fn notifications(&self) -> &NotificationService {
+        &self.notifications
+    }
+
+    /// Live download and scan events for push clients
+    pub fn events(&self) -> &EventHub {
+        &self.events
+    }
+
+    /// Whether Lidarr webhooks are accepted at all
+    pub fn lidarr_webhook_enabled(&self) -> bool {
+        self.config
+            .lidarr_webhook_secret
+            .as_deref()
+            .is_some_and(|secret| !secret.is_empty())
+    }
+
+    /// Check the shared secret presented with a Lidarr webhook
+    pub fn verify_lidarr_webhook(&self, secret: &str) -> bool {
+        self.config
+            .lidarr_webhook_secret
+            .as_deref()
+            .is_some_and(|expected| lidarr_webhook::verify_secret(expected, secret))
+    }
+
+    /// Remember who asked Lidarr to monitor an artist, so they can be told
+    /// when its albums land in the library
+    pub async fn record_artist_request(
+        &self,
+        foreign_artist_id: &str,
+        artist_name: &str,
+        requester: &Requester,
+    ) -> Result<()> {
+        self.store
+            .add_artist_request(foreign_artist_id, artist_name, &requester.user_id)
+            .await
+    }
+
+    /// Apply an event posted by Lidarr, returning the download history entry
+    /// it touched
+    pub async fn handle_lidarr_event(
+        &self,
+        payload: &LidarrWebhookPayload,
+    ) -> Result<Option<DownloadRequest>> {
+        let event = payload.event();
+        info!(
+            "Lidarr event {:?} for {} - {}",
+            event,
+            payload.artist_name(),
+            payload.album_title().unwrap_or_default()
+        );
+
+        let request = match event {
+            LidarrEvent::Grab => Some(self.record_lidarr_grab(payload).await?),
+            LidarrEvent::ReleaseImport | LidarrEvent::Upgrade => {
+                Some(self.record_lidarr_import(payload).await?)
+            }
+            _ => None,
+        };
+
+        if event.changes_library() {
+            if let Some(navidrome) = self.navidrome.clone() {
+                let events = self.events.clone();
+                tokio::spawn(async move {
+                    match navidrome.start_scan(false).await {
+                        Ok(_) => {
+                            events.publish(
+                                EventTopic::Scan,
+                                "scan_started",
+                                None,
+                                serde_json::json!({ "full_scan": false, "source": "lidarr" }),
+                            );
+                        }
+                        Err(e) => warn!("Failed to start library scan after Lidarr event: {}", e),
+                    }
+                });
+            }
+        }
+
+        Ok(request)
+    }
+
+    /// Mirror a release Lidarr grabbed into the download history
+    async fn record_lidarr_grab(&self, payload: &LidarrWebhookPayload) -> Result<DownloadRequest> {
+        let mut request = self.lidarr_request(payload).await?;
+        if request.status != DownloadStatus::Pending {
+            // Lidarr retries deliveries, the grab is already recorded
+            return Ok(request);
+        }
+
+        let release = payload.release.clone().unwrap_or_default();
+        self.transition(
+            &mut request,
+            DownloadStatus::Queued,
+            format!(
+                "Grabbed by Lidarr from {}: {}",
+                release.indexer.as_deref().unwrap_or("unknown indexer"),
+                release
+                    .release_title
+                    .as_deref()
+                    .unwrap_or("unknown release")
+            ),
+        )
+        .await?;
+        self.transition(
+            &mut request,
+            DownloadStatus::Downloading,
+            format!(
+                "Downloading via {}",
+                payload.download_client.as_deref().unwrap_or("Lidarr")
+            ),
+        )
+        .await?;
+
+        Ok(request)
+    }
+
+    /// Record an album Lidarr imported and tell the users who asked for the
+    /// artist that it has arrived
+    async fn record_lidarr_import(
+        &self,
+        payload: &LidarrWebhookPayload,
+    ) -> Result<DownloadRequest> {
+        let mut request = self.lidarr_request(payload).await?;
+        let upgrade = payload.is_upgrade;
+
+        request.file_size = payload.size().or(request.file_size);
+        request.download_path = payload
+            .track_files
+            .first()
+            .and_then(|file| Path::new(&file.path).parent())
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .or_else(|| {
+                payload
+                    .artist
+                    .as_ref()
+                    .and_then(|artist| artist.path.clone())
+            })
+            .or(request.download_path);
+
+        if request.status == DownloadStatus::Imported {
+            self.store.save(&request).await?;
+        }
+
+        // Walk the request through the states Lidarr skipped telling us about
+        while request.status != DownloadStatus::Imported {
+            let (next, reason) = match request.status {
+                DownloadStatus::Pending => {
+                    (DownloadStatus::Queued, "Grabbed by Lidarr".to_string())
+                }
+                DownloadStatus::Queued => (
+                    DownloadStatus::Downloading,
+                    "Downloading via Lidarr".to_string(),
+                ),
+                DownloadStatus::Downloading | DownloadStatus::Stalled => (
+                    DownloadStatus::Completed,
+                    "Lidarr finished downloading".to_string(),
+                ),
+                DownloadStatus::Failed => (
+                    DownloadStatus::Verifying,
+                    "Lidarr retried the import".to_string(),
+                ),
+                DownloadStatus::Completed | DownloadStatus::Verifying => (
+                    DownloadStatus::Importing,
+                    format!("Lidarr importing {} files", payload.track_files.len()),
+                ),
+                DownloadStatus::Importing if upgrade => {
+                    (DownloadStatus::Imported, "Upgraded by Lidarr".to_string())
+                }
+                DownloadStatus::Importing => {
+                    (DownloadStatus::Imported, "Imported by Lidarr".to_string())
+                }
+                _ => break,
+            };
+            self.transition(&mut request, next, reason).await?;
+        }
+
+        // Upgrades replace files users already have, only new albums are news
+        if !upgrade {
+            let artist = payload.artist.as_ref();
+            let requesters = self
+                .store
+                .artist_requesters(
+                    artist.and_then(|artist| artist.mb_id.as_deref()),
+                    payload.artist_name(),
+                )
+                .await?;
+            let album = payload
+                .album_title()
+                .unwrap_or_else(|| "A new album".to_string());
+
+            for user_id in requesters {
+                self.notify(
+                    &user_id,
+                    NotificationKind::AlbumImported,
+                    "Album imported",
+                    &format!(
+                        "{} by {} is now in your library",
+                        album,
+                        payload.artist_name()
+                    ),
+                    &request.id,
+                )
+                .await;
+            }
+        }
+
+        Ok(request)
+    }
+
+    /// Find the history entry for a Lidarr download, or start a new one
+    async fn lidarr_request(&self, payload: &LidarrWebhookPayload) -> Result<DownloadRequest> {
+        let source_url = payload.source_url();
+        if let Some(source_url) = &source_url {
+            if let Some(request) = self.store.get_by_source_url(source_url).await? {
+                return Ok(request);
+            }
+        }
+
+        let album = payload.album_title();
+        let mut request = DownloadRequest::new(
+            LIDARR_USER_ID.to_string(),
+            payload.artist_name().to_string(),
+            album.clone().unwrap_or_else(|| "Unknown Album".to_string()),
+        );
+        request.album_title = album;
+        request.file_size = payload.size();
+        request.source_url =
+            Some(source_url.unwrap_or_else(|| format!("{}{}", LIDARR_SOURCE_PREFIX, request.id)));
+        Ok(request)
+    }
+
+    /// Validate, persist and queue a request, noting why it was queued
+    async fn enqueue(&self, mut request: DownloadRequest, note: Option<String>) -> Result<String> {
         let request_id = request.id.clone();
+        let has_torrent_file =
+            request.magnet_url.is_none() && self.store.has_torrent_file(&request_id).await?;
 
         // Validate magnet URL
-        if let Some(magnet_url) = &request.magnet_url {
-            if !magnet_url.starts_with("magnet:") {
-                request.status = "failed".to_string();
-                request.error_message = Some("Invalid magnet URL".to_string());
-                return Err(anyhow::anyhow!("Invalid magnet URL"));
+        let invalid = match &request.magnet_url {
+            Some(magnet_url) if !magnet_url.starts_with("magnet:") => Some("Invalid magnet URL"),
+            Some(_) => None,
+            None if has_torrent_file => None,
+            // Without a magnet link a release is picked from the indexers
+            None if self.indexers.is_configured() => None,
+            None => Some("No magnet URL provided"),
+        };
+        if let Some(reason) = invalid {
+            if let Err(e) = self
+                .transition(&mut request, DownloadStatus::Failed, reason)
+                .await
+            {
+                warn!("Failed to record rejected download {}: {}", request_id, e);
             }
-        } else {
-            request.status = "failed".to_string();
-            request.error_message = Some("No magnet URL provided".to_string());
-            return Err(anyhow::anyhow!("No magnet URL provided"));
+            return Err(anyhow::anyhow!(reason));
+        }
+
+        if request.torrent_hash.is_none() {
+            request.torrent_hash = request
+                .magnet_url
+                .as_deref()
+                .and_then(torrent_file::magnet_info_hash);
         }
 
+        // Persist before queueing so a restart cannot lose the request
+        let (next, reason) = if request.magnet_url.is_some() || has_torrent_file {
+            (DownloadStatus::Queued, "Queued for download")
+        } else {
+            (
+                DownloadStatus::Searching,
+                "Queued for indexer search and release selection",
+            )
+        };
+        let reason = match &note {
+            Some(note) => format!("{}; {}", note, reason.to_lowercase()),
+            None => reason.to_string(),
+        };
+        self.transition(&mut request, next, reason)
+            .await
+            .context("Failed to persist download request")?;
+
         // Add to queue
         {
             let mut queue = self.download_queue.lock().await;
-            request.status = "queued".to_string();
             queue.push(request);
         }
 
-        // Update stats
-        {
-            let mut stats = self.stats.write().await;
-            stats.queued_downloads += 1;
-            stats.total_downloads += 1;
-            stats.last_updated = Utc::now();
-        }
-
         info!("Added download request to queue: {}", request_id);
         Ok(request_id)
     }
@@ -163,40 +814,235 @@ impl DownloadService {
             }
         }
 
-        // Check active downloads
+        // Fall back to the persisted record
+        let mut request = match self.store.get(request_id).await {
+            Ok(Some(request)) => request,
+            Ok(None) => return None,
+            Err(e) => {
+                error!("Failed to load download request {}: {}", request_id, e);
+                return None;
+            }
+        };
+
+        // Overlay live torrent data for active downloads
         {
             let active = self.active_downloads.read().await;
-            for download in active.values() {
-                if download.download_request_id == request_id {
-                    // Convert TorrentDownload back to DownloadRequest format
-                    let mut request = DownloadRequest::new(
-                        "system".to_string(),
-                        "Unknown".to_string(),
-                        download.name.clone(),
-                    );
-                    request.id = request_id.to_string();
-                    request.status = download.status.clone();
-                    request.progress = Some(download.progress);
-                    request.file_size = Some(download.size);
-                    request.download_speed = Some(download.download_speed);
-                    request.seeds = Some(download.seeds);
-                    request.peers = Some(download.peers);
-                    request.torrent_hash = Some(download.torrent_hash.clone());
-                    request.started_at = Some(download.added_at);
-                    request.completed_at = download.completed_at;
+            if let Some(download) = active
+                .values()
+                .find(|d| d.download_request_id == request_id)
+            {
+                request.progress = Some(download.progress);
+                request.file_size = Some(download.size);
+                request.download_speed = Some(download.download_speed);
+                request.upload_speed = Some(download.upload_speed);
+                request.seeds = Some(download.seeds);
+                request.peers = Some(download.peers);
+                request.torrent_hash = Some(download.torrent_hash.clone());
+            }
+        }
 
-                    return Some(request);
-                }
+        Some(request)
+    }
+
+    /// Queue positions and estimated start times, in start order
+    pub async fn queue_estimates(&self) -> Vec<QueueEstimate> {
+        let now = Utc::now();
+        let (busy_until, average_speed) = {
+            let active = self.active_downloads.read().await;
+            let busy_until: Vec<DateTime<Utc>> = active
+                .values()
+                .map(|download| {
+                    let remaining = if download.progress < 1.0 && download.eta > 0 {
+                        Duration::from_secs(download.eta as u64)
+                    } else {
+                        fair_queue::DEFAULT_DOWNLOAD_ESTIMATE
+                    };
+                    now + chrono::Duration::from_std(remaining).unwrap_or_default()
+                })
+                .collect();
+            let speeds: Vec<u64> = active
+                .values()
+                .map(|download| download.download_speed)
+                .filter(|speed| *speed > 0)
+                .collect();
+            let average_speed =
+                (!speeds.is_empty()).then(|| speeds.iter().sum::<u64>() / speeds.len() as u64);
+            (busy_until, average_speed)
+        };
+
+        let queue = self.download_queue.lock().await;
+        let order = self.fair_queue.lock().await.order(&queue);
+        fair_queue::estimate_starts(
+            &queue,
+            &order,
+            self.config.max_concurrent_downloads,
+            &busy_until,
+            average_speed,
+            now,
+        )
+    }
+
+    /// Where a queued request stands, `None` once it has started
+    pub async fn queue_estimate(&self, request_id: &str) -> Option<QueueEstimate> {
+        self.queue_estimates()
+            .await
+            .into_iter()
+            .find(|estimate| estimate.request_id == request_id)
+    }
+
+    /// Change the priority a requester gave one of their requests
+    pub async fn set_priority(
+        &self,
+        request_id: &str,
+        priority: DownloadPriority,
+        requester: &Requester,
+    ) -> Result<DownloadRequest> {
+        self.update_priority(request_id, requester, |request| {
+            request.priority = priority;
+        })
+        .await
+    }
+
+    /// Set or clear the admin override that ranks a request against other users'
+    pub async fn override_priority(
+        &self,
+        request_id: &str,
+        priority: Option<DownloadPriority>,
+        admin: &Requester,
+    ) -> Result<DownloadRequest> {
+        if !admin.is_admin() {
+            return Err(anyhow::anyhow!("Only admins can override priorities"));
+        }
+        self.update_priority(request_id, admin, |request| {
+            request.priority_override = priority;
+        })
+        .await
+    }
+
+    async fn update_priority(
+        &self,
+        request_id: &str,
+        requester: &Requester,
+        update: impl Fn(&mut DownloadRequest),
+    ) -> Result<DownloadRequest> {
+        let mut request = self
+            .store
+            .get(request_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Download request {} not found", request_id))?;
+        if request.user_id != requester.user_id && !requester.is_admin() {
+            return Err(anyhow::anyhow!(
+                "Download request {} belongs to another user",
+                request_id
+            ));
+        }
+        if request.status.is_terminal() {
+            return Err(anyhow::anyhow!(
+                "Download request {} is already {}",
+                request_id,
+                request.status
+            ));
+        }
+
+        // The queued copy is what the processor picks from
+        {
+            let mut queue = self.download_queue.lock().await;
+            if let Some(queued) = queue.iter_mut().find(|queued| queued.id == request_id) {
+                update(queued);
             }
         }
+        update(&mut request);
+        self.store.save(&request).await?;
+
+        info!(
+            "Download {} priority is now {} (override: {:?})",
+            request_id, request.priority, request.priority_override
+        );
+        Ok(request)
+    }
+
+    /// Query the persisted download history
+    pub async fn get_history(
+        &self,
+        filter: &DownloadHistoryFilter,
+    ) -> Result<Vec<DownloadRequest>> {
+        self.store.history(filter).await
+    }
+
+    /// Get the release candidates considered for a request and why they were rejected
+    pub async fn get_candidates(&self, request_id: &str) -> Result<Vec<CandidateRecord>> {
+        self.store.candidates(request_id).await
+    }
+
+    /// Releases that were given up on and will not be grabbed again
+    pub async fn get_blocklist(&self, limit: u32) -> Result<Vec<BlocklistEntry>> {
+        self.store.blocklist(limit).await
+    }
+
+    /// Whether requests without a magnet link can be resolved through indexers
+    pub fn can_search_indexers(&self) -> bool {
+        self.indexers.is_configured()
+    }
 
-        None
+    /// Get the recorded state transitions of a request
+    pub async fn get_transitions(&self, request_id: &str) -> Result<Vec<DownloadTransition>> {
+        self.store.transitions(request_id).await
     }
 
     /// Get current download statistics
     pub async fn get_stats(&self) -> DownloadStats {
-        let stats = self.stats.read().await;
-        stats.clone()
+        let mut stats = DownloadStats {
+            last_updated: Utc::now(),
+            ..Default::default()
+        };
+
+        let totals = match self.store.status_totals().await {
+            Ok(totals) => totals,
+            Err(e) => {
+                error!("Failed to load download statistics: {}", e);
+                return stats;
+            }
+        };
+
+        for (status, totals) in totals {
+            stats.total_downloads += totals.count;
+            stats.by_status.insert(status, totals.count);
+
+            match status {
+                DownloadStatus::Pending
+                | DownloadStatus::AwaitingApproval
+                | DownloadStatus::Searching
+                | DownloadStatus::Queued => {
+                    stats.queued_downloads += totals.count;
+                }
+                DownloadStatus::Downloading | DownloadStatus::Stalled => {
+                    stats.active_downloads += totals.count;
+                }
+                DownloadStatus::Completed
+                | DownloadStatus::Verifying
+                | DownloadStatus::Importing
+                | DownloadStatus::Imported => {
+                    stats.completed_downloads += totals.count;
+                    stats.total_downloaded_bytes += totals.bytes;
+                }
+                DownloadStatus::Failed => stats.failed_downloads += totals.count,
+                DownloadStatus::Cancelled | DownloadStatus::Rejected => {
+                    stats.cancelled_downloads += totals.count
+                }
+            }
+        }
+
+        // Speeds only make sense for torrents the client is working on right now
+        let active = self.active_downloads.read().await;
+        let downloading: Vec<_> = active.values().filter(|d| !d.is_completed()).collect();
+        if !downloading.is_empty() {
+            stats.average_download_speed =
+                downloading.iter().map(|d| d.download_speed).sum::<u64>()
+                    / downloading.len() as u64;
+        }
+        stats.total_uploaded_bytes = active.values().map(|d| d.uploaded).sum();
+
+        stats
     }
 
     /// Get list of active downloads
@@ -227,21 +1073,43 @@ impl DownloadService {
         Ok(())
     }
 
-    /// Cancel a download
-    pub async fn cancel_download(&self, torrent_hash: &str, delete_files: bool) -> Result<()> {
+    /// Cancel a download; deleted files go to the recycle bin on behalf of `actor`
+    pub async fn cancel_download(
+        &self,
+        torrent_hash: &str,
+        delete_files: bool,
+        actor: &str,
+    ) -> Result<()> {
         // Remove from active downloads
         {
             let mut active = self.active_downloads.write().await;
             active.remove(torrent_hash);
         }
+        self.stalls.lock().await.forget(torrent_hash);
 
-        // Delete from Transmission
-        self.transmission
-            .lock()
-            .await
-            .remove_torrent(torrent_hash, delete_files)
-            .await?;
+        // Delete from Transmission; the request is cancelled even if that fails
+        let removed = if delete_files {
+            self.remove_torrent_to_trash(torrent_hash, "Download cancelled", actor)
+                .await
+                .map(|_| ())
+        } else {
+            self.transmission
+                .lock()
+                .await
+                .remove_torrent(torrent_hash, false)
+                .await
+        };
 
+        if let Some(mut request) = self.stored_request_for(torrent_hash).await {
+            if let Err(e) = self
+                .transition(&mut request, DownloadStatus::Cancelled, "Cancelled by user")
+                .await
+            {
+                warn!("Failed to record cancellation of {}: {}", torrent_hash, e);
+            }
+        }
+
+        removed?;
         info!(
             "Cancelled download: {} (delete_files: {})",
             torrent_hash, delete_files
@@ -270,6 +1138,18 @@ impl DownloadService {
         tokio::spawn(async move {
             service.file_processor_task().await;
         });
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.bandwidth_task().await;
+        });
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.disk_space_task().await;
+        });
+
+        self.recycle_bin.start();
     }
 
     /// Task to process download queue
@@ -289,13 +1169,21 @@ impl DownloadService {
                 continue;
             }
 
-            // Get next download from queue
+            // Queued downloads wait until there is room for them again
+            if self.disk_space.lock().await.level != SpaceLevel::Ok {
+                continue;
+            }
+
+            // Get next download from queue, taking turns between users
             let next_download = {
                 let mut queue = self.download_queue.lock().await;
-                if queue.is_empty() {
+                let mut fair_queue = self.fair_queue.lock().await;
+                let Some(index) = fair_queue.next(&queue) else {
                     continue;
-                }
-                queue.remove(0)
+                };
+                let request = queue.remove(index);
+                fair_queue.served(&request.user_id);
+                request
             };
 
             // Process the download
@@ -344,31 +1232,331 @@ impl DownloadService {
         }
     }
 
-    /// Process a single download request
-    async fn process_download_request(&self, mut request: DownloadRequest) -> Result<()> {
-        info!("Processing download request: {}", request.id);
-
-        let magnet_url = request
-            .magnet_url
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No magnet URL"))?;
+    /// Task to apply the bandwidth schedule
+    async fn bandwidth_task(&self) {
+        let mut interval = interval(self.config.bandwidth.interval);
 
-        // Add to Transmission
-        match self
-            .transmission
-            .lock()
-            .await
-            .add_magnet(
-                magnet_url,
-                Some(&self.config.download_path.to_string_lossy()),
-                Some(false), // Don't start paused
-            )
-            .await
-        {
-            Ok(torrent_hash) => {
-                request.torrent_hash = Some(torrent_hash.clone());
-                request.status = "downloading".to_string();
-                request.started_at = Some(Utc::now());
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.apply_bandwidth_schedule(Utc::now()).await {
+                error!("Failed to apply bandwidth schedule: {}", e);
+            }
+        }
+    }
+
+    /// Task to check free space and forecast growth
+    async fn disk_space_task(&self) {
+        let mut interval = interval(self.config.disk_space.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.check_disk_space(Utc::now()).await {
+                error!("Failed to check disk space: {}", e);
+            }
+        }
+    }
+
+    /// Free space per tier, forecasts and what is paused for lack of space
+    pub async fn disk_space_status(&self) -> DiskSpaceStatus {
+        self.disk_space.lock().await.clone()
+    }
+
+    /// Sample free space, pause torrents when it runs critically low and
+    /// resume them once there is comfortably enough again
+    async fn check_disk_space(&self, now: DateTime<Utc>) -> Result<()> {
+        let config = &self.config.disk_space;
+        let tiers = [
+            ("downloads", self.config.download_path.as_path()),
+            ("processing", self.config.processing_path.as_path()),
+            ("library", self.config.final_library_path.as_path()),
+        ];
+        let spaces = self.disk_history.check(&tiers, config, now).await;
+        let level = spaces
+            .iter()
+            .map(|tier| tier.level)
+            .max()
+            .unwrap_or_default();
+        let retention = chrono::Duration::from_std(config.retention).unwrap_or_default();
+        self.disk_history.prune(now - retention).await?;
+
+        // Torrents the bandwidth schedule paused stay its business
+        let paused_for_streaming = self.bandwidth.lock().await.paused_for_streaming.clone();
+
+        let mut status = self.disk_space.lock().await;
+        let previous = status.level;
+        status.tiers = spaces;
+        status.level = level;
+        status.checked_at = Some(now);
+
+        if level == SpaceLevel::Critical {
+            let downloading: Vec<String> = self
+                .active_downloads
+                .read()
+                .await
+                .values()
+                .filter(|download| download.progress < 1.0)
+                .map(|download| download.torrent_hash.clone())
+                .collect();
+            for hash in downloading {
+                if status.paused_for_space.contains(&hash) {
+                    continue;
+                }
+                match self.transmission.lock().await.pause_torrent(&hash).await {
+                    Ok(()) => {
+                        warn!(
+                            "Paused download {} while disk space is critically low",
+                            hash
+                        );
+                        status.paused_for_space.push(hash);
+                    }
+                    Err(e) => warn!("Failed to pause {} for disk space: {}", hash, e),
+                }
+            }
+        } else if level == SpaceLevel::Ok {
+            let active = self.active_downloads.read().await;
+            let mut still_paused = Vec::new();
+            for hash in std::mem::take(&mut status.paused_for_space) {
+                if !active.contains_key(&hash) || paused_for_streaming.contains(&hash) {
+                    continue;
+                }
+                match self.transmission.lock().await.resume_torrent(&hash).await {
+                    Ok(()) => info!("Resumed download {} now that disk space is back", hash),
+                    Err(e) => {
+                        warn!("Failed to resume {} after freeing space: {}", hash, e);
+                        still_paused.push(hash);
+                    }
+                }
+            }
+            status.paused_for_space = still_paused;
+        }
+
+        if level != previous {
+            let alerts = status.alerts();
+            self.events.publish(
+                EventTopic::Download,
+                "disk_space",
+                None,
+                serde_json::json!({ "level": level, "alerts": alerts, "tiers": status.tiers }),
+            );
+
+            if level > previous {
+                warn!("Disk space is {:?}: {}", level, alerts.join("; "));
+                let title = match level {
+                    SpaceLevel::Critical => "Disk space critically low, downloads paused",
+                    _ => "Disk space low, new downloads refused",
+                };
+                let data = serde_json::json!({ "level": level, "tiers": status.tiers });
+                for user_id in &config.alert_user_ids {
+                    if let Err(e) = self
+                        .notifications
+                        .notify(
+                            user_id,
+                            NotificationKind::LowDiskSpace,
+                            title,
+                            &alerts.join("\n"),
+                            data.clone(),
+                        )
+                        .await
+                    {
+                        warn!("Failed to notify {} about disk space: {}", user_id, e);
+                    }
+                }
+            } else {
+                info!("Disk space recovered to {:?}", level);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record that a user started streaming, so low-priority downloads make room
+    pub fn record_stream(&self, user_id: &str) {
+        self.streams.record(user_id, Utc::now());
+    }
+
+    /// Current speed limits and what is paused for streaming
+    pub async fn bandwidth_status(&self) -> BandwidthStatus {
+        self.bandwidth.lock().await.clone()
+    }
+
+    /// Apply the speed limits of the current schedule window, and pause or
+    /// resume low-priority downloads depending on whether anyone is streaming
+    async fn apply_bandwidth_schedule(&self, now: DateTime<Utc>) -> Result<()> {
+        let plan = self
+            .config
+            .bandwidth
+            .schedule
+            .plan_at(now.with_timezone(&Local).naive_local());
+        // Applied every pass so manual changes in the client are undone;
+        // without a schedule the client's own limits are left alone
+        if !self.config.bandwidth.schedule.windows.is_empty() {
+            self.transmission.as_ref().apply(&plan).await?;
+        }
+
+        let listeners = self
+            .streams
+            .active_listeners(now, self.config.bandwidth.stream_grace);
+        let streaming =
+            !listeners.is_empty() && self.config.bandwidth.pause_low_priority_while_streaming;
+
+        let mut status = self.bandwidth.lock().await;
+        if status.plan.as_ref() != Some(&plan) {
+            info!(
+                "Bandwidth schedule: {}",
+                plan.window.as_deref().unwrap_or("no window, unlimited")
+            );
+            self.events.publish(
+                EventTopic::Download,
+                "bandwidth",
+                None,
+                serde_json::json!({ "plan": plan }),
+            );
+            status.plan = Some(plan);
+            status.applied_at = Some(now);
+        }
+        status.listeners = listeners;
+
+        if streaming {
+            for hash in self.low_priority_downloads().await {
+                if status.paused_for_streaming.contains(&hash) {
+                    continue;
+                }
+                match self.transmission.lock().await.pause_torrent(&hash).await {
+                    Ok(()) => {
+                        info!("Paused low-priority download {} while streaming", hash);
+                        status.paused_for_streaming.push(hash);
+                    }
+                    Err(e) => warn!("Failed to pause {} for streaming: {}", hash, e),
+                }
+            }
+        } else {
+            let active = self.active_downloads.read().await;
+            let paused_for_space = self.disk_space.lock().await.paused_for_space.clone();
+            let mut still_paused = Vec::new();
+            for hash in std::mem::take(&mut status.paused_for_streaming) {
+                // Resumed by the disk space check once there is room again
+                if !active.contains_key(&hash) || paused_for_space.contains(&hash) {
+                    continue;
+                }
+                match self.transmission.lock().await.resume_torrent(&hash).await {
+                    Ok(()) => info!("Resumed low-priority download {}", hash),
+                    Err(e) => {
+                        warn!("Failed to resume {} after streaming: {}", hash, e);
+                        still_paused.push(hash);
+                    }
+                }
+            }
+            status.paused_for_streaming = still_paused;
+        }
+
+        Ok(())
+    }
+
+    /// Torrents of low-priority requests that are still downloading
+    async fn low_priority_downloads(&self) -> Vec<String> {
+        let active: Vec<(String, String)> = self
+            .active_downloads
+            .read()
+            .await
+            .values()
+            .filter(|download| download.progress < 1.0)
+            .map(|download| {
+                (
+                    download.torrent_hash.clone(),
+                    download.download_request_id.clone(),
+                )
+            })
+            .collect();
+
+        let mut hashes = Vec::new();
+        for (hash, request_id) in active {
+            if let Ok(Some(request)) = self.store.get(&request_id).await {
+                if request.effective_priority() == DownloadPriority::Low
+                    && request.status == DownloadStatus::Downloading
+                {
+                    hashes.push(hash);
+                }
+            }
+        }
+        hashes
+    }
+
+    /// Process a single download request
+    async fn process_download_request(&self, mut request: DownloadRequest) -> Result<()> {
+        info!("Processing download request: {}", request.id);
+
+        let torrent = match request.magnet_url {
+            Some(_) => None,
+            None => self.store.torrent_file(&request.id).await?,
+        };
+
+        if request.magnet_url.is_none() && torrent.is_none() {
+            if let Err(e) = self.select_release(&mut request).await {
+                warn!("No release selected for {}: {}", request.id, e);
+                if let Err(e) = self
+                    .transition(&mut request, DownloadStatus::Failed, e.to_string())
+                    .await
+                {
+                    warn!(
+                        "Failed to record release selection failure for {}: {}",
+                        request.id, e
+                    );
+                }
+                return Ok(());
+            }
+        }
+
+        // Add to Transmission
+        let download_dir = self.config.download_path.to_string_lossy().into_owned();
+        let added = match (torrent, request.magnet_url.clone()) {
+            (Some(torrent), _) => {
+                let torrent = Arc::new(torrent);
+                self.with_transmission(move |transmission| {
+                    let torrent = Arc::clone(&torrent);
+                    let download_dir = download_dir.clone();
+                    async move {
+                        transmission
+                            .lock()
+                            .await
+                            .add_torrent_file(&torrent, Some(&download_dir), Some(false))
+                            .await
+                    }
+                })
+                .await
+            }
+            (None, Some(magnet_url)) => {
+                self.with_transmission(move |transmission| {
+                    let magnet_url = magnet_url.clone();
+                    let download_dir = download_dir.clone();
+                    async move {
+                        transmission
+                            .lock()
+                            .await
+                            .add_magnet(
+                                &magnet_url,
+                                Some(&download_dir),
+                                Some(false), // Don't start paused
+                            )
+                            .await
+                    }
+                })
+                .await
+            }
+            (None, None) => return Err(anyhow::anyhow!("No magnet URL")),
+        };
+
+        match added {
+            Ok(torrent_hash) => {
+                request.torrent_hash = Some(torrent_hash.clone());
+                let reason = format!("Added to Transmission as {}", torrent_hash);
+                if let Err(e) = self
+                    .transition(&mut request, DownloadStatus::Downloading, reason)
+                    .await
+                {
+                    warn!("Failed to record download start for {}: {}", request.id, e);
+                }
 
                 // Create torrent download record
                 let torrent_download = TorrentDownload::new(
@@ -384,30 +1572,25 @@ impl DownloadService {
                     active.insert(torrent_hash.clone(), torrent_download);
                 }
 
-                // Update stats
-                {
-                    let mut stats = self.stats.write().await;
-                    stats.queued_downloads = stats.queued_downloads.saturating_sub(1);
-                    stats.active_downloads += 1;
-                    stats.last_updated = Utc::now();
-                }
-
                 info!(
                     "Successfully started download: {} -> {}",
                     request.id, torrent_hash
                 );
             }
             Err(e) => {
-                error!("Failed to get torrents from Transmission: {}", e);
-                request.status = "failed".to_string();
-                request.error_message = Some(e.to_string());
-
-                // Update stats
+                error!("Failed to add {} to Transmission: {}", request.id, e);
+                let reason = format!(
+                    "Transmission did not accept the release after {} retries: {}",
+                    self.config.retry.max_retries, e
+                );
+                if let Err(e) = self
+                    .transition(&mut request, DownloadStatus::Failed, reason)
+                    .await
                 {
-                    let mut stats = self.stats.write().await;
-                    stats.queued_downloads = stats.queued_downloads.saturating_sub(1);
-                    stats.failed_downloads += 1;
-                    stats.last_updated = Utc::now();
+                    warn!(
+                        "Failed to record download failure for {}: {}",
+                        request.id, e
+                    );
                 }
             }
         }
@@ -415,34 +1598,179 @@ impl DownloadService {
         Ok(())
     }
 
+    /// Search the indexers and pick the best release allowed by the quality profile
+    ///
+    /// Every candidate is recorded with its score and rejection reasons so the
+    /// choice can be reviewed later.
+    async fn select_release(&self, request: &mut DownloadRequest) -> Result<()> {
+        let query = match &request.album_title {
+            Some(album) => TorznabQuery::album(&request.artist_name, album),
+            None => TorznabQuery {
+                artist: Some(request.artist_name.clone()),
+                query: Some(request.track_title.clone()),
+                ..Default::default()
+            },
+        };
+
+        let mut candidates = Vec::new();
+        for candidate in self.indexers.search(&query).await? {
+            let blocklisted = self
+                .store
+                .is_blocklisted(
+                    candidate.info_hash.as_deref(),
+                    &candidate.indexer,
+                    &candidate.guid,
+                )
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to check the release blocklist: {}", e);
+                    false
+                });
+            if blocklisted {
+                debug!("Skipping blocklisted release '{}'", candidate.title);
+            } else {
+                candidates.push(candidate);
+            }
+        }
+        let album_tracks = match &request.album_title {
+            Some(album) => self.album_track_count(&request.artist_name, album).await,
+            None => None,
+        };
+        let context = selection_context(request, album_tracks);
+        let selection = self.config.quality_profile.select(&candidates, &context);
+
+        if let Err(e) = self.store.save_candidates(&request.id, &selection).await {
+            warn!(
+                "Failed to record release candidates for {}: {}",
+                request.id, e
+            );
+        }
+
+        let best = selection.selected().ok_or_else(|| {
+            anyhow::anyhow!(
+                "No acceptable release among {} candidates for profile '{}'",
+                selection.evaluations.len(),
+                self.config.quality_profile.name
+            )
+        })?;
+
+        request.magnet_url = best.candidate.magnet();
+        request.source_url = best
+            .candidate
+            .download_url
+            .clone()
+            .or_else(|| Some(best.candidate.guid.clone()));
+        if best.candidate.size > 0 {
+            request.file_size = Some(best.candidate.size);
+        }
+
+        let reason = format!(
+            "Selected '{}' from {} (score {:.3}, {} alternatives rejected)",
+            best.candidate.title,
+            best.candidate.indexer,
+            best.score,
+            selection.rejected_count()
+        );
+        info!("Download {}: {}", request.id, reason);
+        self.transition(request, DownloadStatus::Queued, reason)
+            .await
+    }
+
     /// Update status of all active downloads
     async fn update_download_status(&self) -> Result<()> {
-        let torrents = match self.transmission.lock().await.get_torrents().await {
+        let torrents = match self
+            .with_transmission(|transmission| async move {
+                transmission.lock().await.get_torrents().await
+            })
+            .await
+        {
             Ok(torrents) => torrents,
             Err(e) => {
                 error!("Failed to get torrents from Transmission: {}", e);
                 return Err(e);
             }
         };
-        let mut completed_hashes = Vec::new();
+        let mut tracked = Vec::new();
 
         {
             let mut active = self.active_downloads.write().await;
-            let mut stats = self.stats.write().await;
 
             for torrent in torrents {
                 if let Some(download) = active.get_mut(&torrent.hash) {
                     download.update_from_torrent_info(&torrent);
+                    tracked.push((download.download_request_id.clone(), torrent));
+                }
+            }
+        }
 
-                    if torrent.is_completed() && !completed_hashes.contains(&torrent.hash) {
-                        completed_hashes.push(torrent.hash.clone());
-                    }
+        // Persist progress for requests that are still downloading
+        let mut completed_hashes = Vec::new();
+        for (request_id, torrent) in tracked {
+            let mut request = match self.store.get(&request_id).await {
+                Ok(Some(request)) => request,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed to load download request {}: {}", request_id, e);
+                    continue;
                 }
+            };
+
+            if !request.is_in_progress() {
+                continue;
             }
 
-            // Update global stats
-            stats.active_downloads = active.len() as u64;
-            stats.last_updated = Utc::now();
+            request.update_from_torrent(&torrent);
+
+            if torrent.is_completed() {
+                self.stalls.lock().await.forget(&torrent.hash);
+                match self
+                    .transition(
+                        &mut request,
+                        DownloadStatus::Completed,
+                        "Torrent finished downloading",
+                    )
+                    .await
+                {
+                    Ok(()) => completed_hashes.push(torrent.hash.clone()),
+                    Err(e) => warn!("Failed to record completion of {}: {}", request.id, e),
+                }
+            } else {
+                let health = self.stalls.lock().await.observe(&torrent, Utc::now());
+                if let Some(problem) = health.problem() {
+                    self.handle_stall(&mut request, &torrent, health, problem)
+                        .await;
+                    continue;
+                }
+
+                if request.status == DownloadStatus::Stalled && torrent.download_speed > 0 {
+                    if let Err(e) = self
+                        .transition(
+                            &mut request,
+                            DownloadStatus::Downloading,
+                            "Download is making progress again",
+                        )
+                        .await
+                    {
+                        warn!("Failed to record recovery of {}: {}", request.id, e);
+                    }
+                } else {
+                    self.persist(&request).await;
+                }
+                self.events.publish(
+                    EventTopic::Download,
+                    "progress",
+                    Some(&request.user_id),
+                    serde_json::json!({
+                        "request_id": request.id,
+                        "progress": torrent.progress,
+                        "download_speed": torrent.download_speed,
+                        "upload_speed": torrent.upload_speed,
+                        // Transmission reports negative values when it cannot tell
+                        "eta_seconds": (torrent.eta >= 0).then_some(torrent.eta),
+                        "size": torrent.size,
+                    }),
+                );
+            }
         }
 
         // Add completed torrents to processing queue
@@ -459,6 +1787,220 @@ impl DownloadService {
         Ok(())
     }
 
+    /// Restart a torrent that stalled or errored, or give up on its release
+    /// once it has been restarted often enough
+    async fn handle_stall(
+        &self,
+        request: &mut DownloadRequest,
+        torrent: &TorrentInfo,
+        health: TorrentHealth,
+        problem: String,
+    ) {
+        if request.status == DownloadStatus::Downloading {
+            if let Err(e) = self
+                .transition(request, DownloadStatus::Stalled, problem.clone())
+                .await
+            {
+                warn!("Failed to record stall of {}: {}", request.id, e);
+            }
+        }
+
+        let action = self
+            .stalls
+            .lock()
+            .await
+            .next_action(&torrent.hash, Utc::now());
+        match action {
+            StallAction::Restart { attempt } => {
+                info!(
+                    "Restarting download {} (attempt {}/{}): {}",
+                    request.id, attempt, self.config.stall_policy.max_restarts, problem
+                );
+                let hash = torrent.hash.clone();
+                let restarted = self
+                    .with_transmission(move |transmission| {
+                        let hash = hash.clone();
+                        async move {
+                            let mut transmission = transmission.lock().await;
+                            transmission.pause_torrent(&hash).await?;
+                            transmission.resume_torrent(&hash).await
+                        }
+                    })
+                    .await;
+                if let Err(e) = restarted {
+                    warn!("Failed to restart torrent {}: {}", torrent.hash, e);
+                }
+            }
+            StallAction::Abandon => {
+                let problem = match health {
+                    TorrentHealth::Errored(_) => problem,
+                    _ => format!(
+                        "{} after {} restarts",
+                        problem, self.config.stall_policy.max_restarts
+                    ),
+                };
+                if let Err(e) = self.abandon_release(request, &torrent.hash, &problem).await {
+                    error!("Failed to abandon release of {}: {}", request.id, e);
+                }
+            }
+        }
+    }
+
+    /// Blocklist the release a request is downloading, remove its torrent and
+    /// fall back to the next-best candidate of the last search; the request
+    /// only fails once no acceptable candidate is left
+    async fn abandon_release(
+        &self,
+        request: &mut DownloadRequest,
+        torrent_hash: &str,
+        problem: &str,
+    ) -> Result<()> {
+        let selected = self
+            .store
+            .candidates(&request.id)
+            .await?
+            .into_iter()
+            .find(|candidate| candidate.selected);
+        let title = selected
+            .as_ref()
+            .map(|candidate| candidate.title.clone())
+            .unwrap_or_else(|| request.full_description());
+
+        self.store
+            .blocklist_release(&BlocklistEntry {
+                id: 0,
+                info_hash: Some(torrent_hash.to_string()),
+                guid: selected
+                    .as_ref()
+                    .and_then(|candidate| candidate.guid.clone()),
+                indexer: selected.as_ref().map(|candidate| candidate.indexer.clone()),
+                title: title.clone(),
+                request_id: Some(request.id.clone()),
+                reason: problem.to_string(),
+                created_at: Utc::now(),
+            })
+            .await?;
+        warn!("Blocklisted '{}' for {}: {}", title, request.id, problem);
+
+        self.active_downloads.write().await.remove(torrent_hash);
+        self.stalls.lock().await.forget(torrent_hash);
+        let reason = format!("Abandoned release '{}': {}", title, problem);
+        if let Err(e) = self
+            .remove_torrent_to_trash(torrent_hash, &reason, SYSTEM_ACTOR)
+            .await
+        {
+            warn!("Failed to remove abandoned torrent {}: {}", torrent_hash, e);
+        }
+
+        request.torrent_hash = None;
+        request.progress = None;
+
+        let Some(next) = self.store.next_candidate(&request.id).await? else {
+            let reason = format!(
+                "Gave up on '{}': {}; no alternative releases left",
+                title, problem
+            );
+            self.transition(request, DownloadStatus::Failed, reason.clone())
+                .await?;
+            self.notify(
+                &request.user_id,
+                NotificationKind::DownloadFailed,
+                "Download failed",
+                &format!("{}: {}", request.full_description(), reason),
+                &request.id,
+            )
+            .await;
+            return Ok(());
+        };
+
+        self.store.select_candidate(&request.id, next.id).await?;
+        request.magnet_url = next.magnet_url.clone();
+        request.source_url = next.guid.clone();
+        if let Some(size) = next.size.filter(|size| *size > 0) {
+            request.file_size = Some(size);
+        }
+
+        let reason = format!(
+            "Gave up on '{}': {}; falling back to '{}' from {} (score {:.3})",
+            title, problem, next.title, next.indexer, next.score
+        );
+        info!("Download {}: {}", request.id, reason);
+        self.transition(request, DownloadStatus::Queued, reason)
+            .await?;
+        self.download_queue.lock().await.push(request.clone());
+        Ok(())
+    }
+
+    /// Run a Transmission call, retrying transient failures with backoff
+    async fn with_transmission<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: Fn(Arc<Mutex<TransmissionClient>>) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let transmission = Arc::clone(&self.transmission);
+        retry_with_backoff(
+            || call(Arc::clone(&transmission)).boxed(),
+            &self.config.retry,
+        )
+        .await
+    }
+
+    /// Move a torrent's data into the recycle bin, then remove it from Transmission
+    ///
+    /// The torrent is only removed once its data is in the trash. If the data
+    /// cannot be moved, the error is returned and the torrent is left paused
+    /// with its data where it was; nothing is deleted for good.
+    async fn remove_torrent_to_trash(
+        &self,
+        torrent_hash: &str,
+        reason: &str,
+        actor: &str,
+    ) -> Result<Option<TrashItem>> {
+        let hash = torrent_hash.to_string();
+        let torrent = self
+            .with_transmission(move |transmission| {
+                let hash = hash.clone();
+                async move { transmission.lock().await.get_torrent_by_hash(&hash).await }
+            })
+            .await?;
+        let Some(torrent) = torrent else {
+            return Ok(None);
+        };
+
+        let data_path = Path::new(&torrent.download_dir).join(&torrent.name);
+        let mut trashed = None;
+        if tokio::fs::symlink_metadata(&data_path).await.is_ok() {
+            // Stop writing to the files before they are moved
+            let hash = torrent_hash.to_string();
+            self.with_transmission(move |transmission| {
+                let hash = hash.clone();
+                async move { transmission.lock().await.pause_torrent(&hash).await }
+            })
+            .await?;
+
+            let item = self
+                .recycle_bin
+                .trash(&data_path, reason, actor)
+                .await
+                .with_context(|| format!("Kept torrent {} and its data in place", torrent_hash))?;
+            trashed = Some(item);
+        }
+
+        let hash = torrent_hash.to_string();
+        self.with_transmission(move |transmission| {
+            let hash = hash.clone();
+            async move { transmission.lock().await.remove_torrent(&hash, false).await }
+        })
+        .await?;
+        Ok(trashed)
+    }
+
+    /// Files moved aside by cancellations and cleanups
+    pub fn recycle_bin(&self) -> &RecycleBin {
+        &self.recycle_bin
+    }
+
     /// Process completed files
     async fn process_completed_files(&self) -> Result<()> {
         let mut processing = self.processing_queue.lock().await;
@@ -486,18 +2028,31 @@ impl DownloadService {
             }
         };
 
-        // Get files in torrent
-        // Note: Transmission doesn't have per-file info like qBittorrent
-        // Process the entire torrent directory
-        info!("Processing completed torrent directory for: {}", hash);
+        // Transmission doesn't have per-file info like qBittorrent, so the
+        // whole torrent directory goes through the import pipeline
+        let source = Path::new(&torrent.download_dir).join(&torrent.name);
 
-        // Process the entire torrent
-        match self.process_torrent_directory(&torrent).await {
-            Ok(_) => {
-                info!("Successfully processed torrent: {}", torrent.name);
+        match self.stored_request_for(&hash).await {
+            Some(mut request) => {
+                if let Err(e) = self
+                    .import_request(&mut request, &source, ImportOptions::default())
+                    .await
+                {
+                    warn!("Failed to import torrent {}: {}", torrent.name, e);
+                }
             }
-            Err(e) => {
-                warn!("Failed to process torrent {}: {}", torrent.name, e);
+            None => {
+                let reason = "No download request owns this torrent";
+                warn!("{}: {}, queued for manual import", torrent.name, reason);
+                self.store
+                    .add_manual_import(
+                        None,
+                        Some(&hash),
+                        &source.to_string_lossy(),
+                        ImportStage::Match,
+                        reason,
+                    )
+                    .await?;
             }
         }
 
@@ -512,166 +2067,208 @@ impl DownloadService {
             }
         }
 
-        // Update stats
-        {
-            let mut stats = self.stats.write().await;
-            stats.active_downloads = stats.active_downloads.saturating_sub(1);
-            stats.completed_downloads += 1;
-            stats.last_updated = Utc::now();
-        }
-
         Ok(())
     }
 
-    /// Copy directory recursively
-    async fn copy_directory(&self, source: &Path, destination: &Path) -> Result<()> {
-        tokio::fs::create_dir_all(destination).await?;
-        let mut entries = tokio::fs::read_dir(source).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
-            let source_path = entry.path();
-            let dest_path = destination.join(entry.file_name());
-
-            if source_path.is_dir() {
-                Box::pin(self.copy_directory(&source_path, &dest_path)).await?;
-            } else {
-                tokio::fs::copy(&source_path, &dest_path).await?;
-            }
-        }
-        Ok(())
-    }
-
-    /// Process completed torrent directory
-    async fn process_torrent_directory(&self, torrent: &TorrentInfo) -> Result<()> {
-        let source_path = Path::new(&torrent.download_dir).join(&torrent.name);
+    /// Run a downloaded release through verification and import, parking it
+    /// for manual import if any stage fails
+    async fn import_request(
+        &self,
+        request: &mut DownloadRequest,
+        source: &Path,
+        options: ImportOptions,
+    ) -> Result<()> {
+        self.transition(
+            request,
+            DownloadStatus::Verifying,
+            "Verifying downloaded files",
+        )
+        .await?;
+
+        let release = match self.importer.verify(source).await {
+            Ok(release) => release,
+            Err(e) => return self.fail_import(request, source, e).await,
+        };
 
-        // Create processing directory if it doesn't exist
-        tokio::fs::create_dir_all(&self.config.processing_path).await?;
+        self.transition(
+            request,
+            DownloadStatus::Importing,
+            format!("Importing {} audio files into library", release.files.len()),
+        )
+        .await?;
 
-        // Copy entire torrent directory to processing
-        let processing_dir = self.config.processing_path.join(&torrent.hash);
-        self.copy_directory(&source_path, &processing_dir).await?;
+        let outcome = match self.importer.import(request, release, options).await {
+            Ok(outcome) => outcome,
+            Err(e) => return self.fail_import(request, source, e).await,
+        };
 
-        // Organize the entire directory
-        let final_path = self
-            .organize_music_directory(&processing_dir, torrent)
+        let primary = outcome.primary_file();
+        request.download_path = Some(primary.destination.to_string_lossy().into_owned());
+        let reason = format!(
+            "Imported {} files into library ({} skipped)",
+            outcome.files.len(),
+            outcome.skipped.len()
+        );
+        self.transition(request, DownloadStatus::Imported, reason)
             .await?;
-
-        info!(
-            "Successfully processed torrent: {} -> {}",
-            torrent.name,
-            final_path.display()
+        self.events.publish(
+            EventTopic::Download,
+            "import",
+            Some(&request.user_id),
+            serde_json::json!({
+                "request_id": request.id,
+                "success": true,
+                "files": outcome.files,
+                "skipped": outcome.skipped,
+            }),
         );
+
+        if let Some(navidrome) = self.navidrome.clone() {
+            let store = self.store.clone();
+            let events = self.events.clone();
+            let request = request.clone();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    update_library_index(&navidrome, &store, &events, request, outcome).await
+                {
+                    warn!("Failed to update library index after import: {}", e);
+                }
+            });
+        }
+
         Ok(())
     }
 
-    /// Organize music directory into proper library structure
-    async fn organize_music_directory(
+    /// Record a failed import and queue the download for manual import
+    async fn fail_import(
         &self,
-        processing_path: &Path,
-        torrent: &TorrentInfo,
-    ) -> Result<PathBuf> {
-        // Try to extract metadata from torrent name
-        let (artist, album, _) = self.extract_metadata_from_path(&torrent.name, &torrent.name);
+        request: &mut DownloadRequest,
+        source: &Path,
+        error: ImportError,
+    ) -> Result<()> {
+        warn!("Import of {} failed: {}", request.id, error);
+
+        self.store
+            .add_manual_import(
+                Some(&request.id),
+                request.torrent_hash.as_deref(),
+                &source.to_string_lossy(),
+                error.stage,
+                &error.message,
+            )
+            .await?;
 
-        // Create library directory structure
-        let artist_dir = sanitize_filename(&artist);
-        let album_dir = sanitize_filename(&album);
+        self.events.publish(
+            EventTopic::Download,
+            "import",
+            Some(&request.user_id),
+            serde_json::json!({
+                "request_id": request.id,
+                "success": false,
+                "stage": error.stage,
+                "error": error.message,
+            }),
+        );
 
-        let library_path = self
-            .config
-            .final_library_path
-            .join(&artist_dir)
-            .join(&album_dir);
+        let reason = format!(
+            "Import failed at {}: {}; queued for manual import",
+            error.stage, error.message
+        );
+        self.transition(request, DownloadStatus::Failed, reason)
+            .await
+    }
 
-        tokio::fs::create_dir_all(&library_path).await?;
+    /// List downloads waiting for manual import
+    pub async fn list_manual_imports(&self) -> Result<Vec<ManualImport>> {
+        self.store.unresolved_manual_imports().await
+    }
 
-        // Generate final filename
-        let extension = processing_path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("mp3");
+    /// Retry the import of a parked download, optionally skipping the check
+    /// that the release matches the request
+    pub async fn retry_manual_import(&self, id: i64, force: bool) -> Result<DownloadRequest> {
+        let entry = self
+            .store
+            .manual_import(id)
+            .await?
+            .filter(|entry| entry.resolved_at.is_none())
+            .ok_or_else(|| anyhow::anyhow!("Manual import {} not found", id))?;
+
+        let request_id = entry
+            .request_id
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Manual import {} has no download request", id))?;
+        let mut request = self
+            .store
+            .get(request_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Download request {} not found", request_id))?;
 
-        let final_filename = format!("{}.{}", sanitize_filename(&torrent.name), extension);
-        let final_path = library_path.join(&final_filename);
+        let options = ImportOptions { skip_match: force };
+        self.import_request(&mut request, Path::new(&entry.source_path), options)
+            .await?;
 
-        // Move file to final location
-        tokio::fs::rename(processing_path, &final_path)
-            .await
-            .context("Failed to move file to library")?;
+        if request.status == DownloadStatus::Imported {
+            self.store.resolve_manual_import(id, "imported").await?;
+        }
 
-        Ok(final_path)
+        Ok(request)
     }
 
-    /// Extract metadata from file path and torrent name
-    fn extract_metadata_from_path(
-        &self,
-        file_path: &str,
-        torrent_name: &str,
-    ) -> (String, String, String) {
-        // Simple extraction - in a real implementation, you'd want to use a proper metadata library
-        let path = Path::new(file_path);
-        let filename = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("Unknown");
-
-        // Try to parse from filename patterns like "Artist - Album - Track"
-        let parts: Vec<&str> = filename.split(" - ").collect();
-
-        let (artist, album, track) = match parts.len() {
-            3 => (
-                parts[0].to_string(),
-                parts[1].to_string(),
-                parts[2].to_string(),
-            ),
-            2 => (
-                parts[0].to_string(),
-                "Unknown Album".to_string(),
-                parts[1].to_string(),
-            ),
-            _ => (
-                "Unknown Artist".to_string(),
-                "Unknown Album".to_string(),
-                filename.to_string(),
-            ),
-        };
-
-        (artist, album, track)
+    /// Drop a download from the manual-import queue without importing it
+    pub async fn dismiss_manual_import(&self, id: i64) -> Result<bool> {
+        self.store.resolve_manual_import(id, "dismissed").await
     }
 
-    /// Cleanup completed downloads based on seeding rules
+    /// Remove completed torrents that have seeded enough and been imported
     async fn cleanup_completed_downloads(&self) -> Result<()> {
         if !self.config.auto_delete_completed {
             return Ok(());
         }
 
-        let torrents = self.transmission.lock().await.get_torrents().await?;
-        let completed_torrents: Vec<_> =
-            torrents.into_iter().filter(|t| t.is_completed()).collect();
         let mut cleanup_count = 0;
+        for status in self.get_seeding_status().await? {
+            let SeedingDecision::Remove {
+                delete_data,
+                reason,
+            } = &status.decision
+            else {
+                continue;
+            };
 
-        for torrent in completed_torrents {
-            let should_delete = torrent.ratio >= self.config.seed_ratio_limit;
-            // Note: Transmission doesn't provide seeding_time in basic info
-
-            if should_delete {
-                if let Err(e) = self
-                    .transmission
+            // Seeded data was already imported; it still goes to the recycle
+            // bin, which purges it once the retention period is over
+            let removed = if *delete_data {
+                let reason = format!("Finished seeding: {}", reason);
+                self.remove_torrent_to_trash(&status.torrent_hash, &reason, SYSTEM_ACTOR)
+                    .await
+                    .map(|_| ())
+            } else {
+                self.transmission
                     .lock()
                     .await
-                    .remove_torrent(&torrent.hash, false)
+                    .remove_torrent(&status.torrent_hash, false)
                     .await
-                {
-                    warn!("Failed to delete completed torrent {}: {}", torrent.hash, e);
-                } else {
-                    cleanup_count += 1;
-                    info!(
-                        "Cleaned up completed torrent: {} (ratio: {:.2})",
-                        torrent.hash, torrent.ratio
-                    );
-                }
+            };
+            if let Err(e) = removed {
+                warn!(
+                    "Failed to remove seeded torrent {}: {}",
+                    status.torrent_hash, e
+                );
+                continue;
             }
+
+            self.active_downloads
+                .write()
+                .await
+                .remove(&status.torrent_hash);
+            cleanup_count += 1;
+            info!(
+                "Removed seeded torrent {} (ratio: {:.2}, seeded {}h): {}",
+                status.name,
+                status.ratio,
+                status.seeding_time.as_secs() / 3600,
+                reason
+            );
         }
 
         if cleanup_count > 0 {
@@ -680,31 +2277,459 @@ impl DownloadService {
 
         Ok(())
     }
-}
 
-/// Helper function to check if a file is a music file
-fn is_music_file(filename: &str) -> bool {
-    const MUSIC_EXTENSIONS: &[&str] = &[
-        "mp3", "flac", "wav", "ogg", "m4a", "aac", "wma", "ape", "opus", "aiff", "au",
-    ];
+    /// Evaluate the seeding policy for every completed torrent we requested
+    pub async fn get_seeding_status(&self) -> Result<Vec<SeedingStatus>> {
+        let torrents = self.transmission.lock().await.get_torrents().await?;
+        let mut statuses = Vec::new();
+
+        for torrent in torrents.into_iter().filter(|t| t.is_completed()) {
+            // Torrents added outside StepheyBot are never touched
+            let Some(request) = self.stored_request_for(&torrent.hash).await else {
+                continue;
+            };
+
+            let indexer = self
+                .store
+                .selected_indexer(&request.id)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to look up indexer of {}: {}", request.id, e);
+                    None
+                });
+            let rule = self.seeding.rule_for(indexer.as_deref());
+            let seeding_time = Duration::from_secs(torrent.seconds_seeding);
+            let imported = request.status == DownloadStatus::Imported;
+
+            let data_path = Path::new(&torrent.download_dir).join(&torrent.name);
+            let library_path = self.config.final_library_path.clone();
+            let ratio = torrent.ratio;
+            let decision = tokio::task::spawn_blocking(move || {
+                seeding::decide(
+                    &rule,
+                    ratio,
+                    seeding_time,
+                    imported,
+                    &data_path,
+                    &library_path,
+                )
+            })
+            .await?;
+
+            statuses.push(SeedingStatus {
+                torrent_hash: torrent.hash,
+                name: torrent.name,
+                request_id: Some(request.id),
+                indexer,
+                ratio: torrent.ratio,
+                seeding_time,
+                rule,
+                policy_met: rule.is_met(torrent.ratio, seeding_time),
+                imported,
+                decision,
+            });
+        }
+
+        Ok(statuses)
+    }
+
+    /// Restore queued and in-flight requests from the database, reattaching
+    /// them to torrents the client is still working on
+    async fn restore_state(&self) -> Result<()> {
+        let unfinished = self.store.load_unfinished().await?;
+        if unfinished.is_empty() {
+            return Ok(());
+        }
+
+        // Requests are still requeued when the client can't be reached; only
+        // failing them for a missing torrent has to wait until it can be
+        let listed = self.transmission.lock().await.get_torrents().await;
+        let client_reachable = listed.is_ok();
+        let torrents: HashMap<String, TorrentInfo> = match listed {
+            Ok(torrents) => torrents.into_iter().map(|t| (t.hash.clone(), t)).collect(),
+            Err(e) => {
+                warn!("Could not list torrents while restoring downloads: {}", e);
+                HashMap::new()
+            }
+        };
+
+        let mut with_files = std::collections::HashSet::new();
+        for request in &unfinished {
+            if self.store.has_torrent_file(&request.id).await? {
+                with_files.insert(request.id.clone());
+            }
+        }
+
+        let mut requeued = 0;
+        let mut reattached = 0;
+        let mut failed = 0;
+
+        for mut request in unfinished {
+            let live = request
+                .torrent_hash
+                .as_ref()
+                .and_then(|hash| torrents.get(hash));
+
+            let result = match live {
+                Some(torrent) => {
+                    // The client still has it: resume tracking where we left off
+                    let mut download = TorrentDownload::new(
+                        request.id.clone(),
+                        torrent.hash.clone(),
+                        request.full_description(),
+                        torrent.download_dir.clone(),
+                    );
+                    download.magnet_url = request.magnet_url.clone();
+                    download.added_at = request.started_at.unwrap_or(request.requested_at);
+                    download.update_from_torrent_info(torrent);
+                    request.update_from_torrent(torrent);
+
+                    self.active_downloads
+                        .write()
+                        .await
+                        .insert(torrent.hash.clone(), download);
+                    reattached += 1;
+
+                    // Possibly paused for streaming before the restart; the
+                    // scheduler resumes it once nobody is listening
+                    if request.effective_priority() == DownloadPriority::Low
+                        && torrent.status == 0
+                        && !torrent.is_completed()
+                    {
+                        self.bandwidth
+                            .lock()
+                            .await
+                            .paused_for_streaming
+                            .push(torrent.hash.clone());
+                    }
+
+                    let result = match request.status {
+                        // Added just before the restart, before the request
+                        // could record it
+                        DownloadStatus::Queued => {
+                            self.transition(
+                                &mut request,
+                                DownloadStatus::Downloading,
+                                "Found in client after restart",
+                            )
+                            .await
+                        }
+                        DownloadStatus::Downloading | DownloadStatus::Stalled
+                            if torrent.is_completed() =>
+                        {
+                            self.transition(
+                                &mut request,
+                                DownloadStatus::Completed,
+                                "Torrent finished while offline",
+                            )
+                            .await
+                        }
+                        DownloadStatus::Verifying | DownloadStatus::Importing => {
+                            self.transition(
+                                &mut request,
+                                DownloadStatus::Completed,
+                                "Import interrupted by restart",
+                            )
+                            .await
+                        }
+                        _ => self.store.save(&request).await,
+                    };
+
+                    if request.status == DownloadStatus::Completed {
+                        let mut processing = self.processing_queue.lock().await;
+                        if !processing.contains(&torrent.hash) {
+                            processing.push(torrent.hash.clone());
+                        }
+                    }
+
+                    result
+                }
+                None if (request.magnet_url.is_some() || with_files.contains(&request.id))
+                    && matches!(
+                        request.status,
+                        DownloadStatus::Pending
+                            | DownloadStatus::Queued
+                            | DownloadStatus::Downloading
+                            | DownloadStatus::Stalled
+                    ) =>
+                {
+                    // Never reached the client, or the client lost it: start over
+                    let result = if request.status == DownloadStatus::Queued {
+                        Ok(())
+                    } else {
+                        request.torrent_hash = None;
+                        request.progress = None;
+                        request.started_at = None;
+                        self.transition(
+                            &mut request,
+                            DownloadStatus::Queued,
+                            "Torrent missing from client after restart",
+                        )
+                        .await
+                    };
+                    self.download_queue.lock().await.push(request.clone());
+                    requeued += 1;
+                    result
+                }
+                None if request.status == DownloadStatus::AwaitingApproval => {
+                    // Still waiting for an admin, nothing to resume
+                    Ok(())
+                }
+                None if lidarr_webhook::is_lidarr_download(&request) => {
+                    // Lidarr drives these, its webhook will report progress
+                    Ok(())
+                }
+                None if request.status == DownloadStatus::Searching => {
+                    // Release selection never finished, search again
+                    self.download_queue.lock().await.push(request.clone());
+                    requeued += 1;
+                    Ok(())
+                }
+                None if !client_reachable => {
+                    // Can't tell whether the client still has it
+                    Ok(())
+                }
+                None => {
+                    failed += 1;
+                    self.transition(
+                        &mut request,
+                        DownloadStatus::Failed,
+                        "Torrent no longer present in client after restart",
+                    )
+                    .await
+                }
+            };
+
+            if let Err(e) = result {
+                warn!("Failed to restore download request {}: {}", request.id, e);
+            }
+        }
+
+        info!(
+            "Restored persisted downloads: {} reattached, {} requeued, {} failed",
+            reattached, requeued, failed
+        );
+        Ok(())
+    }
+
+    /// Number of tracks on an album according to MusicBrainz
+    async fn album_track_count(&self, artist: &str, album: &str) -> Option<u32> {
+        match self.musicbrainz.search_release_fuzzy(artist, album).await {
+            Ok(release) => release.and_then(|release| release.track_count()),
+            Err(e) => {
+                debug!("Could not look up tracks of {} - {}: {}", artist, album, e);
+                None
+            }
+        }
+    }
+
+    /// Move a request to a new state, persisting it along with the transition
+    async fn transition(
+        &self,
+        request: &mut DownloadRequest,
+        next: DownloadStatus,
+        reason: impl Into<String>,
+    ) -> Result<()> {
+        let transition = request.transition_to(next, reason)?;
+        debug!(
+            "Download {} transitioned {} -> {}: {}",
+            request.id, transition.from_status, transition.to_status, transition.reason
+        );
+        self.store.save_transition(request, &transition).await?;
+        if next.is_terminal() {
+            if let Err(e) = self.store.delete_torrent_file(&request.id).await {
+                warn!("Failed to drop torrent file of {}: {}", request.id, e);
+            }
+        }
+
+        self.events.publish(
+            EventTopic::Download,
+            "status",
+            Some(&request.user_id),
+            serde_json::json!({
+                "request_id": request.id,
+                "artist": request.artist_name,
+                "title": request.track_title,
+                "from": transition.from_status,
+                "to": transition.to_status,
+                "reason": transition.reason,
+                "progress": request.progress,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Persist a request, logging rather than propagating storage errors
+    async fn persist(&self, request: &DownloadRequest) {
+        if let Err(e) = self.store.save(request).await {
+            error!("Failed to persist download request {}: {}", request.id, e);
+        }
+    }
+
+    /// Usage of a user's quota for the current day and month (UTC)
+    async fn quota_usage(&self, user_id: &str) -> Result<QuotaUsage> {
+        let now = Utc::now();
+        let day_start = Utc
+            .with_ymd_and_hms(now.year(), now.month(), now.day(), 0, 0, 0)
+            .single()
+            .unwrap_or(now);
+        let month_start = Utc
+            .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+            .single()
+            .unwrap_or(now);
+
+        self.store.usage(user_id, day_start, month_start).await
+    }
+
+    /// Load a request that is waiting for an admin decision
+    async fn awaiting_approval(&self, request_id: &str) -> Result<DownloadRequest> {
+        let request = self
+            .store
+            .get(request_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Download request {} not found", request_id))?;
+
+        if request.status != DownloadStatus::AwaitingApproval {
+            return Err(anyhow::anyhow!(
+                "Download request {} is {}, not awaiting approval",
+                request_id,
+                request.status
+            ));
+        }
+
+        Ok(request)
+    }
+
+    /// Notify a user about one of their requests, logging delivery failures
+    async fn notify(
+        &self,
+        user_id: &str,
+        kind: NotificationKind,
+        title: &str,
+        message: &str,
+        request_id: &str,
+    ) {
+        let data = serde_json::json!({ "request_id": request_id });
+        if let Err(e) = self
+            .notifications
+            .notify(user_id, kind, title, message, data)
+            .await
+        {
+            warn!("Failed to notify {} about {}: {}", user_id, request_id, e);
+        }
+    }
 
-    if let Some(ext) = Path::new(filename).extension().and_then(|ext| ext.to_str()) {
-        MUSIC_EXTENSIONS.contains(&ext.to_lowercase().as_str())
-    } else {
-        false
+    /// Load the persisted request that owns a torrent
+    pub async fn stored_request_for(&self, torrent_hash: &str) -> Option<DownloadRequest> {
+        match self.store.get_by_torrent_hash(torrent_hash).await {
+            Ok(Some(request)) => Some(request),
+            Ok(None) => {
+                debug!("No persisted request for torrent {}", torrent_hash);
+                None
+            }
+            Err(e) => {
+                error!(
+                    "Failed to load download request for torrent {}: {}",
+                    torrent_hash, e
+                );
+                None
+            }
+        }
     }
 }
 
-/// Sanitize filename for filesystem use
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .map(|c| match c {
-            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
-            c => c,
+/// Scan the imported files into Navidrome and link the request to its track
+async fn update_library_index(
+    navidrome: &NavidromeClient,
+    store: &DownloadStore,
+    events: &EventHub,
+    mut request: DownloadRequest,
+    outcome: ImportOutcome,
+) -> Result<()> {
+    navidrome.start_scan(false).await?;
+    events.publish(
+        EventTopic::Scan,
+        "scan_started",
+        None,
+        serde_json::json!({ "full_scan": false, "request_id": request.id }),
+    );
+
+    // Incremental scans are quick, but give up rather than poll forever
+    let mut scanned = None;
+    for _ in 0..60 {
+        sleep(Duration::from_secs(2)).await;
+        let status = navidrome.get_scan_status().await?;
+        scanned = status.count;
+        if !status.scanning {
+            break;
+        }
+        events.publish(
+            EventTopic::Scan,
+            "scan_progress",
+            None,
+            serde_json::json!({ "scanned": status.count, "folders": status.folder_count }),
+        );
+    }
+    events.publish(
+        EventTopic::Scan,
+        "scan_finished",
+        None,
+        serde_json::json!({ "scanned": scanned, "request_id": request.id }),
+    );
+
+    let primary = outcome.primary_file();
+    let query = format!("{} {}", primary.artist, primary.title);
+    let songs = navidrome
+        .search(&query, Some(0), Some(0), Some(20))
+        .await?
+        .song
+        .unwrap_or_default();
+
+    let destination = primary.destination.to_string_lossy();
+    let song = songs
+        .iter()
+        .find(|song| {
+            song.path
+                .as_deref()
+                .is_some_and(|path| !path.is_empty() && destination.ends_with(path))
         })
-        .collect::<String>()
-        .trim()
-        .to_string()
+        .or_else(|| {
+            songs.iter().find(|song| {
+                song.title.eq_ignore_ascii_case(&primary.title)
+                    && song
+                        .artist
+                        .as_deref()
+                        .is_some_and(|artist| artist.eq_ignore_ascii_case(&primary.artist))
+            })
+        });
+
+    match song {
+        Some(song) => {
+            info!(
+                "Linked download {} to library track {}",
+                request.id, song.id
+            );
+            request.track_id = Some(song.id.clone());
+            store.save(&request).await
+        }
+        None => {
+            warn!("Imported track '{}' not found in library index yet", query);
+            Ok(())
+        }
+    }
+}
+
+/// What a request's release is chosen against; albums whose track count is
+/// unknown are sized as a typical album
+fn selection_context(request: &DownloadRequest, album_tracks: Option<u32>) -> SelectionContext {
+    let track_count = match request.album_title {
+        Some(_) => album_tracks.unwrap_or(ESTIMATED_ALBUM_TRACKS),
+        None => 1,
+    };
+    SelectionContext {
+        requested: request.full_description(),
+        track_count: Some(track_count),
+    }
 }
 
 impl Clone for DownloadService {
@@ -712,10 +2737,25 @@ impl Clone for DownloadService {
         Self {
             config: self.config.clone(),
             transmission: Arc::clone(&self.transmission),
+            indexers: self.indexers.clone(),
+            importer: self.importer.clone(),
+            navidrome: self.navidrome.clone(),
+            musicbrainz: self.musicbrainz.clone(),
+            seeding: self.seeding.clone(),
             active_downloads: Arc::clone(&self.active_downloads),
             download_queue: Arc::clone(&self.download_queue),
             processing_queue: Arc::clone(&self.processing_queue),
-            stats: Arc::clone(&self.stats),
+            store: self.store.clone(),
+            notifications: self.notifications.clone(),
+            events: self.events.clone(),
+            stalls: Arc::clone(&self.stalls),
+            streams: self.streams.clone(),
+            bandwidth: Arc::clone(&self.bandwidth),
+            fair_queue: Arc::clone(&self.fair_queue),
+            disk_history: self.disk_history.clone(),
+            disk_space: Arc::clone(&self.disk_space),
+            recycle_bin: self.recycle_bin.clone(),
+            admissions: Arc::clone(&self.admissions),
         }
     }
 }
@@ -724,28 +2764,511 @@ impl Clone for DownloadService {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_sanitize_filename() {
-        assert_eq!(sanitize_filename("Test/File:Name"), "Test_File_Name");
-        assert_eq!(sanitize_filename("Normal Name"), "Normal Name");
-        assert_eq!(sanitize_filename(""), "");
-    }
-
-    #[test]
-    fn test_is_music_file() {
-        assert!(is_music_file("song.mp3"));
-        assert!(is_music_file("track.flac"));
-        assert!(!is_music_file("image.jpg"));
-        assert!(!is_music_file("document.txt"));
-    }
-
     #[tokio::test]
     async fn test_download_service_creation() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+
         let config = DownloadConfig::default();
-        let service = DownloadService::new(config);
+        let service = DownloadService::new(config, database).unwrap();
 
         let stats = service.get_stats().await;
         assert_eq!(stats.total_downloads, 0);
         assert_eq!(stats.active_downloads, 0);
     }
+
+    #[tokio::test]
+    async fn test_approval_workflow() {
+        use crate::models::user::UserRole;
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let service = DownloadService::new(DownloadConfig::default(), database).unwrap();
+
+        let alice = Requester {
+            user_id: "7".to_string(),
+            username: "alice".to_string(),
+            role: UserRole::User,
+        };
+        let admin = Requester::system();
+
+        let mut ids = Vec::new();
+        for title in ["One", "Two"] {
+            let request = DownloadRequest::new_with_magnet(
+                "system".to_string(),
+                "Artist".to_string(),
+                title.to_string(),
+                "magnet:?xt=urn:btih:abc".to_string(),
+                None,
+            );
+            let request = service.submit_download(request, &alice).await.unwrap();
+            assert_eq!(request.user_id, "7");
+            assert_eq!(request.status, DownloadStatus::AwaitingApproval);
+            ids.push(request.id);
+        }
+        assert_eq!(service.pending_approvals(50, 0).await.unwrap().len(), 2);
+
+        let approved = service.approve_download(&ids[0], &admin).await.unwrap();
+        assert_eq!(approved.status, DownloadStatus::Queued);
+        let rejected = service
+            .reject_download(&ids[1], &admin, "Already in the library")
+            .await
+            .unwrap();
+        assert_eq!(rejected.status, DownloadStatus::Rejected);
+        assert!(service.approve_download(&ids[1], &admin).await.is_err());
+        assert!(service.pending_approvals(50, 0).await.unwrap().is_empty());
+
+        let inbox = service.notifications().list("7", true, 10).await.unwrap();
+        assert_eq!(inbox.len(), 2);
+        assert_eq!(inbox[0].data["request_id"], ids[1].as_str());
+        assert!(inbox[0].message.contains("Already in the library"));
+
+        let (_, usage) = service.get_quota(&alice).await.unwrap();
+        assert_eq!(usage.requests_today, 1);
+        assert_eq!(usage.active_downloads, 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_submissions_respect_quota() {
+        use crate::models::user::UserRole;
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let service = DownloadService::new(DownloadConfig::default(), database).unwrap();
+
+        let alice = Requester {
+            user_id: "7".to_string(),
+            username: "alice".to_string(),
+            role: UserRole::User,
+        };
+        let limit = service
+            .config
+            .quotas
+            .limits(UserRole::User)
+            .concurrent_downloads
+            .unwrap();
+
+        let submissions = (0..limit * 3).map(|i| {
+            let request = DownloadRequest::new_with_magnet(
+                "system".to_string(),
+                "Artist".to_string(),
+                format!("Track {}", i),
+                "magnet:?xt=urn:btih:abc".to_string(),
+                None,
+            );
+            service.submit_download(request, &alice)
+        });
+        let results = futures::future::join_all(submissions).await;
+
+        let admitted = results.iter().filter(|r| r.is_ok()).count();
+        assert_eq!(admitted, limit as usize);
+        let (_, usage) = service.get_quota(&alice).await.unwrap();
+        assert_eq!(usage.active_downloads, limit);
+    }
+
+    #[tokio::test]
+    async fn test_lidarr_events() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let config = DownloadConfig {
+            lidarr_webhook_secret: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let service = DownloadService::new(config, database).unwrap();
+        assert!(service.verify_lidarr_webhook("s3cret"));
+        assert!(!service.verify_lidarr_webhook("guess"));
+
+        let alice = Requester {
+            user_id: "7".to_string(),
+            username: "alice".to_string(),
+            role: crate::models::user::UserRole::User,
+        };
+        service
+            .record_artist_request("mbid-1", "Artist", &alice)
+            .await
+            .unwrap();
+
+        let grab: LidarrWebhookPayload = serde_json::from_value(serde_json::json!({
+            "eventType": "Grab",
+            "artist": {"name": "Artist", "mbId": "mbid-1"},
+            "albums": [{"title": "Album"}],
+            "release": {"releaseTitle": "Artist - Album [FLAC]", "indexer": "Prowlarr", "size": 300},
+            "downloadId": "ABC"
+        }))
+        .unwrap();
+        let grabbed = service.handle_lidarr_event(&grab).await.unwrap().unwrap();
+        assert_eq!(grabbed.status, DownloadStatus::Downloading);
+        assert_eq!(grabbed.user_id, LIDARR_USER_ID);
+        // Redelivered webhooks do not create duplicate history
+        let again = service.handle_lidarr_event(&grab).await.unwrap().unwrap();
+        assert_eq!(again.id, grabbed.id);
+
+        let import: LidarrWebhookPayload = serde_json::from_value(serde_json::json!({
+            "eventType": "Download",
+            "artist": {"name": "Artist", "mbId": "mbid-1"},
+            "albums": [{"title": "Album"}],
+            "trackFiles": [{"path": "/music/Artist/Album/01 - One.flac", "size": 100}],
+            "downloadId": "ABC"
+        }))
+        .unwrap();
+        let imported = service.handle_lidarr_event(&import).await.unwrap().unwrap();
+        assert_eq!(imported.id, grabbed.id);
+        assert_eq!(imported.status, DownloadStatus::Imported);
+        assert_eq!(
+            imported.download_path.as_deref(),
+            Some("/music/Artist/Album")
+        );
+        assert_eq!(
+            service.get_transitions(&imported.id).await.unwrap().len(),
+            5
+        );
+
+        let inbox = service.notifications().list("7", true, 10).await.unwrap();
+        assert_eq!(inbox.len(), 1);
+        assert_eq!(inbox[0].kind, "album_imported");
+        assert!(inbox[0].message.contains("Album by Artist"));
+
+        // Upgrades are recorded without notifying anyone again
+        let upgrade: LidarrWebhookPayload = serde_json::from_value(serde_json::json!({
+            "eventType": "Download",
+            "isUpgrade": true,
+            "artist": {"name": "Artist", "mbId": "mbid-1"},
+            "albums": [{"title": "Album"}]
+        }))
+        .unwrap();
+        let upgraded = service
+            .handle_lidarr_event(&upgrade)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(upgraded.id, grabbed.id);
+        assert_eq!(upgraded.status, DownloadStatus::Imported);
+        assert_eq!(
+            service
+                .notifications()
+                .list("7", true, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_requeues_without_client() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let config = DownloadConfig {
+            transmission_url: "http://127.0.0.1:9".to_string(),
+            retry: RetryConfig {
+                max_retries: 0,
+                ..RetryConfig::default()
+            },
+            ..DownloadConfig::default()
+        };
+        let service = DownloadService::new(config, database).unwrap();
+
+        let mut magnet = DownloadRequest::new_with_magnet(
+            "7".to_string(),
+            "Artist".to_string(),
+            "Magnet".to_string(),
+            "magnet:?xt=urn:btih:abc".to_string(),
+            None,
+        );
+        let mut plain =
+            DownloadRequest::new("7".to_string(), "Artist".to_string(), "Plain".to_string());
+        for request in [&mut magnet, &mut plain] {
+            service.store.save(request).await.unwrap();
+            for next in [DownloadStatus::Queued, DownloadStatus::Downloading] {
+                service.transition(request, next, "test").await.unwrap();
+            }
+            request.torrent_hash = Some("abc".to_string());
+            service.store.save(request).await.unwrap();
+        }
+
+        service.restore_state().await.unwrap();
+
+        let queue = service.download_queue.lock().await;
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].id, magnet.id);
+        // Without the client there's no telling whether it still has the torrent
+        let plain = service.store.get(&plain.id).await.unwrap().unwrap();
+        assert_eq!(plain.status, DownloadStatus::Downloading);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_without_client_still_cancels() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let config = DownloadConfig {
+            transmission_url: "http://127.0.0.1:9".to_string(),
+            retry: RetryConfig {
+                max_retries: 0,
+                ..RetryConfig::default()
+            },
+            ..DownloadConfig::default()
+        };
+        let service = DownloadService::new(config, database).unwrap();
+
+        let mut request =
+            DownloadRequest::new("7".to_string(), "Artist".to_string(), "Track".to_string());
+        service.store.save(&request).await.unwrap();
+        for next in [DownloadStatus::Queued, DownloadStatus::Downloading] {
+            service
+                .transition(&mut request, next, "test")
+                .await
+                .unwrap();
+        }
+        request.torrent_hash = Some("abc".to_string());
+        service.store.save(&request).await.unwrap();
+
+        // The client error is reported, but the request is cancelled regardless
+        assert!(service.cancel_download("abc", true, "alice").await.is_err());
+        let request = service.store.get(&request.id).await.unwrap().unwrap();
+        assert_eq!(request.status, DownloadStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_album_release_size_limit() {
+        use crate::clients::torznab::parse_torznab_response;
+
+        let xml = r#"<rss xmlns:torznab="http://torznab.com/schemas/2015/feed"><channel>
+            <item><title>Artist - Album [FLAC]</title><guid>1</guid><size>4194304000</size>
+                <torznab:attr name="seeders" value="50"/>
+                <torznab:attr name="infohash" value="1111111111111111111111111111111111111111"/></item>
+        </channel></rss>"#;
+        let candidates = parse_torznab_response(xml, "mock").unwrap();
+        let profile = QualityProfile::standard();
+        let mut request =
+            DownloadRequest::new("7".to_string(), "Artist".to_string(), "Album".to_string());
+        request.album_title = Some("Album".to_string());
+
+        // 4000 MB is too much for a typical album at 150 MB a track
+        let context = selection_context(&request, None);
+        assert_eq!(context.track_count, Some(ESTIMATED_ALBUM_TRACKS));
+        assert!(profile.select(&candidates, &context).selected().is_none());
+
+        // but fits a box set that MusicBrainz says has 40 tracks
+        let context = selection_context(&request, Some(40));
+        assert!(profile.select(&candidates, &context).selected().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_stalled_release_falls_back() {
+        use crate::clients::torznab::parse_torznab_response;
+        use crate::services::quality_profile::SelectionContext;
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let config = DownloadConfig {
+            transmission_url: "http://127.0.0.1:9".to_string(),
+            retry: RetryConfig {
+                max_retries: 0,
+                ..RetryConfig::default()
+            },
+            ..DownloadConfig::default()
+        };
+        let service = DownloadService::new(config, database).unwrap();
+
+        let mut request =
+            DownloadRequest::new("7".to_string(), "Artist".to_string(), "Track".to_string());
+        service.store.save(&request).await.unwrap();
+
+        let xml = r#"<rss xmlns:torznab="http://torznab.com/schemas/2015/feed"><channel>
+            <item><title>Artist - Track [FLAC]</title><guid>1</guid>
+                <torznab:attr name="seeders" value="50"/>
+                <torznab:attr name="infohash" value="1111111111111111111111111111111111111111"/></item>
+            <item><title>Artist - Track [MP3 320]</title><guid>2</guid>
+                <torznab:attr name="seeders" value="5"/>
+                <torznab:attr name="infohash" value="2222222222222222222222222222222222222222"/></item>
+            <item><title>Artist - Track Karaoke [FLAC]</title><guid>3</guid>
+                <torznab:attr name="seeders" value="90"/>
+                <torznab:attr name="infohash" value="3333333333333333333333333333333333333333"/></item>
+        </channel></rss>"#;
+        let candidates = parse_torznab_response(xml, "mock").unwrap();
+        let selection = service
+            .config
+            .quality_profile
+            .select(&candidates, &SelectionContext::default());
+        service
+            .store
+            .save_candidates(&request.id, &selection)
+            .await
+            .unwrap();
+
+        for next in [DownloadStatus::Queued, DownloadStatus::Downloading] {
+            service
+                .transition(&mut request, next, "test")
+                .await
+                .unwrap();
+        }
+        let first = "1111111111111111111111111111111111111111";
+        request.torrent_hash = Some(first.to_string());
+
+        // The stalled release is blocklisted and the next acceptable one queued
+        service
+            .abandon_release(&mut request, first, "No progress for 30 minutes")
+            .await
+            .unwrap();
+        assert_eq!(request.status, DownloadStatus::Queued);
+        assert!(request.magnet_url.as_deref().unwrap().contains("2222"));
+        assert_eq!(service.download_queue.lock().await.len(), 1);
+        assert!(service
+            .store
+            .is_blocklisted(Some(first), "mock", "1")
+            .await
+            .unwrap());
+
+        // With the rejected karaoke release left over, the request fails
+        service
+            .transition(&mut request, DownloadStatus::Downloading, "test")
+            .await
+            .unwrap();
+        let second = "2222222222222222222222222222222222222222";
+        service
+            .abandon_release(
+                &mut request,
+                second,
+                "Transmission error: unregistered torrent",
+            )
+            .await
+            .unwrap();
+        assert_eq!(request.status, DownloadStatus::Failed);
+        assert!(request
+            .error_message
+            .as_deref()
+            .unwrap()
+            .contains("no alternative releases left"));
+
+        let inbox = service.notifications().list("7", true, 10).await.unwrap();
+        assert_eq!(inbox.len(), 1);
+        assert_eq!(inbox[0].kind, NotificationKind::DownloadFailed.as_str());
+        assert_eq!(service.store.blocklist(10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_low_disk_space_refuses_downloads() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = DownloadConfig {
+            download_path: dir.path().to_path_buf(),
+            processing_path: dir.path().to_path_buf(),
+            final_library_path: dir.path().to_path_buf(),
+            disk_space: DiskSpaceConfig {
+                min_free_bytes: u64::MAX,
+                critical_free_bytes: 0,
+                ..DiskSpaceConfig::default()
+            },
+            ..DownloadConfig::default()
+        };
+        let service = DownloadService::new(config, database).unwrap();
+        let request = DownloadRequest::new(
+            "system".to_string(),
+            "Artist".to_string(),
+            "Track".to_string(),
+        );
+
+        service.check_disk_space(Utc::now()).await.unwrap();
+        let status = service.disk_space_status().await;
+        assert_eq!(status.level, SpaceLevel::Low);
+        assert_eq!(status.tiers.len(), 3);
+        assert_eq!(status.alerts().len(), 3);
+        // Low is not critical, so nothing is paused
+        assert!(status.paused_for_space.is_empty());
+
+        let refused = service
+            .submit_download(request, &Requester::system())
+            .await
+            .unwrap_err();
+        let reason = refused
+            .downcast_ref::<crate::services::disk_space::InsufficientSpace>()
+            .unwrap();
+        assert_eq!(reason.tier, "downloads");
+    }
+
+    #[tokio::test]
+    async fn test_torrent_file_submission() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let service = DownloadService::new(DownloadConfig::default(), database).unwrap();
+        let admin = Requester::system();
+
+        let torrent = |name: &str| {
+            format!(
+                "d4:infod6:lengthi500e4:name{}:{}12:piece lengthi16384e6:pieces0:ee",
+                name.len(),
+                name
+            )
+            .into_bytes()
+        };
+        let request = || {
+            DownloadRequest::new(
+                "system".to_string(),
+                "Artist".to_string(),
+                "Track".to_string(),
+            )
+        };
+
+        let video = torrent("movie.mkv");
+        let metainfo = TorrentMetainfo::parse(&video).unwrap();
+        let refused = service
+            .submit_torrent_file(request(), &metainfo, &video, &admin)
+            .await;
+        assert!(refused.is_err());
+
+        let audio = torrent("track.flac");
+        let metainfo = TorrentMetainfo::parse(&audio).unwrap();
+        let queued = service
+            .submit_torrent_file(request(), &metainfo, &audio, &admin)
+            .await
+            .unwrap();
+        // Known before Transmission ever sees the torrent
+        assert_eq!(queued.status, DownloadStatus::Queued);
+        assert_eq!(
+            queued.torrent_hash.as_deref(),
+            Some(metainfo.info_hash.as_str())
+        );
+        assert_eq!(queued.file_size, Some(500));
+        assert_eq!(
+            service.store.torrent_file(&queued.id).await.unwrap(),
+            Some(audio)
+        );
+
+        // Magnet requests learn their hash from the link
+        let magnet = DownloadRequest::new_with_magnet(
+            "system".to_string(),
+            "Artist".to_string(),
+            "Other".to_string(),
+            format!("magnet:?xt=urn:btih:{}", metainfo.info_hash.to_uppercase()),
+            None,
+        );
+        let magnet = service.submit_download(magnet, &admin).await.unwrap();
+        assert_eq!(magnet.torrent_hash, queued.torrent_hash);
+
+        let mut cancelled = queued.clone();
+        service
+            .transition(&mut cancelled, DownloadStatus::Cancelled, "Changed my mind")
+            .await
+            .unwrap();
+        assert!(!service.store.has_torrent_file(&queued.id).await.unwrap());
+    }
 }
@@ -0,0 +1,1162 @@
+//! Download persistence for StepheyBot Music
+//!
+//! This module stores download requests in SQLite so the download queue and
+//! in-flight torrents survive restarts, and provides the per-user download history.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    query::Query,
+    sqlite::{SqliteArguments, SqliteRow},
+    Row, Sqlite,
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::database::Database;
+use crate::models::entities::{DownloadRequest, DownloadStatus, DownloadTransition};
+use crate::services::import::ImportStage;
+use crate::services::quality_profile::{RejectionReason, ReleaseSelection, ReleaseSource};
+use crate::services::quota::QuotaUsage;
+
+/// Filter for querying the download history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DownloadHistoryFilter {
+    pub user_id: Option<String>,
+    pub status: Option<DownloadStatus>,
+    pub artist_name: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    /// List the oldest requests first instead of the newest
+    #[serde(default)]
+    pub oldest_first: bool,
+}
+
+/// Number of requests and their combined size for one status
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct StatusTotals {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// A release that was considered for a download request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandidateRecord {
+    pub id: i64,
+    pub indexer: String,
+    pub title: String,
+    pub guid: Option<String>,
+    pub info_hash: Option<String>,
+    pub magnet_url: Option<String>,
+    pub size: Option<u64>,
+    pub seeders: Option<u32>,
+    pub format: Option<String>,
+    pub bitrate: Option<u32>,
+    pub source: ReleaseSource,
+    pub score: f64,
+    pub selected: bool,
+    pub rejections: Vec<RejectionReason>,
+    pub evaluated_at: DateTime<Utc>,
+}
+
+/// A download that needs to be imported by hand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualImport {
+    pub id: i64,
+    pub request_id: Option<String>,
+    pub torrent_hash: Option<String>,
+    pub source_path: String,
+    pub stage: ImportStage,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolution: Option<String>,
+}
+
+/// A release that must not be grabbed again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlocklistEntry {
+    pub id: i64,
+    pub info_hash: Option<String>,
+    pub guid: Option<String>,
+    pub indexer: Option<String>,
+    pub title: String,
+    pub request_id: Option<String>,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// SQLite-backed store for download requests
+#[derive(Clone)]
+pub struct DownloadStore {
+    database: Arc<Database>,
+}
+
+impl DownloadStore {
+    /// Create a new download store
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+
+    /// Insert or update a download request
+    pub async fn save(&self, request: &DownloadRequest) -> Result<()> {
+        debug!(
+            "Persisting download request {} ({})",
+            request.id, request.status
+        );
+
+        upsert(request).execute(self.database.pool()).await?;
+        Ok(())
+    }
+
+    /// Persist a request together with the transition that produced its status
+    pub async fn save_transition(
+        &self,
+        request: &DownloadRequest,
+        transition: &DownloadTransition,
+    ) -> Result<()> {
+        let mut tx = self.database.pool().begin().await?;
+
+        upsert(request).execute(&mut *tx).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO download_transitions (request_id, from_status, to_status, reason, transitioned_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&transition.request_id)
+        .bind(transition.from_status)
+        .bind(transition.to_status)
+        .bind(&transition.reason)
+        .bind(transition.transitioned_at.timestamp())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the recorded transitions of a request, oldest first
+    pub async fn transitions(&self, request_id: &str) -> Result<Vec<DownloadTransition>> {
+        let rows = sqlx::query(
+            "SELECT * FROM download_transitions WHERE request_id = ? ORDER BY transitioned_at ASC, id ASC",
+        )
+        .bind(request_id)
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| DownloadTransition {
+                request_id: row.get("request_id"),
+                from_status: decode_status(row, "from_status"),
+                to_status: decode_status(row, "to_status"),
+                reason: row.get("reason"),
+                transitioned_at: from_timestamp(row.get("transitioned_at"))
+                    .unwrap_or_else(Utc::now),
+            })
+            .collect())
+    }
+
+    /// Replace the recorded release candidates of a request
+    pub async fn save_candidates(
+        &self,
+        request_id: &str,
+        selection: &ReleaseSelection,
+    ) -> Result<()> {
+        let mut tx = self.database.pool().begin().await?;
+        let evaluated_at = Utc::now().timestamp();
+
+        sqlx::query("DELETE FROM download_candidates WHERE request_id = ?")
+            .bind(request_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for evaluation in &selection.evaluations {
+            let candidate = &evaluation.candidate;
+            sqlx::query(
+                r#"
+                INSERT INTO download_candidates (
+                    request_id, indexer, title, guid, info_hash, magnet_url, size, seeders,
+                    format, bitrate, source, score, selected, rejection_reasons, evaluated_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(request_id)
+            .bind(&candidate.indexer)
+            .bind(&candidate.title)
+            .bind(&candidate.guid)
+            .bind(&candidate.info_hash)
+            .bind(candidate.magnet())
+            .bind(candidate.size as i64)
+            .bind(candidate.seeders)
+            .bind(&candidate.format)
+            .bind(candidate.bitrate)
+            .bind(evaluation.source.as_str())
+            .bind(evaluation.score)
+            .bind(evaluation.selected)
+            .bind(serde_json::to_string(&evaluation.rejections)?)
+            .bind(evaluated_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the release candidates recorded for a request, best first
+    pub async fn candidates(&self, request_id: &str) -> Result<Vec<CandidateRecord>> {
+        let rows = sqlx::query(
+            "SELECT * FROM download_candidates WHERE request_id = ? ORDER BY selected DESC, score DESC, id ASC",
+        )
+        .bind(request_id)
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_candidate).collect())
+    }
+
+    /// Get the best acceptable candidate of a request that has not been tried
+    /// yet and is not blocklisted; releases that were only outscored by the
+    /// selected one are acceptable
+    pub async fn next_candidate(&self, request_id: &str) -> Result<Option<CandidateRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM download_candidates c
+            WHERE c.request_id = ?
+              AND c.selected = FALSE
+              AND c.magnet_url IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM release_blocklist b
+                  WHERE (b.info_hash IS NOT NULL AND b.info_hash = LOWER(c.info_hash))
+                     OR (b.guid IS NOT NULL AND b.guid = c.guid AND b.indexer = c.indexer)
+              )
+            ORDER BY c.score DESC, c.id ASC
+            "#,
+        )
+        .bind(request_id)
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_candidate).find(|candidate| {
+            candidate
+                .rejections
+                .iter()
+                .all(|reason| matches!(reason, RejectionReason::Outscored { .. }))
+        }))
+    }
+
+    /// Make a candidate the selected release of its request
+    pub async fn select_candidate(&self, request_id: &str, candidate_id: i64) -> Result<()> {
+        sqlx::query("UPDATE download_candidates SET selected = (id = ?) WHERE request_id = ?")
+            .bind(candidate_id)
+            .bind(request_id)
+            .execute(self.database.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// Blocklist a release so it is never grabbed again
+    pub async fn blocklist_release(&self, entry: &BlocklistEntry) -> Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO release_blocklist (
+                info_hash, guid, indexer, title, request_id, reason, created_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(entry.info_hash.as_deref().map(str::to_lowercase))
+        .bind(&entry.guid)
+        .bind(&entry.indexer)
+        .bind(&entry.title)
+        .bind(&entry.request_id)
+        .bind(&entry.reason)
+        .bind(entry.created_at.timestamp())
+        .execute(self.database.pool())
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Whether a release is blocklisted, by info hash or by indexer GUID
+    pub async fn is_blocklisted(
+        &self,
+        info_hash: Option<&str>,
+        indexer: &str,
+        guid: &str,
+    ) -> Result<bool> {
+        let row = sqlx::query(
+            r#"
+            SELECT 1 FROM release_blocklist
+            WHERE (info_hash IS NOT NULL AND info_hash = LOWER(?))
+               OR (guid IS NOT NULL AND guid = ? AND indexer = ?)
+            LIMIT 1
+            "#,
+        )
+        .bind(info_hash)
+        .bind(guid)
+        .bind(indexer)
+        .fetch_optional(self.database.pool())
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// List the blocklist, newest first
+    pub async fn blocklist(&self, limit: u32) -> Result<Vec<BlocklistEntry>> {
+        let rows = sqlx::query(
+            "SELECT * FROM release_blocklist ORDER BY created_at DESC, id DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| BlocklistEntry {
+                id: row.get("id"),
+                info_hash: row.get("info_hash"),
+                guid: row.get("guid"),
+                indexer: row.get("indexer"),
+                title: row.get("title"),
+                request_id: row.get("request_id"),
+                reason: row.get("reason"),
+                created_at: from_timestamp(row.get("created_at")).unwrap_or_else(Utc::now),
+            })
+            .collect())
+    }
+
+    /// Keep the `.torrent` file a request downloads
+    pub async fn save_torrent_file(
+        &self,
+        request_id: &str,
+        info_hash: &str,
+        data: &[u8],
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO torrent_files (request_id, info_hash, data, created_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(request_id)
+        .bind(info_hash.to_lowercase())
+        .bind(data)
+        .bind(Utc::now().timestamp())
+        .execute(self.database.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// The `.torrent` file of a request, if it was submitted as one
+    pub async fn torrent_file(&self, request_id: &str) -> Result<Option<Vec<u8>>> {
+        let row = sqlx::query("SELECT data FROM torrent_files WHERE request_id = ?")
+            .bind(request_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+        Ok(row.map(|row| row.get("data")))
+    }
+
+    /// Whether a request was submitted as a `.torrent` file
+    pub async fn has_torrent_file(&self, request_id: &str) -> Result<bool> {
+        let row = sqlx::query("SELECT 1 FROM torrent_files WHERE request_id = ?")
+            .bind(request_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Drop the `.torrent` file of a request that no longer needs it
+    pub async fn delete_torrent_file(&self, request_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM torrent_files WHERE request_id = ?")
+            .bind(request_id)
+            .execute(self.database.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Park a download for manual import, replacing any unresolved entry for
+    /// the same request
+    pub async fn add_manual_import(
+        &self,
+        request_id: Option<&str>,
+        torrent_hash: Option<&str>,
+        source_path: &str,
+        stage: ImportStage,
+        reason: &str,
+    ) -> Result<i64> {
+        let mut tx = self.database.pool().begin().await?;
+
+        if let Some(request_id) = request_id {
+            sqlx::query("DELETE FROM manual_imports WHERE request_id = ? AND resolved_at IS NULL")
+                .bind(request_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let id = sqlx::query(
+            r#"
+            INSERT INTO manual_imports (
+                request_id, torrent_hash, source_path, stage, reason, created_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(request_id)
+        .bind(torrent_hash)
+        .bind(source_path)
+        .bind(stage.as_str())
+        .bind(reason)
+        .bind(Utc::now().timestamp())
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Get a manual import entry by ID
+    pub async fn manual_import(&self, id: i64) -> Result<Option<ManualImport>> {
+        let row = sqlx::query("SELECT * FROM manual_imports WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self.database.pool())
+            .await?;
+
+        Ok(row.as_ref().map(row_to_manual_import))
+    }
+
+    /// List the downloads still waiting for manual import, oldest first
+    pub async fn unresolved_manual_imports(&self) -> Result<Vec<ManualImport>> {
+        let rows = sqlx::query(
+            "SELECT * FROM manual_imports WHERE resolved_at IS NULL ORDER BY created_at ASC, id ASC",
+        )
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_manual_import).collect())
+    }
+
+    /// Mark a manual import entry as handled
+    pub async fn resolve_manual_import(&self, id: i64, resolution: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE manual_imports SET resolved_at = ?, resolution = ? WHERE id = ? AND resolved_at IS NULL",
+        )
+        .bind(Utc::now().timestamp())
+        .bind(resolution)
+        .bind(id)
+        .execute(self.database.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Work out how much of their quota a user has used
+    ///
+    /// Rejected requests are not counted, and failed or cancelled requests
+    /// only count towards volume if they ever started downloading. Requests
+    /// waiting for approval count as active.
+    pub async fn usage(
+        &self,
+        user_id: &str,
+        day_start: DateTime<Utc>,
+        month_start: DateTime<Utc>,
+    ) -> Result<QuotaUsage> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COALESCE(SUM(CASE WHEN requested_at >= ? THEN 1 ELSE 0 END), 0) AS requests_today,
+                COALESCE(SUM(CASE
+                    WHEN requested_at >= ?
+                        AND (status NOT IN ('failed', 'cancelled') OR started_at IS NOT NULL)
+                    THEN COALESCE(file_size, 0) ELSE 0 END), 0) AS bytes_this_month,
+                COALESCE(SUM(CASE
+                    WHEN status IN ('awaiting_approval', 'pending', 'searching', 'queued',
+                                    'downloading', 'stalled')
+                    THEN 1 ELSE 0 END), 0) AS active_downloads
+            FROM download_requests
+            WHERE user_id = ? AND status != 'rejected'
+            "#,
+        )
+        .bind(day_start.timestamp())
+        .bind(month_start.timestamp())
+        .bind(user_id)
+        .fetch_one(self.database.pool())
+        .await?;
+
+        Ok(QuotaUsage {
+            requests_today: row.get::<i64, _>("requests_today") as u32,
+            bytes_this_month: row.get::<i64, _>("bytes_this_month") as u64,
+            active_downloads: row.get::<i64, _>("active_downloads") as u32,
+        })
+    }
+
+    /// Get the indexer the selected release of a request came from
+    pub async fn selected_indexer(&self, request_id: &str) -> Result<Option<String>> {
+        let row = sqlx::query(
+            "SELECT indexer FROM download_candidates WHERE request_id = ? AND selected = TRUE LIMIT 1",
+        )
+        .bind(request_id)
+        .fetch_optional(self.database.pool())
+        .await?;
+
+        Ok(row.map(|row| row.get("indexer")))
+    }
+
+    /// Count requests and bytes per status
+    pub async fn status_totals(&self) -> Result<BTreeMap<DownloadStatus, StatusTotals>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT status, COUNT(*) as count, COALESCE(SUM(file_size), 0) as bytes
+            FROM download_requests
+            GROUP BY status
+            "#,
+        )
+        .fetch_all(self.database.pool())
+        .await?;
+
+        let mut totals = BTreeMap::new();
+        for row in rows {
+            let entry: &mut StatusTotals = totals.entry(decode_status(&row, "status")).or_default();
+            entry.count += row.get::<i64, _>("count") as u64;
+            entry.bytes += row.get::<i64, _>("bytes") as u64;
+        }
+
+        Ok(totals)
+    }
+
+    /// Get a download request by ID
+    pub async fn get(&self, request_id: &str) -> Result<Option<DownloadRequest>> {
+        let row = sqlx::query("SELECT * FROM download_requests WHERE id = ?")
+            .bind(request_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+
+        Ok(row.as_ref().map(row_to_request))
+    }
+
+    /// Get the most recent download request for a torrent hash
+    pub async fn get_by_torrent_hash(&self, torrent_hash: &str) -> Result<Option<DownloadRequest>> {
+        let row = sqlx::query(
+            "SELECT * FROM download_requests WHERE torrent_hash = ? ORDER BY requested_at DESC LIMIT 1",
+        )
+        .bind(torrent_hash)
+        .fetch_optional(self.database.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_request))
+    }
+
+    /// Get the most recent download request for a source URL
+    pub async fn get_by_source_url(&self, source_url: &str) -> Result<Option<DownloadRequest>> {
+        let row = sqlx::query(
+            "SELECT * FROM download_requests WHERE source_url = ? ORDER BY requested_at DESC LIMIT 1",
+        )
+        .bind(source_url)
+        .fetch_optional(self.database.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_request))
+    }
+
+    /// Remember that a user asked Lidarr to monitor an artist
+    pub async fn add_artist_request(
+        &self,
+        foreign_artist_id: &str,
+        artist_name: &str,
+        user_id: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO lidarr_artist_requests (foreign_artist_id, artist_name, user_id, requested_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(foreign_artist_id, user_id) DO UPDATE SET
+                artist_name = excluded.artist_name,
+                requested_at = excluded.requested_at
+            "#,
+        )
+        .bind(foreign_artist_id)
+        .bind(artist_name)
+        .bind(user_id)
+        .bind(Utc::now().timestamp())
+        .execute(self.database.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Users who asked for an artist, most recent first; the name is only
+    /// used when the MusicBrainz ID is unknown
+    pub async fn artist_requesters(
+        &self,
+        foreign_artist_id: Option<&str>,
+        artist_name: &str,
+    ) -> Result<Vec<String>> {
+        let rows = match foreign_artist_id {
+            Some(foreign_artist_id) => {
+                sqlx::query(
+                    "SELECT user_id FROM lidarr_artist_requests WHERE foreign_artist_id = ? ORDER BY requested_at DESC",
+                )
+                .bind(foreign_artist_id)
+                .fetch_all(self.database.pool())
+                .await?
+            }
+            None => {
+                sqlx::query(
+                    "SELECT user_id FROM lidarr_artist_requests WHERE artist_name = ? COLLATE NOCASE ORDER BY requested_at DESC",
+                )
+                .bind(artist_name)
+                .fetch_all(self.database.pool())
+                .await?
+            }
+        };
+
+        Ok(rows.iter().map(|row| row.get("user_id")).collect())
+    }
+
+    /// Load every request that has not reached a terminal status, oldest first
+    pub async fn load_unfinished(&self) -> Result<Vec<DownloadRequest>> {
+        let terminal: Vec<DownloadStatus> = DownloadStatus::ALL
+            .into_iter()
+            .filter(DownloadStatus::is_terminal)
+            .collect();
+        let placeholders = vec!["?"; terminal.len()].join(", ");
+        let sql = format!(
+            "SELECT * FROM download_requests WHERE status NOT IN ({}) ORDER BY requested_at ASC",
+            placeholders
+        );
+
+        let mut query = sqlx::query(&sql);
+        for status in terminal {
+            query = query.bind(status);
+        }
+
+        let rows = query.fetch_all(self.database.pool()).await?;
+        Ok(rows.iter().map(row_to_request).collect())
+    }
+
+    /// Query the download history, newest first
+    pub async fn history(&self, filter: &DownloadHistoryFilter) -> Result<Vec<DownloadRequest>> {
+        let mut sql = String::from("SELECT * FROM download_requests WHERE 1 = 1");
+
+        if filter.user_id.is_some() {
+            sql.push_str(" AND user_id = ?");
+        }
+        if filter.status.is_some() {
+            sql.push_str(" AND status = ?");
+        }
+        if filter.artist_name.is_some() {
+            sql.push_str(" AND artist_name LIKE ?");
+        }
+        if filter.since.is_some() {
+            sql.push_str(" AND requested_at >= ?");
+        }
+        // Insertion order breaks ties so pages never overlap
+        if filter.oldest_first {
+            sql.push_str(" ORDER BY requested_at ASC, rowid ASC LIMIT ? OFFSET ?");
+        } else {
+            sql.push_str(" ORDER BY requested_at DESC, rowid DESC LIMIT ? OFFSET ?");
+        }
+
+        let mut query = sqlx::query(&sql);
+        if let Some(ref user_id) = filter.user_id {
+            query = query.bind(user_id);
+        }
+        if let Some(status) = filter.status {
+            query = query.bind(status);
+        }
+        if let Some(ref artist_name) = filter.artist_name {
+            query = query.bind(format!("%{}%", artist_name));
+        }
+        if let Some(since) = filter.since {
+            query = query.bind(since.timestamp());
+        }
+        query = query
+            .bind(filter.limit.unwrap_or(50).min(500))
+            .bind(filter.offset.unwrap_or(0));
+
+        let rows = query.fetch_all(self.database.pool()).await?;
+        Ok(rows.iter().map(row_to_request).collect())
+    }
+}
+
+/// Build the upsert for a download request
+fn upsert(request: &DownloadRequest) -> Query<'_, Sqlite, SqliteArguments<'_>> {
+    sqlx::query(
+            r#"
+            INSERT INTO download_requests (
+                id, user_id, artist_name, track_title, album_title, status,
+                source_url, magnet_url, torrent_hash, download_path, file_size,
+                progress, download_speed, upload_speed, seeds, peers, error_message,
+                requested_at, started_at, completed_at, processed_at, track_id, priority,
+                priority_override, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))
+            ON CONFLICT(id) DO UPDATE SET
+                user_id = excluded.user_id,
+                artist_name = excluded.artist_name,
+                track_title = excluded.track_title,
+                album_title = excluded.album_title,
+                status = excluded.status,
+                source_url = excluded.source_url,
+                magnet_url = excluded.magnet_url,
+                torrent_hash = excluded.torrent_hash,
+                download_path = excluded.download_path,
+                file_size = excluded.file_size,
+                progress = excluded.progress,
+                download_speed = excluded.download_speed,
+                upload_speed = excluded.upload_speed,
+                seeds = excluded.seeds,
+                peers = excluded.peers,
+                error_message = excluded.error_message,
+                started_at = excluded.started_at,
+                completed_at = excluded.completed_at,
+                processed_at = excluded.processed_at,
+                track_id = excluded.track_id,
+                priority = excluded.priority,
+                priority_override = excluded.priority_override,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&request.id)
+        .bind(&request.user_id)
+        .bind(&request.artist_name)
+        .bind(&request.track_title)
+        .bind(&request.album_title)
+        .bind(request.status)
+        .bind(&request.source_url)
+        .bind(&request.magnet_url)
+        .bind(&request.torrent_hash)
+        .bind(&request.download_path)
+        .bind(request.file_size.map(|v| v as i64))
+        .bind(request.progress)
+        .bind(request.download_speed.map(|v| v as i64))
+        .bind(request.upload_speed.map(|v| v as i64))
+        .bind(request.seeds)
+        .bind(request.peers)
+        .bind(&request.error_message)
+        .bind(request.requested_at.timestamp())
+        .bind(request.started_at.map(|t| t.timestamp()))
+        .bind(request.completed_at.map(|t| t.timestamp()))
+        .bind(request.processed_at.map(|t| t.timestamp()))
+        .bind(&request.track_id)
+        .bind(request.priority)
+        .bind(request.priority_override)
+}
+
+/// Map a `download_requests` row back into a download request
+fn row_to_request(row: &SqliteRow) -> DownloadRequest {
+    DownloadRequest {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        artist_name: row.get("artist_name"),
+        track_title: row.get("track_title"),
+        album_title: row.get("album_title"),
+        status: decode_status(row, "status"),
+        priority: row.try_get("priority").unwrap_or_default(),
+        priority_override: row.try_get("priority_override").unwrap_or_default(),
+        source_url: row.get("source_url"),
+        magnet_url: row.get("magnet_url"),
+        torrent_hash: row.get("torrent_hash"),
+        download_path: row.get("download_path"),
+        file_size: row.get::<Option<i64>, _>("file_size").map(|v| v as u64),
+        progress: row.get("progress"),
+        download_speed: row
+            .get::<Option<i64>, _>("download_speed")
+            .map(|v| v as u64),
+        upload_speed: row.get::<Option<i64>, _>("upload_speed").map(|v| v as u64),
+        seeds: row.get("seeds"),
+        peers: row.get("peers"),
+        error_message: row.get("error_message"),
+        requested_at: from_timestamp(row.get("requested_at")).unwrap_or_else(Utc::now),
+        started_at: row
+            .get::<Option<i64>, _>("started_at")
+            .and_then(from_timestamp),
+        completed_at: row
+            .get::<Option<i64>, _>("completed_at")
+            .and_then(from_timestamp),
+        processed_at: row
+            .get::<Option<i64>, _>("processed_at")
+            .and_then(from_timestamp),
+        track_id: row.get("track_id"),
+    }
+}
+
+/// Decode a status column, treating unknown values as failed
+fn decode_status(row: &SqliteRow, column: &str) -> DownloadStatus {
+    row.try_get(column).unwrap_or_else(|e| {
+        warn!("Unreadable download status in column {}: {}", column, e);
+        DownloadStatus::Failed
+    })
+}
+
+fn row_to_candidate(row: &SqliteRow) -> CandidateRecord {
+    CandidateRecord {
+        id: row.get("id"),
+        indexer: row.get("indexer"),
+        title: row.get("title"),
+        guid: row.get("guid"),
+        info_hash: row.get("info_hash"),
+        magnet_url: row.get("magnet_url"),
+        size: row.get::<Option<i64>, _>("size").map(|v| v as u64),
+        seeders: row.get("seeders"),
+        format: row.get("format"),
+        bitrate: row.get("bitrate"),
+        source: row
+            .get::<String, _>("source")
+            .parse()
+            .unwrap_or(ReleaseSource::Unknown),
+        score: row.get("score"),
+        selected: row.get("selected"),
+        rejections: serde_json::from_str(row.get("rejection_reasons")).unwrap_or_else(|e| {
+            warn!(
+                "Unreadable rejection reasons for candidate {}: {}",
+                row.get::<i64, _>("id"),
+                e
+            );
+            Vec::new()
+        }),
+        evaluated_at: from_timestamp(row.get("evaluated_at")).unwrap_or_else(Utc::now),
+    }
+}
+
+fn row_to_manual_import(row: &SqliteRow) -> ManualImport {
+    ManualImport {
+        id: row.get("id"),
+        request_id: row.get("request_id"),
+        torrent_hash: row.get("torrent_hash"),
+        source_path: row.get("source_path"),
+        stage: row
+            .get::<String, _>("stage")
+            .parse()
+            .unwrap_or(ImportStage::Verify),
+        reason: row.get("reason"),
+        created_at: from_timestamp(row.get("created_at")).unwrap_or_else(Utc::now),
+        resolved_at: row
+            .get::<Option<i64>, _>("resolved_at")
+            .and_then(from_timestamp),
+        resolution: row.get("resolution"),
+    }
+}
+
+fn from_timestamp(seconds: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(seconds, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::entities::DownloadPriority;
+    use tempfile::NamedTempFile;
+
+    async fn create_test_store() -> (DownloadStore, NamedTempFile) {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        (DownloadStore::new(database), temp_file)
+    }
+
+    #[tokio::test]
+    async fn test_save_and_reload_unfinished() {
+        let (store, _temp_file) = create_test_store().await;
+
+        let mut queued = DownloadRequest::new_with_magnet(
+            "alice".to_string(),
+            "Artist".to_string(),
+            "Track".to_string(),
+            "magnet:?xt=urn:btih:abc".to_string(),
+            None,
+        );
+        queued.file_size = Some(1024);
+        queued.priority = DownloadPriority::Low;
+        queued.priority_override = Some(DownloadPriority::High);
+        store.save(&queued).await.unwrap();
+
+        let mut done =
+            DownloadRequest::new("bob".to_string(), "Other".to_string(), "Song".to_string());
+        done.status = DownloadStatus::Imported;
+        store.save(&done).await.unwrap();
+
+        let unfinished = store.load_unfinished().await.unwrap();
+        assert_eq!(unfinished.len(), 1);
+        assert_eq!(unfinished[0].id, queued.id);
+        assert_eq!(unfinished[0].file_size, Some(1024));
+        assert_eq!(unfinished[0].priority, DownloadPriority::Low);
+        assert_eq!(unfinished[0].effective_priority(), DownloadPriority::High);
+
+        queued.torrent_hash = Some("abc".to_string());
+        let transition = queued
+            .transition_to(DownloadStatus::Queued, "Queued for download")
+            .unwrap();
+        store.save_transition(&queued, &transition).await.unwrap();
+
+        let by_hash = store.get_by_torrent_hash("abc").await.unwrap().unwrap();
+        assert_eq!(by_hash.status, DownloadStatus::Queued);
+
+        let transitions = store.transitions(&queued.id).await.unwrap();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].from_status, DownloadStatus::Pending);
+        assert_eq!(transitions[0].reason, "Queued for download");
+
+        let totals = store.status_totals().await.unwrap();
+        assert_eq!(totals[&DownloadStatus::Queued].count, 1);
+        assert_eq!(totals[&DownloadStatus::Queued].bytes, 1024);
+        assert_eq!(totals[&DownloadStatus::Imported].count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_history_filters() {
+        let (store, _temp_file) = create_test_store().await;
+
+        for user in ["alice", "alice", "bob"] {
+            let request =
+                DownloadRequest::new(user.to_string(), "Artist".to_string(), "Track".to_string());
+            store.save(&request).await.unwrap();
+        }
+
+        let filter = DownloadHistoryFilter {
+            user_id: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.history(&filter).await.unwrap().len(), 2);
+
+        let filter = DownloadHistoryFilter {
+            status: Some(DownloadStatus::Imported),
+            ..Default::default()
+        };
+        assert!(store.history(&filter).await.unwrap().is_empty());
+
+        let filter = DownloadHistoryFilter {
+            oldest_first: true,
+            limit: Some(2),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let page = store.history(&filter).await.unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].user_id, "alice");
+        assert_eq!(page[1].user_id, "bob");
+    }
+
+    #[tokio::test]
+    async fn test_save_candidates() {
+        use crate::clients::torznab::parse_torznab_response;
+        use crate::services::quality_profile::{QualityProfile, SelectionContext};
+
+        let (store, _temp_file) = create_test_store().await;
+        let request = DownloadRequest::new(
+            "alice".to_string(),
+            "Artist".to_string(),
+            "Track".to_string(),
+        );
+        store.save(&request).await.unwrap();
+
+        let xml = r#"<rss xmlns:torznab="http://torznab.com/schemas/2015/feed"><channel>
+            <item><title>Artist - Track [FLAC]</title><guid>1</guid>
+                <torznab:attr name="seeders" value="5"/>
+                <torznab:attr name="infohash" value="1111111111111111111111111111111111111111"/></item>
+            <item><title>Artist - Track Karaoke [MP3 320]</title><guid>2</guid>
+                <torznab:attr name="seeders" value="50"/>
+                <torznab:attr name="infohash" value="2222222222222222222222222222222222222222"/></item>
+        </channel></rss>"#;
+        let candidates = parse_torznab_response(xml, "mock").unwrap();
+        let selection =
+            QualityProfile::standard().select(&candidates, &SelectionContext::default());
+        store
+            .save_candidates(&request.id, &selection)
+            .await
+            .unwrap();
+
+        // Saving again replaces the previous evaluation
+        store
+            .save_candidates(&request.id, &selection)
+            .await
+            .unwrap();
+
+        let records = store.candidates(&request.id).await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            store
+                .selected_indexer(&request.id)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("mock")
+        );
+        assert!(records[0].selected);
+        assert_eq!(records[0].title, "Artist - Track [FLAC]");
+        assert!(records[0]
+            .magnet_url
+            .as_deref()
+            .unwrap()
+            .starts_with("magnet:"));
+        assert!(!records[1].selected);
+        assert_eq!(
+            records[1].rejections,
+            vec![RejectionReason::ForbiddenWord {
+                word: "karaoke".to_string()
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manual_imports() {
+        let (store, _temp_file) = create_test_store().await;
+        let request = DownloadRequest::new(
+            "alice".to_string(),
+            "Artist".to_string(),
+            "Track".to_string(),
+        );
+        store.save(&request).await.unwrap();
+
+        store
+            .add_manual_import(
+                Some(&request.id),
+                Some("abc"),
+                "/downloads/Artist - Album",
+                ImportStage::Verify,
+                "No audio files found in download",
+            )
+            .await
+            .unwrap();
+
+        // A second failure replaces the first entry instead of piling up
+        let id = store
+            .add_manual_import(
+                Some(&request.id),
+                Some("abc"),
+                "/downloads/Artist - Album",
+                ImportStage::Match,
+                "Release does not match",
+            )
+            .await
+            .unwrap();
+
+        let pending = store.unresolved_manual_imports().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, id);
+        assert_eq!(pending[0].stage, ImportStage::Match);
+
+        assert!(store.resolve_manual_import(id, "dismissed").await.unwrap());
+        assert!(!store.resolve_manual_import(id, "dismissed").await.unwrap());
+        assert!(store.unresolved_manual_imports().await.unwrap().is_empty());
+
+        let resolved = store.manual_import(id).await.unwrap().unwrap();
+        assert_eq!(resolved.resolution.as_deref(), Some("dismissed"));
+        assert!(resolved.resolved_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_usage() {
+        let (store, _temp_file) = create_test_store().await;
+        let now = Utc::now();
+
+        let mut active =
+            DownloadRequest::new("alice".to_string(), "A".to_string(), "One".to_string());
+        active.status = DownloadStatus::Downloading;
+        active.file_size = Some(100);
+        store.save(&active).await.unwrap();
+
+        let mut rejected =
+            DownloadRequest::new("alice".to_string(), "A".to_string(), "Two".to_string());
+        rejected.status = DownloadStatus::Rejected;
+        rejected.file_size = Some(1000);
+        store.save(&rejected).await.unwrap();
+
+        let mut old =
+            DownloadRequest::new("alice".to_string(), "A".to_string(), "Three".to_string());
+        old.status = DownloadStatus::Imported;
+        old.file_size = Some(10);
+        old.requested_at = now - chrono::Duration::days(2);
+        store.save(&old).await.unwrap();
+
+        // Waiting for an admin still takes up a slot
+        let mut waiting =
+            DownloadRequest::new("alice".to_string(), "A".to_string(), "Five".to_string());
+        waiting.status = DownloadStatus::AwaitingApproval;
+        store.save(&waiting).await.unwrap();
+
+        let other = DownloadRequest::new("bob".to_string(), "B".to_string(), "Four".to_string());
+        store.save(&other).await.unwrap();
+
+        let usage = store
+            .usage(
+                "alice",
+                now - chrono::Duration::days(1),
+                now - chrono::Duration::days(30),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            usage,
+            QuotaUsage {
+                requests_today: 2,
+                bytes_this_month: 110,
+                active_downloads: 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lidarr_lookups() {
+        let (store, _temp_file) = create_test_store().await;
+
+        let mut request = DownloadRequest::new(
+            "alice".to_string(),
+            "Artist".to_string(),
+            "Album".to_string(),
+        );
+        request.source_url = Some("lidarr:abc".to_string());
+        store.save(&request).await.unwrap();
+
+        let found = store
+            .get_by_source_url("lidarr:abc")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, request.id);
+        assert!(store
+            .get_by_source_url("lidarr:def")
+            .await
+            .unwrap()
+            .is_none());
+
+        store
+            .add_artist_request("mbid-1", "Artist", "alice")
+            .await
+            .unwrap();
+        store
+            .add_artist_request("mbid-1", "Artist", "bob")
+            .await
+            .unwrap();
+        // Asking twice does not notify twice
+        store
+            .add_artist_request("mbid-1", "Artist", "alice")
+            .await
+            .unwrap();
+
+        let requesters = store
+            .artist_requesters(Some("mbid-1"), "Artist")
+            .await
+            .unwrap();
+        assert_eq!(requesters.len(), 2);
+        assert!(requesters.contains(&"bob".to_string()));
+        assert_eq!(
+            store.artist_requesters(None, "artist").await.unwrap().len(),
+            2
+        );
+        assert!(store
+            .artist_requesters(Some("mbid-2"), "Artist")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+//! Live events for StepheyBot Music
+//!
+//! Services publish state changes (download transitions and progress, import
+//! results, library scans) to an in-memory hub that the SSE and WebSocket
+//! endpoints stream to clients. Recent events are kept so a client that
+//! reconnects with the ID of the last event it saw gets what it missed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::services::quota::Requester;
+
+/// Events kept for clients that reconnect
+const REPLAY_CAPACITY: usize = 1024;
+
+/// What an event is about; clients can subscribe to a subset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTopic {
+    Download,
+    Scan,
+}
+
+impl std::str::FromStr for EventTopic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "download" => Ok(EventTopic::Download),
+            "scan" => Ok(EventTopic::Scan),
+            other => Err(format!("Unknown event topic: {}", other)),
+        }
+    }
+}
+
+/// One event pushed to clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveEvent {
+    pub id: u64,
+    pub topic: EventTopic,
+    /// e.g. `status`, `progress`, `import` or `scan_finished`
+    pub kind: String,
+    /// Only this user (and admins) see the event; `None` means everyone
+    pub user_id: Option<String>,
+    pub data: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LiveEvent {
+    /// Event sent in place of the ones a client can no longer get back,
+    /// telling it to reload its state from the REST endpoints
+    fn resync(last_id: u64) -> Self {
+        Self {
+            id: last_id,
+            topic: EventTopic::Download,
+            kind: "resync".to_string(),
+            user_id: None,
+            data: serde_json::json!({}),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Which events a subscriber receives
+#[derive(Debug, Clone)]
+pub struct EventFilter {
+    user_id: String,
+    admin: bool,
+    topics: Vec<EventTopic>,
+}
+
+impl EventFilter {
+    /// Events for the requester on the given topics, all topics if empty
+    pub fn new(requester: &Requester, topics: Vec<EventTopic>) -> Self {
+        Self {
+            user_id: requester.user_id.clone(),
+            admin: requester.is_admin(),
+            topics,
+        }
+    }
+
+    pub fn matches(&self, event: &LiveEvent) -> bool {
+        let visible = self.admin
+            || event
+                .user_id
+                .as_deref()
+                .is_none_or(|user_id| user_id == self.user_id);
+        visible && (self.topics.is_empty() || self.topics.contains(&event.topic))
+    }
+}
+
+struct HubInner {
+    sender: broadcast::Sender<LiveEvent>,
+    recent: Mutex<VecDeque<LiveEvent>>,
+    next_id: AtomicU64,
+}
+
+/// Fan-out point for live events
+#[derive(Clone)]
+pub struct EventHub {
+    inner: Arc<HubInner>,
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(REPLAY_CAPACITY);
+        // IDs start from the clock so they keep increasing across restarts and
+        // a stale last-event ID is recognised as such
+        let first_id = Utc::now().timestamp_millis().max(1) as u64;
+
+        Self {
+            inner: Arc::new(HubInner {
+                sender,
+                recent: Mutex::new(VecDeque::with_capacity(REPLAY_CAPACITY)),
+                next_id: AtomicU64::new(first_id),
+            }),
+        }
+    }
+
+    /// Publish an event; it is dropped silently when nobody is listening
+    pub fn publish(
+        &self,
+        topic: EventTopic,
+        kind: &str,
+        user_id: Option<&str>,
+        data: serde_json::Value,
+    ) -> u64 {
+        let mut recent = self.inner.recent.lock().unwrap_or_else(|e| e.into_inner());
+        // Assign the ID under the lock so the replay buffer stays ordered
+        let event = LiveEvent {
+            id: self.inner.next_id.fetch_add(1, Ordering::Relaxed),
+            topic,
+            kind: kind.to_string(),
+            user_id: user_id.map(str::to_string),
+            data,
+            created_at: Utc::now(),
+        };
+
+        if recent.len() == REPLAY_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        let _ = self.inner.sender.send(event.clone());
+        event.id
+    }
+
+    /// Subscribe, replaying what happened after `last_event_id`
+    pub fn subscribe(&self, filter: EventFilter, last_event_id: Option<u64>) -> Subscription {
+        let recent = self.inner.recent.lock().unwrap_or_else(|e| e.into_inner());
+        // Subscribe while holding the lock so nothing slips between replay and live
+        let receiver = self.inner.sender.subscribe();
+
+        let mut replay = VecDeque::new();
+        let mut last_id = recent.back().map_or(0, |event| event.id);
+        if let Some(after) = last_event_id {
+            let oldest = recent.front().map(|event| event.id);
+            let missed_some = match oldest {
+                Some(oldest) => after.saturating_add(1) < oldest,
+                // Nothing happened since the hub started
+                None => after < self.inner.next_id.load(Ordering::Relaxed).saturating_sub(1),
+            };
+            if missed_some {
+                replay.push_back(LiveEvent::resync(last_id));
+            }
+            replay.extend(
+                recent
+                    .iter()
+                    .filter(|event| event.id > after && filter.matches(event))
+                    .cloned(),
+            );
+            last_id = last_id.max(after);
+        }
+
+        Subscription {
+            filter,
+            replay,
+            receiver,
+            last_id,
+        }
+    }
+}
+
+/// A client's stream of events
+pub struct Subscription {
+    filter: EventFilter,
+    replay: VecDeque<LiveEvent>,
+    receiver: broadcast::Receiver<LiveEvent>,
+    last_id: u64,
+}
+
+impl Subscription {
+    /// Wait for the next event the subscriber may see; `None` once the hub is gone
+    pub async fn next(&mut self) -> Option<LiveEvent> {
+        if let Some(event) = self.replay.pop_front() {
+            return Some(event);
+        }
+
+        loop {
+            match self.receiver.recv().await {
+                // Skip events already replayed from the buffer
+                Ok(event) if event.id <= self.last_id => continue,
+                Ok(event) => {
+                    self.last_id = event.id;
+                    if self.filter.matches(&event) {
+                        return Some(event);
+                    }
+                }
+                // A slow client fell behind; tell it to reload rather than
+                // pretend nothing was lost
+                Err(RecvError::Lagged(_)) => return Some(LiveEvent::resync(self.last_id)),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::UserRole;
+    use serde_json::json;
+
+    fn requester(user_id: &str, role: UserRole) -> Requester {
+        Requester {
+            user_id: user_id.to_string(),
+            username: user_id.to_string(),
+            role,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_resume() {
+        let hub = EventHub::new();
+        let alice = requester("1", UserRole::User);
+        let admin = requester("2", UserRole::Admin);
+
+        let first = hub.publish(EventTopic::Download, "status", Some("1"), json!({"n": 1}));
+        hub.publish(EventTopic::Download, "status", Some("3"), json!({"n": 2}));
+        hub.publish(EventTopic::Scan, "scan_started", None, json!({"n": 3}));
+
+        // Resuming after the first event replays only what alice may see
+        let mut resumed = hub.subscribe(EventFilter::new(&alice, Vec::new()), Some(first));
+        let event = resumed.next().await.unwrap();
+        assert_eq!(event.kind, "scan_started");
+
+        let mut downloads =
+            hub.subscribe(EventFilter::new(&alice, vec![EventTopic::Download]), None);
+        let mut everything = hub.subscribe(EventFilter::new(&admin, Vec::new()), None);
+
+        hub.publish(EventTopic::Scan, "scan_finished", None, json!({}));
+        let live = hub.publish(EventTopic::Download, "progress", Some("1"), json!({}));
+
+        assert_eq!(downloads.next().await.unwrap().id, live);
+        assert_eq!(everything.next().await.unwrap().kind, "scan_finished");
+        assert_eq!(everything.next().await.unwrap().id, live);
+        assert_eq!(resumed.next().await.unwrap().kind, "scan_finished");
+    }
+
+    #[tokio::test]
+    async fn test_stale_last_event_id_requests_resync() {
+        let hub = EventHub::new();
+        let alice = requester("1", UserRole::User);
+        let first = hub.publish(EventTopic::Download, "status", Some("1"), json!({}));
+        for _ in 0..=REPLAY_CAPACITY {
+            hub.publish(EventTopic::Download, "progress", Some("1"), json!({}));
+        }
+
+        let mut subscription = hub.subscribe(EventFilter::new(&alice, Vec::new()), Some(first));
+        assert_eq!(subscription.next().await.unwrap().kind, "resync");
+        assert_eq!(subscription.next().await.unwrap().kind, "progress");
+
+        // An ID from before a restart is older than anything buffered
+        let mut stale = hub.subscribe(EventFilter::new(&alice, Vec::new()), Some(1));
+        assert_eq!(stale.next().await.unwrap().kind, "resync");
+    }
+}
@@ -0,0 +1,229 @@
+//! Download queue ordering for StepheyBot Music
+//!
+//! Queued requests start by priority tier and, within a tier, round-robin
+//! across users, so one person queuing fifty albums does not starve everyone
+//! else. A user's priority only orders their own requests (except that they
+//! may lower one below everyone's); an admin override moves a request ahead of
+//! or behind other users' requests.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::Duration;
+
+use crate::models::entities::{DownloadPriority, DownloadRequest};
+
+/// Assumed download time when size or speed is unknown
+pub const DEFAULT_DOWNLOAD_ESTIMATE: Duration = Duration::from_secs(30 * 60);
+
+/// Priority tier a request competes in across users
+pub fn tier(request: &DownloadRequest) -> DownloadPriority {
+    request
+        .priority_override
+        .unwrap_or_else(|| request.priority.min(DownloadPriority::Normal))
+}
+
+/// Remembers whose turn it is
+#[derive(Debug, Clone, Default)]
+pub struct FairQueue {
+    turns: u64,
+    last_served: HashMap<String, u64>,
+}
+
+impl FairQueue {
+    /// Index of the request to start next
+    pub fn next(&self, queue: &[DownloadRequest]) -> Option<usize> {
+        self.pick(queue, &vec![true; queue.len()])
+    }
+
+    /// Record that a user's request was started
+    pub fn served(&mut self, user_id: &str) {
+        self.turns += 1;
+        self.last_served.insert(user_id.to_string(), self.turns);
+    }
+
+    /// The order the whole queue would start in, as indices into it
+    pub fn order(&self, queue: &[DownloadRequest]) -> Vec<usize> {
+        let mut turns = self.clone();
+        let mut waiting = vec![true; queue.len()];
+        let mut order = Vec::with_capacity(queue.len());
+
+        while let Some(index) = turns.pick(queue, &waiting) {
+            waiting[index] = false;
+            turns.served(&queue[index].user_id);
+            order.push(index);
+        }
+        order
+    }
+
+    fn pick(&self, queue: &[DownloadRequest], waiting: &[bool]) -> Option<usize> {
+        let candidates = || {
+            queue
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| waiting[*index])
+        };
+        let top = candidates().map(|(_, request)| tier(request)).max()?;
+
+        // The user who was served longest ago, or who has waited longest
+        let user = candidates()
+            .filter(|(_, request)| tier(request) == top)
+            .min_by_key(|(_, request)| {
+                (
+                    self.last_served.get(&request.user_id).copied().unwrap_or(0),
+                    request.requested_at,
+                )
+            })
+            .map(|(_, request)| request.user_id.as_str())?;
+
+        // Then that user's most urgent request in the tier, oldest first
+        candidates()
+            .filter(|(_, request)| tier(request) == top && request.user_id == user)
+            .min_by_key(|(_, request)| {
+                (Reverse(request.effective_priority()), request.requested_at)
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+/// Where a queued request stands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueEstimate {
+    pub request_id: String,
+    pub user_id: String,
+    /// 1 is next to start
+    pub position: usize,
+    pub estimated_start: DateTime<Utc>,
+}
+
+/// Estimate when each queued request starts
+///
+/// `busy_until` holds when each running download is expected to finish;
+/// queued downloads take their size over `average_speed` (bytes/s).
+pub fn estimate_starts(
+    queue: &[DownloadRequest],
+    order: &[usize],
+    slots: usize,
+    busy_until: &[DateTime<Utc>],
+    average_speed: Option<u64>,
+    now: DateTime<Utc>,
+) -> Vec<QueueEstimate> {
+    let slots = slots.max(1);
+    let mut running: BinaryHeap<Reverse<DateTime<Utc>>> = busy_until
+        .iter()
+        .map(|at| Reverse((*at).max(now)))
+        .collect();
+
+    order
+        .iter()
+        .enumerate()
+        .map(|(position, &index)| {
+            let request = &queue[index];
+            let mut start = now;
+            while running.len() >= slots {
+                match running.pop() {
+                    Some(Reverse(free_at)) => start = start.max(free_at),
+                    None => break,
+                }
+            }
+
+            let duration = match (request.file_size, average_speed) {
+                (Some(size), Some(speed)) if speed > 0 => Duration::from_secs(size / speed),
+                _ => DEFAULT_DOWNLOAD_ESTIMATE,
+            };
+            let duration =
+                ChronoDuration::from_std(duration).unwrap_or_else(|_| ChronoDuration::days(1));
+            running.push(Reverse(start + duration));
+
+            QueueEstimate {
+                request_id: request.id.clone(),
+                user_id: request.user_id.clone(),
+                position: position + 1,
+                estimated_start: start,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(user: &str, title: &str, minutes_ago: i64) -> DownloadRequest {
+        let mut request =
+            DownloadRequest::new(user.to_string(), "Artist".to_string(), title.to_string());
+        request.requested_at = Utc::now() - ChronoDuration::minutes(minutes_ago);
+        request
+    }
+
+    fn titles(queue: &[DownloadRequest], order: &[usize]) -> Vec<String> {
+        order
+            .iter()
+            .map(|&index| queue[index].track_title.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin_and_priorities() {
+        let mut queue = vec![
+            request("alice", "a1", 60),
+            request("alice", "a2", 59),
+            request("alice", "a3", 58),
+            request("bob", "b1", 30),
+            request("carol", "c1", 10),
+        ];
+        let fair = FairQueue::default();
+        assert_eq!(
+            titles(&queue, &fair.order(&queue)),
+            ["a1", "b1", "c1", "a2", "a3"]
+        );
+
+        // A user's own priority reorders only their requests
+        queue[2].priority = DownloadPriority::High;
+        assert_eq!(
+            titles(&queue, &fair.order(&queue)),
+            ["a3", "b1", "c1", "a1", "a2"]
+        );
+
+        // Low priority yields to everyone, an admin override jumps the queue
+        queue[3].priority = DownloadPriority::Low;
+        queue[4].priority_override = Some(DownloadPriority::High);
+        assert_eq!(
+            titles(&queue, &fair.order(&queue)),
+            ["c1", "a3", "a1", "a2", "b1"]
+        );
+
+        // Whoever was just served waits for the others
+        let mut fair = FairQueue::default();
+        fair.served("carol");
+        fair.served("alice");
+        queue[3].priority = DownloadPriority::Normal;
+        queue[4].priority_override = None;
+        assert_eq!(fair.next(&queue), Some(3));
+    }
+
+    #[test]
+    fn test_estimate_starts() {
+        let now = Utc::now();
+        let mut queue = vec![request("alice", "a1", 5), request("bob", "b1", 4)];
+        queue[0].file_size = Some(600_000);
+        let order = FairQueue::default().order(&queue);
+
+        // One free slot, one busy for ten more minutes
+        let busy = [now + ChronoDuration::minutes(10)];
+        let estimates = estimate_starts(&queue, &order, 2, &busy, Some(1000), now);
+        assert_eq!(estimates[0].position, 1);
+        assert_eq!(estimates[0].estimated_start, now);
+        assert_eq!(
+            estimates[1].estimated_start,
+            now + ChronoDuration::minutes(10)
+        );
+
+        let unknown_speed = estimate_starts(&queue, &order, 1, &[], None, now);
+        assert_eq!(
+            unknown_speed[1].estimated_start,
+            now + ChronoDuration::minutes(30)
+        );
+    }
+}
@@ -0,0 +1,1038 @@
+//! Import pipeline for StepheyBot Music
+//!
+//! Completed downloads go through staged verification, matching, tagging and
+//! organization before anything is moved into the library. Files only become
+//! visible in the library once every file of the release has been staged, so
+//! a failed import never leaves a half-copied album behind.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+use crate::models::entities::DownloadRequest;
+use crate::utils::{is_audio_file, normalize_music_name, sanitize_filename};
+
+/// Default library layout, relative to the library root
+pub const DEFAULT_LIBRARY_TEMPLATE: &str = "{artist}/{album}/{track} - {title}";
+
+/// Fraction of the requested words that must be found for a release to match
+const MATCH_THRESHOLD: f64 = 0.6;
+
+/// Stage of the import pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStage {
+    Verify,
+    Match,
+    Tag,
+    Organize,
+}
+
+impl ImportStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportStage::Verify => "verify",
+            ImportStage::Match => "match",
+            ImportStage::Tag => "tag",
+            ImportStage::Organize => "organize",
+        }
+    }
+}
+
+impl std::fmt::Display for ImportStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ImportStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "verify" => Ok(ImportStage::Verify),
+            "match" => Ok(ImportStage::Match),
+            "tag" => Ok(ImportStage::Tag),
+            "organize" => Ok(ImportStage::Organize),
+            other => Err(format!("Unknown import stage: {}", other)),
+        }
+    }
+}
+
+/// An import that stopped at one of the pipeline stages
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{stage} failed: {message}")]
+pub struct ImportError {
+    pub stage: ImportStage,
+    pub message: String,
+}
+
+impl ImportError {
+    fn new(stage: ImportStage, message: impl Into<String>) -> Self {
+        Self {
+            stage,
+            message: message.into(),
+        }
+    }
+}
+
+/// Import pipeline configuration
+#[derive(Debug, Clone)]
+pub struct ImportConfig {
+    /// Scratch space where releases are staged and tagged
+    pub processing_path: PathBuf,
+    /// Root of the music library
+    pub library_path: PathBuf,
+    /// Layout of imported files, see [`DEFAULT_LIBRARY_TEMPLATE`]
+    pub template: String,
+}
+
+/// Per-import overrides
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Import even if the release does not look like what was requested
+    pub skip_match: bool,
+}
+
+/// Tags already present in a file
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TagInfo {
+    artist: Option<String>,
+    album: Option<String>,
+    title: Option<String>,
+    track: Option<u32>,
+}
+
+/// An audio file that passed verification
+#[derive(Debug, Clone)]
+pub struct VerifiedFile {
+    pub path: PathBuf,
+    pub extension: String,
+    tags: TagInfo,
+}
+
+/// A downloaded release whose audio files passed verification
+#[derive(Debug, Clone)]
+pub struct VerifiedRelease {
+    pub source: PathBuf,
+    pub files: Vec<VerifiedFile>,
+    /// Non-audio files that will not be imported
+    pub skipped: Vec<PathBuf>,
+}
+
+/// A file that was moved into the library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedFile {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub artist: String,
+    pub album: String,
+    pub title: String,
+    pub track_number: u32,
+}
+
+/// Result of a successful import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportOutcome {
+    pub files: Vec<ImportedFile>,
+    pub skipped: Vec<PathBuf>,
+    /// Index of the file that best matches the requested track
+    pub primary: usize,
+}
+
+impl ImportOutcome {
+    /// The imported file that corresponds to the requested track
+    pub fn primary_file(&self) -> &ImportedFile {
+        &self.files[self.primary]
+    }
+}
+
+/// Staged import of completed downloads into the library
+#[derive(Debug, Clone)]
+pub struct ImportPipeline {
+    config: ImportConfig,
+}
+
+impl ImportPipeline {
+    /// Create a new import pipeline
+    pub fn new(config: ImportConfig) -> Self {
+        Self { config }
+    }
+
+    /// Verify the integrity of a download and separate audio from other files
+    pub async fn verify(&self, source: &Path) -> Result<VerifiedRelease, ImportError> {
+        let source = source.to_path_buf();
+        tokio::task::spawn_blocking(move || verify_release(&source))
+            .await
+            .map_err(|e| ImportError::new(ImportStage::Verify, e.to_string()))?
+    }
+
+    /// Match, tag and organize a verified release into the library
+    pub async fn import(
+        &self,
+        request: &DownloadRequest,
+        release: VerifiedRelease,
+        options: ImportOptions,
+    ) -> Result<ImportOutcome, ImportError> {
+        let config = self.config.clone();
+        let request = request.clone();
+        tokio::task::spawn_blocking(move || import_release(&config, &request, release, options))
+            .await
+            .map_err(|e| ImportError::new(ImportStage::Organize, e.to_string()))?
+    }
+}
+
+fn verify_release(source: &Path) -> Result<VerifiedRelease, ImportError> {
+    if !source.exists() {
+        return Err(ImportError::new(
+            ImportStage::Verify,
+            format!("Download not found at {}", source.display()),
+        ));
+    }
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+
+    for entry in WalkDir::new(source).sort_by_file_name() {
+        let entry = entry.map_err(|e| ImportError::new(ImportStage::Verify, e.to_string()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+
+        if name.ends_with(".part") {
+            return Err(ImportError::new(
+                ImportStage::Verify,
+                format!("{} is still incomplete", name),
+            ));
+        }
+
+        if !is_audio_file(path) {
+            debug!("Skipping non-audio file: {}", path.display());
+            skipped.push(path.to_path_buf());
+            continue;
+        }
+
+        let extension = extension_of(path);
+        verify_audio_file(path, &extension).map_err(|reason| {
+            ImportError::new(ImportStage::Verify, format!("{}: {}", name, reason))
+        })?;
+
+        files.push(VerifiedFile {
+            path: path.to_path_buf(),
+            tags: read_tags(path, &extension),
+            extension,
+        });
+    }
+
+    if files.is_empty() {
+        return Err(ImportError::new(
+            ImportStage::Verify,
+            "No audio files found in download",
+        ));
+    }
+
+    Ok(VerifiedRelease {
+        source: source.to_path_buf(),
+        files,
+        skipped,
+    })
+}
+
+/// Check that a file is non-empty and starts like the format its extension claims
+fn verify_audio_file(path: &Path, extension: &str) -> Result<(), String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut header = Vec::with_capacity(12);
+    file.take(12)
+        .read_to_end(&mut header)
+        .map_err(|e| e.to_string())?;
+
+    if header.is_empty() {
+        return Err("file is empty".to_string());
+    }
+
+    let mpeg_sync = header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0;
+    let mp4 = header.len() >= 8 && &header[4..8] == b"ftyp";
+
+    let valid = match extension {
+        "flac" => header.starts_with(b"fLaC"),
+        "mp3" => header.starts_with(b"ID3") || mpeg_sync,
+        "aac" => header.starts_with(b"ID3") || mpeg_sync || mp4,
+        "m4a" => mp4,
+        "ogg" | "opus" => header.starts_with(b"OggS"),
+        "wav" => header.starts_with(b"RIFF") && header.len() >= 12 && &header[8..12] == b"WAVE",
+        "aiff" => header.starts_with(b"FORM"),
+        "ape" => header.starts_with(b"MAC "),
+        "wma" => header.starts_with(&[0x30, 0x26, 0xB2, 0x75]),
+        _ => true,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("contents are not a valid {} file", extension))
+    }
+}
+
+/// A file of the release and where it will go
+#[derive(Debug, Clone)]
+struct TrackPlan {
+    source: PathBuf,
+    extension: String,
+    album: String,
+    title: String,
+    track_number: u32,
+}
+
+fn import_release(
+    config: &ImportConfig,
+    request: &DownloadRequest,
+    release: VerifiedRelease,
+    options: ImportOptions,
+) -> Result<ImportOutcome, ImportError> {
+    let plans = plan_tracks(request, &release);
+    let (selected, primary) = select_tracks(request, &release, &plans, options)?;
+    let plans: Vec<TrackPlan> = selected.iter().map(|&i| plans[i].clone()).collect();
+    let primary = selected.iter().position(|&i| i == primary).unwrap_or(0);
+
+    let staging = config.processing_path.join(&request.id);
+    let result = stage_and_place(config, request, &plans, &staging);
+
+    if staging.exists() {
+        if let Err(e) = fs::remove_dir_all(&staging) {
+            warn!(
+                "Failed to clean up staging area {}: {}",
+                staging.display(),
+                e
+            );
+        }
+    }
+
+    let files = result?;
+    info!(
+        "Imported {} files for {} ({} non-audio files skipped)",
+        files.len(),
+        request.full_description(),
+        release.skipped.len()
+    );
+
+    Ok(ImportOutcome {
+        files,
+        skipped: release.skipped,
+        primary,
+    })
+}
+
+/// Work out title, track number and album of every audio file
+fn plan_tracks(request: &DownloadRequest, release: &VerifiedRelease) -> Vec<TrackPlan> {
+    release
+        .files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let stem = file
+                .path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Unknown");
+            let (number, name) = split_track_prefix(stem);
+
+            TrackPlan {
+                source: file.path.clone(),
+                extension: file.extension.clone(),
+                album: request
+                    .album_title
+                    .clone()
+                    .or_else(|| file.tags.album.clone())
+                    .unwrap_or_else(|| "Unknown Album".to_string()),
+                title: file
+                    .tags
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| strip_artist_prefix(name, &request.artist_name)),
+                track_number: file.tags.track.or(number).unwrap_or(index as u32 + 1),
+            }
+        })
+        .collect()
+}
+
+/// Check the release against the request and choose which files to import
+///
+/// Returns the indices of the files to import and the index of the file that
+/// corresponds to the requested track.
+fn select_tracks(
+    request: &DownloadRequest,
+    release: &VerifiedRelease,
+    plans: &[TrackPlan],
+    options: ImportOptions,
+) -> Result<(Vec<usize>, usize), ImportError> {
+    let release_name = release
+        .source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    if !options.skip_match {
+        let mut haystack = release_name.clone();
+        for file in &release.files {
+            if let Ok(relative) = file.path.strip_prefix(&release.source) {
+                haystack.push(' ');
+                haystack.push_str(&relative.to_string_lossy());
+            }
+            for tag in [&file.tags.artist, &file.tags.album].into_iter().flatten() {
+                haystack.push(' ');
+                haystack.push_str(tag);
+            }
+        }
+
+        if similarity(&request.artist_name, &haystack) < MATCH_THRESHOLD {
+            return Err(ImportError::new(
+                ImportStage::Match,
+                format!(
+                    "Release '{}' does not match artist '{}'",
+                    release_name, request.artist_name
+                ),
+            ));
+        }
+
+        if let Some(album) = &request.album_title {
+            if similarity(album, &haystack) < MATCH_THRESHOLD {
+                return Err(ImportError::new(
+                    ImportStage::Match,
+                    format!(
+                        "Release '{}' does not match album '{}'",
+                        release_name, album
+                    ),
+                ));
+            }
+        }
+    }
+
+    let (best, best_score) = plans
+        .iter()
+        .enumerate()
+        .map(|(index, plan)| {
+            let stem = plan
+                .source
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            let text = format!("{} {}", plan.title, stem);
+            (index, similarity(&request.track_title, &text))
+        })
+        .fold(
+            (0, -1.0),
+            |best, current| {
+                if current.1 > best.1 {
+                    current
+                } else {
+                    best
+                }
+            },
+        );
+
+    if request.album_title.is_some() {
+        // Whole album: import everything, point at the requested track if present
+        let primary = if best_score >= MATCH_THRESHOLD {
+            best
+        } else {
+            0
+        };
+        return Ok(((0..plans.len()).collect(), primary));
+    }
+
+    if plans.len() == 1 || best_score >= MATCH_THRESHOLD || options.skip_match {
+        Ok((vec![best], best))
+    } else {
+        Err(ImportError::new(
+            ImportStage::Match,
+            format!(
+                "No file in '{}' matches track '{}'",
+                release_name, request.track_title
+            ),
+        ))
+    }
+}
+
+/// Copy files into the staging area, tag them and move them into the library
+fn stage_and_place(
+    config: &ImportConfig,
+    request: &DownloadRequest,
+    plans: &[TrackPlan],
+    staging: &Path,
+) -> Result<Vec<ImportedFile>, ImportError> {
+    let tag_error = |e: std::io::Error| ImportError::new(ImportStage::Tag, e.to_string());
+
+    if staging.exists() {
+        fs::remove_dir_all(staging).map_err(tag_error)?;
+    }
+    fs::create_dir_all(staging).map_err(tag_error)?;
+
+    let mut staged = Vec::with_capacity(plans.len());
+    for (index, plan) in plans.iter().enumerate() {
+        let staged_path = staging.join(format!("{:03}.{}", index, plan.extension));
+        fs::copy(&plan.source, &staged_path).map_err(tag_error)?;
+
+        let tags = TagInfo {
+            artist: Some(request.artist_name.clone()),
+            album: Some(plan.album.clone()),
+            title: Some(plan.title.clone()),
+            track: Some(plan.track_number),
+        };
+        write_tags(&staged_path, &plan.extension, &tags).map_err(|reason| {
+            ImportError::new(
+                ImportStage::Tag,
+                format!("{}: {}", plan.source.display(), reason),
+            )
+        })?;
+
+        staged.push(staged_path);
+    }
+
+    // Resolve every destination before touching the library
+    let mut destinations = Vec::with_capacity(plans.len());
+    let mut seen = HashSet::new();
+    for plan in plans {
+        let relative = render_template(
+            &config.template,
+            &request.artist_name,
+            &plan.album,
+            &plan.title,
+            plan.track_number,
+        );
+        let destination =
+            config
+                .library_path
+                .join(format!("{}.{}", relative.display(), plan.extension));
+
+        if destination.exists() {
+            return Err(ImportError::new(
+                ImportStage::Organize,
+                format!("{} already exists in the library", destination.display()),
+            ));
+        }
+        if !seen.insert(destination.clone()) {
+            return Err(ImportError::new(
+                ImportStage::Organize,
+                format!(
+                    "Several files would be imported as {}",
+                    destination.display()
+                ),
+            ));
+        }
+        destinations.push(destination);
+    }
+
+    let mut placed: Vec<PathBuf> = Vec::with_capacity(plans.len());
+    for (staged_path, destination) in staged.iter().zip(&destinations) {
+        if let Err(e) = place_file(staged_path, destination) {
+            // Roll back so the library never holds a partial release
+            for path in &placed {
+                if let Err(e) = fs::remove_file(path) {
+                    warn!("Failed to roll back {}: {}", path.display(), e);
+                }
+            }
+            return Err(ImportError::new(
+                ImportStage::Organize,
+                format!("Failed to move into {}: {}", destination.display(), e),
+            ));
+        }
+        placed.push(destination.clone());
+    }
+
+    Ok(plans
+        .iter()
+        .zip(destinations)
+        .map(|(plan, destination)| ImportedFile {
+            source: plan.source.clone(),
+            destination,
+            artist: request.artist_name.clone(),
+            album: plan.album.clone(),
+            title: plan.title.clone(),
+            track_number: plan.track_number,
+        })
+        .collect())
+}
+
+/// Copy a file next to its destination under a hidden name, then rename it
+/// into place so it appears in the library atomically
+fn place_file(staged: &Path, destination: &Path) -> std::io::Result<()> {
+    let parent = destination.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(parent)?;
+
+    let file_name = destination
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("import");
+    let partial = parent.join(format!(".{}.partial", file_name));
+
+    let result = fs::copy(staged, &partial).and_then(|_| fs::rename(&partial, destination));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Render the library template into a relative path
+fn render_template(template: &str, artist: &str, album: &str, title: &str, track: u32) -> PathBuf {
+    let rendered = template
+        .replace("{artist}", &sanitize_filename(artist))
+        .replace("{album}", &sanitize_filename(album))
+        .replace("{title}", &sanitize_filename(title))
+        .replace("{track}", &format!("{:02}", track));
+
+    // Never let metadata escape the library root
+    Path::new(&rendered)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_os_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Split a leading track number off a file name, e.g. "03 - Title"
+fn split_track_prefix(stem: &str) -> (Option<u32>, &str) {
+    let digits: String = stem.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() || digits.len() > 3 {
+        return (None, stem);
+    }
+
+    let rest = stem[digits.len()..].trim_start_matches(|c: char| {
+        c.is_whitespace() || c == '.' || c == '-' || c == '_' || c == ')'
+    });
+    if rest.is_empty() {
+        return (None, stem);
+    }
+
+    (digits.parse().ok(), rest)
+}
+
+/// Drop a leading "Artist - " from a file name
+fn strip_artist_prefix(name: &str, artist: &str) -> String {
+    if let Some((prefix, rest)) = name.split_once(" - ") {
+        if normalize_music_name(prefix) == normalize_music_name(artist) && !rest.trim().is_empty() {
+            return rest.trim().to_string();
+        }
+    }
+    name.trim().to_string()
+}
+
+/// Fraction of the words of `needle` that appear in `haystack`
+fn similarity(needle: &str, haystack: &str) -> f64 {
+    let needle = normalize_music_name(needle);
+    let wanted: Vec<&str> = needle.split_whitespace().collect();
+    if wanted.is_empty() {
+        return 1.0;
+    }
+
+    let haystack = normalize_music_name(haystack);
+    let available: HashSet<&str> = haystack.split_whitespace().collect();
+    let found = wanted.iter().filter(|w| available.contains(*w)).count();
+    found as f64 / wanted.len() as f64
+}
+
+fn extension_of(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default()
+}
+
+fn read_tags(path: &Path, extension: &str) -> TagInfo {
+    match extension {
+        "mp3" => match id3::Tag::read_from_path(path) {
+            Ok(tag) => {
+                use id3::TagLike;
+                TagInfo {
+                    artist: tag.artist().map(str::to_string),
+                    album: tag.album().map(str::to_string),
+                    title: tag.title().map(str::to_string),
+                    track: tag.track(),
+                }
+            }
+            Err(_) => TagInfo::default(),
+        },
+        "flac" => fs::read(path)
+            .ok()
+            .and_then(|bytes| flac::read_comments(&bytes).ok())
+            .map(|comments| {
+                let get = |key: &str| {
+                    comments
+                        .iter()
+                        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                        .map(|(_, v)| v.clone())
+                };
+                TagInfo {
+                    artist: get("ARTIST"),
+                    album: get("ALBUM"),
+                    title: get("TITLE"),
+                    track: get("TRACKNUMBER")
+                        .and_then(|n| n.split('/').next().and_then(|n| n.trim().parse().ok())),
+                }
+            })
+            .unwrap_or_default(),
+        _ => TagInfo::default(),
+    }
+}
+
+fn write_tags(path: &Path, extension: &str, tags: &TagInfo) -> Result<(), String> {
+    match extension {
+        "mp3" => {
+            use id3::TagLike;
+            let mut tag = id3::Tag::read_from_path(path).unwrap_or_else(|_| id3::Tag::new());
+            if let Some(artist) = &tags.artist {
+                tag.set_artist(artist.as_str());
+            }
+            if let Some(album) = &tags.album {
+                tag.set_album(album.as_str());
+            }
+            if let Some(title) = &tags.title {
+                tag.set_title(title.as_str());
+            }
+            if let Some(track) = tags.track {
+                tag.set_track(track);
+            }
+            tag.write_to_path(path, id3::Version::Id3v24)
+                .map_err(|e| e.to_string())
+        }
+        "flac" => {
+            let mut comments = Vec::new();
+            for (key, value) in [
+                ("ARTIST", &tags.artist),
+                ("ALBUM", &tags.album),
+                ("TITLE", &tags.title),
+            ] {
+                if let Some(value) = value {
+                    comments.push((key.to_string(), value.clone()));
+                }
+            }
+            if let Some(track) = tags.track {
+                comments.push(("TRACKNUMBER".to_string(), track.to_string()));
+            }
+            flac::write_comments(path, &comments)
+        }
+        _ => {
+            debug!(
+                "Tagging {} files is not supported, leaving tags as-is",
+                extension
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Minimal FLAC metadata handling for Vorbis comments
+mod flac {
+    use std::fs;
+    use std::path::Path;
+
+    const STREAMINFO: u8 = 0;
+    const VORBIS_COMMENT: u8 = 4;
+
+    struct Block {
+        kind: u8,
+        data: Vec<u8>,
+    }
+
+    /// Split a FLAC file into its metadata blocks and the offset of the audio frames
+    fn parse(bytes: &[u8]) -> Result<(Vec<Block>, usize), String> {
+        if !bytes.starts_with(b"fLaC") {
+            return Err("missing FLAC signature".to_string());
+        }
+
+        let mut blocks = Vec::new();
+        let mut pos = 4;
+        loop {
+            let header = bytes
+                .get(pos..pos + 4)
+                .ok_or_else(|| "truncated metadata block header".to_string())?;
+            let last = header[0] & 0x80 != 0;
+            let kind = header[0] & 0x7F;
+            let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            pos += 4;
+
+            let data = bytes
+                .get(pos..pos + length)
+                .ok_or_else(|| "truncated metadata block".to_string())?;
+            blocks.push(Block {
+                kind,
+                data: data.to_vec(),
+            });
+            pos += length;
+
+            if last {
+                break;
+            }
+        }
+
+        if blocks.first().map(|b| b.kind) != Some(STREAMINFO) {
+            return Err("first metadata block is not STREAMINFO".to_string());
+        }
+
+        Ok((blocks, pos))
+    }
+
+    fn decode_comments(data: &[u8]) -> Result<(String, Vec<(String, String)>), String> {
+        let mut pos = 0;
+        let read_u32 = |pos: &mut usize| -> Result<usize, String> {
+            let raw = data
+                .get(*pos..*pos + 4)
+                .ok_or_else(|| "truncated vorbis comment".to_string())?;
+            *pos += 4;
+            Ok(u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as usize)
+        };
+        let read_str = |pos: &mut usize, len: usize| -> Result<String, String> {
+            let raw = data
+                .get(*pos..*pos + len)
+                .ok_or_else(|| "truncated vorbis comment".to_string())?;
+            *pos += len;
+            Ok(String::from_utf8_lossy(raw).into_owned())
+        };
+
+        let vendor_len = read_u32(&mut pos)?;
+        let vendor = read_str(&mut pos, vendor_len)?;
+        let count = read_u32(&mut pos)?;
+
+        let mut comments = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let len = read_u32(&mut pos)?;
+            let entry = read_str(&mut pos, len)?;
+            if let Some((key, value)) = entry.split_once('=') {
+                comments.push((key.to_string(), value.to_string()));
+            }
+        }
+
+        Ok((vendor, comments))
+    }
+
+    fn encode_comments(vendor: &str, comments: &[(String, String)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        data.extend_from_slice(vendor.as_bytes());
+        data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for (key, value) in comments {
+            let entry = format!("{}={}", key, value);
+            data.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+            data.extend_from_slice(entry.as_bytes());
+        }
+        data
+    }
+
+    /// Read the Vorbis comments of a FLAC file
+    pub fn read_comments(bytes: &[u8]) -> Result<Vec<(String, String)>, String> {
+        let (blocks, _) = parse(bytes)?;
+        match blocks.iter().find(|b| b.kind == VORBIS_COMMENT) {
+            Some(block) => Ok(decode_comments(&block.data)?.1),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Set Vorbis comments, replacing existing values for the same keys
+    pub fn write_comments(path: &Path, updates: &[(String, String)]) -> Result<(), String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let (blocks, audio_start) = parse(&bytes)?;
+
+        let (vendor, mut comments) = match blocks.iter().find(|b| b.kind == VORBIS_COMMENT) {
+            Some(block) => decode_comments(&block.data)?,
+            None => ("StepheyBot-Music".to_string(), Vec::new()),
+        };
+        comments.retain(|(key, _)| !updates.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)));
+        comments.extend(updates.iter().cloned());
+
+        let comment_block = Block {
+            kind: VORBIS_COMMENT,
+            data: encode_comments(&vendor, &comments),
+        };
+        if comment_block.data.len() >= 1 << 24 {
+            return Err("vorbis comment block too large".to_string());
+        }
+
+        let mut ordered: Vec<&Block> = Vec::with_capacity(blocks.len() + 1);
+        ordered.push(&blocks[0]);
+        ordered.push(&comment_block);
+        ordered.extend(blocks[1..].iter().filter(|b| b.kind != VORBIS_COMMENT));
+
+        let mut output = Vec::with_capacity(bytes.len() + comment_block.data.len());
+        output.extend_from_slice(b"fLaC");
+        for (index, block) in ordered.iter().enumerate() {
+            let last = if index == ordered.len() - 1 { 0x80 } else { 0 };
+            let length = (block.data.len() as u32).to_be_bytes();
+            output.push(last | block.kind);
+            output.extend_from_slice(&length[1..]);
+            output.extend_from_slice(&block.data);
+        }
+        output.extend_from_slice(&bytes[audio_start..]);
+
+        let temp = path.with_extension("flac.tmp");
+        fs::write(&temp, &output).map_err(|e| e.to_string())?;
+        fs::rename(&temp, path).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MP3_FRAME: &[u8] = &[0xFF, 0xFB, 0x90, 0x64, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    fn flac_bytes() -> Vec<u8> {
+        let mut bytes = b"fLaC".to_vec();
+        bytes.extend_from_slice(&[0x80, 0, 0, 34]);
+        bytes.extend_from_slice(&[0u8; 34]);
+        bytes.extend_from_slice(&[0xFF, 0xF8, 1, 2, 3, 4]);
+        bytes
+    }
+
+    fn pipeline(root: &Path) -> ImportPipeline {
+        ImportPipeline::new(ImportConfig {
+            processing_path: root.join("processing"),
+            library_path: root.join("library"),
+            template: DEFAULT_LIBRARY_TEMPLATE.to_string(),
+        })
+    }
+
+    fn album_request() -> DownloadRequest {
+        let mut request = DownloadRequest::new(
+            "alice".to_string(),
+            "Boards of Canada".to_string(),
+            "Roygbiv".to_string(),
+        );
+        request.album_title = Some("Music Has the Right to Children".to_string());
+        request
+    }
+
+    #[test]
+    fn test_render_template() {
+        let path = render_template(
+            DEFAULT_LIBRARY_TEMPLATE,
+            "AC/DC",
+            "Back in Black",
+            "Hells Bells",
+            1,
+        );
+        assert_eq!(path, PathBuf::from("AC_DC/Back in Black/01 - Hells Bells"));
+
+        let path = render_template("{artist}/{title}", "..", "x", "y", 1);
+        assert_eq!(path, PathBuf::from("y"));
+    }
+
+    #[test]
+    fn test_split_track_prefix() {
+        assert_eq!(split_track_prefix("03 - Roygbiv"), (Some(3), "Roygbiv"));
+        assert_eq!(split_track_prefix("1. Intro"), (Some(1), "Intro"));
+        assert_eq!(split_track_prefix("1979"), (None, "1979"));
+        assert_eq!(
+            split_track_prefix("Wildlife Analysis"),
+            (None, "Wildlife Analysis")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_album() {
+        let root = TempDir::new().unwrap();
+        let source = root
+            .path()
+            .join("Boards of Canada - Music Has the Right to Children (1998) [FLAC]");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("01 - Wildlife Analysis.flac"), flac_bytes()).unwrap();
+        fs::write(
+            source.join("02 - Boards of Canada - Roygbiv.mp3"),
+            MP3_FRAME,
+        )
+        .unwrap();
+        fs::write(source.join("cover.jpg"), b"jpeg").unwrap();
+        fs::write(source.join("info.nfo"), b"nfo").unwrap();
+
+        let pipeline = pipeline(root.path());
+        let release = pipeline.verify(&source).await.unwrap();
+        assert_eq!(release.files.len(), 2);
+        assert_eq!(release.skipped.len(), 2);
+
+        let request = album_request();
+        let outcome = pipeline
+            .import(&request, release, ImportOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.files.len(), 2);
+        let primary = outcome.primary_file();
+        assert_eq!(primary.title, "Roygbiv");
+        assert_eq!(
+            primary.destination,
+            root.path()
+                .join("library/Boards of Canada/Music Has the Right to Children/02 - Roygbiv.mp3")
+        );
+
+        let flac = &outcome.files[0].destination;
+        assert!(flac.ends_with("01 - Wildlife Analysis.flac"));
+        let tags = read_tags(flac, "flac");
+        assert_eq!(tags.artist.as_deref(), Some("Boards of Canada"));
+        assert_eq!(tags.track, Some(1));
+        // Audio frames survive the metadata rewrite
+        assert!(fs::read(flac).unwrap().ends_with(&[0xFF, 0xF8, 1, 2, 3, 4]));
+
+        let tags = read_tags(&primary.destination, "mp3");
+        assert_eq!(
+            tags.album.as_deref(),
+            Some("Music Has the Right to Children")
+        );
+        assert_eq!(tags.title.as_deref(), Some("Roygbiv"));
+
+        // The source is left for seeding and the staging area is cleaned up
+        assert!(source.join("cover.jpg").exists());
+        assert!(!root.path().join("processing").join(&request.id).exists());
+    }
+
+    #[tokio::test]
+    async fn test_import_failures_leave_library_untouched() {
+        let root = TempDir::new().unwrap();
+        let pipeline = pipeline(root.path());
+
+        // Corrupt audio is caught by verification
+        let corrupt = root.path().join("Boards of Canada - Corrupt");
+        fs::create_dir_all(&corrupt).unwrap();
+        fs::write(corrupt.join("01 - Track.flac"), b"not really flac").unwrap();
+        let err = pipeline.verify(&corrupt).await.unwrap_err();
+        assert_eq!(err.stage, ImportStage::Verify);
+
+        // A release by somebody else does not match
+        let other = root.path().join("Aphex Twin - Drukqs");
+        fs::create_dir_all(&other).unwrap();
+        fs::write(other.join("01 - Jynweythek.mp3"), MP3_FRAME).unwrap();
+        let release = pipeline.verify(&other).await.unwrap();
+        let err = pipeline
+            .import(&album_request(), release.clone(), ImportOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.stage, ImportStage::Match);
+
+        // A conflicting file in the library aborts before anything is moved
+        let conflict = root
+            .path()
+            .join("library/Boards of Canada/Music Has the Right to Children/01 - Jynweythek.mp3");
+        fs::create_dir_all(conflict.parent().unwrap()).unwrap();
+        fs::write(&conflict, b"existing").unwrap();
+        let err = pipeline
+            .import(
+                &album_request(),
+                release,
+                ImportOptions { skip_match: true },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.stage, ImportStage::Organize);
+        assert_eq!(fs::read(&conflict).unwrap(), b"existing");
+    }
+}
@@ -10,6 +10,7 @@ use tokio::fs;
 use tracing::{debug, error, info, warn};
 
 use crate::database::Database;
+use crate::services::recycle_bin::{RecycleBin, SYSTEM_ACTOR};
 use crate::services::{LibraryStats, Service};
 use crate::utils;
 
@@ -19,6 +20,8 @@ pub struct LibraryService {
     database: Arc<Database>,
     music_path: PathBuf,
     download_path: PathBuf,
+    /// Where replaced files and cleaned-up directories go instead of being deleted
+    recycle_bin: Option<RecycleBin>,
 }
 
 /// Library scan result
@@ -60,9 +63,16 @@ impl LibraryService {
             database,
             music_path,
             download_path,
+            recycle_bin: None,
         })
     }
 
+    /// Send replaced files and cleaned-up directories to the recycle bin
+    pub fn with_recycle_bin(mut self, recycle_bin: RecycleBin) -> Self {
+        self.recycle_bin = Some(recycle_bin);
+        self
+    }
+
     /// Scan the music library for new or changed files
     pub async fn scan_library(&self) -> Result<ScanResult> {
         let start_time = std::time::Instant::now();
@@ -280,6 +290,18 @@ impl LibraryService {
             utils::ensure_directory_exists(parent).await?;
         }
 
+        // Keep the file being replaced so a bad reorganize can be undone
+        if let (Some(bin), true) = (&self.recycle_bin, target_path.exists()) {
+            bin.trash(&target_path, "Replaced in library", SYSTEM_ACTOR)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Kept {} in place instead of replacing it",
+                        target_path.display()
+                    )
+                })?;
+        }
+
         // Move the file
         fs::rename(source_path, &target_path)
             .await
@@ -319,7 +341,7 @@ impl LibraryService {
         let mut stack = vec![self.music_path.clone()];
 
         while let Some(dir) = stack.pop() {
-            if !dir.exists() || dir == self.music_path {
+            if !dir.exists() {
                 continue;
             }
 
@@ -342,11 +364,18 @@ impl LibraryService {
                 stack.push(subdir);
             }
 
-            // Remove directory if it's empty
-            if !has_files {
+            // Remove directory if it's empty, but never the library root itself
+            if !has_files && dir != self.music_path {
                 if let Ok(mut check_entries) = fs::read_dir(&dir).await {
                     if check_entries.next_entry().await?.is_none() {
-                        if let Err(e) = fs::remove_dir(&dir).await {
+                        let removed = match &self.recycle_bin {
+                            Some(bin) => bin
+                                .trash(&dir, "Empty directory cleanup", SYSTEM_ACTOR)
+                                .await
+                                .map(|_| ()),
+                            None => fs::remove_dir(&dir).await.map_err(Into::into),
+                        };
+                        if let Err(e) = removed {
                             warn!("Failed to remove empty directory {}: {}", dir.display(), e);
                         } else {
                             debug!("Removed empty directory: {}", dir.display());
@@ -476,6 +505,68 @@ mod tests {
         assert_eq!(path, expected);
     }
 
+    #[tokio::test]
+    async fn test_replaced_files_and_empty_directories_go_to_the_trash() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(crate::database::Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let music_path = temp_dir.path().join("music");
+        let download_path = temp_dir.path().join("downloads");
+        tokio::fs::create_dir_all(music_path.join("Artist/Album"))
+            .await
+            .unwrap();
+        tokio::fs::create_dir_all(music_path.join("Empty/Album"))
+            .await
+            .unwrap();
+        tokio::fs::create_dir_all(&download_path).await.unwrap();
+        tokio::fs::write(music_path.join("Artist/Album/01.flac"), b"old")
+            .await
+            .unwrap();
+        tokio::fs::write(download_path.join("01.flac"), b"new")
+            .await
+            .unwrap();
+
+        let bin = RecycleBin::new(
+            crate::services::recycle_bin::RecycleBinConfig {
+                path: temp_dir.path().join("trash"),
+                ..Default::default()
+            },
+            database.clone(),
+        );
+        let service = LibraryService::new(
+            database,
+            music_path.to_str().unwrap(),
+            download_path.to_str().unwrap(),
+        )
+        .unwrap()
+        .with_recycle_bin(bin.clone());
+
+        service
+            .add_file_to_library(&download_path.join("01.flac"), "Artist/Album/01.flac")
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read(music_path.join("Artist/Album/01.flac"))
+                .await
+                .unwrap(),
+            b"new"
+        );
+
+        assert_eq!(service.cleanup_empty_directories().await.unwrap(), 1);
+        assert!(!music_path.join("Empty/Album").exists());
+
+        let items = bin.list(None, 10).await.unwrap();
+        assert_eq!(items.len(), 2);
+        let replaced = items
+            .iter()
+            .find(|item| !item.is_dir)
+            .expect("replaced file was trashed");
+        assert_eq!(tokio::fs::read(&replaced.trash_path).await.unwrap(), b"old");
+    }
+
     #[test]
     fn test_music_file_creation() {
         let temp_path = PathBuf::from("/tmp/test.mp3");
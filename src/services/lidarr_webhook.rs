@@ -0,0 +1,246 @@
+//! Lidarr webhook payloads for StepheyBot Music
+//!
+//! Lidarr's "Webhook" connection posts a JSON payload for each event. Only
+//! the fields StepheyBot acts on are modelled here; everything else is
+//! ignored so newer Lidarr versions keep working.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::entities::DownloadRequest;
+
+/// Prefix of `DownloadRequest::source_url` for downloads Lidarr manages
+pub const LIDARR_SOURCE_PREFIX: &str = "lidarr:";
+
+/// User that downloads grabbed by Lidarr are attributed to
+pub const LIDARR_USER_ID: &str = "lidarr";
+
+/// Whether a download request mirrors a download Lidarr manages
+pub fn is_lidarr_download(request: &DownloadRequest) -> bool {
+    request
+        .source_url
+        .as_deref()
+        .is_some_and(|url| url.starts_with(LIDARR_SOURCE_PREFIX))
+}
+
+/// Event kinds StepheyBot reacts to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LidarrEvent {
+    Test,
+    Grab,
+    ReleaseImport,
+    Upgrade,
+    Rename,
+    Delete,
+    Other(String),
+}
+
+impl LidarrEvent {
+    /// Whether the library on disk changed and needs re-indexing
+    pub fn changes_library(&self) -> bool {
+        matches!(
+            self,
+            LidarrEvent::ReleaseImport
+                | LidarrEvent::Upgrade
+                | LidarrEvent::Rename
+                | LidarrEvent::Delete
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookArtist {
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "mbId")]
+    pub mb_id: Option<String>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookAlbum {
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(rename = "mbId")]
+    pub mb_id: Option<String>,
+    pub release_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookRelease {
+    pub quality: Option<String>,
+    pub release_group: Option<String>,
+    pub release_title: Option<String>,
+    pub indexer: Option<String>,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookTrackFile {
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub path: String,
+    pub quality: Option<String>,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookRenamedTrackFile {
+    #[serde(default)]
+    pub previous_path: String,
+    #[serde(default)]
+    pub path: String,
+}
+
+/// Payload posted by Lidarr's webhook connection
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LidarrWebhookPayload {
+    pub event_type: String,
+    pub instance_name: Option<String>,
+    pub artist: Option<WebhookArtist>,
+    /// Set on album deletion events
+    pub album: Option<WebhookAlbum>,
+    #[serde(default)]
+    pub albums: Vec<WebhookAlbum>,
+    pub release: Option<WebhookRelease>,
+    #[serde(default)]
+    pub track_files: Vec<WebhookTrackFile>,
+    #[serde(default)]
+    pub renamed_track_files: Vec<WebhookRenamedTrackFile>,
+    #[serde(default)]
+    pub is_upgrade: bool,
+    pub download_client: Option<String>,
+    pub download_id: Option<String>,
+}
+
+impl LidarrWebhookPayload {
+    /// Classify the payload
+    pub fn event(&self) -> LidarrEvent {
+        match self.event_type.as_str() {
+            "Test" => LidarrEvent::Test,
+            "Grab" => LidarrEvent::Grab,
+            "Download" | "AlbumDownload" if self.is_upgrade => LidarrEvent::Upgrade,
+            "Download" | "AlbumDownload" => LidarrEvent::ReleaseImport,
+            "Rename" => LidarrEvent::Rename,
+            "ArtistDelete" | "AlbumDelete" | "TrackFileDelete" => LidarrEvent::Delete,
+            other => LidarrEvent::Other(other.to_string()),
+        }
+    }
+
+    pub fn artist_name(&self) -> &str {
+        self.artist
+            .as_ref()
+            .map(|artist| artist.name.as_str())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("Unknown Artist")
+    }
+
+    /// Title of the album the event is about, joining several if needed
+    pub fn album_title(&self) -> Option<String> {
+        let titles: Vec<&str> = self
+            .albums
+            .iter()
+            .chain(self.album.as_ref())
+            .map(|album| album.title.as_str())
+            .filter(|title| !title.is_empty())
+            .collect();
+
+        (!titles.is_empty()).then(|| titles.join(", "))
+    }
+
+    /// Value stored in `DownloadRequest::source_url` to find this download again
+    pub fn source_url(&self) -> Option<String> {
+        self.download_id
+            .as_deref()
+            .filter(|id| !id.is_empty())
+            .map(|id| format!("{}{}", LIDARR_SOURCE_PREFIX, id.to_lowercase()))
+    }
+
+    /// Total size of the imported files, falling back to the grabbed release
+    pub fn size(&self) -> Option<u64> {
+        let imported: u64 = self.track_files.iter().filter_map(|file| file.size).sum();
+        if imported > 0 {
+            Some(imported)
+        } else {
+            self.release.as_ref().and_then(|release| release.size)
+        }
+    }
+}
+
+/// Compare a presented webhook secret without leaking its length or content
+/// through timing
+pub fn verify_secret(expected: &str, provided: &str) -> bool {
+    let expected = expected.as_bytes();
+    let provided = provided.as_bytes();
+
+    let mut diff = expected.len() ^ provided.len();
+    for (index, byte) in expected.iter().enumerate() {
+        diff |= (byte ^ provided.get(index).copied().unwrap_or(0)) as usize;
+    }
+    diff == 0 && !expected.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_payloads() {
+        let grab: LidarrWebhookPayload = serde_json::from_str(
+            r#"{
+                "eventType": "Grab",
+                "artist": {"id": 1, "name": "Boards of Canada", "mbId": "69158f97", "path": "/music/Boards of Canada"},
+                "albums": [{"id": 2, "title": "Geogaddi", "releaseDate": "2002-02-18T00:00:00Z"}],
+                "release": {"quality": "FLAC", "releaseTitle": "Boards of Canada - Geogaddi (2002) [FLAC]", "indexer": "Prowlarr", "size": 400000000},
+                "downloadClient": "Transmission",
+                "downloadId": "ABCDEF0123"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(grab.event(), LidarrEvent::Grab);
+        assert_eq!(grab.artist_name(), "Boards of Canada");
+        assert_eq!(grab.album_title().as_deref(), Some("Geogaddi"));
+        assert_eq!(grab.source_url().as_deref(), Some("lidarr:abcdef0123"));
+        assert_eq!(grab.size(), Some(400000000));
+
+        let upgrade: LidarrWebhookPayload = serde_json::from_str(
+            r#"{
+                "eventType": "Download",
+                "isUpgrade": true,
+                "artist": {"name": "Boards of Canada"},
+                "albums": [{"title": "Geogaddi"}],
+                "trackFiles": [{"path": "/music/a.flac", "size": 10}, {"path": "/music/b.flac", "size": 5}],
+                "deletedFiles": [{"path": "/music/a.mp3"}]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(upgrade.event(), LidarrEvent::Upgrade);
+        assert!(upgrade.event().changes_library());
+        assert_eq!(upgrade.size(), Some(15));
+        assert_eq!(upgrade.source_url(), None);
+
+        let delete: LidarrWebhookPayload = serde_json::from_str(
+            r#"{"eventType": "AlbumDelete", "album": {"title": "Geogaddi"}, "deletedFiles": true}"#,
+        )
+        .unwrap();
+        assert_eq!(delete.event(), LidarrEvent::Delete);
+        assert_eq!(delete.album_title().as_deref(), Some("Geogaddi"));
+        assert_eq!(delete.artist_name(), "Unknown Artist");
+    }
+
+    #[test]
+    fn test_verify_secret() {
+        assert!(verify_secret("s3cret", "s3cret"));
+        assert!(!verify_secret("s3cret", "s3cre"));
+        assert!(!verify_secret("s3cret", "s3cret!"));
+        assert!(!verify_secret("", ""));
+    }
+}
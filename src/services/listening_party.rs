@@ -0,0 +1,897 @@
+//! Listening parties for StepheyBot Music
+//!
+//! A host opens a party and others join to hear the same queue at the same
+//! moment. The server owns the clock: each track is given the server time at
+//! which its position zero plays, members measure their offset from the server
+//! clock with pings, and anyone joining late is told where to seek. Members
+//! can vote to skip and, when the host allows it, add to the queue.
+//!
+//! Who may see and join a party follows the host's `PrivacyLevel`: public
+//! parties are open to everyone, friends-only parties to users who follow the
+//! host and are followed back, and private parties to those invited.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info};
+
+use crate::database::Database;
+use crate::models::user::{PrivacyLevel, UserProfile};
+use crate::services::play_queue::QueueTrack;
+
+/// How far ahead a track is scheduled so every member can buffer it first
+pub const START_LEAD_MS: i64 = 1500;
+
+/// Milliseconds since the Unix epoch on the server clock
+pub fn server_time() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PartyError {
+    #[error("Party not found")]
+    NotFound,
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+pub type PartyResult<T> = Result<T, PartyError>;
+
+/// A queued track and who added it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartyTrack {
+    #[serde(flatten)]
+    pub track: QueueTrack,
+    pub added_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartyMember {
+    pub user_id: String,
+    pub username: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+/// Where the party is in its current track
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Playback {
+    /// Position zero of the track plays at this server time
+    Playing {
+        started_at: i64,
+    },
+    Paused {
+        position_ms: u64,
+    },
+}
+
+/// What members should be playing: the track, and where to be in it at
+/// `start_at` on the server clock (`None` while paused)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cue {
+    pub index: usize,
+    pub track: PartyTrack,
+    pub position_ms: u64,
+    pub start_at: Option<i64>,
+}
+
+/// Host controls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PartyCommand {
+    Play,
+    Pause,
+    Seek { position_ms: u64 },
+    Next,
+}
+
+/// Body for opening a party
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateParty {
+    pub name: String,
+    #[serde(default)]
+    pub collaborative: bool,
+    /// User ids let in regardless of the host's privacy level
+    #[serde(default)]
+    pub invite: Vec<String>,
+}
+
+/// Body for inviting someone into a party
+#[derive(Debug, Clone, Deserialize)]
+pub struct InviteMember {
+    pub user_id: String,
+}
+
+/// Tally after a skip vote
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SkipVote {
+    pub votes: usize,
+    pub needed: usize,
+    pub skipped: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Party {
+    pub id: String,
+    pub name: String,
+    pub host_id: String,
+    pub host_name: String,
+    /// The host's privacy level when the party opened
+    pub privacy: PrivacyLevel,
+    /// Whether members other than the host may add tracks
+    pub collaborative: bool,
+    pub invited: HashSet<String>,
+    pub members: Vec<PartyMember>,
+    pub queue: Vec<PartyTrack>,
+    /// Index of the current track; `queue.len()` once the queue has run out
+    pub current: usize,
+    pub playback: Playback,
+    pub skip_votes: HashSet<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Party {
+    pub fn new(
+        name: &str,
+        host_id: &str,
+        host_name: &str,
+        privacy: PrivacyLevel,
+        collaborative: bool,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            host_id: host_id.to_string(),
+            host_name: host_name.to_string(),
+            privacy,
+            collaborative,
+            invited: HashSet::new(),
+            members: vec![PartyMember {
+                user_id: host_id.to_string(),
+                username: host_name.to_string(),
+                joined_at: now,
+            }],
+            queue: Vec::new(),
+            current: 0,
+            playback: Playback::Paused { position_ms: 0 },
+            skip_votes: HashSet::new(),
+            created_at: now,
+        }
+    }
+
+    pub fn is_member(&self, user_id: &str) -> bool {
+        self.members.iter().any(|member| member.user_id == user_id)
+    }
+
+    pub fn current_track(&self) -> Option<&PartyTrack> {
+        self.queue.get(self.current)
+    }
+
+    pub fn position_ms(&self, now: i64) -> u64 {
+        match self.playback {
+            Playback::Playing { started_at } => (now - started_at).max(0) as u64,
+            Playback::Paused { position_ms } => position_ms,
+        }
+    }
+
+    /// Where to be a lead time from now; what a member joining now should do
+    pub fn cue(&self, now: i64) -> Option<Cue> {
+        let track = self.current_track()?.clone();
+        let (position_ms, start_at) = match self.playback {
+            Playback::Playing { .. } => {
+                let start_at = now + START_LEAD_MS;
+                (self.position_ms(start_at), Some(start_at))
+            }
+            Playback::Paused { position_ms } => (position_ms, None),
+        };
+        Some(Cue {
+            index: self.current,
+            track,
+            position_ms,
+            start_at,
+        })
+    }
+
+    pub fn play(&mut self, now: i64) {
+        if self.current_track().is_none() {
+            return;
+        }
+        if let Playback::Paused { position_ms } = self.playback {
+            self.playback = Playback::Playing {
+                started_at: now + START_LEAD_MS - position_ms as i64,
+            };
+        }
+    }
+
+    pub fn pause(&mut self, now: i64) {
+        self.playback = Playback::Paused {
+            position_ms: self.position_ms(now),
+        };
+    }
+
+    pub fn seek(&mut self, position_ms: u64, now: i64) {
+        self.playback = match self.playback {
+            Playback::Playing { .. } => Playback::Playing {
+                started_at: now + START_LEAD_MS - position_ms as i64,
+            },
+            Playback::Paused { .. } => Playback::Paused { position_ms },
+        };
+    }
+
+    /// Move to the next track, keeping on playing if the party was
+    pub fn next(&mut self, now: i64) {
+        let playing = matches!(self.playback, Playback::Playing { .. });
+        self.current = (self.current + 1).min(self.queue.len());
+        self.skip_votes.clear();
+        self.playback = Playback::Paused { position_ms: 0 };
+        if playing {
+            self.play(now);
+        }
+    }
+
+    /// Whether the current track has played to its end
+    pub fn is_finished(&self, now: i64) -> bool {
+        let Some(duration) = self
+            .current_track()
+            .and_then(|track| track.track.duration_secs)
+        else {
+            return false;
+        };
+        matches!(self.playback, Playback::Playing { .. })
+            && self.position_ms(now) >= duration as u64 * 1000
+    }
+
+    pub fn add(&mut self, track: QueueTrack, added_by: &str, now: i64) {
+        // A party whose queue had run out picks up with the new track
+        let resume = self.current >= self.queue.len() && !self.queue.is_empty();
+        self.queue.push(PartyTrack {
+            track,
+            added_by: added_by.to_string(),
+        });
+        if resume {
+            self.play(now);
+        }
+    }
+
+    /// More than half of the members have to want the skip
+    pub fn vote_skip(&mut self, user_id: &str, now: i64) -> SkipVote {
+        if self.current_track().is_some() {
+            self.skip_votes.insert(user_id.to_string());
+        }
+        let votes = self.skip_votes.len();
+        let needed = self.members.len() / 2 + 1;
+        let skipped = votes > 0 && votes >= needed;
+        if skipped {
+            self.next(now);
+        }
+        SkipVote {
+            votes,
+            needed,
+            skipped,
+        }
+    }
+
+    fn leave(&mut self, user_id: &str) {
+        self.members.retain(|member| member.user_id != user_id);
+        self.skip_votes.remove(user_id);
+    }
+}
+
+/// Messages sent to members
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PartyMessage {
+    /// Full state after any change; play `cue` as described
+    State {
+        party: Box<Party>,
+        cue: Option<Cue>,
+        server_time: i64,
+    },
+    /// Reply to a ping: the member's offset is about
+    /// `server_time - (client_time + round_trip / 2)`
+    Pong {
+        client_time: i64,
+        server_time: i64,
+    },
+    Error {
+        message: String,
+    },
+    /// The host ended the party
+    Closed,
+}
+
+/// Messages members send
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MemberMessage {
+    Ping { client_time: i64 },
+    VoteSkip,
+    Add { track: QueueTrack },
+    Control { command: PartyCommand },
+}
+
+/// One member's live connection; pass it back to [`ListeningParties::disconnect`]
+pub struct PartyConnection {
+    pub party_id: String,
+    pub user_id: String,
+    connection_id: u64,
+    receiver: mpsc::UnboundedReceiver<PartyMessage>,
+}
+
+impl PartyConnection {
+    /// Next message for this member, `None` once replaced by a reconnect or
+    /// after the party closed
+    pub async fn recv(&mut self) -> Option<PartyMessage> {
+        self.receiver.recv().await
+    }
+}
+
+struct PartyEntry {
+    party: Party,
+    connections: HashMap<String, (u64, mpsc::UnboundedSender<PartyMessage>)>,
+}
+
+/// Running parties and their members' connections
+#[derive(Clone)]
+pub struct ListeningParties {
+    database: Arc<Database>,
+    parties: Arc<Mutex<HashMap<String, PartyEntry>>>,
+    next_connection_id: Arc<AtomicU64>,
+}
+
+impl ListeningParties {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self {
+            database,
+            parties: Arc::new(Mutex::new(HashMap::new())),
+            next_connection_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Move parties on when their current track runs out
+    pub fn start(&self) {
+        let parties = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                ticker.tick().await;
+                parties.advance_finished(server_time());
+            }
+        });
+    }
+
+    /// Open a party hosted by the caller; a host runs one party at a time
+    pub async fn create(
+        &self,
+        host_id: &str,
+        host_name: &str,
+        request: CreateParty,
+    ) -> PartyResult<Party> {
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err(PartyError::Invalid("A party needs a name".to_string()));
+        }
+        let privacy = self.privacy_level(host_id).await?;
+        let mut party = Party::new(name, host_id, host_name, privacy, request.collaborative);
+        party
+            .invited
+            .extend(request.invite.into_iter().filter(|id| id != host_id));
+
+        let mut parties = self.lock();
+        if parties.values().any(|entry| entry.party.host_id == host_id) {
+            return Err(PartyError::Invalid(
+                "You are already hosting a party".to_string(),
+            ));
+        }
+        parties.insert(
+            party.id.clone(),
+            PartyEntry {
+                party: party.clone(),
+                connections: HashMap::new(),
+            },
+        );
+        info!("{} opened listening party {}", host_name, party.id);
+        Ok(party)
+    }
+
+    /// Parties the user can see: their own, ones they are in or invited to,
+    /// and those the hosts' privacy opens to them
+    pub async fn visible(&self, user_id: &str) -> PartyResult<Vec<Party>> {
+        let mut visible = Vec::new();
+        for party in self.snapshot() {
+            if self.can_join(&party, user_id).await? {
+                visible.push(party);
+            }
+        }
+        visible.sort_by_key(|party| party.created_at);
+        Ok(visible)
+    }
+
+    pub async fn get(&self, party_id: &str, user_id: &str) -> PartyResult<Party> {
+        let party = self.party(party_id)?;
+        if !self.can_join(&party, user_id).await? {
+            // Don't confirm that a party someone can't see exists
+            return Err(PartyError::NotFound);
+        }
+        Ok(party)
+    }
+
+    pub async fn join(
+        &self,
+        party_id: &str,
+        user_id: &str,
+        username: &str,
+    ) -> PartyResult<(Party, Option<Cue>)> {
+        let party = self.get(party_id, user_id).await?;
+        if !party.is_member(user_id) {
+            self.update(party_id, |party, _| {
+                party.members.push(PartyMember {
+                    user_id: user_id.to_string(),
+                    username: username.to_string(),
+                    joined_at: Utc::now(),
+                });
+                Ok(())
+            })?;
+            debug!("{} joined listening party {}", username, party_id);
+        }
+        let party = self.party(party_id)?;
+        let cue = party.cue(server_time());
+        Ok((party, cue))
+    }
+
+    /// Leave a party; the host leaving ends it
+    pub fn leave(&self, party_id: &str, user_id: &str) -> PartyResult<()> {
+        let party = self.party(party_id)?;
+        if party.host_id == user_id {
+            return self.end(party_id, user_id);
+        }
+        self.update(party_id, |party, _| {
+            party.leave(user_id);
+            Ok(())
+        })?;
+        let mut parties = self.lock();
+        if let Some(entry) = parties.get_mut(party_id) {
+            entry.connections.remove(user_id);
+        }
+        Ok(())
+    }
+
+    pub fn invite(&self, party_id: &str, host_id: &str, user_id: &str) -> PartyResult<Party> {
+        self.update(party_id, |party, _| {
+            ensure_host(party, host_id)?;
+            if user_id != party.host_id {
+                party.invited.insert(user_id.to_string());
+            }
+            Ok(())
+        })
+    }
+
+    pub fn add_track(
+        &self,
+        party_id: &str,
+        user_id: &str,
+        track: QueueTrack,
+    ) -> PartyResult<Party> {
+        self.update(party_id, |party, now| {
+            ensure_member(party, user_id)?;
+            if !party.collaborative && party.host_id != user_id {
+                return Err(PartyError::Forbidden(
+                    "Only the host can add tracks to this party".to_string(),
+                ));
+            }
+            party.add(track, user_id, now);
+            Ok(())
+        })
+    }
+
+    pub fn vote_skip(&self, party_id: &str, user_id: &str) -> PartyResult<SkipVote> {
+        self.modify(party_id, |party, now| {
+            ensure_member(party, user_id)?;
+            Ok(party.vote_skip(user_id, now))
+        })
+    }
+
+    pub fn control(
+        &self,
+        party_id: &str,
+        user_id: &str,
+        command: PartyCommand,
+    ) -> PartyResult<Party> {
+        self.update(party_id, |party, now| {
+            ensure_host(party, user_id)?;
+            match command {
+                PartyCommand::Play => party.play(now),
+                PartyCommand::Pause => party.pause(now),
+                PartyCommand::Seek { position_ms } => party.seek(position_ms, now),
+                PartyCommand::Next => party.next(now),
+            }
+            Ok(())
+        })
+    }
+
+    /// Close a party; only its host can
+    pub fn end(&self, party_id: &str, user_id: &str) -> PartyResult<()> {
+        let mut parties = self.lock();
+        let entry = match parties.entry(party_id.to_string()) {
+            Entry::Occupied(entry) => {
+                ensure_host(&entry.get().party, user_id)?;
+                entry.remove()
+            }
+            Entry::Vacant(_) => return Err(PartyError::NotFound),
+        };
+        for (_, sender) in entry.connections.values() {
+            let _ = sender.send(PartyMessage::Closed);
+        }
+        info!("Listening party {} ended", party_id);
+        Ok(())
+    }
+
+    /// Register a member's live connection; it starts with the current state
+    pub fn connect(&self, party_id: &str, user_id: &str) -> PartyResult<PartyConnection> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let connection_id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        let mut parties = self.lock();
+        let entry = parties.get_mut(party_id).ok_or(PartyError::NotFound)?;
+        ensure_member(&entry.party, user_id)?;
+
+        let _ = sender.send(state_message(&entry.party, server_time()));
+        entry
+            .connections
+            .insert(user_id.to_string(), (connection_id, sender));
+        Ok(PartyConnection {
+            party_id: party_id.to_string(),
+            user_id: user_id.to_string(),
+            connection_id,
+            receiver,
+        })
+    }
+
+    pub fn disconnect(&self, connection: &PartyConnection) {
+        let mut parties = self.lock();
+        let Some(entry) = parties.get_mut(&connection.party_id) else {
+            return;
+        };
+        // A newer connection from the same member stays registered
+        if entry
+            .connections
+            .get(&connection.user_id)
+            .is_some_and(|(id, _)| *id == connection.connection_id)
+        {
+            entry.connections.remove(&connection.user_id);
+        }
+    }
+
+    pub fn handle(&self, connection: &PartyConnection, message: MemberMessage) -> PartyResult<()> {
+        let party_id = connection.party_id.as_str();
+        let user_id = connection.user_id.as_str();
+        match message {
+            MemberMessage::Ping { client_time } => {
+                self.send(
+                    party_id,
+                    user_id,
+                    PartyMessage::Pong {
+                        client_time,
+                        server_time: server_time(),
+                    },
+                );
+            }
+            MemberMessage::VoteSkip => {
+                self.vote_skip(party_id, user_id)?;
+            }
+            MemberMessage::Add { track } => {
+                self.add_track(party_id, user_id, track)?;
+            }
+            MemberMessage::Control { command } => {
+                self.control(party_id, user_id, command)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn advance_finished(&self, now: i64) {
+        let mut parties = self.lock();
+        for entry in parties.values_mut() {
+            if entry.party.is_finished(now) {
+                entry.party.next(now);
+                broadcast(entry, now);
+            }
+        }
+    }
+
+    /// Change a party and tell its members
+    fn update(
+        &self,
+        party_id: &str,
+        change: impl FnOnce(&mut Party, i64) -> PartyResult<()>,
+    ) -> PartyResult<Party> {
+        self.modify(party_id, |party, now| {
+            change(party, now)?;
+            Ok(party.clone())
+        })
+    }
+
+    /// Like [`Self::update`], returning what the change returned
+    fn modify<T>(
+        &self,
+        party_id: &str,
+        change: impl FnOnce(&mut Party, i64) -> PartyResult<T>,
+    ) -> PartyResult<T> {
+        let now = server_time();
+        let mut parties = self.lock();
+        let entry = parties.get_mut(party_id).ok_or(PartyError::NotFound)?;
+        let result = change(&mut entry.party, now)?;
+        broadcast(entry, now);
+        Ok(result)
+    }
+
+    /// The running parties; a panic elsewhere while holding the lock leaves
+    /// them usable
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, PartyEntry>> {
+        self.parties.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn party(&self, party_id: &str) -> PartyResult<Party> {
+        let parties = self.lock();
+        parties
+            .get(party_id)
+            .map(|entry| entry.party.clone())
+            .ok_or(PartyError::NotFound)
+    }
+
+    fn snapshot(&self) -> Vec<Party> {
+        let parties = self.lock();
+        parties.values().map(|entry| entry.party.clone()).collect()
+    }
+
+    fn send(&self, party_id: &str, user_id: &str, message: PartyMessage) {
+        let parties = self.lock();
+        if let Some((_, sender)) = parties
+            .get(party_id)
+            .and_then(|entry| entry.connections.get(user_id))
+        {
+            let _ = sender.send(message);
+        }
+    }
+
+    async fn can_join(&self, party: &Party, user_id: &str) -> PartyResult<bool> {
+        if party.host_id == user_id || party.invited.contains(user_id) || party.is_member(user_id) {
+            return Ok(true);
+        }
+        match party.privacy {
+            PrivacyLevel::Public => Ok(true),
+            PrivacyLevel::Friends => self.are_friends(&party.host_id, user_id).await,
+            PrivacyLevel::Private => Ok(false),
+        }
+    }
+
+    /// Friends follow each other
+    async fn are_friends(&self, a: &str, b: &str) -> PartyResult<bool> {
+        let follows: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM user_follows
+            WHERE (follower_id = ? AND following_id = ?) OR (follower_id = ? AND following_id = ?)
+            "#,
+        )
+        .bind(a)
+        .bind(b)
+        .bind(b)
+        .bind(a)
+        .fetch_one(self.database.pool())
+        .await?;
+        Ok(follows == 2)
+    }
+
+    /// Users without a profile get the schema default, friends only
+    async fn privacy_level(&self, user_id: &str) -> PartyResult<PrivacyLevel> {
+        let profile =
+            sqlx::query_as::<_, UserProfile>("SELECT * FROM user_profiles WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_optional(self.database.pool())
+                .await?;
+        Ok(profile.map_or(PrivacyLevel::Friends, |profile| profile.privacy_level()))
+    }
+}
+
+fn state_message(party: &Party, now: i64) -> PartyMessage {
+    PartyMessage::State {
+        party: Box::new(party.clone()),
+        cue: party.cue(now),
+        server_time: now,
+    }
+}
+
+fn broadcast(entry: &PartyEntry, now: i64) {
+    let message = state_message(&entry.party, now);
+    for (_, sender) in entry.connections.values() {
+        let _ = sender.send(message.clone());
+    }
+}
+
+fn ensure_member(party: &Party, user_id: &str) -> PartyResult<()> {
+    if party.is_member(user_id) {
+        Ok(())
+    } else {
+        Err(PartyError::Forbidden("Join the party first".to_string()))
+    }
+}
+
+fn ensure_host(party: &Party, user_id: &str) -> PartyResult<()> {
+    if party.host_id == user_id {
+        Ok(())
+    } else {
+        Err(PartyError::Forbidden(
+            "Only the host can do that".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn track(id: &str, duration_secs: u32) -> QueueTrack {
+        QueueTrack {
+            duration_secs: Some(duration_secs),
+            ..QueueTrack::new(id)
+        }
+    }
+
+    #[test]
+    fn test_party_keeps_members_in_sync() {
+        let mut party = Party::new("Friday", "1", "host", PrivacyLevel::Public, true);
+        party.add(track("a", 180), "1", 0);
+        party.add(track("b", 200), "1", 0);
+
+        // Everyone starts the track a lead time from now
+        party.play(10_000);
+        let cue = party.cue(10_000).unwrap();
+        assert_eq!(cue.track.track.id, "a");
+        assert_eq!(cue.position_ms, 0);
+        assert_eq!(cue.start_at, Some(10_000 + START_LEAD_MS));
+
+        // Someone joining a minute in seeks to where the others will be
+        let late = 10_000 + START_LEAD_MS + 60_000;
+        let cue = party.cue(late).unwrap();
+        assert_eq!(cue.position_ms, 60_000 + START_LEAD_MS as u64);
+        assert_eq!(cue.start_at, Some(late + START_LEAD_MS));
+
+        party.pause(late);
+        assert_eq!(party.cue(late + 5_000).unwrap().position_ms, 60_000);
+        assert_eq!(party.cue(late).unwrap().start_at, None);
+        party.seek(30_000, late);
+        party.play(late);
+        assert_eq!(party.position_ms(late + START_LEAD_MS), 30_000);
+
+        // The track runs out and the party moves on
+        let end = late + START_LEAD_MS + 150_000;
+        assert!(party.is_finished(end));
+        party.next(end);
+        assert_eq!(party.current_track().unwrap().track.id, "b");
+        assert_eq!(party.position_ms(end + START_LEAD_MS), 0);
+    }
+
+    #[test]
+    fn test_vote_to_skip_needs_a_majority() {
+        let mut party = Party::new("Friday", "1", "host", PrivacyLevel::Public, true);
+        for (id, name) in [("2", "two"), ("3", "three")] {
+            party.members.push(PartyMember {
+                user_id: id.to_string(),
+                username: name.to_string(),
+                joined_at: Utc::now(),
+            });
+        }
+        party.add(track("a", 180), "2", 0);
+        party.add(track("b", 180), "3", 0);
+        party.play(0);
+
+        let vote = party.vote_skip("2", 1_000);
+        assert_eq!((vote.votes, vote.needed, vote.skipped), (1, 2, false));
+        // Voting twice doesn't count twice
+        assert!(!party.vote_skip("2", 1_000).skipped);
+        assert!(party.vote_skip("3", 2_000).skipped);
+        assert_eq!(party.current_track().unwrap().track.id, "b");
+        assert!(party.skip_votes.is_empty());
+
+        // Past the end of the queue, a new track starts playing
+        party.next(3_000);
+        assert!(party.current_track().is_none());
+        party.add(track("c", 180), "3", 4_000);
+        assert_eq!(party.current_track().unwrap().track.id, "c");
+        assert!(matches!(party.playback, Playback::Playing { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_privacy_and_follows_decide_who_can_join() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        for (id, name) in [(2, "friend"), (3, "fan"), (4, "guest")] {
+            sqlx::query("INSERT INTO users (id, keycloak_id, username, email) VALUES (?, ?, ?, ?)")
+                .bind(id)
+                .bind(format!("kc-{}", id))
+                .bind(name)
+                .bind(format!("{}@example.com", name))
+                .execute(database.pool())
+                .await
+                .unwrap();
+        }
+        sqlx::query("INSERT INTO user_profiles (user_id, privacy_level) VALUES (2, 1)")
+            .execute(database.pool())
+            .await
+            .unwrap();
+        // 2 and 3 are friends; 4 follows 2 without being followed back
+        for (follower, following) in [(2, 3), (3, 2), (4, 2)] {
+            sqlx::query("INSERT INTO user_follows (follower_id, following_id) VALUES (?, ?)")
+                .bind(follower)
+                .bind(following)
+                .execute(database.pool())
+                .await
+                .unwrap();
+        }
+
+        let parties = ListeningParties::new(database.clone());
+        let request = |name: &str| CreateParty {
+            name: name.to_string(),
+            collaborative: false,
+            invite: Vec::new(),
+        };
+        let party = parties
+            .create("2", "friend", request("Friends only"))
+            .await
+            .unwrap();
+        assert!(matches!(party.privacy, PrivacyLevel::Friends));
+        assert!(parties
+            .create("2", "friend", request("Another"))
+            .await
+            .is_err());
+
+        assert_eq!(parties.visible("3").await.unwrap().len(), 1);
+        assert!(parties.visible("4").await.unwrap().is_empty());
+        assert!(matches!(
+            parties.join(&party.id, "4", "guest").await,
+            Err(PartyError::NotFound)
+        ));
+
+        parties.invite(&party.id, "2", "4").unwrap();
+        let (party, cue) = parties.join(&party.id, "4", "guest").await.unwrap();
+        assert!(party.is_member("4"));
+        assert!(cue.is_none());
+
+        // Not collaborative: only the host adds tracks
+        assert!(matches!(
+            parties.add_track(&party.id, "4", track("a", 180)),
+            Err(PartyError::Forbidden(_))
+        ));
+        parties.add_track(&party.id, "2", track("a", 180)).unwrap();
+        assert!(parties.control(&party.id, "4", PartyCommand::Play).is_err());
+
+        let mut connection = parties.connect(&party.id, "4").unwrap();
+        assert!(matches!(
+            connection.recv().await,
+            Some(PartyMessage::State { .. })
+        ));
+        parties.control(&party.id, "2", PartyCommand::Play).unwrap();
+        match connection.recv().await {
+            Some(PartyMessage::State { cue: Some(cue), .. }) => assert!(cue.start_at.is_some()),
+            other => panic!("expected a cue, got {:?}", other),
+        }
+
+        parties.leave(&party.id, "2").unwrap();
+        assert!(matches!(
+            connection.recv().await,
+            Some(PartyMessage::Closed)
+        ));
+        assert!(parties.visible("3").await.unwrap().is_empty());
+    }
+}
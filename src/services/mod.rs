@@ -3,19 +3,44 @@
 //! This module contains all the business logic services that power the music
 //! recommendation and management system.
 
+pub mod bandwidth;
+pub mod disk_space;
 pub mod download_service;
+pub mod download_store;
+pub mod events;
+pub mod fair_queue;
+pub mod import;
 pub mod library;
+pub mod lidarr_webhook;
+#[cfg(feature = "social")]
+pub mod listening_party;
+pub mod notifications;
+pub mod play_queue;
+pub mod playback;
 pub mod playlist;
+pub mod quality_profile;
+pub mod quota;
+pub mod radio;
 pub mod recommendation;
+pub mod recycle_bin;
+pub mod remote_control;
+pub mod scrobble;
+pub mod seeding;
+pub mod stall;
+#[cfg(feature = "tiered-storage")]
 pub mod storage;
 pub mod sync;
+pub mod tiering;
+pub mod torrent_file;
 pub mod user_service;
+pub mod wishlist;
 
 // Re-export for convenience
 pub use download_service::DownloadService;
 pub use library::LibraryService;
 pub use playlist::PlaylistService;
 pub use recommendation::RecommendationService;
+#[cfg(feature = "tiered-storage")]
 pub use storage::StorageManager;
 pub use sync::SyncService;
 pub use user_service::UserService;
@@ -39,6 +64,7 @@ pub struct ServiceManager {
     pub library: Arc<LibraryService>,
     pub playlist: Arc<PlaylistService>,
     pub recommendation: Arc<RecommendationService>,
+    #[cfg(feature = "tiered-storage")]
     pub storage: Arc<StorageManager>,
     pub sync: Arc<SyncService>,
     pub user: Arc<UserService>,
@@ -63,14 +89,13 @@ impl ServiceManager {
             final_library_path: std::path::PathBuf::from(music_path),
             ..Default::default()
         };
-        let download = Arc::new(DownloadService::new(download_config));
+        let download = Arc::new(DownloadService::new(download_config, database.clone())?);
 
         // Initialize core services
-        let library = Arc::new(LibraryService::new(
-            database.clone(),
-            music_path,
-            download_path,
-        )?);
+        let library = Arc::new(
+            LibraryService::new(database.clone(), music_path, download_path)?
+                .with_recycle_bin(download.recycle_bin().clone()),
+        );
 
         let playlist = Arc::new(PlaylistService::new(
             database.clone(),
@@ -84,9 +109,16 @@ impl ServiceManager {
             cache_dir,
         )?);
 
-        let storage = Arc::new(crate::services::storage::create_storage_manager());
-        storage.initialize().await?;
-        storage.start_monitor().await?;
+        #[cfg(feature = "tiered-storage")]
+        let storage = {
+            let storage = Arc::new(
+                crate::services::storage::create_storage_manager()
+                    .with_recycle_bin(download.recycle_bin().clone()),
+            );
+            storage.initialize().await?;
+            storage.start_monitor().await?;
+            storage
+        };
 
         let sync = Arc::new(SyncService::new(
             database.clone(),
@@ -103,6 +135,7 @@ impl ServiceManager {
             library,
             playlist,
             recommendation,
+            #[cfg(feature = "tiered-storage")]
             storage,
             sync,
             user,
@@ -139,7 +172,10 @@ impl ServiceManager {
         let library_stats = self.library.get_stats().await.unwrap_or_default();
         let playlist_stats = self.playlist.get_stats().await.unwrap_or_default();
         let recommendation_stats = self.recommendation.get_stats().await.unwrap_or_default();
+        #[cfg(feature = "tiered-storage")]
         let storage_stats = self.storage.get_storage_stats().await.ok();
+        #[cfg(not(feature = "tiered-storage"))]
+        let storage_stats = None;
         let sync_stats = self.sync.get_stats().await.unwrap_or_default();
 
         Ok(ServiceStats {
@@ -161,6 +197,7 @@ impl ServiceManager {
             error!("Failed to shutdown sync service: {}", e);
         }
 
+        #[cfg(feature = "tiered-storage")]
         if let Err(e) = self.storage.cleanup_processing().await {
             error!("Failed to cleanup storage processing: {}", e);
         }
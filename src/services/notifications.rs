@@ -0,0 +1,193 @@
+//! User notifications for StepheyBot Music
+//!
+//! Notifications are stored per user in SQLite and fetched by clients, so
+//! users learn about events that happened while they were away.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{sqlite::SqliteRow, Row};
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::database::Database;
+
+/// What a notification is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    DownloadApproved,
+    DownloadRejected,
+    AlbumImported,
+    DownloadFailed,
+    LowDiskSpace,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::DownloadApproved => "download_approved",
+            NotificationKind::DownloadRejected => "download_rejected",
+            NotificationKind::AlbumImported => "album_imported",
+            NotificationKind::DownloadFailed => "download_failed",
+            NotificationKind::LowDiskSpace => "low_disk_space",
+        }
+    }
+}
+
+/// A notification addressed to one user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: i64,
+    pub user_id: String,
+    pub kind: String,
+    pub title: String,
+    pub message: String,
+    pub data: Value,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// SQLite-backed notification inbox
+#[derive(Clone)]
+pub struct NotificationService {
+    database: Arc<Database>,
+}
+
+impl NotificationService {
+    /// Create a new notification service
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+
+    /// Send a notification to a user
+    pub async fn notify(
+        &self,
+        user_id: &str,
+        kind: NotificationKind,
+        title: &str,
+        message: &str,
+        data: Value,
+    ) -> Result<i64> {
+        debug!("Notifying {} ({}): {}", user_id, kind.as_str(), title);
+
+        let id = sqlx::query(
+            r#"
+            INSERT INTO notifications (user_id, kind, title, message, data, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(user_id)
+        .bind(kind.as_str())
+        .bind(title)
+        .bind(message)
+        .bind(data.to_string())
+        .bind(Utc::now().timestamp())
+        .execute(self.database.pool())
+        .await?
+        .last_insert_rowid();
+
+        Ok(id)
+    }
+
+    /// List a user's notifications, newest first
+    pub async fn list(
+        &self,
+        user_id: &str,
+        unread_only: bool,
+        limit: u32,
+    ) -> Result<Vec<Notification>> {
+        let sql = if unread_only {
+            "SELECT * FROM notifications WHERE user_id = ? AND read_at IS NULL ORDER BY created_at DESC, id DESC LIMIT ?"
+        } else {
+            "SELECT * FROM notifications WHERE user_id = ? ORDER BY created_at DESC, id DESC LIMIT ?"
+        };
+
+        let rows = sqlx::query(sql)
+            .bind(user_id)
+            .bind(limit.min(500))
+            .fetch_all(self.database.pool())
+            .await?;
+
+        Ok(rows.iter().map(row_to_notification).collect())
+    }
+
+    /// Mark one of a user's notifications as read
+    pub async fn mark_read(&self, user_id: &str, id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE notifications SET read_at = ? WHERE id = ? AND user_id = ? AND read_at IS NULL",
+        )
+        .bind(Utc::now().timestamp())
+        .bind(id)
+        .bind(user_id)
+        .execute(self.database.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn row_to_notification(row: &SqliteRow) -> Notification {
+    Notification {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        kind: row.get("kind"),
+        title: row.get("title"),
+        message: row.get("message"),
+        data: serde_json::from_str(row.get("data")).unwrap_or(Value::Null),
+        created_at: DateTime::from_timestamp(row.get("created_at"), 0).unwrap_or_else(Utc::now),
+        read_at: row
+            .get::<Option<i64>, _>("read_at")
+            .and_then(|t| DateTime::from_timestamp(t, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_notifications() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let notifications = NotificationService::new(database);
+
+        let id = notifications
+            .notify(
+                "alice",
+                NotificationKind::DownloadRejected,
+                "Download rejected",
+                "Already in the library",
+                serde_json::json!({"request_id": "abc"}),
+            )
+            .await
+            .unwrap();
+
+        let inbox = notifications.list("alice", true, 10).await.unwrap();
+        assert_eq!(inbox.len(), 1);
+        assert_eq!(inbox[0].kind, "download_rejected");
+        assert_eq!(inbox[0].data["request_id"], "abc");
+        assert!(notifications
+            .list("bob", false, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Users cannot touch each other's notifications
+        assert!(!notifications.mark_read("bob", id).await.unwrap());
+        assert!(notifications.mark_read("alice", id).await.unwrap());
+        assert!(notifications
+            .list("alice", true, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            notifications.list("alice", false, 10).await.unwrap().len(),
+            1
+        );
+    }
+}
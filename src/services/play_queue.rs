@@ -0,0 +1,490 @@
+//! Play queue for StepheyBot Music
+//!
+//! Each user has one queue kept in SQLite, modelled on Subsonic's
+//! `savePlayQueue`/`getPlayQueue`: the ordered tracks, which one is playing
+//! and how far in, the shuffle order and repeat mode, and the device that last
+//! touched it. A queue started on one device can be picked up on another.
+
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, Row};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::clients::navidrome::NavidromeSong;
+use crate::database::Database;
+use crate::models::user::RepeatMode;
+
+/// Past this point "previous" restarts the current track instead
+pub const RESTART_THRESHOLD_MS: u64 = 3000;
+
+/// A track in the queue; only the id is required
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueTrack {
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub duration_secs: Option<u32>,
+    #[serde(default)]
+    pub genre: Option<String>,
+}
+
+impl QueueTrack {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            title: None,
+            artist: None,
+            album: None,
+            duration_secs: None,
+            genre: None,
+        }
+    }
+}
+
+impl From<NavidromeSong> for QueueTrack {
+    fn from(song: NavidromeSong) -> Self {
+        Self {
+            id: song.id,
+            title: Some(song.title),
+            artist: song.artist,
+            album: song.album,
+            duration_secs: song.duration,
+            genre: song.genre,
+        }
+    }
+}
+
+/// Body of a queue save: the full queue, with optional playback settings
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavePlayQueue {
+    pub tracks: Vec<QueueTrack>,
+    #[serde(default)]
+    pub current_index: usize,
+    #[serde(default)]
+    pub position_ms: u64,
+    pub shuffle: Option<bool>,
+    pub repeat_mode: Option<RepeatMode>,
+    pub device: Option<String>,
+}
+
+/// A user's queue and playback position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayQueue {
+    pub user_id: String,
+    pub tracks: Vec<QueueTrack>,
+    /// Index into `tracks` of the current track
+    pub current_index: usize,
+    pub position_ms: u64,
+    /// Playback order as indexes into `tracks`, when shuffled
+    pub shuffle_order: Option<Vec<usize>>,
+    pub repeat_mode: RepeatMode,
+    pub is_playing: bool,
+    pub updated_by_device: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PlayQueue {
+    pub fn new(user_id: impl Into<String>) -> Self {
+        Self {
+            user_id: user_id.into(),
+            tracks: Vec::new(),
+            current_index: 0,
+            position_ms: 0,
+            shuffle_order: None,
+            repeat_mode: RepeatMode::Off,
+            is_playing: false,
+            updated_by_device: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn current(&self) -> Option<&QueueTrack> {
+        self.tracks.get(self.current_index)
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.shuffle_order.is_some()
+    }
+
+    /// Track indexes in the order they play
+    pub fn play_order(&self) -> Vec<usize> {
+        self.shuffle_order
+            .clone()
+            .unwrap_or_else(|| (0..self.tracks.len()).collect())
+    }
+
+    /// Replace the whole queue, as Subsonic's `savePlayQueue` does
+    pub fn replace(&mut self, tracks: Vec<QueueTrack>, current_index: usize, position_ms: u64) {
+        self.tracks = tracks;
+        self.current_index = current_index.min(self.tracks.len().saturating_sub(1));
+        self.position_ms = position_ms;
+        if self.is_shuffled() {
+            self.shuffle();
+        }
+        if self.tracks.is_empty() {
+            self.is_playing = false;
+        }
+    }
+
+    /// Add tracks to the end of the queue; when shuffled they play last
+    pub fn append(&mut self, tracks: Vec<QueueTrack>) {
+        let start = self.tracks.len();
+        self.tracks.extend(tracks);
+        if let Some(order) = self.shuffle_order.as_mut() {
+            order.extend(start..self.tracks.len());
+        }
+    }
+
+    /// Tracks left to play after the current one, ignoring repeat
+    pub fn remaining(&self) -> usize {
+        let order = self.play_order();
+        order.len().saturating_sub(self.order_position(&order) + 1)
+    }
+
+    /// Move to the next track, honouring the repeat mode.
+    ///
+    /// Returns `None` and stops playback at the end of an unrepeated queue.
+    pub fn next_track(&mut self) -> Option<&QueueTrack> {
+        if self.tracks.is_empty() {
+            return None;
+        }
+        self.position_ms = 0;
+
+        if self.repeat_mode != RepeatMode::Track {
+            let order = self.play_order();
+            let position = self.order_position(&order);
+            match order.get(position + 1) {
+                Some(&next) => self.current_index = next,
+                None if self.repeat_mode == RepeatMode::Playlist => self.current_index = order[0],
+                None => {
+                    self.is_playing = false;
+                    return None;
+                }
+            }
+        }
+        self.current()
+    }
+
+    /// Restart the current track, or move to the previous one near its start
+    pub fn previous_track(&mut self) -> Option<&QueueTrack> {
+        if self.tracks.is_empty() {
+            return None;
+        }
+        let restart = self.position_ms > RESTART_THRESHOLD_MS;
+        self.position_ms = 0;
+        if restart || self.repeat_mode == RepeatMode::Track {
+            return self.current();
+        }
+
+        let order = self.play_order();
+        let position = self.order_position(&order);
+        if position > 0 {
+            self.current_index = order[position - 1];
+        } else if self.repeat_mode == RepeatMode::Playlist {
+            self.current_index = order[order.len() - 1];
+        }
+        self.current()
+    }
+
+    /// Jump to a track, queueing it after the current one if it is not queued yet
+    pub fn play_track(&mut self, track: QueueTrack) -> &QueueTrack {
+        let index = match self.tracks.iter().position(|queued| queued.id == track.id) {
+            Some(index) => index,
+            None => {
+                let index = if self.tracks.is_empty() {
+                    0
+                } else {
+                    self.current_index + 1
+                };
+                self.tracks.insert(index, track);
+                if let Some(order) = self.shuffle_order.as_mut() {
+                    let after = order
+                        .iter()
+                        .position(|&i| i == self.current_index)
+                        .map_or(0, |position| position + 1);
+                    for i in order.iter_mut().filter(|i| **i >= index) {
+                        *i += 1;
+                    }
+                    order.insert(after.min(order.len()), index);
+                }
+                index
+            }
+        };
+
+        self.current_index = index;
+        self.position_ms = 0;
+        self.is_playing = true;
+        &self.tracks[index]
+    }
+
+    /// Stop playing, remembering where
+    pub fn pause(&mut self, position_ms: Option<u64>) {
+        self.is_playing = false;
+        if let Some(position_ms) = position_ms {
+            self.position_ms = position_ms;
+        }
+    }
+
+    pub fn set_shuffle(&mut self, enabled: bool) {
+        if enabled {
+            self.shuffle();
+        } else {
+            self.shuffle_order = None;
+        }
+    }
+
+    pub fn set_repeat(&mut self, repeat_mode: RepeatMode) {
+        self.repeat_mode = repeat_mode;
+    }
+
+    /// Shuffle everything but the current track, which plays first
+    fn shuffle(&mut self) {
+        let mut rest: Vec<usize> = (0..self.tracks.len())
+            .filter(|&i| i != self.current_index)
+            .collect();
+        rest.shuffle(&mut rand::thread_rng());
+
+        let mut order = Vec::with_capacity(self.tracks.len());
+        if !self.tracks.is_empty() {
+            order.push(self.current_index);
+        }
+        order.extend(rest);
+        self.shuffle_order = Some(order);
+    }
+
+    fn order_position(&self, order: &[usize]) -> usize {
+        order
+            .iter()
+            .position(|&i| i == self.current_index)
+            .unwrap_or(0)
+    }
+}
+
+/// Loads, changes and saves play queues
+#[derive(Clone)]
+pub struct PlayQueueService {
+    database: Arc<Database>,
+    // Serialises read-modify-write cycles so devices don't overwrite each other
+    lock: Arc<Mutex<()>>,
+}
+
+impl PlayQueueService {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self {
+            database,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// The user's queue, empty if they never saved one
+    pub async fn get(&self, user_id: &str) -> Result<PlayQueue> {
+        let row = sqlx::query("SELECT * FROM play_queues WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+        match row {
+            Some(row) => row_to_queue(&row),
+            None => Ok(PlayQueue::new(user_id)),
+        }
+    }
+
+    /// Apply a change to the user's queue and save it on behalf of `device`
+    pub async fn update<F>(
+        &self,
+        user_id: &str,
+        device: Option<&str>,
+        change: F,
+    ) -> Result<PlayQueue>
+    where
+        F: FnOnce(&mut PlayQueue),
+    {
+        let _guard = self.lock.lock().await;
+        let mut queue = self.get(user_id).await?;
+        change(&mut queue);
+        if let Some(device) = device {
+            queue.updated_by_device = Some(device.to_string());
+        }
+        queue.updated_at = Utc::now();
+        self.save(&queue).await?;
+        Ok(queue)
+    }
+
+    async fn save(&self, queue: &PlayQueue) -> Result<()> {
+        let shuffle_order = queue
+            .shuffle_order
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        sqlx::query(
+            r#"
+            INSERT INTO play_queues
+                (user_id, tracks, current_index, position_ms, shuffle_order,
+                 repeat_mode, is_playing, updated_by_device, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET
+                tracks = excluded.tracks,
+                current_index = excluded.current_index,
+                position_ms = excluded.position_ms,
+                shuffle_order = excluded.shuffle_order,
+                repeat_mode = excluded.repeat_mode,
+                is_playing = excluded.is_playing,
+                updated_by_device = excluded.updated_by_device,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&queue.user_id)
+        .bind(serde_json::to_string(&queue.tracks)?)
+        .bind(queue.current_index as i64)
+        .bind(queue.position_ms as i64)
+        .bind(shuffle_order)
+        .bind(repeat_mode_name(queue.repeat_mode))
+        .bind(queue.is_playing)
+        .bind(&queue.updated_by_device)
+        .bind(queue.updated_at.timestamp())
+        .execute(self.database.pool())
+        .await?;
+        Ok(())
+    }
+}
+
+fn repeat_mode_name(repeat_mode: RepeatMode) -> &'static str {
+    match repeat_mode {
+        RepeatMode::Off => "Off",
+        RepeatMode::Track => "Track",
+        RepeatMode::Playlist => "Playlist",
+    }
+}
+
+fn row_to_queue(row: &SqliteRow) -> Result<PlayQueue> {
+    let tracks: String = row.get("tracks");
+    let shuffle_order: Option<String> = row.get("shuffle_order");
+    let repeat_mode: String = row.get("repeat_mode");
+
+    Ok(PlayQueue {
+        user_id: row.get("user_id"),
+        tracks: serde_json::from_str(&tracks)?,
+        current_index: row.get::<i64, _>("current_index").max(0) as usize,
+        position_ms: row.get::<i64, _>("position_ms").max(0) as u64,
+        shuffle_order: shuffle_order
+            .map(|order| serde_json::from_str(&order))
+            .transpose()?,
+        repeat_mode: match repeat_mode.as_str() {
+            "Track" => RepeatMode::Track,
+            "Playlist" => RepeatMode::Playlist,
+            _ => RepeatMode::Off,
+        },
+        is_playing: row.get("is_playing"),
+        updated_by_device: row.get("updated_by_device"),
+        updated_at: Utc
+            .timestamp_opt(row.get("updated_at"), 0)
+            .single()
+            .unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn queue_of(ids: &[&str]) -> PlayQueue {
+        let mut queue = PlayQueue::new("1");
+        queue.replace(ids.iter().map(|id| QueueTrack::new(*id)).collect(), 0, 0);
+        queue
+    }
+
+    #[test]
+    fn test_navigation_and_repeat() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        assert_eq!(queue.next_track().unwrap().id, "b");
+        assert_eq!(queue.next_track().unwrap().id, "c");
+        assert!(queue.next_track().is_none());
+        assert_eq!(queue.current().unwrap().id, "c");
+
+        queue.set_repeat(RepeatMode::Playlist);
+        assert_eq!(queue.next_track().unwrap().id, "a");
+        assert_eq!(queue.previous_track().unwrap().id, "c");
+
+        // Far enough into a track, previous restarts it
+        queue.position_ms = 10_000;
+        assert_eq!(queue.previous_track().unwrap().id, "c");
+        assert_eq!(queue.position_ms, 0);
+
+        queue.set_repeat(RepeatMode::Track);
+        assert_eq!(queue.next_track().unwrap().id, "c");
+    }
+
+    #[test]
+    fn test_shuffle_and_play_track() {
+        let mut queue = queue_of(&["a", "b", "c", "d"]);
+        queue.next_track();
+        queue.set_shuffle(true);
+
+        let order = queue.play_order();
+        assert_eq!(order[0], 1);
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec![0, 1, 2, 3]);
+
+        // A new track is inserted after the current one, in both orders
+        assert_eq!(queue.play_track(QueueTrack::new("x")).id, "x");
+        assert_eq!(queue.tracks[2].id, "x");
+        assert_eq!(queue.play_order()[1], 2);
+        let mut visited = vec![queue.current().unwrap().id.clone()];
+        while let Some(track) = queue.next_track() {
+            visited.push(track.id.clone());
+        }
+        assert_eq!(visited.len(), 4);
+        assert!(!visited.contains(&"b".to_string()));
+
+        // Playing a queued track jumps to it
+        queue.play_track(QueueTrack::new("a"));
+        assert_eq!(queue.current_index, 0);
+        assert!(queue.is_playing);
+    }
+
+    #[tokio::test]
+    async fn test_queue_persists_across_devices() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let service = PlayQueueService::new(database);
+
+        assert!(service.get("1").await.unwrap().tracks.is_empty());
+
+        service
+            .update("1", Some("desktop"), |queue| {
+                queue.replace(vec![QueueTrack::new("a"), QueueTrack::new("b")], 1, 42_000);
+                queue.set_repeat(RepeatMode::Playlist);
+                queue.set_shuffle(true);
+            })
+            .await
+            .unwrap();
+
+        let queue = service.get("1").await.unwrap();
+        assert_eq!(queue.current().unwrap().id, "b");
+        assert_eq!(queue.position_ms, 42_000);
+        assert_eq!(queue.repeat_mode, RepeatMode::Playlist);
+        assert_eq!(queue.shuffle_order.as_ref().unwrap()[0], 1);
+        assert_eq!(queue.updated_by_device.as_deref(), Some("desktop"));
+
+        let queue = service
+            .update("1", Some("phone"), |queue| {
+                queue.next_track();
+            })
+            .await
+            .unwrap();
+        assert_eq!(queue.current().unwrap().id, "a");
+        assert_eq!(queue.updated_by_device.as_deref(), Some("phone"));
+        assert!(service.get("2").await.unwrap().tracks.is_empty());
+    }
+}
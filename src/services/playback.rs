@@ -0,0 +1,577 @@
+//! Play reports for StepheyBot Music
+//!
+//! Players report the track they start, a progress heartbeat while it plays
+//! and how it ended. Each play becomes a row in `listening_sessions` with the
+//! time actually listened, measured from the heartbeats so seeking ahead
+//! doesn't count. Tracks skipped early are fed to the radio as negative
+//! feedback, and plays long enough to count are scrobbled.
+
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{sqlite::SqliteRow, Row};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::clients::navidrome::NavidromeClient;
+use crate::database::Database;
+use crate::services::play_queue::QueueTrack;
+use crate::services::radio::RadioService;
+use crate::services::scrobble::{Scrobble, Scrobbler};
+
+/// Share of a track that has to be heard for the play to be complete
+pub const COMPLETED_RATIO: f64 = 0.8;
+
+/// ListenBrainz counts a listen after half the track or four minutes
+const SCROBBLE_AFTER_SECS: u64 = 240;
+
+/// ListenBrainz ignores tracks shorter than this
+const SCROBBLE_MIN_TRACK_SECS: u32 = 30;
+
+/// Body of a now-playing report; anything left out is looked up in Navidrome
+#[derive(Debug, Clone, Deserialize)]
+pub struct NowPlayingReport {
+    pub track_id: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_secs: Option<u32>,
+    /// Library path of the file, as Navidrome reports it
+    pub path: Option<String>,
+    /// 'web', 'mobile', 'api'...
+    pub source: Option<String>,
+    /// User agent, app version and the like
+    #[serde(default)]
+    pub client: Value,
+    #[serde(default)]
+    pub position_ms: u64,
+}
+
+/// Body of a progress heartbeat
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProgressReport {
+    pub position_ms: u64,
+}
+
+/// Body of an end report
+#[derive(Debug, Clone, Deserialize)]
+pub struct EndReport {
+    /// Where playback stopped; the last heartbeat if left out
+    pub position_ms: Option<u64>,
+    /// The listener moved on rather than the track running out
+    #[serde(default)]
+    pub skipped: bool,
+}
+
+/// Why a session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    Ended,
+    Skipped,
+    /// Another track was reported without this one ending
+    Replaced,
+}
+
+impl EndReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndReason::Ended => "ended",
+            EndReason::Skipped => "skipped",
+            EndReason::Replaced => "replaced",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ended" => Some(EndReason::Ended),
+            "skipped" => Some(EndReason::Skipped),
+            "replaced" => Some(EndReason::Replaced),
+            _ => None,
+        }
+    }
+}
+
+/// One play of one track
+#[derive(Debug, Clone, Serialize)]
+pub struct ListeningSession {
+    pub id: i64,
+    pub user_id: String,
+    pub track: QueueTrack,
+    pub track_path: String,
+    pub source: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub position_ms: u64,
+    pub played_ms: u64,
+    pub completed: bool,
+    pub end_reason: Option<EndReason>,
+    /// Counted as a skip for the radio
+    pub skipped: bool,
+    /// Queued for scrobbling when it ended
+    pub scrobbled: bool,
+}
+
+impl ListeningSession {
+    pub fn duration_played(&self) -> u64 {
+        self.played_ms / 1000
+    }
+
+    fn is_complete(&self, reason: EndReason) -> bool {
+        match self.track.duration_secs {
+            Some(duration) if duration > 0 => {
+                self.played_ms as f64 >= duration as f64 * 1000.0 * COMPLETED_RATIO
+            }
+            _ => reason == EndReason::Ended,
+        }
+    }
+
+    fn is_scrobblable(&self) -> bool {
+        match self.track.duration_secs {
+            Some(duration) if duration < SCROBBLE_MIN_TRACK_SECS => false,
+            Some(duration) => {
+                self.duration_played() >= (duration as u64 / 2).min(SCROBBLE_AFTER_SECS)
+            }
+            None => self.completed || self.duration_played() >= SCROBBLE_AFTER_SECS,
+        }
+    }
+}
+
+/// Time heard between two reports: forward movement, but no more than the
+/// wall clock allows, so a seek ahead isn't counted as listening
+fn heard_ms(previous_ms: u64, position_ms: u64, elapsed_secs: i64) -> u64 {
+    // Reports are stamped to the second
+    let allowed = (elapsed_secs.max(0) as u64 + 1) * 1000;
+    position_ms.saturating_sub(previous_ms).min(allowed)
+}
+
+/// Records play reports and turns them into skips and scrobbles
+pub struct PlaybackService {
+    database: Arc<Database>,
+    radio: Arc<RadioService>,
+    scrobbler: Arc<Scrobbler>,
+    navidrome: Option<NavidromeClient>,
+}
+
+impl PlaybackService {
+    pub fn new(
+        database: Arc<Database>,
+        radio: Arc<RadioService>,
+        scrobbler: Arc<Scrobbler>,
+        navidrome: Option<NavidromeClient>,
+    ) -> Self {
+        Self {
+            database,
+            radio,
+            scrobbler,
+            navidrome,
+        }
+    }
+
+    /// Start a session for a track, closing the one it replaces
+    pub async fn now_playing(
+        &self,
+        user_id: &str,
+        report: NowPlayingReport,
+    ) -> Result<ListeningSession> {
+        let now = Utc::now();
+        let open: Vec<i64> = sqlx::query_scalar(
+            "SELECT id FROM listening_sessions WHERE user_id = ? AND ended_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_all(self.database.pool())
+        .await?;
+        for session_id in open {
+            self.finish(user_id, session_id, None, EndReason::Replaced)
+                .await?;
+        }
+
+        let (track, path) = self.describe(report.clone()).await;
+        let track_path = path.unwrap_or_else(|| track.id.clone());
+        let result = sqlx::query(
+            r#"
+            INSERT INTO listening_sessions
+                (user_id, track_path, track_id, track_json, started_at, duration_played,
+                 position_ms, played_ms, last_report_at, source, client_info_json)
+            VALUES (?, ?, ?, ?, ?, 0, ?, 0, ?, ?, ?)
+            "#,
+        )
+        .bind(user_id)
+        .bind(&track_path)
+        .bind(&track.id)
+        .bind(serde_json::to_string(&track)?)
+        .bind(now.timestamp())
+        .bind(report.position_ms as i64)
+        .bind(now.timestamp())
+        .bind(report.source.as_deref().unwrap_or("api"))
+        .bind(match &report.client {
+            Value::Null => "{}".to_string(),
+            client => client.to_string(),
+        })
+        .execute(self.database.pool())
+        .await?;
+
+        let session_id = result.last_insert_rowid();
+        debug!(
+            "User {} started {} (session {})",
+            user_id, track.id, session_id
+        );
+        if let Err(e) = self
+            .scrobbler
+            .enqueue(Scrobble::NowPlaying {
+                user_id: user_id.to_string(),
+                track,
+            })
+            .await
+        {
+            warn!("Failed to queue now playing for user {}: {}", user_id, e);
+        }
+
+        self.get(user_id, session_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Session {} vanished", session_id))
+    }
+
+    /// Record how far an open session has got
+    pub async fn progress(
+        &self,
+        user_id: &str,
+        session_id: i64,
+        position_ms: u64,
+    ) -> Result<Option<ListeningSession>> {
+        let Some(session) = self.open_session(user_id, session_id).await? else {
+            return Ok(None);
+        };
+        self.record_position(&session, position_ms, None).await?;
+        self.get(user_id, session_id).await
+    }
+
+    /// Close a session because the track ended or was skipped
+    pub async fn end(
+        &self,
+        user_id: &str,
+        session_id: i64,
+        report: EndReport,
+    ) -> Result<Option<ListeningSession>> {
+        let reason = if report.skipped {
+            EndReason::Skipped
+        } else {
+            EndReason::Ended
+        };
+        self.finish(user_id, session_id, report.position_ms, reason)
+            .await
+    }
+
+    pub async fn get(&self, user_id: &str, session_id: i64) -> Result<Option<ListeningSession>> {
+        let row = sqlx::query("SELECT * FROM listening_sessions WHERE id = ? AND user_id = ?")
+            .bind(session_id)
+            .bind(user_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+        Ok(row.map(|row| session_from_row(&row)))
+    }
+
+    /// The user's most recent sessions, newest first
+    pub async fn recent(&self, user_id: &str, limit: i64) -> Result<Vec<ListeningSession>> {
+        let rows = sqlx::query(
+            "SELECT * FROM listening_sessions WHERE user_id = ? ORDER BY started_at DESC, id DESC LIMIT ?",
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(self.database.pool())
+        .await?;
+        Ok(rows.iter().map(session_from_row).collect())
+    }
+
+    async fn open_session(
+        &self,
+        user_id: &str,
+        session_id: i64,
+    ) -> Result<Option<ListeningSession>> {
+        Ok(self
+            .get(user_id, session_id)
+            .await?
+            .filter(|session| session.ended_at.is_none()))
+    }
+
+    async fn finish(
+        &self,
+        user_id: &str,
+        session_id: i64,
+        position_ms: Option<u64>,
+        reason: EndReason,
+    ) -> Result<Option<ListeningSession>> {
+        let Some(session) = self.open_session(user_id, session_id).await? else {
+            return Ok(None);
+        };
+        let mut session = self
+            .record_position(
+                &session,
+                position_ms.unwrap_or(session.position_ms),
+                Some(reason),
+            )
+            .await?;
+
+        if reason == EndReason::Skipped && !session.completed {
+            let track = session.track.clone();
+            match self
+                .radio
+                .record_early_skip(user_id, &track, session.position_ms)
+                .await
+            {
+                Ok(skipped) => session.skipped = skipped,
+                Err(e) => warn!("Failed to record skip for user {}: {}", user_id, e),
+            }
+        }
+
+        if session.is_scrobblable() {
+            let scrobble = Scrobble::Listen {
+                user_id: user_id.to_string(),
+                session_id,
+                track: session.track.clone(),
+                listened_at: session.started_at,
+            };
+            match self.scrobbler.enqueue(scrobble).await {
+                Ok(queued) => session.scrobbled = queued,
+                Err(e) => warn!("Failed to queue scrobble for user {}: {}", user_id, e),
+            }
+        }
+
+        debug!(
+            "Session {} {}: {}s played, completed {}",
+            session_id,
+            reason.as_str(),
+            session.duration_played(),
+            session.completed
+        );
+        Ok(Some(session))
+    }
+
+    /// Move a session to `position_ms`, closing it when a reason is given
+    async fn record_position(
+        &self,
+        session: &ListeningSession,
+        position_ms: u64,
+        reason: Option<EndReason>,
+    ) -> Result<ListeningSession> {
+        let now = Utc::now();
+        let last_report_at: Option<i64> =
+            sqlx::query_scalar("SELECT last_report_at FROM listening_sessions WHERE id = ?")
+                .bind(session.id)
+                .fetch_one(self.database.pool())
+                .await?;
+        let elapsed = now.timestamp() - last_report_at.unwrap_or(session.started_at.timestamp());
+
+        let mut updated = session.clone();
+        updated.played_ms += heard_ms(session.position_ms, position_ms, elapsed);
+        updated.position_ms = position_ms;
+        if let Some(reason) = reason {
+            updated.ended_at = Some(now);
+            updated.end_reason = Some(reason);
+            updated.completed = updated.is_complete(reason);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE listening_sessions
+            SET position_ms = ?, played_ms = ?, duration_played = ?, last_report_at = ?,
+                ended_at = ?, end_reason = ?, completed = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(updated.position_ms as i64)
+        .bind(updated.played_ms as i64)
+        .bind(updated.duration_played() as i64)
+        .bind(now.timestamp())
+        .bind(updated.ended_at.map(|at| at.timestamp()))
+        .bind(updated.end_reason.map(|reason| reason.as_str()))
+        .bind(updated.completed)
+        .bind(session.id)
+        .execute(self.database.pool())
+        .await?;
+
+        Ok(updated)
+    }
+
+    /// The reported track, filled in from Navidrome where the player left gaps
+    async fn describe(&self, report: NowPlayingReport) -> (QueueTrack, Option<String>) {
+        let mut track = QueueTrack {
+            title: report.title,
+            artist: report.artist,
+            album: report.album,
+            duration_secs: report.duration_secs,
+            ..QueueTrack::new(report.track_id)
+        };
+        let mut path = report.path;
+
+        let incomplete = track.title.is_none()
+            || track.artist.is_none()
+            || track.duration_secs.is_none()
+            || path.is_none();
+        if let (true, Some(navidrome)) = (incomplete, &self.navidrome) {
+            match navidrome.get_song(&track.id).await {
+                Ok(Some(song)) => {
+                    path = path.or(song.path.clone());
+                    let song = QueueTrack::from(song);
+                    track.title = track.title.or(song.title);
+                    track.artist = track.artist.or(song.artist);
+                    track.album = track.album.or(song.album);
+                    track.duration_secs = track.duration_secs.or(song.duration_secs);
+                    track.genre = song.genre;
+                }
+                Ok(None) => debug!("Navidrome doesn't know track {}", track.id),
+                Err(e) => warn!("Failed to look up track {}: {}", track.id, e),
+            }
+        }
+        (track, path)
+    }
+}
+
+fn session_from_row(row: &SqliteRow) -> ListeningSession {
+    let track_id: Option<String> = row.get("track_id");
+    let track_path: String = row.get("track_path");
+    let track = row
+        .get::<Option<String>, _>("track_json")
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(|| QueueTrack::new(track_id.unwrap_or_else(|| track_path.clone())));
+    let timestamp = |seconds: i64| Utc.timestamp_opt(seconds, 0).single().unwrap_or_default();
+    let end_reason: Option<String> = row.get("end_reason");
+
+    ListeningSession {
+        id: row.get("id"),
+        user_id: row.get::<i64, _>("user_id").to_string(),
+        track,
+        track_path,
+        source: row.get("source"),
+        started_at: timestamp(row.get("started_at")),
+        ended_at: row.get::<Option<i64>, _>("ended_at").map(timestamp),
+        position_ms: row.get::<i64, _>("position_ms") as u64,
+        played_ms: row.get::<i64, _>("played_ms") as u64,
+        completed: row.get::<Option<bool>, _>("completed").unwrap_or(false),
+        end_reason: end_reason.as_deref().and_then(EndReason::parse),
+        skipped: false,
+        scrobbled: row
+            .get::<Option<bool>, _>("scrobbled_listenbrainz")
+            .unwrap_or(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::play_queue::PlayQueueService;
+    use crate::services::radio::RadioConfig;
+    use crate::services::scrobble::ScrobbleConfig;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_heard_ms_ignores_seeks() {
+        assert_eq!(heard_ms(0, 10_000, 10), 10_000);
+        // Jumped ahead a minute in ten seconds
+        assert_eq!(heard_ms(10_000, 70_000, 10), 11_000);
+        // Went back
+        assert_eq!(heard_ms(70_000, 5_000, 10), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_record_plays_and_skips() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let play_queues = Arc::new(PlayQueueService::new(database.clone()));
+        let radio = Arc::new(RadioService::new(
+            RadioConfig::default(),
+            database.clone(),
+            play_queues,
+            None,
+        ));
+        let scrobbler = Arc::new(Scrobbler::new(
+            ScrobbleConfig::default(),
+            database.clone(),
+            None,
+        ));
+        let playback = PlaybackService::new(database.clone(), radio, scrobbler, None);
+
+        let report = |id: &str, duration_secs: u32| NowPlayingReport {
+            track_id: id.to_string(),
+            title: Some(format!("Song {}", id)),
+            artist: Some("Band".to_string()),
+            album: None,
+            duration_secs: Some(duration_secs),
+            path: Some(format!("Band/{}.flac", id)),
+            source: Some("web".to_string()),
+            client: serde_json::json!({"user_agent": "test"}),
+            position_ms: 0,
+        };
+
+        let first = playback.now_playing("1", report("a", 200)).await.unwrap();
+        assert_eq!(first.track_path, "Band/a.flac");
+        assert_eq!(first.source.as_deref(), Some("web"));
+        let progress = playback
+            .progress("1", first.id, 1_000)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(progress.played_ms, 1_000);
+
+        // Skipped a second in
+        let ended = playback
+            .end(
+                "1",
+                first.id,
+                EndReport {
+                    position_ms: None,
+                    skipped: true,
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ended.end_reason, Some(EndReason::Skipped));
+        assert!(!ended.completed);
+        assert!(ended.skipped);
+        assert!(!ended.scrobbled);
+        let skips: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM track_skips WHERE track_id = 'a'")
+                .fetch_one(database.pool())
+                .await
+                .unwrap();
+        assert_eq!(skips, 1);
+
+        // Ending twice does nothing
+        assert!(playback
+            .end(
+                "1",
+                first.id,
+                EndReport {
+                    position_ms: None,
+                    skipped: false,
+                },
+            )
+            .await
+            .unwrap()
+            .is_none());
+
+        // A track that never reports its end is closed by the next one
+        let second = playback.now_playing("1", report("b", 2)).await.unwrap();
+        let third = playback.now_playing("1", report("c", 200)).await.unwrap();
+        let replaced = playback.get("1", second.id).await.unwrap().unwrap();
+        assert_eq!(replaced.end_reason, Some(EndReason::Replaced));
+        assert!(replaced.ended_at.is_some());
+        assert!(playback
+            .get("1", third.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .ended_at
+            .is_none());
+
+        let recent = playback.recent("1", 10).await.unwrap();
+        assert_eq!(
+            recent.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![third.id, second.id, first.id]
+        );
+    }
+}
@@ -0,0 +1,565 @@
+//! Release quality profiles for StepheyBot Music
+//!
+//! Quality profiles decide which indexer releases are acceptable for a direct
+//! download and rank the acceptable ones, so the download pipeline can pick a
+//! release automatically and explain why every alternative was passed over.
+
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+
+use crate::clients::torznab::ReleaseCandidate;
+
+/// Where a release was sourced from, as advertised in its title
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseSource {
+    Cd,
+    Web,
+    Vinyl,
+    Scene,
+    Unknown,
+}
+
+impl ReleaseSource {
+    /// Detect the release source from a release title
+    pub fn detect(title: &str) -> Self {
+        let trimmed = title.trim();
+
+        // Scene releases use dotted/dashed names without spaces and end in a
+        // group tag, e.g. "Artist-Album-WEB-2020-GRP"
+        if !trimmed.contains(' ') && trimmed.contains('-') {
+            if let Some(group) = trimmed.rsplit('-').next() {
+                if !group.is_empty() && group.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return ReleaseSource::Scene;
+                }
+            }
+        }
+
+        let tokens = tokenize(trimmed);
+        if tokens.iter().any(|t| t == "vinyl" || t == "vinylrip") {
+            ReleaseSource::Vinyl
+        } else if tokens.iter().any(|t| t == "web" || t == "webrip") {
+            ReleaseSource::Web
+        } else if tokens.iter().any(|t| t == "cd" || t == "cdrip") {
+            ReleaseSource::Cd
+        } else {
+            ReleaseSource::Unknown
+        }
+    }
+}
+
+impl ReleaseSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReleaseSource::Cd => "cd",
+            ReleaseSource::Web => "web",
+            ReleaseSource::Vinyl => "vinyl",
+            ReleaseSource::Scene => "scene",
+            ReleaseSource::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::str::FromStr for ReleaseSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "cd" => Ok(ReleaseSource::Cd),
+            "web" => Ok(ReleaseSource::Web),
+            "vinyl" => Ok(ReleaseSource::Vinyl),
+            "scene" => Ok(ReleaseSource::Scene),
+            "unknown" => Ok(ReleaseSource::Unknown),
+            other => Err(format!("Unknown release source: {}", other)),
+        }
+    }
+}
+
+/// Why a candidate release was not selected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum RejectionReason {
+    FormatNotAllowed { format: String },
+    BitrateTooLow { bitrate: u32, minimum: u32 },
+    TooLarge { size: u64, maximum: u64 },
+    MissingRequiredWord { word: String },
+    ForbiddenWord { word: String },
+    NotEnoughSeeders { seeders: u32, minimum: u32 },
+    NoMagnetLink,
+    Outscored { selected: String, score: f64 },
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectionReason::FormatNotAllowed { format } => {
+                write!(f, "format {} is not allowed by the profile", format)
+            }
+            RejectionReason::BitrateTooLow { bitrate, minimum } => {
+                write!(f, "bitrate {} kbps is below {} kbps", bitrate, minimum)
+            }
+            RejectionReason::TooLarge { size, maximum } => write!(
+                f,
+                "size {} MB exceeds {} MB",
+                size / 1024 / 1024,
+                maximum / 1024 / 1024
+            ),
+            RejectionReason::MissingRequiredWord { word } => {
+                write!(f, "title does not contain required word '{}'", word)
+            }
+            RejectionReason::ForbiddenWord { word } => {
+                write!(f, "title contains forbidden word '{}'", word)
+            }
+            RejectionReason::NotEnoughSeeders { seeders, minimum } => {
+                write!(f, "{} seeders is below the minimum of {}", seeders, minimum)
+            }
+            RejectionReason::NoMagnetLink => write!(f, "no magnet link available"),
+            RejectionReason::Outscored { selected, score } => {
+                write!(f, "outscored by '{}' ({:.3})", selected, score)
+            }
+        }
+    }
+}
+
+/// Tracks assumed for an album whose track list couldn't be looked up
+pub const ESTIMATED_ALBUM_TRACKS: u32 = 12;
+
+/// What the release is being selected for
+#[derive(Debug, Clone, Default)]
+pub struct SelectionContext {
+    /// Text of the request (artist, album, track); forbidden words that the
+    /// user actually asked for are not held against a release
+    pub requested: String,
+    /// Number of tracks expected in the release, when known
+    pub track_count: Option<u32>,
+}
+
+/// Outcome of evaluating a single candidate against a profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandidateEvaluation {
+    pub candidate: ReleaseCandidate,
+    pub source: ReleaseSource,
+    pub score: f64,
+    pub selected: bool,
+    pub rejections: Vec<RejectionReason>,
+}
+
+/// Result of choosing between candidates, best first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseSelection {
+    pub evaluations: Vec<CandidateEvaluation>,
+}
+
+impl ReleaseSelection {
+    /// The chosen release, if any candidate was acceptable
+    pub fn selected(&self) -> Option<&CandidateEvaluation> {
+        self.evaluations.iter().find(|e| e.selected)
+    }
+
+    /// Number of candidates that were passed over
+    pub fn rejected_count(&self) -> usize {
+        self.evaluations.iter().filter(|e| !e.selected).count()
+    }
+}
+
+/// Configurable rules for accepting and ranking releases
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityProfile {
+    pub name: String,
+    /// Allowed formats, most preferred first; empty allows any format
+    pub preferred_formats: Vec<String>,
+    /// Minimum bitrate for lossy formats, in kbps
+    pub min_bitrate: Option<u32>,
+    /// Maximum size per track, in megabytes
+    pub max_track_size_mb: Option<u64>,
+    pub required_words: Vec<String>,
+    pub forbidden_words: Vec<String>,
+    pub min_seeders: u32,
+    /// Preferred release sources, most preferred first
+    pub preferred_sources: Vec<ReleaseSource>,
+}
+
+impl Default for QualityProfile {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl QualityProfile {
+    /// Prefer lossless, accept good lossy encodes
+    pub fn standard() -> Self {
+        Self {
+            name: "standard".to_string(),
+            preferred_formats: strings(&["flac", "alac", "mp3", "aac"]),
+            min_bitrate: Some(192),
+            max_track_size_mb: Some(150),
+            required_words: Vec::new(),
+            forbidden_words: strings(&["karaoke", "live", "tribute"]),
+            min_seeders: 1,
+            preferred_sources: vec![
+                ReleaseSource::Cd,
+                ReleaseSource::Web,
+                ReleaseSource::Vinyl,
+                ReleaseSource::Scene,
+            ],
+        }
+    }
+
+    /// Only accept lossless releases
+    pub fn lossless() -> Self {
+        Self {
+            name: "lossless".to_string(),
+            preferred_formats: strings(&["flac", "alac"]),
+            min_bitrate: None,
+            max_track_size_mb: Some(300),
+            ..Self::standard()
+        }
+    }
+
+    /// Accept anything that is seeded
+    pub fn any() -> Self {
+        Self {
+            name: "any".to_string(),
+            preferred_formats: Vec::new(),
+            min_bitrate: None,
+            max_track_size_mb: None,
+            required_words: Vec::new(),
+            forbidden_words: Vec::new(),
+            min_seeders: 1,
+            preferred_sources: Vec::new(),
+        }
+    }
+
+    /// Look up a built-in profile by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "standard" => Some(Self::standard()),
+            "lossless" => Some(Self::lossless()),
+            "any" => Some(Self::any()),
+            _ => None,
+        }
+    }
+
+    /// Load the active profile from the environment
+    ///
+    /// `STEPHEYBOT__QUALITY__PROFILE` selects a built-in profile, and the
+    /// individual `STEPHEYBOT__QUALITY__*` variables override its rules.
+    pub fn from_env() -> Self {
+        let mut profile = env::var("STEPHEYBOT__QUALITY__PROFILE")
+            .ok()
+            .and_then(|name| Self::builtin(&name))
+            .unwrap_or_default();
+
+        if let Ok(formats) = env::var("STEPHEYBOT__QUALITY__PREFERRED_FORMATS") {
+            profile.preferred_formats = split_list(&formats);
+        }
+        if let Ok(bitrate) = env::var("STEPHEYBOT__QUALITY__MIN_BITRATE") {
+            profile.min_bitrate = bitrate.parse().ok().filter(|b| *b > 0);
+        }
+        if let Ok(size) = env::var("STEPHEYBOT__QUALITY__MAX_TRACK_SIZE_MB") {
+            profile.max_track_size_mb = size.parse().ok().filter(|s| *s > 0);
+        }
+        if let Ok(words) = env::var("STEPHEYBOT__QUALITY__REQUIRED_WORDS") {
+            profile.required_words = split_list(&words);
+        }
+        if let Ok(words) = env::var("STEPHEYBOT__QUALITY__FORBIDDEN_WORDS") {
+            profile.forbidden_words = split_list(&words);
+        }
+        if let Ok(seeders) = env::var("STEPHEYBOT__QUALITY__MIN_SEEDERS") {
+            profile.min_seeders = seeders.parse().unwrap_or(profile.min_seeders);
+        }
+        if let Ok(sources) = env::var("STEPHEYBOT__QUALITY__PREFERRED_SOURCES") {
+            profile.preferred_sources = split_list(&sources)
+                .iter()
+                .filter_map(|s| s.parse().ok())
+                .collect();
+        }
+
+        profile
+    }
+
+    /// Check a candidate against every rule and score it
+    pub fn evaluate(
+        &self,
+        candidate: &ReleaseCandidate,
+        context: &SelectionContext,
+    ) -> CandidateEvaluation {
+        let mut rejections = Vec::new();
+        let title = format!(" {} ", tokenize(&candidate.title).join(" "));
+        let requested = format!(" {} ", tokenize(&context.requested).join(" "));
+
+        let format_preference = match &candidate.format {
+            _ if self.preferred_formats.is_empty() => 0.5,
+            Some(format) => match self.preferred_formats.iter().position(|f| f == format) {
+                Some(index) => 1.0 - index as f64 / self.preferred_formats.len() as f64,
+                None => {
+                    rejections.push(RejectionReason::FormatNotAllowed {
+                        format: format.clone(),
+                    });
+                    0.0
+                }
+            },
+            // Unlabelled releases are allowed but ranked below labelled ones
+            None => 0.0,
+        };
+
+        if let (Some(minimum), Some(bitrate)) = (self.min_bitrate, candidate.bitrate) {
+            if !candidate.is_lossless() && bitrate < minimum {
+                rejections.push(RejectionReason::BitrateTooLow { bitrate, minimum });
+            }
+        }
+
+        if let (Some(max_mb), Some(tracks)) = (self.max_track_size_mb, context.track_count) {
+            let maximum = max_mb * 1024 * 1024 * tracks.max(1) as u64;
+            if candidate.size > maximum {
+                rejections.push(RejectionReason::TooLarge {
+                    size: candidate.size,
+                    maximum,
+                });
+            }
+        }
+
+        for word in &self.required_words {
+            if !contains_phrase(&title, word) {
+                rejections.push(RejectionReason::MissingRequiredWord { word: word.clone() });
+            }
+        }
+
+        for word in &self.forbidden_words {
+            if contains_phrase(&title, word) && !contains_phrase(&requested, word) {
+                rejections.push(RejectionReason::ForbiddenWord { word: word.clone() });
+            }
+        }
+
+        if let Some(seeders) = candidate.seeders {
+            if seeders < self.min_seeders {
+                rejections.push(RejectionReason::NotEnoughSeeders {
+                    seeders,
+                    minimum: self.min_seeders,
+                });
+            }
+        }
+
+        if candidate.magnet().is_none() {
+            rejections.push(RejectionReason::NoMagnetLink);
+        }
+
+        let source = ReleaseSource::detect(&candidate.title);
+        let source_preference = match self.preferred_sources.iter().position(|s| *s == source) {
+            Some(index) => 1.0 - index as f64 / self.preferred_sources.len() as f64,
+            None => 0.0,
+        };
+
+        let score =
+            format_preference * 0.5 + source_preference * 0.2 + candidate.score.min(1.0) * 0.3;
+
+        CandidateEvaluation {
+            candidate: candidate.clone(),
+            source,
+            score: (score * 1000.0).round() / 1000.0,
+            selected: false,
+            rejections,
+        }
+    }
+
+    /// Evaluate all candidates and select the best acceptable one
+    ///
+    /// Every candidate that is not selected carries at least one rejection
+    /// reason, including acceptable ones that were simply outscored.
+    pub fn select(
+        &self,
+        candidates: &[ReleaseCandidate],
+        context: &SelectionContext,
+    ) -> ReleaseSelection {
+        let mut evaluations: Vec<CandidateEvaluation> = candidates
+            .iter()
+            .map(|candidate| self.evaluate(candidate, context))
+            .collect();
+
+        evaluations.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.candidate.seeders.cmp(&a.candidate.seeders))
+        });
+
+        let best = evaluations
+            .iter()
+            .position(|e| e.rejections.is_empty())
+            .map(|index| {
+                evaluations[index].selected = true;
+                (
+                    evaluations[index].candidate.title.clone(),
+                    evaluations[index].score,
+                )
+            });
+
+        if let Some((selected, score)) = best {
+            for evaluation in evaluations
+                .iter_mut()
+                .filter(|e| !e.selected && e.rejections.is_empty())
+            {
+                evaluation.rejections.push(RejectionReason::Outscored {
+                    selected: selected.clone(),
+                    score,
+                });
+            }
+        }
+
+        ReleaseSelection { evaluations }
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whole-word match of a (possibly multi-word) phrase in padded, tokenized text
+fn contains_phrase(padded_tokens: &str, phrase: &str) -> bool {
+    let phrase = tokenize(phrase).join(" ");
+    !phrase.is_empty() && padded_tokens.contains(&format!(" {} ", phrase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::torznab::parse_torznab_response;
+
+    fn candidates(items: &[(&str, u32, u64)]) -> Vec<ReleaseCandidate> {
+        let items: String = items
+            .iter()
+            .enumerate()
+            .map(|(i, (title, seeders, size))| {
+                format!(
+                    r#"<item><title>{}</title><guid>{}</guid><size>{}</size>
+                    <torznab:attr name="seeders" value="{}"/>
+                    <torznab:attr name="infohash" value="{:040x}"/></item>"#,
+                    title, i, size, seeders, i
+                )
+            })
+            .collect();
+        let xml = format!(
+            r#"<rss xmlns:torznab="http://torznab.com/schemas/2015/feed"><channel>{}</channel></rss>"#,
+            items
+        );
+        parse_torznab_response(&xml, "mock").unwrap()
+    }
+
+    #[test]
+    fn test_release_source_detection() {
+        assert_eq!(
+            ReleaseSource::detect("Artist-Album-WEB-2020-GRP"),
+            ReleaseSource::Scene
+        );
+        assert_eq!(
+            ReleaseSource::detect("Artist - Album (2020) [WEB FLAC]"),
+            ReleaseSource::Web
+        );
+        assert_eq!(
+            ReleaseSource::detect("Artist - Album (1999) [CD FLAC]"),
+            ReleaseSource::Cd
+        );
+        assert_eq!(
+            ReleaseSource::detect("Artist - Album [MP3]"),
+            ReleaseSource::Unknown
+        );
+    }
+
+    #[test]
+    fn test_select_prefers_flac_and_records_rejections() {
+        let mb = 1024 * 1024;
+        let releases = candidates(&[
+            ("Artist - Album [MP3 320]", 50, 100 * mb),
+            ("Artist - Album [CD FLAC]", 10, 400 * mb),
+            ("Artist - Album [MP3 128]", 90, 60 * mb),
+            ("Artist - Album (Live) [FLAC]", 30, 400 * mb),
+            ("Artist - Album Karaoke [FLAC]", 30, 400 * mb),
+            ("Artist - Album [FLAC] dead", 0, 400 * mb),
+            ("Artist - Album [FLAC] huge", 40, 4000 * mb),
+        ]);
+
+        let context = SelectionContext {
+            requested: "Artist Album".to_string(),
+            track_count: Some(10),
+        };
+        let selection = QualityProfile::standard().select(&releases, &context);
+
+        let selected = selection.selected().unwrap();
+        assert_eq!(selected.candidate.title, "Artist - Album [CD FLAC]");
+        assert_eq!(selected.source, ReleaseSource::Cd);
+        assert_eq!(selection.rejected_count(), releases.len() - 1);
+
+        let reasons = |title: &str| {
+            selection
+                .evaluations
+                .iter()
+                .find(|e| e.candidate.title == title)
+                .unwrap()
+                .rejections
+                .clone()
+        };
+
+        assert!(matches!(
+            reasons("Artist - Album [MP3 320]")[0],
+            RejectionReason::Outscored { .. }
+        ));
+        assert!(
+            reasons("Artist - Album [MP3 128]").contains(&RejectionReason::BitrateTooLow {
+                bitrate: 128,
+                minimum: 192
+            })
+        );
+        assert!(reasons("Artist - Album (Live) [FLAC]").contains(
+            &RejectionReason::ForbiddenWord {
+                word: "live".to_string()
+            }
+        ));
+        assert!(matches!(
+            reasons("Artist - Album [FLAC] dead")[0],
+            RejectionReason::NotEnoughSeeders { .. }
+        ));
+        assert!(matches!(
+            reasons("Artist - Album [FLAC] huge")[0],
+            RejectionReason::TooLarge { .. }
+        ));
+
+        // Asking for the live album exempts the word
+        let live = SelectionContext {
+            requested: "Artist Album Live".to_string(),
+            track_count: None,
+        };
+        let evaluation = QualityProfile::standard().evaluate(&releases[3], &live);
+        assert!(evaluation.rejections.is_empty());
+    }
+
+    #[test]
+    fn test_lossless_profile_rejects_lossy() {
+        let releases = candidates(&[("Artist - Album [MP3 320]", 50, 0)]);
+        let selection = QualityProfile::lossless().select(&releases, &SelectionContext::default());
+
+        assert!(selection.selected().is_none());
+        assert_eq!(
+            selection.evaluations[0].rejections,
+            vec![RejectionReason::FormatNotAllowed {
+                format: "mp3".to_string()
+            }]
+        );
+    }
+}
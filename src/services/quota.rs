@@ -0,0 +1,226 @@
+//! Download quotas for StepheyBot Music
+//!
+//! Every download request is attributed to the user who made it and checked
+//! against the limits of their role before it is queued. Roles can also be
+//! required to wait for an admin to approve their requests.
+
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::models::user::{AuthenticatedUser, UserRole};
+
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Who is asking for a download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Requester {
+    pub user_id: String,
+    pub username: String,
+    pub role: UserRole,
+}
+
+impl Requester {
+    /// Requests made by the server itself or without authentication configured
+    pub fn system() -> Self {
+        Self {
+            user_id: "system".to_string(),
+            username: "system".to_string(),
+            role: UserRole::Admin,
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == UserRole::Admin
+    }
+}
+
+impl From<&AuthenticatedUser> for Requester {
+    fn from(user: &AuthenticatedUser) -> Self {
+        Self {
+            user_id: user.id.to_string(),
+            username: user.username.clone(),
+            role: user.role(),
+        }
+    }
+}
+
+/// Limits that apply to one role; `None` means unlimited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    pub requests_per_day: Option<u32>,
+    pub gb_per_month: Option<f64>,
+    pub concurrent_downloads: Option<u32>,
+    pub requires_approval: bool,
+}
+
+/// What a user has used so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub requests_today: u32,
+    pub bytes_this_month: u64,
+    pub active_downloads: u32,
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[serde(tag = "quota", rename_all = "snake_case")]
+pub enum QuotaExceeded {
+    #[error("Daily request limit reached ({used}/{limit})")]
+    RequestsPerDay { used: u32, limit: u32 },
+    #[error("Monthly download volume exceeded ({used_gb:.1}/{limit_gb:.1} GB)")]
+    GbPerMonth { used_gb: f64, limit_gb: f64 },
+    #[error("Too many active downloads ({active}/{limit})")]
+    ConcurrentDownloads { active: u32, limit: u32 },
+}
+
+/// Quota limits per role
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaPolicy {
+    pub admin: QuotaLimits,
+    pub user: QuotaLimits,
+    pub guest: QuotaLimits,
+    /// Whether unauthenticated requests are accepted, attributed to "system"
+    pub allow_anonymous: bool,
+}
+
+impl Default for QuotaPolicy {
+    fn default() -> Self {
+        Self {
+            admin: QuotaLimits::default(),
+            user: QuotaLimits {
+                requests_per_day: Some(25),
+                gb_per_month: Some(50.0),
+                concurrent_downloads: Some(3),
+                requires_approval: true,
+            },
+            guest: QuotaLimits {
+                requests_per_day: Some(5),
+                gb_per_month: Some(5.0),
+                concurrent_downloads: Some(1),
+                requires_approval: true,
+            },
+            allow_anonymous: true,
+        }
+    }
+}
+
+impl QuotaPolicy {
+    /// Load the policy, overriding defaults from
+    /// `STEPHEYBOT__QUOTAS__<ROLE>__<LIMIT>` (0 means unlimited)
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        for (role, limits) in [
+            ("ADMIN", &mut policy.admin),
+            ("USER", &mut policy.user),
+            ("GUEST", &mut policy.guest),
+        ] {
+            let var = |name: &str| env::var(format!("STEPHEYBOT__QUOTAS__{}__{}", role, name)).ok();
+
+            if let Some(value) = var("REQUESTS_PER_DAY").and_then(|v| v.parse::<u32>().ok()) {
+                limits.requests_per_day = (value > 0).then_some(value);
+            }
+            if let Some(value) = var("GB_PER_MONTH").and_then(|v| v.parse::<f64>().ok()) {
+                limits.gb_per_month = (value > 0.0).then_some(value);
+            }
+            if let Some(value) = var("CONCURRENT_DOWNLOADS").and_then(|v| v.parse::<u32>().ok()) {
+                limits.concurrent_downloads = (value > 0).then_some(value);
+            }
+            if let Some(value) = var("REQUIRE_APPROVAL").and_then(|v| v.parse::<bool>().ok()) {
+                limits.requires_approval = value;
+            }
+        }
+        policy
+    }
+
+    /// Limits for a role
+    pub fn limits(&self, role: UserRole) -> &QuotaLimits {
+        match role {
+            UserRole::Admin => &self.admin,
+            UserRole::User => &self.user,
+            UserRole::Guest => &self.guest,
+        }
+    }
+
+    /// Check whether a new request of `expected_bytes` fits in the quota
+    pub fn check(
+        &self,
+        role: UserRole,
+        usage: &QuotaUsage,
+        expected_bytes: Option<u64>,
+    ) -> Result<(), QuotaExceeded> {
+        let limits = self.limits(role);
+
+        if let Some(limit) = limits.requests_per_day {
+            if usage.requests_today >= limit {
+                return Err(QuotaExceeded::RequestsPerDay {
+                    used: usage.requests_today,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = limits.concurrent_downloads {
+            if usage.active_downloads >= limit {
+                return Err(QuotaExceeded::ConcurrentDownloads {
+                    active: usage.active_downloads,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit_gb) = limits.gb_per_month {
+            let used = usage.bytes_this_month + expected_bytes.unwrap_or(0);
+            let used_gb = used as f64 / BYTES_PER_GB;
+            if used_gb > limit_gb {
+                return Err(QuotaExceeded::GbPerMonth { used_gb, limit_gb });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_check() {
+        let policy = QuotaPolicy::default();
+        let usage = QuotaUsage {
+            requests_today: 5,
+            bytes_this_month: 4 * BYTES_PER_GB as u64,
+            active_downloads: 0,
+        };
+
+        // Admins are unlimited by default
+        assert!(policy.check(UserRole::Admin, &usage, None).is_ok());
+        assert!(policy.check(UserRole::User, &usage, None).is_ok());
+        assert_eq!(
+            policy.check(UserRole::Guest, &usage, None),
+            Err(QuotaExceeded::RequestsPerDay { used: 5, limit: 5 })
+        );
+
+        let usage = QuotaUsage {
+            requests_today: 0,
+            ..usage
+        };
+        assert!(policy.check(UserRole::Guest, &usage, None).is_ok());
+        assert!(matches!(
+            policy.check(UserRole::Guest, &usage, Some(2 * BYTES_PER_GB as u64)),
+            Err(QuotaExceeded::GbPerMonth { .. })
+        ));
+
+        let usage = QuotaUsage {
+            active_downloads: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.check(UserRole::User, &usage, None),
+            Err(QuotaExceeded::ConcurrentDownloads {
+                active: 3,
+                limit: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,928 @@
+//! Endless radio for StepheyBot Music
+//!
+//! A station is seeded from a track, artist, album, playlist or genre. When
+//! the user's play queue runs low it is topped up with tracks drawn from the
+//! seed, from songs similar to what is queued and from the wider library,
+//! mixed according to the user's `DiscoveryMode`. Artists are not repeated
+//! within a window, and tracks skipped early count against themselves and
+//! their artist for a while.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use crate::clients::navidrome::NavidromeClient;
+use crate::database::Database;
+use crate::models::user::{DiscoveryMode, UserPreferences};
+use crate::services::play_queue::{PlayQueue, PlayQueueService, QueueTrack};
+
+/// How stations pick and extend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RadioConfig {
+    /// Tracks added each time the queue is topped up
+    pub batch_size: usize,
+    /// Top up once no more than this many tracks are left
+    pub low_water: usize,
+    /// An artist is not repeated within this many tracks
+    pub artist_window: usize,
+    /// Moving on before this point counts as a skip
+    pub skip_threshold_ms: u64,
+    /// How long skips count against a track or artist
+    pub skip_memory_days: i64,
+}
+
+impl Default for RadioConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 10,
+            low_water: 3,
+            artist_window: 5,
+            skip_threshold_ms: 30_000,
+            skip_memory_days: 90,
+        }
+    }
+}
+
+impl RadioConfig {
+    /// Load the configuration, overriding defaults from `STEPHEYBOT__RADIO__*`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(size) = var("STEPHEYBOT__RADIO__BATCH_SIZE").and_then(|v| v.parse().ok()) {
+            config.batch_size = size;
+        }
+        if let Some(low) = var("STEPHEYBOT__RADIO__LOW_WATER").and_then(|v| v.parse().ok()) {
+            config.low_water = low;
+        }
+        if let Some(window) = var("STEPHEYBOT__RADIO__ARTIST_WINDOW").and_then(|v| v.parse().ok()) {
+            config.artist_window = window;
+        }
+        if let Some(secs) =
+            var("STEPHEYBOT__RADIO__SKIP_THRESHOLD_SECONDS").and_then(|v| v.parse::<u64>().ok())
+        {
+            config.skip_threshold_ms = secs * 1000;
+        }
+        if let Some(days) = var("STEPHEYBOT__RADIO__SKIP_MEMORY_DAYS").and_then(|v| v.parse().ok())
+        {
+            config.skip_memory_days = days;
+        }
+
+        config
+    }
+}
+
+/// What a station is built around
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum RadioSeed {
+    /// A Navidrome song ID
+    Track(String),
+    /// An artist name
+    Artist(String),
+    /// A Navidrome album ID
+    Album(String),
+    /// A Navidrome playlist ID
+    Playlist(String),
+    Genre(String),
+}
+
+impl RadioSeed {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RadioSeed::Track(_) => "track",
+            RadioSeed::Artist(_) => "artist",
+            RadioSeed::Album(_) => "album",
+            RadioSeed::Playlist(_) => "playlist",
+            RadioSeed::Genre(_) => "genre",
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            RadioSeed::Track(value)
+            | RadioSeed::Artist(value)
+            | RadioSeed::Album(value)
+            | RadioSeed::Playlist(value)
+            | RadioSeed::Genre(value) => value,
+        }
+    }
+
+    pub fn from_parts(kind: &str, value: String) -> Option<Self> {
+        match kind {
+            "track" => Some(RadioSeed::Track(value)),
+            "artist" => Some(RadioSeed::Artist(value)),
+            "album" => Some(RadioSeed::Album(value)),
+            "playlist" => Some(RadioSeed::Playlist(value)),
+            "genre" => Some(RadioSeed::Genre(value)),
+            _ => None,
+        }
+    }
+}
+
+/// Where a candidate track came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    /// The seed itself: the track, the artist's top songs, the album...
+    Seed,
+    /// Similar to the seed or to what is queued
+    Similar,
+    /// Anywhere else in the library
+    Library,
+}
+
+impl Origin {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Origin::Seed => "From what the station is built around",
+            Origin::Similar => "Similar to what you've been playing",
+            Origin::Library => "Something different from your library",
+        }
+    }
+}
+
+/// A track the station could play
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub track: QueueTrack,
+    pub origin: Origin,
+}
+
+/// A chosen track and why
+#[derive(Debug, Clone, Serialize)]
+pub struct RadioPick {
+    pub track: QueueTrack,
+    pub origin: Origin,
+    pub score: f64,
+}
+
+/// Body of a radio start
+#[derive(Debug, Clone, Deserialize)]
+pub struct StartRadio {
+    pub seed: RadioSeed,
+    /// Overrides the user's preferred discovery mode for this station
+    pub discovery_mode: Option<DiscoveryMode>,
+    pub device: Option<String>,
+}
+
+/// A user's running station
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RadioStation {
+    pub user_id: String,
+    /// `None` for one-off recommendations that aren't a station
+    pub seed: Option<RadioSeed>,
+    pub seed_genres: Vec<String>,
+    pub discovery_mode: DiscoveryMode,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Recent early skips of a user
+#[derive(Debug, Clone, Default)]
+pub struct SkipFeedback {
+    tracks: HashSet<String>,
+    artists: HashMap<String, u32>,
+}
+
+impl SkipFeedback {
+    pub fn record(&mut self, track_id: &str, artist: Option<&str>) {
+        self.tracks.insert(track_id.to_string());
+        if let Some(artist) = artist {
+            *self.artists.entry(artist_key(artist)).or_default() += 1;
+        }
+    }
+
+    fn artist_skips(&self, artist: &str) -> u32 {
+        self.artists.get(&artist_key(artist)).copied().unwrap_or(0)
+    }
+}
+
+fn artist_key(artist: &str) -> String {
+    artist.trim().to_lowercase()
+}
+
+/// How much each source counts for in a discovery mode
+fn origin_weight(mode: DiscoveryMode, origin: Origin) -> f64 {
+    match (mode, origin) {
+        (DiscoveryMode::Conservative, Origin::Seed) => 1.0,
+        (DiscoveryMode::Conservative, Origin::Similar) => 0.8,
+        (DiscoveryMode::Conservative, Origin::Library) => 0.2,
+        (DiscoveryMode::Balanced, Origin::Seed) => 0.8,
+        (DiscoveryMode::Balanced, Origin::Similar) => 0.8,
+        (DiscoveryMode::Balanced, Origin::Library) => 0.5,
+        (DiscoveryMode::Adventurous, Origin::Seed) => 0.5,
+        (DiscoveryMode::Adventurous, Origin::Similar) => 0.7,
+        (DiscoveryMode::Adventurous, Origin::Library) => 0.9,
+    }
+}
+
+/// Conservative stations stay in the seed's genres, adventurous ones leave them
+fn genre_weight(mode: DiscoveryMode, genre: Option<&str>, seed_genres: &[String]) -> f64 {
+    let Some(genre) = genre else {
+        return 1.0;
+    };
+    if seed_genres.is_empty() {
+        return 1.0;
+    }
+    let matches = seed_genres
+        .iter()
+        .any(|seed| seed.eq_ignore_ascii_case(genre));
+    match (mode, matches) {
+        (DiscoveryMode::Conservative, true) => 1.2,
+        (DiscoveryMode::Conservative, false) => 0.5,
+        (DiscoveryMode::Balanced, true) => 1.1,
+        (DiscoveryMode::Balanced, false) => 0.9,
+        (DiscoveryMode::Adventurous, true) => 0.9,
+        (DiscoveryMode::Adventurous, false) => 1.2,
+    }
+}
+
+impl RadioStation {
+    /// Pick up to `count` tracks to follow the queue.
+    ///
+    /// Queued and recently skipped tracks are left out, and each early skip
+    /// of an artist halves their score. Artists in the last `artist_window`
+    /// tracks are held back unless that leaves too few tracks, in which case
+    /// only back-to-back repeats are avoided.
+    pub fn choose<R: Rng>(
+        &self,
+        candidates: Vec<Candidate>,
+        queue: &PlayQueue,
+        feedback: &SkipFeedback,
+        config: &RadioConfig,
+        count: usize,
+        rng: &mut R,
+    ) -> Vec<RadioPick> {
+        let queued: HashSet<&str> = queue.tracks.iter().map(|t| t.id.as_str()).collect();
+        let mut seen = HashSet::new();
+        let mut scored: Vec<RadioPick> = candidates
+            .into_iter()
+            .filter(|c| !queued.contains(c.track.id.as_str()))
+            .filter(|c| !feedback.tracks.contains(&c.track.id))
+            .filter(|c| seen.insert(c.track.id.clone()))
+            .map(|c| {
+                let skips = c
+                    .track
+                    .artist
+                    .as_deref()
+                    .map_or(0, |artist| feedback.artist_skips(artist));
+                let score = origin_weight(self.discovery_mode, c.origin)
+                    * genre_weight(
+                        self.discovery_mode,
+                        c.track.genre.as_deref(),
+                        &self.seed_genres,
+                    )
+                    * 0.5f64.powi(skips.min(16) as i32)
+                    * rng.gen_range(0.85..1.0);
+                RadioPick {
+                    track: c.track,
+                    origin: c.origin,
+                    score,
+                }
+            })
+            .collect();
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        let mut recent: Vec<String> = queue
+            .play_order()
+            .iter()
+            .filter_map(|&i| queue.tracks[i].artist.as_deref())
+            .map(artist_key)
+            .collect();
+        let mut picks = Vec::new();
+        for window in [config.artist_window, 1] {
+            let mut i = 0;
+            while i < scored.len() && picks.len() < count {
+                let artist = scored[i].track.artist.as_deref().map(artist_key);
+                let repeated = artist.as_ref().is_some_and(|artist| {
+                    recent
+                        .iter()
+                        .rev()
+                        .take(window)
+                        .any(|recent| recent == artist)
+                });
+                if repeated {
+                    i += 1;
+                    continue;
+                }
+                recent.extend(artist);
+                picks.push(scored.remove(i));
+            }
+        }
+        picks
+    }
+}
+
+/// Where stations find tracks
+#[async_trait]
+pub trait RadioSource: Send + Sync {
+    /// Tracks the seed stands for: the track itself, an artist's top songs...
+    async fn seed_tracks(&self, seed: &RadioSeed, count: u32) -> Result<Vec<QueueTrack>>;
+    async fn similar_tracks(&self, track_id: &str, count: u32) -> Result<Vec<QueueTrack>>;
+    async fn library_tracks(&self, genre: Option<&str>, count: u32) -> Result<Vec<QueueTrack>>;
+}
+
+#[async_trait]
+impl RadioSource for NavidromeClient {
+    async fn seed_tracks(&self, seed: &RadioSeed, count: u32) -> Result<Vec<QueueTrack>> {
+        let songs = match seed {
+            RadioSeed::Track(id) => self.get_song(id).await?.into_iter().collect(),
+            RadioSeed::Artist(name) => self.get_top_songs(name, count).await?,
+            RadioSeed::Album(id) => self.get_album_songs(id).await?,
+            RadioSeed::Playlist(id) => self.get_playlist_songs(id).await?,
+            RadioSeed::Genre(genre) => {
+                self.get_random_songs(Some(count), Some(genre), None, None)
+                    .await?
+            }
+        };
+        Ok(songs.into_iter().map(QueueTrack::from).collect())
+    }
+
+    async fn similar_tracks(&self, track_id: &str, count: u32) -> Result<Vec<QueueTrack>> {
+        let songs = self.get_similar_songs(track_id, count).await?;
+        Ok(songs.into_iter().map(QueueTrack::from).collect())
+    }
+
+    async fn library_tracks(&self, genre: Option<&str>, count: u32) -> Result<Vec<QueueTrack>> {
+        let songs = self
+            .get_random_songs(Some(count), genre, None, None)
+            .await?;
+        Ok(songs.into_iter().map(QueueTrack::from).collect())
+    }
+}
+
+fn mode_name(mode: DiscoveryMode) -> &'static str {
+    match mode {
+        DiscoveryMode::Conservative => "Conservative",
+        DiscoveryMode::Balanced => "Balanced",
+        DiscoveryMode::Adventurous => "Adventurous",
+    }
+}
+
+fn parse_mode(name: &str) -> DiscoveryMode {
+    match name {
+        "Conservative" => DiscoveryMode::Conservative,
+        "Adventurous" => DiscoveryMode::Adventurous,
+        _ => DiscoveryMode::Balanced,
+    }
+}
+
+/// Runs stations and keeps their queues topped up
+#[derive(Clone)]
+pub struct RadioService {
+    config: RadioConfig,
+    database: Arc<Database>,
+    play_queues: Arc<PlayQueueService>,
+    source: Option<Arc<dyn RadioSource>>,
+}
+
+impl RadioService {
+    pub fn new(
+        config: RadioConfig,
+        database: Arc<Database>,
+        play_queues: Arc<PlayQueueService>,
+        source: Option<Arc<dyn RadioSource>>,
+    ) -> Self {
+        Self {
+            config,
+            database,
+            play_queues,
+            source,
+        }
+    }
+
+    /// Start a station, replacing the user's queue with its first tracks
+    pub async fn start(
+        &self,
+        user_id: &str,
+        seed: RadioSeed,
+        discovery_mode: Option<DiscoveryMode>,
+        device: Option<&str>,
+    ) -> Result<(RadioStation, PlayQueue)> {
+        let source = self.source()?;
+        let seed_tracks = source
+            .seed_tracks(&seed, self.config.batch_size as u32 * 2)
+            .await?;
+        if seed_tracks.is_empty() && !matches!(seed, RadioSeed::Genre(_)) {
+            anyhow::bail!("Nothing found for {} '{}'", seed.kind(), seed.value());
+        }
+
+        let mut seed_genres: Vec<String> = Vec::new();
+        if let RadioSeed::Genre(genre) = &seed {
+            seed_genres.push(genre.clone());
+        }
+        for genre in seed_tracks.iter().filter_map(|t| t.genre.as_ref()) {
+            if !seed_genres.iter().any(|g| g.eq_ignore_ascii_case(genre)) {
+                seed_genres.push(genre.clone());
+            }
+        }
+
+        let discovery_mode = match discovery_mode {
+            Some(mode) => mode,
+            None => self.discovery_mode(user_id).await,
+        };
+        let station = RadioStation {
+            user_id: user_id.to_string(),
+            seed: Some(seed),
+            seed_genres,
+            discovery_mode,
+            started_at: Utc::now(),
+        };
+        self.save_station(&station).await?;
+
+        // A track station opens with that track
+        let first: Vec<QueueTrack> = match &station.seed {
+            Some(RadioSeed::Track(id)) => seed_tracks.into_iter().filter(|t| &t.id == id).collect(),
+            _ => Vec::new(),
+        };
+        let mut queue = PlayQueue::new(user_id);
+        queue.replace(first, 0, 0);
+        let picks = self.pick(&station, &queue, self.config.batch_size).await?;
+        let queue = self
+            .play_queues
+            .update(user_id, device, |user_queue| {
+                user_queue.replace(queue.tracks, 0, 0);
+                user_queue.append(picks.into_iter().map(|pick| pick.track).collect());
+                user_queue.is_playing = user_queue.current().is_some();
+            })
+            .await?;
+
+        info!(
+            "Started {} radio '{}' for user {} ({} tracks)",
+            station.seed.as_ref().map_or("", |seed| seed.kind()),
+            station.seed.as_ref().map_or("", |seed| seed.value()),
+            user_id,
+            queue.tracks.len()
+        );
+        Ok((station, queue))
+    }
+
+    /// Stop the user's station, leaving the queue as it is
+    pub async fn stop(&self, user_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM radio_stations WHERE user_id = ?")
+            .bind(user_id)
+            .execute(self.database.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn station(&self, user_id: &str) -> Result<Option<RadioStation>> {
+        let row = sqlx::query("SELECT * FROM radio_stations WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let seed_kind: String = row.get("seed_kind");
+        let seed_genres: String = row.get("seed_genres");
+        let discovery_mode: String = row.get("discovery_mode");
+        Ok(Some(RadioStation {
+            user_id: row.get("user_id"),
+            seed: RadioSeed::from_parts(&seed_kind, row.get("seed_value")),
+            seed_genres: serde_json::from_str(&seed_genres).unwrap_or_default(),
+            discovery_mode: parse_mode(&discovery_mode),
+            started_at: Utc
+                .timestamp_opt(row.get("started_at"), 0)
+                .single()
+                .unwrap_or_default(),
+        }))
+    }
+
+    /// Note that the user moved on from `track` after `position_ms`,
+    /// returning whether that was early enough to count as a skip
+    pub async fn record_early_skip(
+        &self,
+        user_id: &str,
+        track: &QueueTrack,
+        position_ms: u64,
+    ) -> Result<bool> {
+        if position_ms >= self.config.skip_threshold_ms {
+            return Ok(false);
+        }
+
+        // A remote "next" and the player's own play report describe the same skip
+        let now = Utc::now().timestamp();
+        let recent = sqlx::query(
+            "SELECT 1 FROM track_skips WHERE user_id = ? AND track_id = ? AND skipped_at >= ?",
+        )
+        .bind(user_id)
+        .bind(&track.id)
+        .bind(now - 60)
+        .fetch_optional(self.database.pool())
+        .await?;
+        if recent.is_some() {
+            return Ok(true);
+        }
+
+        sqlx::query(
+            "INSERT INTO track_skips (user_id, track_id, artist, skipped_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(&track.id)
+        .bind(&track.artist)
+        .bind(now)
+        .execute(self.database.pool())
+        .await?;
+        debug!("User {} skipped {} at {}ms", user_id, track.id, position_ms);
+        Ok(true)
+    }
+
+    /// Extend the user's queue if their station is running low.
+    ///
+    /// Returns the new queue when tracks were added.
+    pub async fn top_up(&self, user_id: &str) -> Result<Option<PlayQueue>> {
+        let Some(station) = self.station(user_id).await? else {
+            return Ok(None);
+        };
+        let queue = self.play_queues.get(user_id).await?;
+        if queue.remaining() > self.config.low_water {
+            return Ok(None);
+        }
+
+        let picks = self.pick(&station, &queue, self.config.batch_size).await?;
+        if picks.is_empty() {
+            warn!("Radio for user {} found nothing new to play", user_id);
+            return Ok(None);
+        }
+        let tracks: Vec<QueueTrack> = picks.into_iter().map(|pick| pick.track).collect();
+
+        // Another top-up may have landed while picking, so check again under the
+        // queue lock and never queue the same track twice
+        let low_water = self.config.low_water;
+        let mut added = 0;
+        let queue = self
+            .play_queues
+            .update(user_id, None, |queue| {
+                if queue.remaining() > low_water {
+                    return;
+                }
+                let queued: HashSet<String> =
+                    queue.tracks.iter().map(|track| track.id.clone()).collect();
+                let tracks: Vec<QueueTrack> = tracks
+                    .into_iter()
+                    .filter(|track| !queued.contains(&track.id))
+                    .collect();
+                added = tracks.len();
+                queue.append(tracks);
+            })
+            .await?;
+        if added == 0 {
+            return Ok(None);
+        }
+        debug!("Added {} radio tracks for user {}", added, user_id);
+        Ok(Some(queue))
+    }
+
+    /// Suggestions for the user from their station, or from what they are
+    /// playing when no station is running
+    pub async fn recommend(&self, user_id: &str, count: usize) -> Result<Vec<RadioPick>> {
+        let queue = self.play_queues.get(user_id).await?;
+        let station = match self.station(user_id).await? {
+            Some(station) => station,
+            None => RadioStation {
+                user_id: user_id.to_string(),
+                seed: queue
+                    .current()
+                    .map(|track| RadioSeed::Track(track.id.clone())),
+                seed_genres: queue
+                    .current()
+                    .and_then(|track| track.genre.clone())
+                    .into_iter()
+                    .collect(),
+                discovery_mode: self.discovery_mode(user_id).await,
+                started_at: Utc::now(),
+            },
+        };
+        self.pick(&station, &queue, count).await
+    }
+
+    fn source(&self) -> Result<&Arc<dyn RadioSource>> {
+        self.source
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Radio needs Navidrome to be configured"))
+    }
+
+    async fn pick(
+        &self,
+        station: &RadioStation,
+        queue: &PlayQueue,
+        count: usize,
+    ) -> Result<Vec<RadioPick>> {
+        let source = self.source()?;
+        let per_source = (count * 2).max(10) as u32;
+        let mut candidates = Vec::new();
+        let mut add = |tracks: Result<Vec<QueueTrack>>, origin: Origin| match tracks {
+            Ok(tracks) => {
+                candidates.extend(tracks.into_iter().map(|track| Candidate { track, origin }))
+            }
+            Err(e) => debug!("Radio source failed for {:?} tracks: {}", origin, e),
+        };
+
+        if let Some(seed) = &station.seed {
+            add(source.seed_tracks(seed, per_source).await, Origin::Seed);
+        }
+
+        // Similar to the last couple of queued tracks, or to a track seed
+        let order = queue.play_order();
+        let mut similar_to: Vec<&str> = order
+            .iter()
+            .rev()
+            .take(2)
+            .map(|&i| queue.tracks[i].id.as_str())
+            .collect();
+        if let Some(RadioSeed::Track(id)) = &station.seed {
+            if !similar_to.contains(&id.as_str()) {
+                similar_to.push(id);
+            }
+        }
+        for track_id in similar_to {
+            add(
+                source.similar_tracks(track_id, per_source).await,
+                Origin::Similar,
+            );
+        }
+
+        let genre = match station.discovery_mode {
+            DiscoveryMode::Conservative => station.seed_genres.first().map(String::as_str),
+            _ => None,
+        };
+        add(
+            source.library_tracks(genre, per_source).await,
+            Origin::Library,
+        );
+
+        let feedback = self.feedback(&station.user_id).await?;
+        Ok(station.choose(
+            candidates,
+            queue,
+            &feedback,
+            &self.config,
+            count,
+            &mut rand::thread_rng(),
+        ))
+    }
+
+    async fn feedback(&self, user_id: &str) -> Result<SkipFeedback> {
+        let since = Utc::now() - ChronoDuration::days(self.config.skip_memory_days);
+        let rows = sqlx::query(
+            "SELECT track_id, artist FROM track_skips WHERE user_id = ? AND skipped_at >= ?",
+        )
+        .bind(user_id)
+        .bind(since.timestamp())
+        .fetch_all(self.database.pool())
+        .await?;
+
+        let mut feedback = SkipFeedback::default();
+        for row in rows {
+            let artist: Option<String> = row.get("artist");
+            feedback.record(row.get::<String, _>("track_id").as_str(), artist.as_deref());
+        }
+        Ok(feedback)
+    }
+
+    /// The user's preferred discovery mode, `Balanced` if they never chose
+    async fn discovery_mode(&self, user_id: &str) -> DiscoveryMode {
+        let preferences: Option<String> =
+            sqlx::query_scalar("SELECT preferences_json FROM users WHERE id = ?")
+                .bind(user_id)
+                .fetch_optional(self.database.pool())
+                .await
+                .ok()
+                .flatten();
+        preferences
+            .and_then(|json| serde_json::from_str::<UserPreferences>(&json).ok())
+            .map_or(DiscoveryMode::Balanced, |preferences| {
+                preferences.discovery_mode
+            })
+    }
+
+    async fn save_station(&self, station: &RadioStation) -> Result<()> {
+        let seed = station
+            .seed
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("A station needs a seed"))?;
+        sqlx::query(
+            r#"
+            INSERT INTO radio_stations
+                (user_id, seed_kind, seed_value, seed_genres, discovery_mode, started_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET
+                seed_kind = excluded.seed_kind,
+                seed_value = excluded.seed_value,
+                seed_genres = excluded.seed_genres,
+                discovery_mode = excluded.discovery_mode,
+                started_at = excluded.started_at
+            "#,
+        )
+        .bind(&station.user_id)
+        .bind(seed.kind())
+        .bind(seed.value())
+        .bind(serde_json::to_string(&station.seed_genres)?)
+        .bind(mode_name(station.discovery_mode))
+        .bind(station.started_at.timestamp())
+        .execute(self.database.pool())
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use tempfile::NamedTempFile;
+
+    fn track(id: &str, artist: &str, genre: &str) -> QueueTrack {
+        QueueTrack {
+            artist: Some(artist.to_string()),
+            genre: Some(genre.to_string()),
+            ..QueueTrack::new(id)
+        }
+    }
+
+    fn station(mode: DiscoveryMode) -> RadioStation {
+        RadioStation {
+            user_id: "1".to_string(),
+            seed: Some(RadioSeed::Genre("Rock".to_string())),
+            seed_genres: vec!["Rock".to_string()],
+            discovery_mode: mode,
+            started_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_choose_respects_mode_window_and_skips() {
+        let candidates = || {
+            vec![
+                Candidate {
+                    track: track("seed-1", "Seed Band", "Rock"),
+                    origin: Origin::Seed,
+                },
+                Candidate {
+                    track: track("seed-2", "Seed Band", "Rock"),
+                    origin: Origin::Seed,
+                },
+                Candidate {
+                    track: track("lib-1", "Stranger", "Jazz"),
+                    origin: Origin::Library,
+                },
+                Candidate {
+                    track: track("lib-2", "Skipped Artist", "Rock"),
+                    origin: Origin::Library,
+                },
+            ]
+        };
+        let config = RadioConfig::default();
+        let queue = PlayQueue::new("1");
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let picks = station(DiscoveryMode::Conservative).choose(
+            candidates(),
+            &queue,
+            &SkipFeedback::default(),
+            &config,
+            2,
+            &mut rng,
+        );
+        // One track per artist within the window
+        assert_eq!(picks[0].track.artist.as_deref(), Some("Seed Band"));
+        assert_ne!(picks[1].track.artist.as_deref(), Some("Seed Band"));
+
+        let picks = station(DiscoveryMode::Adventurous).choose(
+            candidates(),
+            &queue,
+            &SkipFeedback::default(),
+            &config,
+            1,
+            &mut rng,
+        );
+        assert_eq!(picks[0].track.id, "lib-1");
+
+        // Skipping an artist pushes them down; a skipped track never comes back
+        let mut feedback = SkipFeedback::default();
+        feedback.record("seed-1", Some("Seed Band"));
+        feedback.record("other", Some("seed band"));
+        feedback.record("another", Some("Seed Band "));
+        let picks = station(DiscoveryMode::Conservative).choose(
+            candidates(),
+            &queue,
+            &feedback,
+            &config,
+            4,
+            &mut rng,
+        );
+        let ids: Vec<&str> = picks.iter().map(|pick| pick.track.id.as_str()).collect();
+        assert!(!ids.contains(&"seed-1"));
+        assert_eq!(ids, vec!["lib-2", "seed-2", "lib-1"]);
+    }
+
+    struct FakeSource;
+
+    #[async_trait]
+    impl RadioSource for FakeSource {
+        async fn seed_tracks(&self, seed: &RadioSeed, _count: u32) -> Result<Vec<QueueTrack>> {
+            Ok(match seed {
+                RadioSeed::Artist(name) => (0..3)
+                    .map(|i| track(&format!("{}-{}", name, i), name, "Rock"))
+                    .collect(),
+                _ => Vec::new(),
+            })
+        }
+
+        async fn similar_tracks(&self, _track_id: &str, _count: u32) -> Result<Vec<QueueTrack>> {
+            anyhow::bail!("agents unavailable")
+        }
+
+        async fn library_tracks(
+            &self,
+            _genre: Option<&str>,
+            count: u32,
+        ) -> Result<Vec<QueueTrack>> {
+            Ok((0..count)
+                .map(|i| track(&format!("lib-{}", i), &format!("Artist {}", i), "Pop"))
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_station_tops_up_queue() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let play_queues = Arc::new(PlayQueueService::new(database.clone()));
+        let radio = RadioService::new(
+            RadioConfig {
+                batch_size: 4,
+                low_water: 1,
+                ..RadioConfig::default()
+            },
+            database,
+            play_queues.clone(),
+            Some(Arc::new(FakeSource)),
+        );
+
+        let (station, queue) = radio
+            .start(
+                "1",
+                RadioSeed::Artist("Band".to_string()),
+                None,
+                Some("phone"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(station.discovery_mode, DiscoveryMode::Balanced);
+        assert_eq!(station.seed_genres, vec!["Rock".to_string()]);
+        assert_eq!(queue.tracks.len(), 4);
+        assert!(queue.is_playing);
+        let band_tracks = queue
+            .tracks
+            .iter()
+            .filter(|t| t.artist.as_deref() == Some("Band"))
+            .count();
+        assert_eq!(band_tracks, 1);
+
+        // Plenty left: nothing to do
+        assert!(radio.top_up("1").await.unwrap().is_none());
+
+        let skipped = queue.current().unwrap().clone();
+        assert!(radio.record_early_skip("1", &skipped, 5_000).await.unwrap());
+        assert!(!radio
+            .record_early_skip("1", &skipped, 60_000)
+            .await
+            .unwrap());
+        play_queues
+            .update("1", None, |queue| {
+                queue.next_track();
+                queue.next_track();
+                queue.next_track();
+            })
+            .await
+            .unwrap();
+
+        // Concurrent top-ups add a single batch between them
+        let (first, second) = tokio::join!(radio.top_up("1"), radio.top_up("1"));
+        let topped_up: Vec<PlayQueue> = [first.unwrap(), second.unwrap()]
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(topped_up.len(), 1);
+        let queue = play_queues.get("1").await.unwrap();
+        assert_eq!(queue.tracks.len(), 8);
+        let ids: HashSet<&str> = queue.tracks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids.len(), 8);
+
+        assert!(radio.stop("1").await.unwrap());
+        assert!(radio.top_up("1").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,548 @@
+//! Recycle bin for StepheyBot Music
+//!
+//! Operations that would delete files move them into a trash area instead,
+//! recording where they came from, why, who asked and when. Trashed items can
+//! be restored to their original path or purged, and are purged automatically
+//! once they have been in the trash longer than the retention period.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, Row};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{info, warn};
+
+use crate::database::Database;
+
+/// Actor recorded for housekeeping StepheyBot does on its own
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// Name of the trash directory kept on the download volume by default
+pub const TRASH_DIR_NAME: &str = ".trash";
+
+/// Where trashed files go and how long they stay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecycleBinConfig {
+    pub path: PathBuf,
+    pub retention: Duration,
+    pub purge_interval: Duration,
+}
+
+impl Default for RecycleBinConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("/trash"),
+            retention: Duration::from_secs(30 * 86400),
+            purge_interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RecycleBinConfig {
+    /// Load the configuration, overriding defaults from `STEPHEYBOT__TRASH__*`
+    ///
+    /// Without a configured path the trash lives in `default_path`, which
+    /// should be on the same filesystem as the downloads so trashing them is
+    /// a rename rather than a copy.
+    pub fn from_env(default_path: &Path) -> Self {
+        let mut config = Self {
+            path: default_path.to_path_buf(),
+            ..Self::default()
+        };
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(path) = var("STEPHEYBOT__TRASH__PATH") {
+            config.path = PathBuf::from(path);
+        }
+        if let Some(days) = var("STEPHEYBOT__TRASH__RETENTION_DAYS")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|days| *days > 0)
+        {
+            config.retention = Duration::from_secs(days * 86400);
+        }
+
+        config
+    }
+}
+
+/// What happened to a trashed item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashStatus {
+    Trashed,
+    Restored,
+    Purged,
+}
+
+impl TrashStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrashStatus::Trashed => "trashed",
+            TrashStatus::Restored => "restored",
+            TrashStatus::Purged => "purged",
+        }
+    }
+}
+
+impl std::str::FromStr for TrashStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            TrashStatus::Trashed,
+            TrashStatus::Restored,
+            TrashStatus::Purged,
+        ]
+        .into_iter()
+        .find(|status| status.as_str() == s)
+        .ok_or_else(|| format!("Unknown trash status: {}", s))
+    }
+}
+
+/// A file or directory in the recycle bin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub id: i64,
+    pub original_path: PathBuf,
+    pub trash_path: PathBuf,
+    pub reason: String,
+    pub actor: String,
+    pub size_bytes: u64,
+    pub is_dir: bool,
+    pub status: TrashStatus,
+    pub trashed_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolved_by: Option<String>,
+}
+
+/// Moves files aside instead of deleting them
+#[derive(Clone)]
+pub struct RecycleBin {
+    config: RecycleBinConfig,
+    database: Arc<Database>,
+}
+
+impl RecycleBin {
+    pub fn new(config: RecycleBinConfig, database: Arc<Database>) -> Self {
+        Self { config, database }
+    }
+
+    /// Purge expired items periodically
+    pub fn start(&self) {
+        let bin = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = interval(bin.config.purge_interval);
+            loop {
+                ticker.tick().await;
+                match bin.purge_expired(Utc::now()).await {
+                    Ok(0) => {}
+                    Ok(purged) => info!("Purged {} expired items from the recycle bin", purged),
+                    Err(e) => warn!("Failed to purge the recycle bin: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Move a file or directory into the trash
+    pub async fn trash(&self, path: &Path, reason: &str, actor: &str) -> Result<TrashItem> {
+        let metadata = tokio::fs::symlink_metadata(path)
+            .await
+            .with_context(|| format!("Cannot trash {:?}", path))?;
+        let is_dir = metadata.is_dir();
+        let size_bytes = if is_dir {
+            directory_size(path).await
+        } else {
+            metadata.len()
+        };
+        let original_path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+        // Each item gets its own directory so equal names never collide
+        let slot = self.config.path.join(format!(
+            "{}-{}",
+            Utc::now().timestamp(),
+            uuid::Uuid::new_v4()
+        ));
+        let trash_path = slot.join(path.file_name().unwrap_or_else(|| "item".as_ref()));
+        tokio::fs::create_dir_all(&slot).await?;
+        if let Err(e) = move_path(path, &trash_path).await {
+            remove_slot(&trash_path).await;
+            return Err(e).context(format!("Failed to move {:?} to the recycle bin", path));
+        }
+
+        let trashed_at = Utc::now();
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO trash_items
+                (original_path, trash_path, reason, actor, size_bytes, is_dir, status, trashed_at)
+            VALUES (?, ?, ?, ?, ?, ?, 'trashed', ?)
+            "#,
+        )
+        .bind(original_path.to_string_lossy().as_ref())
+        .bind(trash_path.to_string_lossy().as_ref())
+        .bind(reason)
+        .bind(actor)
+        .bind(size_bytes as i64)
+        .bind(is_dir)
+        .bind(trashed_at.timestamp())
+        .execute(self.database.pool())
+        .await;
+        let id = match inserted {
+            Ok(result) => result.last_insert_rowid(),
+            Err(e) => {
+                // Without a row the item could never be restored or purged
+                if let Err(undo) = move_path(&trash_path, path).await {
+                    warn!(
+                        "Failed to move {:?} back from the recycle bin to {:?}: {}",
+                        trash_path, path, undo
+                    );
+                } else {
+                    remove_slot(&trash_path).await;
+                }
+                return Err(e).context(format!("Failed to record {:?} in the recycle bin", path));
+            }
+        };
+
+        info!(
+            "Moved {:?} to the recycle bin ({}): {}",
+            path, actor, reason
+        );
+        Ok(TrashItem {
+            id,
+            original_path,
+            trash_path,
+            reason: reason.to_string(),
+            actor: actor.to_string(),
+            size_bytes,
+            is_dir,
+            status: TrashStatus::Trashed,
+            trashed_at,
+            resolved_at: None,
+            resolved_by: None,
+        })
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<TrashItem>> {
+        let row = sqlx::query("SELECT * FROM trash_items WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self.database.pool())
+            .await?;
+        row.as_ref().map(row_to_item).transpose()
+    }
+
+    /// Items in a given state, newest first
+    pub async fn list(&self, status: Option<TrashStatus>, limit: u32) -> Result<Vec<TrashItem>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM trash_items
+            WHERE (?1 IS NULL OR status = ?1)
+            ORDER BY trashed_at DESC, id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(status.map(|status| status.as_str()))
+        .bind(limit as i64)
+        .fetch_all(self.database.pool())
+        .await?;
+        rows.iter().map(row_to_item).collect()
+    }
+
+    /// Put an item back where it came from, refusing to overwrite anything
+    pub async fn restore(&self, id: i64, actor: &str) -> Result<TrashItem> {
+        let item = self.trashed(id).await?;
+        if tokio::fs::symlink_metadata(&item.original_path)
+            .await
+            .is_ok()
+        {
+            anyhow::bail!(
+                "Cannot restore item {}: {:?} already exists",
+                id,
+                item.original_path
+            );
+        }
+
+        if let Some(parent) = item.original_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        move_path(&item.trash_path, &item.original_path)
+            .await
+            .with_context(|| format!("Failed to restore {:?}", item.original_path))?;
+        remove_slot(&item.trash_path).await;
+
+        info!(
+            "Restored {:?} from the recycle bin ({})",
+            item.original_path, actor
+        );
+        self.resolve(item, TrashStatus::Restored, actor).await
+    }
+
+    /// Delete an item for good
+    pub async fn purge(&self, id: i64, actor: &str) -> Result<TrashItem> {
+        let item = self.trashed(id).await?;
+        let removed = if item.is_dir {
+            tokio::fs::remove_dir_all(&item.trash_path).await
+        } else {
+            tokio::fs::remove_file(&item.trash_path).await
+        };
+        match removed {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context(format!("Failed to purge {:?}", item.trash_path)),
+        }
+        remove_slot(&item.trash_path).await;
+
+        self.resolve(item, TrashStatus::Purged, actor).await
+    }
+
+    /// Purge everything trashed before the retention period, returning how many
+    pub async fn purge_expired(&self, now: DateTime<Utc>) -> Result<usize> {
+        let cutoff = now - ChronoDuration::from_std(self.config.retention).unwrap_or_default();
+        let expired: Vec<i64> =
+            sqlx::query("SELECT id FROM trash_items WHERE status = 'trashed' AND trashed_at < ?")
+                .bind(cutoff.timestamp())
+                .fetch_all(self.database.pool())
+                .await?
+                .iter()
+                .map(|row| row.get("id"))
+                .collect();
+
+        let mut purged = 0;
+        for id in expired {
+            match self.purge(id, SYSTEM_ACTOR).await {
+                Ok(_) => purged += 1,
+                Err(e) => warn!("Failed to purge expired trash item {}: {}", id, e),
+            }
+        }
+        Ok(purged)
+    }
+
+    async fn trashed(&self, id: i64) -> Result<TrashItem> {
+        let item = self
+            .get(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Trash item {} not found", id))?;
+        if item.status != TrashStatus::Trashed {
+            anyhow::bail!("Trash item {} was already {}", id, item.status.as_str());
+        }
+        Ok(item)
+    }
+
+    async fn resolve(
+        &self,
+        mut item: TrashItem,
+        status: TrashStatus,
+        actor: &str,
+    ) -> Result<TrashItem> {
+        let now = Utc::now();
+        sqlx::query(
+            "UPDATE trash_items SET status = ?, resolved_at = ?, resolved_by = ? WHERE id = ?",
+        )
+        .bind(status.as_str())
+        .bind(now.timestamp())
+        .bind(actor)
+        .bind(item.id)
+        .execute(self.database.pool())
+        .await?;
+
+        item.status = status;
+        item.resolved_at = Some(now);
+        item.resolved_by = Some(actor.to_string());
+        Ok(item)
+    }
+}
+
+fn row_to_item(row: &SqliteRow) -> Result<TrashItem> {
+    let timestamp = |at: i64| Utc.timestamp_opt(at, 0).single();
+    let original_path: String = row.get("original_path");
+    let trash_path: String = row.get("trash_path");
+    let status: String = row.get("status");
+
+    Ok(TrashItem {
+        id: row.get("id"),
+        original_path: PathBuf::from(original_path),
+        trash_path: PathBuf::from(trash_path),
+        reason: row.get("reason"),
+        actor: row.get("actor"),
+        size_bytes: row.get::<i64, _>("size_bytes").max(0) as u64,
+        is_dir: row.get("is_dir"),
+        status: status.parse().map_err(anyhow::Error::msg)?,
+        trashed_at: timestamp(row.get("trashed_at")).unwrap_or_default(),
+        resolved_at: row.get::<Option<i64>, _>("resolved_at").and_then(timestamp),
+        resolved_by: row.get("resolved_by"),
+    })
+}
+
+/// Rename when possible, otherwise copy across filesystems and remove the original
+async fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    match tokio::fs::rename(from, to).await {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            let (from, to) = (from.to_path_buf(), to.to_path_buf());
+            tokio::task::spawn_blocking(move || {
+                copy_recursive(&from, &to)?;
+                if from.is_dir() {
+                    std::fs::remove_dir_all(&from)
+                } else {
+                    std::fs::remove_file(&from)
+                }
+            })
+            .await
+            .map_err(std::io::Error::other)?
+        }
+        result => result,
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !from.is_dir() {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry.map_err(std::io::Error::other)?;
+        let relative = entry
+            .path()
+            .strip_prefix(from)
+            .map_err(std::io::Error::other)?;
+        let target = to.join(relative);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+async fn directory_size(path: &Path) -> u64 {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        walkdir::WalkDir::new(path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum()
+    })
+    .await
+    .unwrap_or(0)
+}
+
+/// Drop the per-item directory once its item has left the trash
+async fn remove_slot(trash_path: &Path) {
+    if let Some(slot) = trash_path.parent() {
+        let _ = tokio::fs::remove_dir(slot).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{NamedTempFile, TempDir};
+
+    async fn setup() -> (NamedTempFile, TempDir, RecycleBin) {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let bin = RecycleBin::new(
+            RecycleBinConfig {
+                path: dir.path().join("trash"),
+                ..RecycleBinConfig::default()
+            },
+            database,
+        );
+        (temp_file, dir, bin)
+    }
+
+    #[tokio::test]
+    async fn test_trash_and_restore() {
+        let (_db, dir, bin) = setup().await;
+        let album = dir.path().join("library/Artist/Album");
+        tokio::fs::create_dir_all(&album).await.unwrap();
+        tokio::fs::write(album.join("01.flac"), b"music")
+            .await
+            .unwrap();
+
+        let item = bin
+            .trash(&album, "Cancelled download", "alice")
+            .await
+            .unwrap();
+        assert!(!album.exists());
+        assert!(item.trash_path.join("01.flac").exists());
+        assert!(item.is_dir);
+        assert_eq!(item.size_bytes, 5);
+        assert_eq!(
+            bin.list(Some(TrashStatus::Trashed), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Restoring never overwrites something that took its place
+        tokio::fs::create_dir_all(&album).await.unwrap();
+        assert!(bin.restore(item.id, "bob").await.is_err());
+        tokio::fs::remove_dir(&album).await.unwrap();
+
+        let restored = bin.restore(item.id, "bob").await.unwrap();
+        assert_eq!(restored.status, TrashStatus::Restored);
+        assert_eq!(restored.resolved_by.as_deref(), Some("bob"));
+        assert_eq!(
+            tokio::fs::read(album.join("01.flac")).await.unwrap(),
+            b"music"
+        );
+        assert!(!item.trash_path.parent().unwrap().exists());
+        assert!(bin.restore(item.id, "bob").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purge_and_expiry() {
+        let (_db, dir, bin) = setup().await;
+        let first = dir.path().join("first.flac");
+        let second = dir.path().join("second.flac");
+        tokio::fs::write(&first, b"1").await.unwrap();
+        tokio::fs::write(&second, b"2").await.unwrap();
+
+        let first = bin.trash(&first, "Duplicate", SYSTEM_ACTOR).await.unwrap();
+        let second = bin.trash(&second, "Duplicate", SYSTEM_ACTOR).await.unwrap();
+
+        let purged = bin.purge(first.id, "alice").await.unwrap();
+        assert_eq!(purged.status, TrashStatus::Purged);
+        assert!(!first.trash_path.exists());
+
+        // Nothing is old enough yet
+        assert_eq!(bin.purge_expired(Utc::now()).await.unwrap(), 0);
+        let later = Utc::now() + ChronoDuration::days(31);
+        assert_eq!(bin.purge_expired(later).await.unwrap(), 1);
+        assert!(!second.trash_path.exists());
+        assert_eq!(
+            bin.list(Some(TrashStatus::Trashed), 10)
+                .await
+                .unwrap()
+                .len(),
+            0
+        );
+        assert_eq!(bin.list(None, 10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_record_puts_the_file_back() {
+        let (_db, dir, bin) = setup().await;
+        let track = dir.path().join("track.flac");
+        tokio::fs::write(&track, b"music").await.unwrap();
+
+        sqlx::query("DROP TABLE trash_items")
+            .execute(bin.database.pool())
+            .await
+            .unwrap();
+        assert!(bin.trash(&track, "Duplicate", SYSTEM_ACTOR).await.is_err());
+        assert_eq!(tokio::fs::read(&track).await.unwrap(), b"music");
+        let mut slots = tokio::fs::read_dir(dir.path().join("trash")).await.unwrap();
+        assert!(slots.next_entry().await.unwrap().is_none());
+    }
+}
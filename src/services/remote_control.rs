@@ -0,0 +1,691 @@
+//! Cross-device remote control for StepheyBot Music
+//!
+//! Every logged-in player connects over a WebSocket and registers as a device.
+//! A user can see their connected devices, move playback from one to another
+//! and send player commands to any of them. Commands update the persisted
+//! play queue, are forwarded to the device that should carry them out, and
+//! the resulting state is broadcast to all of the user's devices.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::services::play_queue::{PlayQueue, PlayQueueService, QueueTrack};
+use crate::services::radio::RadioService;
+
+/// A command for a player
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlayerCommand {
+    /// Resume, or play the given track
+    Play {
+        #[serde(default)]
+        track_id: Option<String>,
+    },
+    Pause {
+        #[serde(default)]
+        position_ms: Option<u64>,
+    },
+    Seek {
+        position_ms: u64,
+    },
+    Next,
+    Previous,
+    /// Volume from 0 to 100
+    Volume {
+        level: u8,
+    },
+}
+
+impl PlayerCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayerCommand::Play { .. } => "play",
+            PlayerCommand::Pause { .. } => "pause",
+            PlayerCommand::Seek { .. } => "seek",
+            PlayerCommand::Next => "next",
+            PlayerCommand::Previous => "previous",
+            PlayerCommand::Volume { .. } => "volume",
+        }
+    }
+
+    /// Apply the command to the queue; volume lives on the device instead
+    fn apply(&self, queue: &mut PlayQueue) {
+        match self {
+            PlayerCommand::Play { track_id: Some(id) } => {
+                queue.play_track(QueueTrack::new(id.clone()));
+            }
+            PlayerCommand::Play { track_id: None } => {
+                queue.is_playing = queue.current().is_some();
+            }
+            PlayerCommand::Pause { position_ms } => queue.pause(*position_ms),
+            PlayerCommand::Seek { position_ms } => queue.position_ms = *position_ms,
+            PlayerCommand::Next => {
+                queue.next_track();
+            }
+            PlayerCommand::Previous => {
+                queue.previous_track();
+            }
+            PlayerCommand::Volume { .. } => {}
+        }
+    }
+}
+
+/// A connected player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub name: String,
+    pub volume: Option<u8>,
+    pub connected_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Messages sent to a device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Carry out a command; `from_device` is who asked, if it was a device
+    Command {
+        command: PlayerCommand,
+        from_device: Option<String>,
+    },
+    /// Full state after any change
+    State {
+        queue: PlayQueue,
+        active_device: Option<String>,
+        devices: Vec<DeviceInfo>,
+    },
+    /// Playback progress reported by the active device
+    Progress {
+        device_id: String,
+        position_ms: u64,
+        is_playing: bool,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Messages a device sends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeviceMessage {
+    /// Control a device, by default the active one
+    Command {
+        #[serde(default)]
+        target: Option<String>,
+        command: PlayerCommand,
+    },
+    /// Move playback to another device
+    Transfer {
+        device_id: String,
+        #[serde(default = "default_true")]
+        play: bool,
+    },
+    /// Where this device is in the current track
+    Progress { position_ms: u64, is_playing: bool },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+struct Device {
+    info: DeviceInfo,
+    connection_id: u64,
+    sender: mpsc::UnboundedSender<ServerMessage>,
+}
+
+#[derive(Default)]
+struct UserDevices {
+    devices: HashMap<String, Device>,
+    active: Option<String>,
+}
+
+/// One device's registration; pass it back to [`RemoteControl::disconnect`]
+pub struct DeviceConnection {
+    pub user_id: String,
+    pub device_id: String,
+    connection_id: u64,
+    receiver: mpsc::UnboundedReceiver<ServerMessage>,
+}
+
+impl DeviceConnection {
+    /// Next message for this device, `None` once it was replaced by a reconnect
+    pub async fn recv(&mut self) -> Option<ServerMessage> {
+        self.receiver.recv().await
+    }
+}
+
+/// Registry of connected devices per user
+#[derive(Clone)]
+pub struct RemoteControl {
+    play_queues: Arc<PlayQueueService>,
+    radio: Option<Arc<RadioService>>,
+    users: Arc<Mutex<HashMap<String, UserDevices>>>,
+    next_connection_id: Arc<AtomicU64>,
+}
+
+impl RemoteControl {
+    pub fn new(play_queues: Arc<PlayQueueService>) -> Self {
+        Self {
+            play_queues,
+            radio: None,
+            users: Arc::new(Mutex::new(HashMap::new())),
+            next_connection_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Keep radio stations topped up and learn from skips as commands arrive
+    pub fn with_radio(mut self, radio: Arc<RadioService>) -> Self {
+        self.radio = Some(radio);
+        self
+    }
+
+    /// Register a device; reconnecting with the same id replaces the old connection
+    pub async fn connect(&self, user_id: &str, device_id: &str, name: &str) -> DeviceConnection {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let connection_id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        let now = Utc::now();
+        {
+            let mut users = self.users.lock().unwrap();
+            let user = users.entry(user_id.to_string()).or_default();
+            let volume = user
+                .devices
+                .get(device_id)
+                .and_then(|device| device.info.volume);
+            user.devices.insert(
+                device_id.to_string(),
+                Device {
+                    info: DeviceInfo {
+                        id: device_id.to_string(),
+                        name: name.to_string(),
+                        volume,
+                        connected_at: now,
+                        last_seen: now,
+                    },
+                    connection_id,
+                    sender,
+                },
+            );
+        }
+        info!(
+            "Device {} ({}) connected for user {}",
+            device_id, name, user_id
+        );
+        self.broadcast_state(user_id).await;
+
+        DeviceConnection {
+            user_id: user_id.to_string(),
+            device_id: device_id.to_string(),
+            connection_id,
+            receiver,
+        }
+    }
+
+    /// Forget a device once its socket closes
+    pub async fn disconnect(&self, connection: &DeviceConnection) {
+        {
+            let mut users = self.users.lock().unwrap();
+            let Some(user) = users.get_mut(&connection.user_id) else {
+                return;
+            };
+            // A newer connection for the same device stays registered
+            let current = user
+                .devices
+                .get(&connection.device_id)
+                .is_some_and(|device| device.connection_id == connection.connection_id);
+            if !current {
+                return;
+            }
+            user.devices.remove(&connection.device_id);
+            if user.active.as_deref() == Some(connection.device_id.as_str()) {
+                user.active = None;
+            }
+            if user.devices.is_empty() {
+                users.remove(&connection.user_id);
+            }
+        }
+        info!(
+            "Device {} disconnected for user {}",
+            connection.device_id, connection.user_id
+        );
+        self.broadcast_state(&connection.user_id).await;
+    }
+
+    /// The user's connected devices and which one is playing
+    pub fn devices(&self, user_id: &str) -> (Vec<DeviceInfo>, Option<String>) {
+        let users = self.users.lock().unwrap();
+        let Some(user) = users.get(user_id) else {
+            return (Vec::new(), None);
+        };
+        let mut devices: Vec<DeviceInfo> = user
+            .devices
+            .values()
+            .map(|device| device.info.clone())
+            .collect();
+        devices.sort_by_key(|device| device.connected_at);
+        (devices, user.active.clone())
+    }
+
+    /// Run a command on `target`, or the active device when there is none.
+    ///
+    /// The queue is updated even when no device is connected, so the REST
+    /// player controls keep working without a WebSocket client.
+    pub async fn execute(
+        &self,
+        user_id: &str,
+        from_device: Option<&str>,
+        target: Option<&str>,
+        command: PlayerCommand,
+    ) -> Result<PlayQueue> {
+        let target = match target {
+            Some(target) => {
+                self.ensure_connected(user_id, target)?;
+                Some(target.to_string())
+            }
+            None => self.devices(user_id).1,
+        };
+
+        let queue = match &command {
+            PlayerCommand::Volume { level } => {
+                let target = target
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("No device to set the volume on"))?;
+                self.with_device(user_id, target, |device| {
+                    device.info.volume = Some((*level).min(100))
+                });
+                self.play_queues.get(user_id).await?
+            }
+            command => {
+                let mut left = None;
+                let queue = self
+                    .play_queues
+                    .update(user_id, from_device.or(target.as_deref()), |queue| {
+                        if matches!(command, PlayerCommand::Next) && queue.is_playing {
+                            left = queue.current().cloned().map(|t| (t, queue.position_ms));
+                        }
+                        command.apply(queue)
+                    })
+                    .await?;
+                self.follow_radio(user_id, left);
+                queue
+            }
+        };
+
+        if let Some(target) = &target {
+            debug!("Sending {} to device {}", command.name(), target);
+            self.send(
+                user_id,
+                target,
+                ServerMessage::Command {
+                    command,
+                    from_device: from_device.map(str::to_string),
+                },
+            );
+        }
+        self.broadcast_queue(user_id, &queue);
+        Ok(queue)
+    }
+
+    /// Move playback to another device, pausing the one that had it
+    pub async fn transfer(
+        &self,
+        user_id: &str,
+        from_device: Option<&str>,
+        device_id: &str,
+        play: bool,
+    ) -> Result<PlayQueue> {
+        self.ensure_connected(user_id, device_id)?;
+        let previous = {
+            let mut users = self.users.lock().unwrap();
+            let user = users.entry(user_id.to_string()).or_default();
+            user.active.replace(device_id.to_string())
+        };
+
+        let queue = self
+            .play_queues
+            .update(user_id, Some(device_id), |queue| {
+                queue.is_playing = play && queue.current().is_some();
+            })
+            .await?;
+
+        if let Some(previous) = previous.filter(|previous| previous != device_id) {
+            self.send(
+                user_id,
+                &previous,
+                ServerMessage::Command {
+                    command: PlayerCommand::Pause { position_ms: None },
+                    from_device: from_device.map(str::to_string),
+                },
+            );
+        }
+        // The new device picks up the queue's track and position
+        let command = if queue.is_playing {
+            PlayerCommand::Play { track_id: None }
+        } else {
+            PlayerCommand::Seek {
+                position_ms: queue.position_ms,
+            }
+        };
+        self.send(
+            user_id,
+            device_id,
+            ServerMessage::Command {
+                command,
+                from_device: from_device.map(str::to_string),
+            },
+        );
+        info!("Transferred playback for user {} to {}", user_id, device_id);
+        self.broadcast_queue(user_id, &queue);
+        Ok(queue)
+    }
+
+    /// Handle a message from a connected device
+    pub async fn handle(
+        &self,
+        user_id: &str,
+        device_id: &str,
+        message: DeviceMessage,
+    ) -> Result<()> {
+        self.with_device(user_id, device_id, |device| {
+            device.info.last_seen = Utc::now()
+        });
+        match message {
+            DeviceMessage::Command { target, command } => {
+                self.execute(user_id, Some(device_id), target.as_deref(), command)
+                    .await?;
+            }
+            DeviceMessage::Transfer {
+                device_id: to,
+                play,
+            } => {
+                self.transfer(user_id, Some(device_id), &to, play).await?;
+            }
+            DeviceMessage::Progress {
+                position_ms,
+                is_playing,
+            } => {
+                self.report_progress(user_id, device_id, position_ms, is_playing)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A device started playing on its own or moved along its track
+    async fn report_progress(
+        &self,
+        user_id: &str,
+        device_id: &str,
+        position_ms: u64,
+        is_playing: bool,
+    ) -> Result<()> {
+        let active = self.devices(user_id).1;
+        let takes_over = is_playing && active.as_deref() != Some(device_id);
+        if active.as_deref() != Some(device_id) && !takes_over {
+            // Progress from an idle device doesn't change anything
+            return Ok(());
+        }
+
+        let queue = self
+            .play_queues
+            .update(user_id, Some(device_id), |queue| {
+                queue.position_ms = position_ms;
+                queue.is_playing = is_playing;
+            })
+            .await?;
+
+        if takes_over {
+            self.transfer(user_id, Some(device_id), device_id, true)
+                .await?;
+        } else {
+            self.broadcast(
+                user_id,
+                Some(device_id),
+                ServerMessage::Progress {
+                    device_id: device_id.to_string(),
+                    position_ms: queue.position_ms,
+                    is_playing: queue.is_playing,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Send the current state to all of the user's devices
+    pub async fn broadcast_state(&self, user_id: &str) {
+        match self.play_queues.get(user_id).await {
+            Ok(queue) => self.broadcast_queue(user_id, &queue),
+            Err(e) => debug!("Failed to load play queue of {}: {}", user_id, e),
+        }
+    }
+
+    /// Record an early skip of the track left behind and top up the user's
+    /// station in the background
+    fn follow_radio(&self, user_id: &str, left: Option<(QueueTrack, u64)>) {
+        let Some(radio) = self.radio.clone() else {
+            return;
+        };
+        let remote = self.clone();
+        let user_id = user_id.to_string();
+        tokio::spawn(async move {
+            if let Some((track, position_ms)) = left {
+                if let Err(e) = radio.record_early_skip(&user_id, &track, position_ms).await {
+                    warn!("Failed to record skip for user {}: {}", user_id, e);
+                }
+            }
+            match radio.top_up(&user_id).await {
+                Ok(Some(queue)) => remote.broadcast_queue(&user_id, &queue),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to extend radio for user {}: {}", user_id, e),
+            }
+        });
+    }
+
+    fn broadcast_queue(&self, user_id: &str, queue: &PlayQueue) {
+        let (devices, active_device) = self.devices(user_id);
+        self.broadcast(
+            user_id,
+            None,
+            ServerMessage::State {
+                queue: queue.clone(),
+                active_device,
+                devices,
+            },
+        );
+    }
+
+    fn broadcast(&self, user_id: &str, except: Option<&str>, message: ServerMessage) {
+        let users = self.users.lock().unwrap();
+        let Some(user) = users.get(user_id) else {
+            return;
+        };
+        for (id, device) in &user.devices {
+            if except != Some(id.as_str()) {
+                let _ = device.sender.send(message.clone());
+            }
+        }
+    }
+
+    fn send(&self, user_id: &str, device_id: &str, message: ServerMessage) {
+        self.with_device(user_id, device_id, |device| {
+            let _ = device.sender.send(message);
+        });
+    }
+
+    fn ensure_connected(&self, user_id: &str, device_id: &str) -> Result<()> {
+        if self.with_device(user_id, device_id, |_| ()).is_none() {
+            anyhow::bail!("Device {} is not connected", device_id);
+        }
+        Ok(())
+    }
+
+    fn with_device<T>(
+        &self,
+        user_id: &str,
+        device_id: &str,
+        f: impl FnOnce(&mut Device) -> T,
+    ) -> Option<T> {
+        let mut users = self.users.lock().unwrap();
+        users
+            .get_mut(user_id)
+            .and_then(|user| user.devices.get_mut(device_id))
+            .map(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use tempfile::NamedTempFile;
+
+    async fn setup() -> (NamedTempFile, RemoteControl) {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let remote = RemoteControl::new(Arc::new(PlayQueueService::new(database)));
+        (temp_file, remote)
+    }
+
+    /// Drain queued messages, keeping commands and the last state
+    fn drain(connection: &mut DeviceConnection) -> (Vec<PlayerCommand>, Option<ServerMessage>) {
+        let mut commands = Vec::new();
+        let mut state = None;
+        while let Ok(message) = connection.receiver.try_recv() {
+            match message {
+                ServerMessage::Command { command, .. } => commands.push(command),
+                message @ ServerMessage::State { .. } => state = Some(message),
+                _ => {}
+            }
+        }
+        (commands, state)
+    }
+
+    #[tokio::test]
+    async fn test_commands_reach_the_active_device() {
+        let (_db, remote) = setup().await;
+        let mut desktop = remote.connect("1", "desktop", "Desktop").await;
+        let mut phone = remote.connect("1", "phone", "Phone").await;
+        assert_eq!(remote.devices("1").0.len(), 2);
+
+        remote.transfer("1", None, "desktop", false).await.unwrap();
+        drain(&mut desktop);
+        drain(&mut phone);
+
+        // The phone acts as a remote for the desktop
+        remote
+            .handle(
+                "1",
+                "phone",
+                DeviceMessage::Command {
+                    target: None,
+                    command: PlayerCommand::Play {
+                        track_id: Some("track-1".to_string()),
+                    },
+                },
+            )
+            .await
+            .unwrap();
+
+        let (commands, _) = drain(&mut desktop);
+        assert_eq!(
+            commands,
+            vec![PlayerCommand::Play {
+                track_id: Some("track-1".to_string())
+            }]
+        );
+        let (commands, state) = drain(&mut phone);
+        assert!(commands.is_empty());
+        let Some(ServerMessage::State {
+            queue,
+            active_device,
+            ..
+        }) = state
+        else {
+            panic!("phone got no state");
+        };
+        assert_eq!(queue.current().unwrap().id, "track-1");
+        assert!(queue.is_playing);
+        assert_eq!(active_device.as_deref(), Some("desktop"));
+
+        remote
+            .execute(
+                "1",
+                None,
+                Some("phone"),
+                PlayerCommand::Volume { level: 150 },
+            )
+            .await
+            .unwrap();
+        let (devices, _) = remote.devices("1");
+        let phone_info = devices.iter().find(|d| d.id == "phone").unwrap();
+        assert_eq!(phone_info.volume, Some(100));
+        assert!(remote
+            .execute("1", None, Some("tv"), PlayerCommand::Next)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_pauses_previous_device() {
+        let (_db, remote) = setup().await;
+        let mut desktop = remote.connect("1", "desktop", "Desktop").await;
+        let mut phone = remote.connect("1", "phone", "Phone").await;
+
+        remote
+            .execute(
+                "1",
+                None,
+                None,
+                PlayerCommand::Play {
+                    track_id: Some("a".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+        remote.transfer("1", None, "desktop", true).await.unwrap();
+        remote
+            .handle(
+                "1",
+                "desktop",
+                DeviceMessage::Progress {
+                    position_ms: 61_000,
+                    is_playing: true,
+                },
+            )
+            .await
+            .unwrap();
+        drain(&mut desktop);
+        drain(&mut phone);
+
+        let queue = remote.transfer("1", None, "phone", true).await.unwrap();
+        assert_eq!(queue.position_ms, 61_000);
+        assert_eq!(queue.updated_by_device.as_deref(), Some("phone"));
+        assert_eq!(
+            drain(&mut desktop).0,
+            vec![PlayerCommand::Pause { position_ms: None }]
+        );
+        assert_eq!(
+            drain(&mut phone).0,
+            vec![PlayerCommand::Play { track_id: None }]
+        );
+
+        // Leaving clears the active device; a stale connection can't remove a newer one
+        let reconnected = remote.connect("1", "phone", "Phone").await;
+        remote.disconnect(&phone).await;
+        assert_eq!(remote.devices("1").0.len(), 2);
+        remote.disconnect(&reconnected).await;
+        let (devices, active) = remote.devices("1");
+        assert_eq!(devices.len(), 1);
+        assert_eq!(active, None);
+    }
+}
@@ -0,0 +1,714 @@
+//! Scrobbling for StepheyBot Music
+//!
+//! Finished plays go into a queue in SQLite, one entry per destination: the
+//! ListenBrainz account a user linked, and Navidrome so its play counts stay
+//! current. A background task sends what is due in batches. When a service is
+//! down or a token is wrong the entries wait with exponential backoff instead
+//! of being lost, and the failure is recorded on the user's integration.
+//! Now-playing notices are sent straight away; there is no point retrying them.
+//! Users who turned `scrobble_enabled` off are never queued.
+
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tracing::{debug, info, warn};
+
+use crate::clients::listenbrainz::{AdditionalInfo, Listen, ListenBrainzClient, TrackMetadata};
+use crate::clients::navidrome::NavidromeClient;
+use crate::database::Database;
+use crate::models::user::UserPreferences;
+use crate::services::play_queue::QueueTrack;
+
+pub const LISTENBRAINZ: &str = "listenbrainz";
+pub const NAVIDROME: &str = "navidrome";
+
+/// Where scrobbles go and how hard to try
+#[derive(Debug, Clone)]
+pub struct ScrobbleConfig {
+    pub listenbrainz_url: String,
+    /// Listens per ListenBrainz request
+    pub batch_size: usize,
+    /// How often due entries are looked for, besides right after a play
+    pub interval: Duration,
+    /// First retry delay; doubles with each failure
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+    /// Entries are given up on after this many failed attempts
+    pub max_attempts: u32,
+}
+
+impl Default for ScrobbleConfig {
+    fn default() -> Self {
+        Self {
+            listenbrainz_url: "https://api.listenbrainz.org".to_string(),
+            batch_size: 50,
+            interval: Duration::from_secs(60),
+            retry_base: Duration::from_secs(60),
+            retry_max: Duration::from_secs(6 * 3600),
+            max_attempts: 20,
+        }
+    }
+}
+
+impl ScrobbleConfig {
+    /// Load the configuration, overriding defaults from `STEPHEYBOT__SCROBBLE__*`
+    /// and the ListenBrainz settings
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(url) = var("STEPHEYBOT__LISTENBRAINZ__URL") {
+            config.listenbrainz_url = url;
+        }
+        if let Some(size) = var("STEPHEYBOT__SCROBBLE__BATCH_SIZE")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|size| *size > 0)
+        {
+            config.batch_size = size;
+        }
+        if let Some(secs) = var("STEPHEYBOT__SCROBBLE__INTERVAL_SECONDS")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+        {
+            config.interval = Duration::from_secs(secs);
+        }
+        if let Some(hours) =
+            var("STEPHEYBOT__SCROBBLE__RETRY_MAX_HOURS").and_then(|v| v.parse::<u64>().ok())
+        {
+            config.retry_max = Duration::from_secs(hours * 3600);
+        }
+        if let Some(attempts) =
+            var("STEPHEYBOT__SCROBBLE__MAX_ATTEMPTS").and_then(|v| v.parse().ok())
+        {
+            config.max_attempts = attempts;
+        }
+
+        config
+    }
+
+    /// Wait before retrying after `attempts` failures
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.retry_base.saturating_mul(factor).min(self.retry_max)
+    }
+}
+
+/// Something to tell the scrobbling services
+#[derive(Debug, Clone)]
+pub enum Scrobble {
+    NowPlaying {
+        user_id: String,
+        track: QueueTrack,
+    },
+    Listen {
+        user_id: String,
+        /// The `listening_sessions` row the listen came from
+        session_id: i64,
+        track: QueueTrack,
+        listened_at: DateTime<Utc>,
+    },
+}
+
+impl Scrobble {
+    pub fn user_id(&self) -> &str {
+        match self {
+            Scrobble::NowPlaying { user_id, .. } | Scrobble::Listen { user_id, .. } => user_id,
+        }
+    }
+}
+
+/// How one of a user's scrobble destinations is doing
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrationStatus {
+    pub service: String,
+    /// When a submission last succeeded
+    pub last_sync: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub pending: i64,
+    /// Given up on after too many attempts
+    pub failed: i64,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// A queued listen
+#[derive(Debug, Clone)]
+struct QueuedListen {
+    id: i64,
+    session_id: Option<i64>,
+    track: QueueTrack,
+    listened_at: DateTime<Utc>,
+    attempts: u32,
+}
+
+/// Queues scrobbles in SQLite and submits them in the background
+pub struct Scrobbler {
+    config: ScrobbleConfig,
+    database: Arc<Database>,
+    navidrome: Option<NavidromeClient>,
+    wake: Notify,
+    /// Serialises flushes so entries are never sent twice
+    flush_lock: Mutex<()>,
+}
+
+impl Scrobbler {
+    pub fn new(
+        config: ScrobbleConfig,
+        database: Arc<Database>,
+        navidrome: Option<NavidromeClient>,
+    ) -> Self {
+        Self {
+            config,
+            database,
+            navidrome,
+            wake: Notify::new(),
+            flush_lock: Mutex::new(()),
+        }
+    }
+
+    /// Start sending queued scrobbles
+    pub fn start(self: &Arc<Self>) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = service.flush(Utc::now()).await {
+                    warn!("Scrobble flush failed: {}", e);
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(service.config.interval) => {}
+                    _ = service.wake.notified() => {}
+                }
+            }
+        });
+        info!("Scrobbler started");
+    }
+
+    /// Queue a scrobble unless the user turned scrobbling off.
+    ///
+    /// Returns whether anything new was queued; a listen already queued for
+    /// the same recording and time is ignored.
+    pub async fn enqueue(&self, scrobble: Scrobble) -> Result<bool> {
+        if !self.scrobble_enabled(scrobble.user_id()).await {
+            debug!("Scrobbling is off for user {}", scrobble.user_id());
+            return Ok(false);
+        }
+
+        match scrobble {
+            Scrobble::NowPlaying { user_id, track } => {
+                let listenbrainz = self.listenbrainz(&user_id).await?;
+                let navidrome = self.navidrome.clone();
+                tokio::spawn(async move {
+                    if let (Some((_, client)), Some(listen)) = (listenbrainz, listen(&track, None))
+                    {
+                        if let Err(e) = client.submit_now_playing(&listen).await {
+                            warn!(
+                                "ListenBrainz now playing failed for user {}: {}",
+                                user_id, e
+                            );
+                        }
+                    }
+                    if let Some(navidrome) = navidrome {
+                        if let Err(e) = navidrome.scrobble(&track.id, None, Some(false)).await {
+                            warn!("Navidrome now playing failed for {}: {}", track.id, e);
+                        }
+                    }
+                });
+                Ok(true)
+            }
+            Scrobble::Listen {
+                user_id,
+                session_id,
+                track,
+                listened_at,
+            } => {
+                let mut services = Vec::new();
+                if listen(&track, None).is_some() && self.listenbrainz(&user_id).await?.is_some() {
+                    services.push(LISTENBRAINZ);
+                }
+                if self.navidrome.is_some() {
+                    services.push(NAVIDROME);
+                }
+
+                let now = Utc::now().timestamp();
+                let track_json = serde_json::to_string(&track)?;
+                let mut queued = false;
+                for service in services {
+                    let result = sqlx::query(
+                        r#"
+                        INSERT OR IGNORE INTO scrobble_queue
+                            (user_id, service, session_id, track_id, recording_key, track_json,
+                             listened_at, next_attempt_at, created_at)
+                        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                        "#,
+                    )
+                    .bind(&user_id)
+                    .bind(service)
+                    .bind(session_id)
+                    .bind(&track.id)
+                    .bind(recording_key(&track))
+                    .bind(&track_json)
+                    .bind(listened_at.timestamp())
+                    .bind(now)
+                    .bind(now)
+                    .execute(self.database.pool())
+                    .await?;
+                    queued |= result.rows_affected() > 0;
+                }
+
+                if queued {
+                    self.wake.notify_one();
+                }
+                Ok(queued)
+            }
+        }
+    }
+
+    /// Send every entry that is due, grouped per user and service
+    pub async fn flush(&self, now: DateTime<Utc>) -> Result<usize> {
+        let _guard = self.flush_lock.lock().await;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM scrobble_queue
+            WHERE status = 'pending' AND next_attempt_at <= ?
+            ORDER BY user_id, service, listened_at
+            LIMIT 1000
+            "#,
+        )
+        .bind(now.timestamp())
+        .fetch_all(self.database.pool())
+        .await?;
+
+        let mut groups: BTreeMap<(String, String), Vec<QueuedListen>> = BTreeMap::new();
+        for row in rows {
+            let track_id: String = row.get("track_id");
+            let track = serde_json::from_str(row.get::<String, _>("track_json").as_str())
+                .unwrap_or_else(|_| QueueTrack::new(track_id));
+            groups
+                .entry((row.get("user_id"), row.get("service")))
+                .or_default()
+                .push(QueuedListen {
+                    id: row.get("id"),
+                    session_id: row.get("session_id"),
+                    track,
+                    listened_at: Utc
+                        .timestamp_opt(row.get("listened_at"), 0)
+                        .single()
+                        .unwrap_or_default(),
+                    attempts: row.get::<i64, _>("attempts") as u32,
+                });
+        }
+
+        let mut sent = 0;
+        for ((user_id, service), listens) in groups {
+            let (submitted, error) = match service.as_str() {
+                LISTENBRAINZ => self.send_listenbrainz(&user_id, &listens).await,
+                NAVIDROME => self.send_navidrome(&listens).await,
+                other => (0, Some(format!("Unknown scrobble service '{}'", other))),
+            };
+            let (done, rest) = listens.split_at(submitted);
+            self.mark_sent(&service, done, now).await?;
+            sent += done.len();
+
+            if let Some(error) = &error {
+                warn!(
+                    "Scrobbling to {} failed for user {}, {} waiting: {}",
+                    service,
+                    user_id,
+                    rest.len(),
+                    error
+                );
+                self.postpone(rest, error, now).await?;
+            }
+            self.record_outcome(&user_id, &service, !done.is_empty(), error.as_deref(), now)
+                .await?;
+        }
+
+        if sent > 0 {
+            debug!("Sent {} scrobbles", sent);
+        }
+        Ok(sent)
+    }
+
+    /// Queue health per destination for the user's integrations page
+    pub async fn status(&self, user_id: &str) -> Result<Vec<IntegrationStatus>> {
+        let mut statuses: BTreeMap<String, IntegrationStatus> = BTreeMap::new();
+        let empty = |service: &str| IntegrationStatus {
+            service: service.to_string(),
+            last_sync: None,
+            last_error: None,
+            last_error_at: None,
+            pending: 0,
+            failed: 0,
+            next_attempt_at: None,
+        };
+        let timestamp = |seconds: Option<i64>| {
+            seconds.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+        };
+
+        let integrations = sqlx::query(
+            "SELECT service_name, last_sync, last_error, last_error_at FROM user_integrations WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_all(self.database.pool())
+        .await?;
+        for row in integrations {
+            let service: String = row.get("service_name");
+            let status = statuses
+                .entry(service.clone())
+                .or_insert_with(|| empty(&service));
+            status.last_sync = timestamp(row.get("last_sync"));
+            status.last_error = row.get("last_error");
+            status.last_error_at = timestamp(row.get("last_error_at"));
+        }
+
+        let counts = sqlx::query(
+            r#"
+            SELECT service,
+                   SUM(CASE WHEN status = 'pending' THEN 1 ELSE 0 END) AS pending,
+                   SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS failed,
+                   MIN(CASE WHEN status = 'pending' THEN next_attempt_at END) AS next_attempt_at,
+                   MAX(CASE WHEN status = 'sent' THEN sent_at END) AS last_sent_at
+            FROM scrobble_queue
+            WHERE user_id = ?
+            GROUP BY service
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.database.pool())
+        .await?;
+        for row in counts {
+            let service: String = row.get("service");
+            let status = statuses
+                .entry(service.clone())
+                .or_insert_with(|| empty(&service));
+            status.pending = row.get("pending");
+            status.failed = row.get("failed");
+            status.next_attempt_at = timestamp(row.get("next_attempt_at"));
+            if status.last_sync.is_none() {
+                status.last_sync = timestamp(row.get("last_sent_at"));
+            }
+        }
+
+        Ok(statuses.into_values().collect())
+    }
+
+    /// Make the user's waiting and given-up entries due now, for after a
+    /// token has been fixed
+    pub async fn retry(&self, user_id: &str, service: Option<&str>) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE scrobble_queue
+            SET status = 'pending', attempts = 0, next_attempt_at = ?
+            WHERE user_id = ? AND status IN ('pending', 'failed') AND (? IS NULL OR service = ?)
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .bind(user_id)
+        .bind(service)
+        .bind(service)
+        .execute(self.database.pool())
+        .await?;
+
+        if result.rows_affected() > 0 {
+            self.wake.notify_one();
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Returns how many listens went through and, if it stopped early, why
+    async fn send_listenbrainz(
+        &self,
+        user_id: &str,
+        listens: &[QueuedListen],
+    ) -> (usize, Option<String>) {
+        let (user_name, client) = match self.listenbrainz(user_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return (0, Some("No ListenBrainz account linked".to_string())),
+            Err(e) => return (0, Some(e.to_string())),
+        };
+
+        let payload: Vec<Listen> = listens
+            .iter()
+            .filter_map(|queued| listen(&queued.track, Some(queued.listened_at)))
+            .collect();
+        if payload.len() != listens.len() {
+            // Only complete tracks are queued for ListenBrainz
+            return (0, Some("Listen is missing its artist or title".to_string()));
+        }
+
+        match client
+            .batch_submit_listens(&user_name, payload, self.config.batch_size)
+            .await
+        {
+            Ok(submitted) => (submitted, None),
+            Err(e) => (e.submitted, Some(e.message)),
+        }
+    }
+
+    async fn send_navidrome(&self, listens: &[QueuedListen]) -> (usize, Option<String>) {
+        let Some(navidrome) = &self.navidrome else {
+            return (0, Some("Navidrome is not configured".to_string()));
+        };
+        for (i, queued) in listens.iter().enumerate() {
+            let time = queued.listened_at.timestamp_millis() as u64;
+            if let Err(e) = navidrome
+                .scrobble(&queued.track.id, Some(time), Some(true))
+                .await
+            {
+                return (i, Some(e.to_string()));
+            }
+        }
+        (listens.len(), None)
+    }
+
+    async fn mark_sent(
+        &self,
+        service: &str,
+        listens: &[QueuedListen],
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        for queued in listens {
+            sqlx::query(
+                "UPDATE scrobble_queue SET status = 'sent', sent_at = ?, last_error = NULL WHERE id = ?",
+            )
+            .bind(now.timestamp())
+            .bind(queued.id)
+            .execute(self.database.pool())
+            .await?;
+
+            if let (LISTENBRAINZ, Some(session_id)) = (service, queued.session_id) {
+                sqlx::query(
+                    "UPDATE listening_sessions SET scrobbled_listenbrainz = 1 WHERE id = ?",
+                )
+                .bind(session_id)
+                .execute(self.database.pool())
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Push entries back with backoff, giving up on those out of attempts
+    async fn postpone(
+        &self,
+        listens: &[QueuedListen],
+        error: &str,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        for queued in listens {
+            let attempts = queued.attempts + 1;
+            let status = if attempts >= self.config.max_attempts {
+                "failed"
+            } else {
+                "pending"
+            };
+            let next_attempt_at = now.timestamp() + self.config.backoff(attempts).as_secs() as i64;
+            sqlx::query(
+                r#"
+                UPDATE scrobble_queue
+                SET status = ?, attempts = ?, next_attempt_at = ?, last_error = ?
+                WHERE id = ?
+                "#,
+            )
+            .bind(status)
+            .bind(attempts as i64)
+            .bind(next_attempt_at)
+            .bind(error)
+            .bind(queued.id)
+            .execute(self.database.pool())
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Note on the user's integration when it last worked and why it didn't
+    async fn record_outcome(
+        &self,
+        user_id: &str,
+        service: &str,
+        synced: bool,
+        error: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE user_integrations
+            SET last_sync = CASE WHEN ? THEN ? ELSE last_sync END,
+                last_error = ?,
+                last_error_at = CASE WHEN ? IS NULL THEN NULL ELSE ? END
+            WHERE user_id = ? AND service_name = ?
+            "#,
+        )
+        .bind(synced)
+        .bind(now.timestamp())
+        .bind(error)
+        .bind(error)
+        .bind(now.timestamp())
+        .bind(user_id)
+        .bind(service)
+        .execute(self.database.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// The ListenBrainz account and token a user linked, if any
+    async fn listenbrainz(&self, user_id: &str) -> Result<Option<(String, ListenBrainzClient)>> {
+        let row = sqlx::query(
+            r#"
+            SELECT service_user_id, api_token_encrypted FROM user_integrations
+            WHERE user_id = ? AND service_name = 'listenbrainz' AND enabled = 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(self.database.pool())
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let user_name: Option<String> = row.get("service_user_id");
+        let token: Option<String> = row.get("api_token_encrypted");
+        match (user_name, token) {
+            (Some(user_name), Some(token)) => Ok(Some((
+                user_name,
+                ListenBrainzClient::new(&self.config.listenbrainz_url, Some(&token))?,
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    async fn scrobble_enabled(&self, user_id: &str) -> bool {
+        let preferences: Option<String> =
+            sqlx::query_scalar("SELECT preferences_json FROM users WHERE id = ?")
+                .bind(user_id)
+                .fetch_optional(self.database.pool())
+                .await
+                .ok()
+                .flatten();
+        preferences
+            .and_then(|json| serde_json::from_str::<UserPreferences>(&json).ok())
+            .unwrap_or_default()
+            .scrobble_enabled
+    }
+}
+
+/// What makes two listens the same recording: artist and title, or the
+/// track id when those are unknown
+fn recording_key(track: &QueueTrack) -> String {
+    match (&track.artist, &track.title) {
+        (Some(artist), Some(title)) => format!(
+            "{}\u{1f}{}",
+            artist.trim().to_lowercase(),
+            title.trim().to_lowercase()
+        ),
+        _ => track.id.clone(),
+    }
+}
+
+/// The ListenBrainz form of a track; it needs at least an artist and a title
+fn listen(track: &QueueTrack, listened_at: Option<DateTime<Utc>>) -> Option<Listen> {
+    Some(Listen {
+        track_metadata: TrackMetadata {
+            artist_name: track.artist.clone()?,
+            track_name: track.title.clone()?,
+            release_name: track.album.clone(),
+            additional_info: Some(AdditionalInfo {
+                duration_ms: track.duration_secs.map(|secs| secs as u64 * 1000),
+                recording_mbid: None,
+                artist_mbid: None,
+                release_mbid: None,
+            }),
+        },
+        listened_at: listened_at.map(|at| at.timestamp()),
+        recording_msid: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_queue_dedupes_and_backs_off() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO user_integrations (user_id, service_name, service_user_id, api_token_encrypted)
+            VALUES (1, 'listenbrainz', 'admin', 'token')
+            "#,
+        )
+        .execute(database.pool())
+        .await
+        .unwrap();
+
+        // Nothing listens here, so every submission fails
+        let scrobbler = Scrobbler::new(
+            ScrobbleConfig {
+                listenbrainz_url: "http://127.0.0.1:9".to_string(),
+                ..ScrobbleConfig::default()
+            },
+            database.clone(),
+            None,
+        );
+        let listened_at = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let scrobble = |id: &str| Scrobble::Listen {
+            user_id: "1".to_string(),
+            session_id: 1,
+            track: QueueTrack {
+                title: Some("Song".to_string()),
+                artist: Some("Band".to_string()),
+                ..QueueTrack::new(id)
+            },
+            listened_at,
+        };
+
+        assert!(scrobbler.enqueue(scrobble("a")).await.unwrap());
+        // Same recording at the same time, even from another copy of the file
+        assert!(!scrobbler.enqueue(scrobble("a-copy")).await.unwrap());
+
+        let now = Utc::now();
+        assert_eq!(scrobbler.flush(now).await.unwrap(), 0);
+        let status = scrobbler.status("1").await.unwrap();
+        let listenbrainz = status.iter().find(|s| s.service == LISTENBRAINZ).unwrap();
+        assert_eq!(listenbrainz.pending, 1);
+        assert!(listenbrainz.last_error.is_some());
+        assert!(listenbrainz.last_sync.is_none());
+        assert_eq!(
+            listenbrainz.next_attempt_at.unwrap().timestamp(),
+            now.timestamp() + 60
+        );
+
+        // Not due yet
+        assert_eq!(scrobbler.flush(now).await.unwrap(), 0);
+        let attempts: i64 = sqlx::query_scalar("SELECT attempts FROM scrobble_queue")
+            .fetch_one(database.pool())
+            .await
+            .unwrap();
+        assert_eq!(attempts, 1);
+
+        assert_eq!(scrobbler.retry("1", Some(LISTENBRAINZ)).await.unwrap(), 1);
+        let attempts: i64 = sqlx::query_scalar("SELECT attempts FROM scrobble_queue")
+            .fetch_one(database.pool())
+            .await
+            .unwrap();
+        assert_eq!(attempts, 0);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let config = ScrobbleConfig::default();
+        assert_eq!(config.backoff(1), Duration::from_secs(60));
+        assert_eq!(config.backoff(3), Duration::from_secs(240));
+        assert_eq!(config.backoff(30), config.retry_max);
+    }
+}
@@ -0,0 +1,251 @@
+//! Seeding policy for StepheyBot Music
+//!
+//! Completed torrents keep seeding until they reach the ratio or seeding time
+//! required by the indexer they came from. A torrent is only removed once its
+//! release has been imported, and its data is only deleted when nothing in
+//! the library still points at it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use walkdir::WalkDir;
+
+use crate::clients::torznab::IndexerConfig;
+
+/// Limits after which a torrent has seeded enough; `None` means no limit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SeedingRule {
+    pub ratio_limit: Option<f64>,
+    pub time_limit: Option<Duration>,
+}
+
+impl SeedingRule {
+    /// Whether a torrent with this ratio and seeding time satisfies the rule;
+    /// reaching either limit is enough, a rule without limits seeds forever
+    pub fn is_met(&self, ratio: f64, seeding_time: Duration) -> bool {
+        self.ratio_limit.is_some_and(|limit| ratio >= limit)
+            || self.time_limit.is_some_and(|limit| seeding_time >= limit)
+    }
+}
+
+/// Seeding rules, with overrides for indexers that need longer seeding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedingPolicy {
+    pub default: SeedingRule,
+    pub overrides: HashMap<String, SeedingRule>,
+}
+
+impl SeedingPolicy {
+    /// Build the policy from the global limits and per-indexer overrides
+    ///
+    /// An override replaces the global rule as a whole: a limit the indexer
+    /// leaves unset means no limit, so a tracker that only asks for seeding
+    /// time isn't cut short by the global ratio.
+    pub fn new(default: SeedingRule, indexers: &[IndexerConfig]) -> Self {
+        let overrides = indexers
+            .iter()
+            .filter(|indexer| indexer.seed_ratio.is_some() || indexer.seed_time.is_some())
+            .map(|indexer| {
+                let rule = SeedingRule {
+                    ratio_limit: indexer.seed_ratio,
+                    time_limit: indexer.seed_time,
+                };
+                (indexer.name.to_lowercase(), rule)
+            })
+            .collect();
+
+        Self { default, overrides }
+    }
+
+    /// The rule for a torrent grabbed from `indexer`
+    pub fn rule_for(&self, indexer: Option<&str>) -> SeedingRule {
+        indexer
+            .and_then(|name| self.overrides.get(&name.to_lowercase()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Whether a torrent can go, and why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SeedingDecision {
+    /// Keep seeding
+    Keep { reason: String },
+    /// Remove the torrent, deleting its data only if `delete_data` is set
+    Remove { delete_data: bool, reason: String },
+}
+
+/// Seeding state of one torrent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedingStatus {
+    pub torrent_hash: String,
+    pub name: String,
+    pub request_id: Option<String>,
+    pub indexer: Option<String>,
+    pub ratio: f64,
+    pub seeding_time: Duration,
+    pub rule: SeedingRule,
+    pub policy_met: bool,
+    pub imported: bool,
+    pub decision: SeedingDecision,
+}
+
+/// Decide what to do with a completed torrent
+pub fn decide(
+    rule: &SeedingRule,
+    ratio: f64,
+    seeding_time: Duration,
+    imported: bool,
+    data_path: &Path,
+    library_path: &Path,
+) -> SeedingDecision {
+    if !imported {
+        return SeedingDecision::Keep {
+            reason: "Release has not been imported yet".to_string(),
+        };
+    }
+
+    if !rule.is_met(ratio, seeding_time) {
+        return SeedingDecision::Keep {
+            reason: format!(
+                "Seeding policy not met (ratio {:.2}, {}h seeded)",
+                ratio,
+                seeding_time.as_secs() / 3600
+            ),
+        };
+    }
+
+    match library_reference(data_path, library_path) {
+        Some(reason) => SeedingDecision::Remove {
+            delete_data: false,
+            reason: format!("Seeding policy met, keeping data: {}", reason),
+        },
+        None => SeedingDecision::Remove {
+            delete_data: true,
+            reason: "Seeding policy met and release imported".to_string(),
+        },
+    }
+}
+
+/// Explain why deleting `data_path` could break the library, if it could
+fn library_reference(data_path: &Path, library_path: &Path) -> Option<String> {
+    let data = canonical(data_path);
+    let library = canonical(library_path);
+
+    if data.starts_with(&library) || library.starts_with(&data) {
+        return Some(format!("{} overlaps the library", data.display()));
+    }
+
+    // Hardlinked files are shared with the library even though they live elsewhere
+    for entry in WalkDir::new(&data).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() && is_hardlinked(entry.path()) {
+            return Some(format!("{} is hardlinked", entry.path().display()));
+        }
+    }
+
+    None
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(unix)]
+fn is_hardlinked(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.nlink() > 1)
+}
+
+#[cfg(not(unix))]
+fn is_hardlinked(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const DAY: Duration = Duration::from_secs(86400);
+
+    #[test]
+    fn test_rule_for_indexer() {
+        let default = SeedingRule {
+            ratio_limit: Some(2.0),
+            time_limit: Some(DAY),
+        };
+        let mut private = IndexerConfig::new("Private", "http://private", "key");
+        private.seed_time = Some(DAY * 7);
+        let public = IndexerConfig::new("public", "http://public", "key");
+
+        let policy = SeedingPolicy::new(default, &[private, public]);
+        let rule = policy.rule_for(Some("private"));
+        assert_eq!(rule.time_limit, Some(DAY * 7));
+        assert_eq!(rule.ratio_limit, None);
+        assert_eq!(policy.rule_for(Some("public")), default);
+        assert_eq!(policy.rule_for(None), default);
+
+        assert!(default.is_met(2.0, Duration::ZERO));
+        assert!(default.is_met(0.1, DAY));
+        assert!(!rule.is_met(0.1, DAY));
+        // The global ratio doesn't end a time-only override early
+        assert!(!rule.is_met(5.0, DAY));
+        assert!(rule.is_met(0.0, DAY * 7));
+        let forever = SeedingRule {
+            ratio_limit: None,
+            time_limit: None,
+        };
+        assert!(!forever.is_met(100.0, DAY * 365));
+    }
+
+    #[test]
+    fn test_decide() {
+        let root = TempDir::new().unwrap();
+        let library = root.path().join("library");
+        let downloads = root.path().join("downloads/Artist - Album");
+        std::fs::create_dir_all(&library).unwrap();
+        std::fs::create_dir_all(&downloads).unwrap();
+        std::fs::write(downloads.join("01.flac"), b"audio").unwrap();
+
+        let rule = SeedingRule {
+            ratio_limit: Some(1.0),
+            time_limit: None,
+        };
+
+        let keep = decide(&rule, 5.0, DAY, false, &downloads, &library);
+        assert!(matches!(keep, SeedingDecision::Keep { .. }));
+        let keep = decide(&rule, 0.5, DAY, true, &downloads, &library);
+        assert!(matches!(keep, SeedingDecision::Keep { .. }));
+
+        let remove = decide(&rule, 1.5, DAY, true, &downloads, &library);
+        assert!(matches!(
+            remove,
+            SeedingDecision::Remove {
+                delete_data: true,
+                ..
+            }
+        ));
+
+        // Data inside the library or shared through hardlinks is never deleted
+        let inside = decide(&rule, 1.5, DAY, true, &library, &library);
+        assert!(matches!(
+            inside,
+            SeedingDecision::Remove {
+                delete_data: false,
+                ..
+            }
+        ));
+
+        std::fs::hard_link(downloads.join("01.flac"), library.join("01.flac")).unwrap();
+        let linked = decide(&rule, 1.5, DAY, true, &downloads, &library);
+        assert!(matches!(
+            linked,
+            SeedingDecision::Remove {
+                delete_data: false,
+                ..
+            }
+        ));
+    }
+}
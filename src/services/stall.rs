@@ -0,0 +1,223 @@
+//! Stall detection for StepheyBot Music
+//!
+//! A torrent that makes no progress for the configured time, or that
+//! Transmission reports an error for, is stalled. Stalled torrents are
+//! restarted a few times before the release is given up on, blocklisted and
+//! replaced by the next-best candidate from the last search.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::clients::transmission::TorrentInfo;
+
+/// Progress smaller than this is noise, not a sign of life
+const MIN_PROGRESS: f64 = 0.0001;
+
+/// When a torrent counts as stalled and how often it is restarted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StallPolicy {
+    /// Time without progress after which a torrent is stalled
+    pub timeout: Duration,
+    /// Restarts before the release is blocklisted
+    pub max_restarts: u32,
+}
+
+impl Default for StallPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30 * 60),
+            max_restarts: 2,
+        }
+    }
+}
+
+/// How a downloading torrent is doing
+#[derive(Debug, Clone, PartialEq)]
+pub enum TorrentHealth {
+    Healthy,
+    /// No progress for `idle`
+    Stalled {
+        idle: Duration,
+    },
+    /// Transmission reports an error
+    Errored(String),
+}
+
+impl TorrentHealth {
+    /// Why the torrent needs attention, if it does
+    pub fn problem(&self) -> Option<String> {
+        match self {
+            TorrentHealth::Healthy => None,
+            TorrentHealth::Stalled { idle } => {
+                Some(format!("No progress for {} minutes", idle.as_secs() / 60))
+            }
+            TorrentHealth::Errored(error) => Some(format!("Transmission error: {}", error)),
+        }
+    }
+}
+
+/// What to do about a torrent that needs attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallAction {
+    /// Stop and start the torrent to look for peers again
+    Restart { attempt: u32 },
+    /// Give up on the release
+    Abandon,
+}
+
+#[derive(Debug, Clone)]
+struct Progress {
+    progress: f64,
+    changed_at: DateTime<Utc>,
+    restarts: u32,
+}
+
+/// Tracks the progress of downloading torrents between monitor passes
+#[derive(Debug, Clone, Default)]
+pub struct StallDetector {
+    policy: StallPolicy,
+    torrents: HashMap<String, Progress>,
+}
+
+impl StallDetector {
+    pub fn new(policy: StallPolicy) -> Self {
+        Self {
+            policy,
+            torrents: HashMap::new(),
+        }
+    }
+
+    /// Record the latest state of a torrent and judge its health
+    pub fn observe(&mut self, torrent: &TorrentInfo, now: DateTime<Utc>) -> TorrentHealth {
+        let tracked = self
+            .torrents
+            .entry(torrent.hash.clone())
+            .or_insert_with(|| Progress {
+                progress: torrent.progress,
+                changed_at: now,
+                restarts: 0,
+            });
+
+        if !torrent.error.is_empty() {
+            return TorrentHealth::Errored(torrent.error.clone());
+        }
+
+        // Paused or queued torrents are not stalled; the clock starts over
+        // when Transmission downloads them again
+        if torrent.progress > tracked.progress + MIN_PROGRESS || !torrent.is_downloading() {
+            tracked.progress = torrent.progress;
+            tracked.changed_at = now;
+        }
+
+        let idle = (now - tracked.changed_at).to_std().unwrap_or_default();
+        if idle >= self.policy.timeout {
+            TorrentHealth::Stalled { idle }
+        } else {
+            TorrentHealth::Healthy
+        }
+    }
+
+    /// Decide what to do about a torrent that needs attention; a restart
+    /// gives the torrent another full timeout to make progress
+    pub fn next_action(&mut self, hash: &str, now: DateTime<Utc>) -> StallAction {
+        let Some(tracked) = self.torrents.get_mut(hash) else {
+            return StallAction::Abandon;
+        };
+
+        if tracked.restarts >= self.policy.max_restarts {
+            return StallAction::Abandon;
+        }
+
+        tracked.restarts += 1;
+        tracked.changed_at = now;
+        StallAction::Restart {
+            attempt: tracked.restarts,
+        }
+    }
+
+    /// Stop tracking a torrent that finished or was removed
+    pub fn forget(&mut self, hash: &str) {
+        self.torrents.remove(hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as ChronoDuration;
+
+    fn torrent(progress: f64, error: &str) -> TorrentInfo {
+        TorrentInfo {
+            id: 1,
+            name: "Artist - Album".to_string(),
+            size: 1000,
+            progress,
+            download_speed: 0,
+            upload_speed: 0,
+            status: 4,
+            hash: "abc".to_string(),
+            download_dir: "/downloads".to_string(),
+            error: error.to_string(),
+            eta: -1,
+            ratio: 0.0,
+            seconds_seeding: 0,
+        }
+    }
+
+    #[test]
+    fn test_stall_detection_and_escalation() {
+        let mut detector = StallDetector::new(StallPolicy {
+            timeout: Duration::from_secs(600),
+            max_restarts: 1,
+        });
+        let start = Utc::now();
+
+        assert_eq!(
+            detector.observe(&torrent(0.1, ""), start),
+            TorrentHealth::Healthy
+        );
+        // Progress resets the clock
+        let later = start + ChronoDuration::minutes(8);
+        assert_eq!(
+            detector.observe(&torrent(0.2, ""), later),
+            TorrentHealth::Healthy
+        );
+        let stalled = later + ChronoDuration::minutes(10);
+        let health = detector.observe(&torrent(0.2, ""), stalled);
+        assert_eq!(
+            health,
+            TorrentHealth::Stalled {
+                idle: Duration::from_secs(600)
+            }
+        );
+        assert_eq!(health.problem().unwrap(), "No progress for 10 minutes");
+
+        assert_eq!(
+            detector.next_action("abc", stalled),
+            StallAction::Restart { attempt: 1 }
+        );
+        // The restart earns a fresh timeout, errors are reported right away
+        assert_eq!(
+            detector.observe(&torrent(0.2, ""), stalled + ChronoDuration::minutes(5)),
+            TorrentHealth::Healthy
+        );
+        assert!(matches!(
+            detector.observe(&torrent(0.2, "Tracker gave HTTP 404"), stalled),
+            TorrentHealth::Errored(_)
+        ));
+        assert_eq!(detector.next_action("abc", stalled), StallAction::Abandon);
+
+        let mut paused = torrent(0.2, "");
+        paused.status = 0;
+        detector.observe(&paused, stalled + ChronoDuration::hours(1));
+        assert_eq!(
+            detector.observe(&torrent(0.2, ""), stalled + ChronoDuration::minutes(65)),
+            TorrentHealth::Healthy
+        );
+
+        detector.forget("abc");
+        assert_eq!(detector.next_action("abc", stalled), StallAction::Abandon);
+    }
+}
@@ -1,11 +1,35 @@
+//! Tiered storage for StepheyBot Music
+//!
+//! Moves finished audio from the hot download directory to the library, with
+//! a manifest so an interrupted offload is finished rather than lost. This is
+//! library-only and built with the `tiered-storage` feature: the server moves
+//! completed downloads itself after seeding, so it does not run the monitor.
+
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
+use crate::services::disk_space::disk_usage;
+use crate::services::recycle_bin::{RecycleBin, SYSTEM_ACTOR};
+use crate::utils::sha256_file;
+
+/// Offloads in flight, kept in the hot tier so an interrupted offload can be
+/// finished instead of leaving a half-copied file behind
+const MANIFEST_FILE: &str = ".offload-manifest.json";
+
+/// Suffix of a destination that is still being copied
+const PARTIAL_SUFFIX: &str = ".offloading";
+
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
 /// Configuration for tiered storage operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageConfig {
@@ -29,7 +53,7 @@ impl Default for StorageConfig {
             enable_tiered: true,
             auto_offload: true,
             offload_delay_seconds: 300, // 5 minutes
-            verify_integrity: false,    // Simplified for now
+            verify_integrity: true,
         }
     }
 }
@@ -43,18 +67,115 @@ pub struct StorageOperationResult {
     pub destination_path: Option<PathBuf>,
     pub duration_ms: u64,
     pub bytes_transferred: u64,
+    /// SHA-256 of the file, when integrity verification is enabled
+    pub sha256: Option<String>,
     pub error: Option<String>,
 }
 
+/// How far an offload got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OffloadStage {
+    /// Copying into the partial destination file
+    Copying,
+    /// The destination is in place and verified; the source can be removed
+    Verified,
+}
+
+/// One offload in the manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OffloadEntry {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub size: u64,
+    /// Source modification time, so a file rewritten in place at the same
+    /// size is not resumed onto a stale partial copy
+    #[serde(default)]
+    pub modified: Option<SystemTime>,
+    pub sha256: Option<String>,
+    pub stage: OffloadStage,
+}
+
+/// Offloads that have started but not finished, keyed by source path
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OffloadManifest {
+    entries: HashMap<PathBuf, OffloadEntry>,
+}
+
+impl OffloadManifest {
+    fn path(config: &StorageConfig) -> PathBuf {
+        config.hot_downloads_path.join(MANIFEST_FILE)
+    }
+
+    /// Load the manifest, treating a missing file as empty
+    pub async fn load(config: &StorageConfig) -> Result<Self> {
+        let path = Self::path(config);
+        match fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Failed to parse offload manifest: {:?}", path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Failed to read offload manifest: {:?}", path))
+            }
+        }
+    }
+
+    /// Write the manifest through a temporary file so a crash never leaves it truncated
+    async fn save(&self, config: &StorageConfig) -> Result<()> {
+        let path = Self::path(config);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?).await?;
+        fs::rename(&temp_path, &path)
+            .await
+            .with_context(|| format!("Failed to write offload manifest: {:?}", path))
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &OffloadEntry> {
+        self.entries.values()
+    }
+
+    async fn record(&mut self, entry: OffloadEntry, config: &StorageConfig) -> Result<()> {
+        self.entries.insert(entry.source.clone(), entry);
+        self.save(config).await
+    }
+
+    async fn finish(&mut self, source: &Path, config: &StorageConfig) -> Result<()> {
+        if self.entries.remove(source).is_some() {
+            self.save(config).await?;
+        }
+        Ok(())
+    }
+}
+
+fn partial_path(destination: &Path) -> PathBuf {
+    let mut name = destination.file_name().unwrap_or_default().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    destination.with_file_name(name)
+}
+
 /// Storage management service for handling tiered storage operations
 pub struct StorageManager {
     config: StorageConfig,
+    /// Serialises offloads so the monitor and manual offloads share the manifest safely
+    offload_lock: Arc<Mutex<()>>,
+    /// Where cleaned-up directories go instead of being deleted
+    recycle_bin: Option<RecycleBin>,
 }
 
 impl StorageManager {
     /// Create a new storage manager with the given configuration
     pub fn new(config: StorageConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            offload_lock: Arc::new(Mutex::new(())),
+            recycle_bin: None,
+        }
+    }
+
+    /// Send cleaned-up directories to the recycle bin
+    pub fn with_recycle_bin(mut self, recycle_bin: RecycleBin) -> Self {
+        self.recycle_bin = Some(recycle_bin);
+        self
     }
 
     /// Initialize storage directories
@@ -91,10 +212,14 @@ impl StorageManager {
         info!("Starting storage monitor for tiered offloading");
 
         let config = self.config.clone();
+        let offload_lock = Arc::clone(&self.offload_lock);
         tokio::spawn(async move {
             loop {
-                if let Err(e) = Self::monitor_and_offload(&config).await {
-                    error!("Storage monitor error: {}", e);
+                {
+                    let _guard = offload_lock.lock().await;
+                    if let Err(e) = Self::monitor_and_offload(&config).await {
+                        error!("Storage monitor error: {}", e);
+                    }
                 }
                 sleep(Duration::from_secs(30)).await; // Check every 30 seconds
             }
@@ -105,6 +230,8 @@ impl StorageManager {
 
     /// Monitor downloads and offload completed files
     async fn monitor_and_offload(config: &StorageConfig) -> Result<()> {
+        Self::resume_offloads(config).await?;
+
         let completed_files = Self::find_completed_downloads(&config.hot_downloads_path).await?;
 
         for file_path in completed_files {
@@ -194,39 +321,85 @@ impl StorageManager {
         Ok(age >= delay_seconds)
     }
 
+    /// Finish offloads that were interrupted, e.g. by a restart
+    pub async fn resume_offloads(config: &StorageConfig) -> Result<Vec<StorageOperationResult>> {
+        let manifest = OffloadManifest::load(config).await?;
+        let pending: Vec<OffloadEntry> = manifest.entries().cloned().collect();
+        let mut results = Vec::new();
+
+        for entry in pending {
+            info!(
+                "Resuming interrupted offload: {:?} -> {:?}",
+                entry.source, entry.destination
+            );
+            match Self::offload_file(&entry.source, config).await {
+                Ok(result) => results.push(result),
+                Err(e) => error!("Failed to resume offload of {:?}: {}", entry.source, e),
+            }
+        }
+
+        Ok(results)
+    }
+
     /// Offload a file from hot to cold storage
+    ///
+    /// The file is copied to a partial destination, verified against the
+    /// source and only then renamed into place and removed from the hot tier.
+    /// Every step is recorded in the manifest, so calling this again after an
+    /// interruption picks up where the last attempt stopped.
     async fn offload_file(
         source_path: &Path,
         config: &StorageConfig,
     ) -> Result<StorageOperationResult> {
         let start_time = std::time::Instant::now();
+        let mut manifest = OffloadManifest::load(config).await?;
+        let entry = manifest.entries.get(source_path).cloned();
 
-        // Determine destination path in cold storage
-        let filename = source_path.file_name().unwrap();
-        let destination_path = config.final_library_path.join(filename);
-
-        // Create destination directory
-        if let Some(parent) = destination_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-
-        // Get file size
-        let file_size = fs::metadata(source_path).await?.len();
-
-        // Copy file to final destination
-        fs::copy(source_path, &destination_path)
-            .await
-            .with_context(|| {
-                format!(
-                    "Failed to copy to destination: {:?} -> {:?}",
-                    source_path, destination_path
-                )
-            })?;
+        let (entry, bytes_transferred) = match entry {
+            // The copy was verified before the interruption, only the source is left
+            Some(entry) if entry.stage == OffloadStage::Verified && entry.destination.exists() => {
+                (entry, 0)
+            }
+            Some(entry) if !source_path.exists() => {
+                // Nothing left to copy from; keep whatever made it across
+                manifest.finish(source_path, config).await?;
+                anyhow::bail!(
+                    "Source of interrupted offload disappeared: {:?} (partial copy left at {:?})",
+                    source_path,
+                    partial_path(&entry.destination)
+                );
+            }
+            entry => {
+                let mut entry = match entry {
+                    Some(entry) => entry,
+                    None => {
+                        let entry = Self::plan_offload(source_path, config).await?;
+                        manifest.record(entry.clone(), config).await?;
+                        entry
+                    }
+                };
+                let bytes_transferred = Self::copy_and_verify(&mut entry, config).await?;
+                fs::rename(partial_path(&entry.destination), &entry.destination)
+                    .await
+                    .with_context(|| {
+                        format!("Failed to move offload into place: {:?}", entry.destination)
+                    })?;
+                entry.stage = OffloadStage::Verified;
+                manifest.record(entry.clone(), config).await?;
+                (entry, bytes_transferred)
+            }
+        };
 
         // Remove original file from hot storage
-        fs::remove_file(source_path)
-            .await
-            .with_context(|| format!("Failed to remove original file: {:?}", source_path))?;
+        match fs::remove_file(source_path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to remove original file: {:?}", source_path))
+            }
+        }
+        manifest.finish(source_path, config).await?;
 
         let duration = start_time.elapsed();
 
@@ -234,13 +407,136 @@ impl StorageManager {
             success: true,
             operation: "offload".to_string(),
             source_path: source_path.to_path_buf(),
-            destination_path: Some(destination_path),
+            destination_path: Some(entry.destination),
             duration_ms: duration.as_millis() as u64,
-            bytes_transferred: file_size,
+            bytes_transferred,
+            sha256: entry.sha256,
             error: None,
         })
     }
 
+    /// Pick the destination of a new offload without overwriting a different file
+    async fn plan_offload(source_path: &Path, config: &StorageConfig) -> Result<OffloadEntry> {
+        let filename = source_path
+            .file_name()
+            .with_context(|| format!("Not a file: {:?}", source_path))?;
+        let metadata = fs::metadata(source_path).await?;
+
+        let mut destination = config.final_library_path.join(filename);
+        let stem = Path::new(filename)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let extension = Path::new(filename)
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        let mut copy = 1;
+        while destination.exists() {
+            copy += 1;
+            destination = config
+                .final_library_path
+                .join(format!("{} ({}){}", stem, copy, extension));
+        }
+
+        Ok(OffloadEntry {
+            source: source_path.to_path_buf(),
+            destination,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            sha256: None,
+            stage: OffloadStage::Copying,
+        })
+    }
+
+    /// Copy the source into the partial destination, continuing a previous
+    /// partial copy, and check the result. Returns the bytes written this time.
+    async fn copy_and_verify(entry: &mut OffloadEntry, config: &StorageConfig) -> Result<u64> {
+        let partial = partial_path(&entry.destination);
+        if let Some(parent) = partial.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let metadata = fs::metadata(&entry.source).await?;
+        let size = metadata.len();
+        let modified = metadata.modified().ok();
+        if size != entry.size || modified.is_none() || modified != entry.modified {
+            // The source changed since the offload started, so start over
+            warn!(
+                "{:?} changed during offload ({} -> {} bytes), restarting",
+                entry.source, entry.size, size
+            );
+            entry.size = size;
+            entry.modified = modified;
+            let _ = fs::remove_file(&partial).await;
+        }
+
+        let mut destination = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&partial)
+            .await
+            .with_context(|| format!("Failed to open offload destination: {:?}", partial))?;
+        let mut offset = destination.metadata().await?.len();
+        if offset > size {
+            destination.set_len(0).await?;
+            offset = 0;
+        }
+        if offset > 0 {
+            debug!(
+                "Resuming offload of {:?} at {} of {} bytes",
+                entry.source, offset, size
+            );
+        }
+
+        let mut source = fs::File::open(&entry.source).await?;
+        source.seek(std::io::SeekFrom::Start(offset)).await?;
+        let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+        let mut bytes_transferred = 0u64;
+        loop {
+            let read = source.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            destination.write_all(&buffer[..read]).await?;
+            bytes_transferred += read as u64;
+        }
+        destination.sync_all().await?;
+        drop(destination);
+
+        let copied = fs::metadata(&partial).await?.len();
+        let verified = if copied != size {
+            Err(format!("copied {} of {} bytes", copied, size))
+        } else if config.verify_integrity {
+            let source_hash = sha256_file(&entry.source).await?;
+            let destination_hash = sha256_file(&partial).await?;
+            if source_hash == destination_hash {
+                entry.sha256 = Some(source_hash);
+                Ok(())
+            } else {
+                Err(format!(
+                    "checksum mismatch (source {}, destination {})",
+                    source_hash, destination_hash
+                ))
+            }
+        } else {
+            Ok(())
+        };
+
+        if let Err(reason) = verified {
+            // A corrupt copy is discarded so the next attempt copies from scratch
+            let _ = fs::remove_file(&partial).await;
+            anyhow::bail!(
+                "Offload of {:?} failed verification: {}",
+                entry.source,
+                reason
+            );
+        }
+
+        Ok(bytes_transferred)
+    }
+
     /// Get storage statistics
     pub async fn get_storage_stats(&self) -> Result<serde_json::Value> {
         let hot_stats = Self::get_directory_stats(&self.config.hot_downloads_path).await?;
@@ -295,11 +591,15 @@ impl StorageManager {
             }
         }
 
+        let disk = disk_usage(path).await.ok();
+
         Ok(serde_json::json!({
             "exists": true,
             "total_files": total_files,
             "total_size_bytes": total_size,
             "audio_files": audio_files,
+            "filesystem_free_bytes": disk.map(|disk| disk.free_bytes),
+            "filesystem_total_bytes": disk.map(|disk| disk.total_bytes),
             "path": path.to_string_lossy()
         }))
     }
@@ -314,16 +614,28 @@ impl StorageManager {
             anyhow::bail!("File does not exist: {:?}", file_path);
         }
 
+        let _guard = self.offload_lock.lock().await;
         Self::offload_file(file_path, &self.config).await
     }
 
     /// Clean up empty directories in processing path
     pub async fn cleanup_processing(&self) -> Result<()> {
-        Self::remove_empty_directories(&self.config.processing_path).await
+        let mut entries = match fs::read_dir(&self.config.processing_path).await {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        // The processing directory itself stays so later offloads can use it
+        while let Some(entry) = entries.next_entry().await? {
+            let entry_path = entry.path();
+            if entry_path.is_dir() {
+                self.remove_empty_directories(&entry_path).await?;
+            }
+        }
+        Ok(())
     }
 
     /// Recursively remove empty directories
-    async fn remove_empty_directories(path: &Path) -> Result<()> {
+    async fn remove_empty_directories(&self, path: &Path) -> Result<()> {
         if !path.is_dir() {
             return Ok(());
         }
@@ -332,14 +644,20 @@ impl StorageManager {
         while let Some(entry) = entries.next_entry().await? {
             let entry_path = entry.path();
             if entry_path.is_dir() {
-                Box::pin(Self::remove_empty_directories(&entry_path)).await?;
+                Box::pin(self.remove_empty_directories(&entry_path)).await?;
             }
         }
 
         // Check again if directory is empty after recursive cleanup
         let mut entries = fs::read_dir(path).await?;
         if entries.next_entry().await?.is_none() {
-            fs::remove_dir(path).await?;
+            match &self.recycle_bin {
+                Some(bin) => {
+                    bin.trash(path, "Empty directory cleanup", SYSTEM_ACTOR)
+                        .await?;
+                }
+                None => fs::remove_dir(path).await?,
+            }
             debug!("Removed empty directory: {:?}", path);
         }
 
@@ -379,10 +697,187 @@ pub fn create_storage_manager() -> StorageManager {
             .parse()
             .unwrap_or(300),
         verify_integrity: std::env::var("STEPHEYBOT__STORAGE__VERIFY_INTEGRITY")
-            .unwrap_or_else(|_| "false".to_string())
+            .unwrap_or_else(|_| "true".to_string())
             .parse()
-            .unwrap_or(false),
+            .unwrap_or(true),
     };
 
     StorageManager::new(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+
+    fn config(root: &Path) -> StorageConfig {
+        StorageConfig {
+            hot_downloads_path: root.join("hot"),
+            cold_downloads_path: root.join("cold"),
+            processing_path: root.join("processing"),
+            final_library_path: root.join("library"),
+            ..StorageConfig::default()
+        }
+    }
+
+    async fn setup() -> (TempDir, StorageConfig, PathBuf, Vec<u8>) {
+        let dir = TempDir::new().unwrap();
+        let config = config(dir.path());
+        StorageManager::new(config.clone())
+            .initialize()
+            .await
+            .unwrap();
+
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let source = config.hot_downloads_path.join("track.flac");
+        fs::write(&source, &data).await.unwrap();
+        (dir, config, source, data)
+    }
+
+    #[tokio::test]
+    async fn test_offload_verifies_checksum() {
+        let (_dir, config, source, data) = setup().await;
+
+        let result = StorageManager::offload_file(&source, &config)
+            .await
+            .unwrap();
+        let destination = result.destination_path.unwrap();
+        assert_eq!(result.bytes_transferred, data.len() as u64);
+        assert_eq!(result.sha256, Some(format!("{:x}", Sha256::digest(&data))));
+        assert_eq!(fs::read(&destination).await.unwrap(), data);
+        assert!(!source.exists());
+        assert_eq!(
+            OffloadManifest::load(&config)
+                .await
+                .unwrap()
+                .entries()
+                .count(),
+            0
+        );
+
+        // An unrelated file with the same name is not overwritten
+        fs::write(&source, b"another track").await.unwrap();
+        let result = StorageManager::offload_file(&source, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.destination_path.unwrap(),
+            config.final_library_path.join("track (2).flac")
+        );
+        assert_eq!(fs::read(&destination).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_offload_resumes() {
+        let (_dir, config, source, data) = setup().await;
+
+        // Simulate a crash halfway through the copy
+        let entry = StorageManager::plan_offload(&source, &config)
+            .await
+            .unwrap();
+        fs::write(partial_path(&entry.destination), &data[..40_000])
+            .await
+            .unwrap();
+        let mut manifest = OffloadManifest::load(&config).await.unwrap();
+        manifest.record(entry.clone(), &config).await.unwrap();
+
+        let results = StorageManager::resume_offloads(&config).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].bytes_transferred, 60_000);
+        assert_eq!(fs::read(&entry.destination).await.unwrap(), data);
+        assert!(!source.exists());
+        assert!(!partial_path(&entry.destination).exists());
+        assert_eq!(
+            OffloadManifest::load(&config)
+                .await
+                .unwrap()
+                .entries()
+                .count(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rewritten_source_restarts_the_copy() {
+        let (_dir, mut config, source, data) = setup().await;
+        config.verify_integrity = false;
+
+        let mut entry = StorageManager::plan_offload(&source, &config)
+            .await
+            .unwrap();
+        fs::write(partial_path(&entry.destination), &data[..40_000])
+            .await
+            .unwrap();
+        // The source was rewritten in place at the same size after the crash
+        let rewritten: Vec<u8> = data.iter().map(|byte| byte.wrapping_add(1)).collect();
+        fs::write(&source, &rewritten).await.unwrap();
+        entry.modified = Some(std::time::UNIX_EPOCH);
+        let mut manifest = OffloadManifest::load(&config).await.unwrap();
+        manifest.record(entry.clone(), &config).await.unwrap();
+
+        let results = StorageManager::resume_offloads(&config).await.unwrap();
+        assert_eq!(results[0].bytes_transferred, rewritten.len() as u64);
+        assert_eq!(fs::read(&entry.destination).await.unwrap(), rewritten);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_partial_copy_is_discarded() {
+        let (_dir, config, source, data) = setup().await;
+
+        let entry = StorageManager::plan_offload(&source, &config)
+            .await
+            .unwrap();
+        fs::write(partial_path(&entry.destination), vec![0u8; 40_000])
+            .await
+            .unwrap();
+        let mut manifest = OffloadManifest::load(&config).await.unwrap();
+        manifest.record(entry.clone(), &config).await.unwrap();
+
+        let error = StorageManager::offload_file(&source, &config)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("checksum mismatch"));
+        assert_eq!(fs::read(&source).await.unwrap(), data);
+        assert!(!entry.destination.exists());
+
+        // The next attempt copies from scratch
+        let result = StorageManager::offload_file(&source, &config)
+            .await
+            .unwrap();
+        assert_eq!(result.bytes_transferred, data.len() as u64);
+        assert_eq!(fs::read(&entry.destination).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_processing_trashes_empty_directories() {
+        let (dir, config, _source, _data) = setup().await;
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let database = Arc::new(
+            crate::database::Database::new(&format!("sqlite:{}", temp_file.path().display()))
+                .await
+                .unwrap(),
+        );
+        database.migrate().await.unwrap();
+        let bin = RecycleBin::new(
+            crate::services::recycle_bin::RecycleBinConfig {
+                path: dir.path().join("trash"),
+                ..Default::default()
+            },
+            database,
+        );
+        fs::create_dir_all(config.processing_path.join("Artist/Album"))
+            .await
+            .unwrap();
+
+        StorageManager::new(config.clone())
+            .with_recycle_bin(bin.clone())
+            .cleanup_processing()
+            .await
+            .unwrap();
+
+        assert!(config.processing_path.exists());
+        assert!(!config.processing_path.join("Artist").exists());
+        assert_eq!(bin.list(None, 10).await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,857 @@
+//! Popularity-aware tier placement for StepheyBot Music
+//!
+//! The library on the cold tier stays the canonical copy that Navidrome
+//! scans. Tracks played often enough are promoted by placing a verified copy
+//! on the hot (NVMe) tier, up to a byte budget, and demoted again by dropping
+//! that copy once they go unplayed for months or lose their place to more
+//! popular tracks. Streams read the hot copy when there is one.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::fs;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+
+use crate::database::Database;
+use crate::utils::sha256_file;
+
+/// Suffix of a hot copy that is still being written
+const PROMOTING_SUFFIX: &str = ".promoting";
+
+/// Track ids whose library path is remembered; the oldest are evicted first
+const TRACK_PATH_CAPACITY: usize = 4096;
+
+/// Tier placement settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieringConfig {
+    /// Canonical library on the cold tier, where `listening_sessions.track_path` is relative to
+    pub library_path: PathBuf,
+    /// Where hot copies live; placement is disabled without one
+    pub hot_library_path: Option<PathBuf>,
+    /// Most bytes of hot copies to keep
+    pub hot_budget_bytes: u64,
+    /// Plays within `popularity_window` needed for promotion
+    pub promote_min_plays: u32,
+    pub popularity_window: Duration,
+    /// Hot copies unplayed for this long are dropped even with budget to spare
+    pub demote_after: Duration,
+    pub interval: Duration,
+}
+
+impl Default for TieringConfig {
+    fn default() -> Self {
+        Self {
+            library_path: PathBuf::from("/music"),
+            hot_library_path: None,
+            hot_budget_bytes: 50 * 1024 * 1024 * 1024,
+            promote_min_plays: 3,
+            popularity_window: Duration::from_secs(30 * 86400),
+            demote_after: Duration::from_secs(90 * 86400),
+            interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl TieringConfig {
+    /// Load the configuration, overriding defaults from `STEPHEYBOT__TIERING__*`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let days = |name: &str| {
+            var(name)
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|days| *days > 0)
+                .map(|days| Duration::from_secs(days * 86400))
+        };
+
+        if let Some(path) = var("STEPHEYBOT__PATHS__MUSIC_PATH") {
+            config.library_path = PathBuf::from(path);
+        }
+        config.hot_library_path = var("STEPHEYBOT__TIERING__HOT_LIBRARY_PATH").map(PathBuf::from);
+        if let Some(gb) =
+            var("STEPHEYBOT__TIERING__HOT_BUDGET_GB").and_then(|v| v.parse::<f64>().ok())
+        {
+            config.hot_budget_bytes = (gb.max(0.0) * 1024.0 * 1024.0 * 1024.0) as u64;
+        }
+        if let Some(plays) =
+            var("STEPHEYBOT__TIERING__PROMOTE_MIN_PLAYS").and_then(|v| v.parse().ok())
+        {
+            config.promote_min_plays = plays;
+        }
+        if let Some(window) = days("STEPHEYBOT__TIERING__WINDOW_DAYS") {
+            config.popularity_window = window;
+        }
+        if let Some(after) = days("STEPHEYBOT__TIERING__DEMOTE_AFTER_DAYS") {
+            config.demote_after = after;
+        }
+        if let Some(minutes) = var("STEPHEYBOT__TIERING__INTERVAL_MINUTES")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0)
+        {
+            config.interval = Duration::from_secs(minutes * 60);
+        }
+
+        config
+    }
+}
+
+/// How much a library track is listened to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackPopularity {
+    pub track_path: String,
+    /// Plays within the popularity window
+    pub plays: u32,
+    pub last_played_at: Option<DateTime<Utc>>,
+    pub size_bytes: u64,
+}
+
+/// Which tracks to promote and demote
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlacementPlan {
+    pub promote: Vec<String>,
+    pub demote: Vec<String>,
+    /// Bytes on the hot tier once the plan is carried out
+    pub hot_bytes: u64,
+}
+
+/// Decide which tracks belong on the hot tier
+///
+/// Popular tracks and hot tracks that are still being played compete for the
+/// budget by play count, most recently played first on ties, so a track does
+/// not bounce between tiers when it hovers around the promotion threshold.
+pub fn plan_placement(
+    tracks: &[TrackPopularity],
+    hot: &HashSet<String>,
+    config: &TieringConfig,
+    now: DateTime<Utc>,
+) -> PlacementPlan {
+    let stale_before = now - ChronoDuration::from_std(config.demote_after).unwrap_or_default();
+    let mut candidates: Vec<&TrackPopularity> = tracks
+        .iter()
+        .filter(|track| {
+            if hot.contains(&track.track_path) {
+                track.last_played_at.is_some_and(|at| at >= stale_before)
+            } else {
+                track.plays >= config.promote_min_plays.max(1)
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.plays
+            .cmp(&a.plays)
+            .then(b.last_played_at.cmp(&a.last_played_at))
+            .then(a.track_path.cmp(&b.track_path))
+    });
+
+    let mut plan = PlacementPlan::default();
+    let mut keep = HashSet::new();
+    for track in candidates {
+        if plan.hot_bytes + track.size_bytes > config.hot_budget_bytes {
+            continue;
+        }
+        plan.hot_bytes += track.size_bytes;
+        keep.insert(track.track_path.as_str());
+        if !hot.contains(&track.track_path) {
+            plan.promote.push(track.track_path.clone());
+        }
+    }
+
+    plan.demote = hot
+        .iter()
+        .filter(|path| !keep.contains(path.as_str()))
+        .cloned()
+        .collect();
+    plan.demote.sort();
+    plan
+}
+
+/// What a placement run did
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TieringReport {
+    pub promoted: Vec<String>,
+    pub demoted: Vec<String>,
+    pub failed: Vec<String>,
+    pub hot_bytes: u64,
+    pub hot_budget_bytes: u64,
+    pub ran_at: Option<DateTime<Utc>>,
+}
+
+/// Storage tier a file is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    Hot,
+    Cold,
+}
+
+/// Where a library track can be read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackLocation {
+    pub path: PathBuf,
+    pub tier: Tier,
+}
+
+/// A library track with a hot copy
+#[derive(Debug, Clone)]
+struct Placement {
+    hot_path: PathBuf,
+    size_bytes: u64,
+    /// Modification time of the library file the copy was taken from
+    source_mtime: Option<i64>,
+}
+
+impl Placement {
+    /// Whether the library file changed since the hot copy was taken
+    fn is_stale(&self, cold: &std::fs::Metadata) -> bool {
+        cold.len() != self.size_bytes
+            || self
+                .source_mtime
+                .is_some_and(|mtime| modified_secs(cold) != Some(mtime))
+    }
+}
+
+fn modified_secs(metadata: &std::fs::Metadata) -> Option<i64> {
+    let modified = metadata.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+}
+
+/// Library paths of recently streamed Navidrome track ids
+#[derive(Default)]
+struct TrackPaths {
+    paths: HashMap<String, String>,
+    order: VecDeque<String>,
+}
+
+impl TrackPaths {
+    fn insert(&mut self, track_id: &str, track_path: &str) {
+        if self
+            .paths
+            .insert(track_id.to_string(), track_path.to_string())
+            .is_none()
+        {
+            self.order.push_back(track_id.to_string());
+        }
+        while self.paths.len() > TRACK_PATH_CAPACITY {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.paths.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.paths.retain(|_, track_path| keep(track_path));
+        let paths = &self.paths;
+        self.order.retain(|track_id| paths.contains_key(track_id));
+    }
+}
+
+/// Moves library tracks between the hot and cold tiers by popularity
+pub struct TieringService {
+    config: TieringConfig,
+    database: Arc<Database>,
+    last_report: Mutex<TieringReport>,
+    /// Serialises placement runs with each other
+    run_lock: Mutex<()>,
+    /// Library paths of the Navidrome track ids streamed recently
+    track_paths: Mutex<TrackPaths>,
+}
+
+impl TieringService {
+    pub fn new(config: TieringConfig, database: Arc<Database>) -> Self {
+        Self {
+            config,
+            database,
+            last_report: Mutex::new(TieringReport::default()),
+            run_lock: Mutex::new(()),
+            track_paths: Mutex::new(TrackPaths::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.hot_library_path.is_some()
+    }
+
+    /// Re-plan placement periodically
+    pub fn start(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            info!("No hot library path configured, tier placement disabled");
+            return;
+        }
+
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = interval(service.config.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = service.run().await {
+                    warn!("Tier placement failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// The result of the most recent placement run
+    pub async fn last_report(&self) -> TieringReport {
+        self.last_report.lock().await.clone()
+    }
+
+    /// The library path of a Navidrome track id, if it was streamed recently
+    /// and the file is still there
+    pub async fn track_path(&self, track_id: &str) -> Option<String> {
+        let track_path = self.track_paths.lock().await.paths.get(track_id).cloned()?;
+        let exists = match self.relative_path(&track_path) {
+            Some(relative) => fs::metadata(self.config.library_path.join(relative))
+                .await
+                .is_ok(),
+            None => false,
+        };
+        if exists {
+            return Some(track_path);
+        }
+
+        // Moved, re-imported or deleted since; have the caller resolve it again
+        let mut track_paths = self.track_paths.lock().await;
+        if track_paths.paths.get(track_id) == Some(&track_path) {
+            track_paths.retain(|path| path != track_path);
+        }
+        None
+    }
+
+    /// Remember the library path of a Navidrome track id
+    pub async fn remember_track_path(&self, track_id: &str, track_path: &str) {
+        self.track_paths.lock().await.insert(track_id, track_path);
+    }
+
+    /// Forget the track ids of library tracks whose placement changed
+    async fn forget_track_paths(&self, changed: &HashSet<String>) {
+        if changed.is_empty() {
+            return;
+        }
+        self.track_paths.lock().await.retain(|track_path| {
+            self.relative_path(track_path)
+                .is_some_and(|relative| !changed.contains(relative.to_string_lossy().as_ref()))
+        });
+    }
+
+    /// Where a library track can be read from right now, preferring the hot copy
+    /// unless the library file changed since it was taken
+    pub async fn locate(&self, track_path: &str) -> Option<TrackLocation> {
+        let relative = self.relative_path(track_path)?;
+        let key = relative.to_string_lossy().into_owned();
+        let cold = self.config.library_path.join(&relative);
+        let cold_metadata = fs::metadata(&cold).await.ok();
+
+        if self.is_enabled() {
+            match self.placement(&key).await {
+                Ok(Some(placement)) => {
+                    let stale = cold_metadata
+                        .as_ref()
+                        .is_some_and(|metadata| placement.is_stale(metadata));
+                    if stale {
+                        // Leave it to a running placement pass if there is one
+                        if let Ok(_guard) = self.run_lock.try_lock() {
+                            info!("Hot copy of {} is out of date, dropping it", key);
+                            if let Err(e) = self.demote(&key, &placement).await {
+                                warn!("Failed to drop stale hot copy of {}: {}", key, e);
+                            }
+                            self.forget_track_paths(&HashSet::from([key.clone()])).await;
+                        }
+                    } else if fs::metadata(&placement.hot_path).await.is_ok() {
+                        return Some(TrackLocation {
+                            path: placement.hot_path,
+                            tier: Tier::Hot,
+                        });
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => debug!("Failed to load the placement of {}: {}", key, e),
+            }
+        }
+
+        cold_metadata.map(|_| TrackLocation {
+            path: cold,
+            tier: Tier::Cold,
+        })
+    }
+
+    /// Promote and demote tracks according to recent listening
+    pub async fn run(&self) -> Result<TieringReport> {
+        let Some(hot_root) = self.config.hot_library_path.clone() else {
+            anyhow::bail!("No hot library path configured");
+        };
+        let _guard = self.run_lock.lock().await;
+        let now = Utc::now();
+
+        let mut placements = self.placements().await?;
+
+        // A hot copy deleted behind our back is simply no longer hot, and one
+        // of a library file that changed since is dropped so it can be promoted afresh
+        let mut lost = Vec::new();
+        let mut stale = Vec::new();
+        let mut changed = HashSet::new();
+        for (track_path, placement) in &placements {
+            if fs::metadata(&placement.hot_path).await.is_err() {
+                lost.push(track_path.clone());
+            } else if let Ok(cold) = fs::metadata(self.config.library_path.join(track_path)).await {
+                if placement.is_stale(&cold) {
+                    stale.push(track_path.clone());
+                }
+            }
+        }
+        changed.extend(lost.iter().chain(&stale).cloned());
+        for track_path in stale {
+            info!("Hot copy of {} is out of date, dropping it", track_path);
+            match self.demote(&track_path, &placements[&track_path]).await {
+                Ok(()) => {
+                    placements.remove(&track_path);
+                }
+                Err(e) => warn!("Failed to drop stale hot copy of {}: {}", track_path, e),
+            }
+        }
+        for track_path in lost {
+            warn!("Hot copy of {} disappeared", track_path);
+            sqlx::query("DELETE FROM track_placements WHERE track_path = ?")
+                .bind(&track_path)
+                .execute(self.database.pool())
+                .await?;
+            placements.remove(&track_path);
+        }
+
+        let hot: HashSet<String> = placements.keys().cloned().collect();
+        let tracks = self.popularity(&placements, now).await?;
+        let plan = plan_placement(&tracks, &hot, &self.config, now);
+        let plays: HashMap<&str, u32> = tracks
+            .iter()
+            .map(|track| (track.track_path.as_str(), track.plays))
+            .collect();
+
+        let mut report = TieringReport {
+            hot_budget_bytes: self.config.hot_budget_bytes,
+            ran_at: Some(now),
+            ..TieringReport::default()
+        };
+
+        // Make room before copying anything new
+        for track_path in &plan.demote {
+            match self.demote(track_path, &placements[track_path]).await {
+                Ok(()) => report.demoted.push(track_path.clone()),
+                Err(e) => {
+                    warn!("Failed to demote {}: {}", track_path, e);
+                    report.failed.push(track_path.clone());
+                }
+            }
+        }
+        for track_path in &plan.promote {
+            let plays = plays.get(track_path.as_str()).copied().unwrap_or(0);
+            match self.promote(track_path, &hot_root, plays).await {
+                Ok(()) => report.promoted.push(track_path.clone()),
+                Err(e) => {
+                    warn!("Failed to promote {}: {}", track_path, e);
+                    report.failed.push(track_path.clone());
+                }
+            }
+        }
+
+        changed.extend(report.promoted.iter().chain(&report.demoted).cloned());
+        self.forget_track_paths(&changed).await;
+
+        report.hot_bytes = self
+            .placements()
+            .await?
+            .values()
+            .map(|placement| placement.size_bytes)
+            .sum();
+        if !report.promoted.is_empty() || !report.demoted.is_empty() {
+            info!(
+                "Tier placement: {} promoted, {} demoted, {} of {} hot bytes used",
+                report.promoted.len(),
+                report.demoted.len(),
+                report.hot_bytes,
+                report.hot_budget_bytes
+            );
+        }
+
+        *self.last_report.lock().await = report.clone();
+        Ok(report)
+    }
+
+    /// Copy a library track to the hot tier, verifying the copy
+    async fn promote(&self, track_path: &str, hot_root: &Path, plays: u32) -> Result<()> {
+        let relative = self
+            .relative_path(track_path)
+            .with_context(|| format!("Not a library path: {}", track_path))?;
+        let cold = self.config.library_path.join(&relative);
+        let hot = hot_root.join(&relative);
+        if let Some(parent) = hot.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let mut partial_name = hot.file_name().unwrap_or_default().to_os_string();
+        partial_name.push(PROMOTING_SUFFIX);
+        let partial = hot.with_file_name(partial_name);
+        let source_mtime = modified_secs(&fs::metadata(&cold).await?);
+        let size_bytes = fs::copy(&cold, &partial)
+            .await
+            .with_context(|| format!("Failed to copy {:?} to the hot tier", cold))?;
+
+        let cold_hash = sha256_file(&cold).await?;
+        let hot_hash = sha256_file(&partial).await?;
+        if cold_hash != hot_hash {
+            let _ = fs::remove_file(&partial).await;
+            anyhow::bail!("Checksum mismatch copying {:?} to the hot tier", cold);
+        }
+        fs::rename(&partial, &hot).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO track_placements
+                (track_path, hot_path, size_bytes, sha256, source_mtime, plays, promoted_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(track_path) DO UPDATE SET
+                hot_path = excluded.hot_path,
+                size_bytes = excluded.size_bytes,
+                sha256 = excluded.sha256,
+                source_mtime = excluded.source_mtime,
+                plays = excluded.plays,
+                promoted_at = excluded.promoted_at
+            "#,
+        )
+        .bind(relative.to_string_lossy().as_ref())
+        .bind(hot.to_string_lossy().as_ref())
+        .bind(size_bytes as i64)
+        .bind(&cold_hash)
+        .bind(source_mtime)
+        .bind(plays as i64)
+        .bind(Utc::now().timestamp())
+        .execute(self.database.pool())
+        .await?;
+
+        debug!("Promoted {} to {:?}", track_path, hot);
+        Ok(())
+    }
+
+    /// Drop a hot copy, unless it has become the only copy
+    async fn demote(&self, track_path: &str, placement: &Placement) -> Result<()> {
+        let cold = self.config.library_path.join(track_path);
+        if fs::metadata(&cold).await.is_err() && fs::metadata(&placement.hot_path).await.is_ok() {
+            anyhow::bail!(
+                "{:?} is missing from the library, keeping the hot copy at {:?}",
+                cold,
+                placement.hot_path
+            );
+        }
+
+        match fs::remove_file(&placement.hot_path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        sqlx::query("DELETE FROM track_placements WHERE track_path = ?")
+            .bind(track_path)
+            .execute(self.database.pool())
+            .await?;
+
+        debug!("Demoted {}", track_path);
+        Ok(())
+    }
+
+    /// Tracks with a hot copy, keyed by library-relative path
+    async fn placements(&self) -> Result<HashMap<String, Placement>> {
+        let rows = sqlx::query(
+            "SELECT track_path, hot_path, size_bytes, source_mtime FROM track_placements",
+        )
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("track_path"), placement_from_row(row)))
+            .collect())
+    }
+
+    /// The hot copy of one library-relative track path
+    async fn placement(&self, track_path: &str) -> Result<Option<Placement>> {
+        let row = sqlx::query(
+            "SELECT hot_path, size_bytes, source_mtime FROM track_placements WHERE track_path = ?",
+        )
+        .bind(track_path)
+        .fetch_optional(self.database.pool())
+        .await?;
+
+        Ok(row.as_ref().map(placement_from_row))
+    }
+
+    /// Play counts for every library track that was ever played or is hot
+    async fn popularity(
+        &self,
+        placements: &HashMap<String, Placement>,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<TrackPopularity>> {
+        let window_start =
+            now - ChronoDuration::from_std(self.config.popularity_window).unwrap_or_default();
+        let rows = sqlx::query(
+            r#"
+            SELECT track_path,
+                   SUM(CASE WHEN started_at >= ? THEN 1 ELSE 0 END) AS plays,
+                   MAX(started_at) AS last_played_at
+            FROM listening_sessions
+            GROUP BY track_path
+            "#,
+        )
+        .bind(window_start.timestamp())
+        .fetch_all(self.database.pool())
+        .await?;
+
+        let mut tracks: HashMap<String, TrackPopularity> = HashMap::new();
+        for row in rows {
+            let track_path: String = row.get("track_path");
+            let Some(relative) = self.relative_path(&track_path) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().into_owned();
+            let plays = row.get::<i64, _>("plays").max(0) as u32;
+            let last_played_at = row
+                .get::<Option<i64>, _>("last_played_at")
+                .and_then(|at| Utc.timestamp_opt(at, 0).single());
+
+            // The same track may have been logged by absolute and relative path
+            let track = tracks
+                .entry(relative.clone())
+                .or_insert_with(|| TrackPopularity {
+                    track_path: relative,
+                    plays: 0,
+                    last_played_at: None,
+                    size_bytes: 0,
+                });
+            track.plays += plays;
+            track.last_played_at = track.last_played_at.max(last_played_at);
+        }
+        for track_path in placements.keys() {
+            tracks
+                .entry(track_path.clone())
+                .or_insert_with(|| TrackPopularity {
+                    track_path: track_path.clone(),
+                    plays: 0,
+                    last_played_at: None,
+                    size_bytes: 0,
+                });
+        }
+
+        // Tracks no longer in the library cannot be promoted
+        let mut result = Vec::with_capacity(tracks.len());
+        for (track_path, mut track) in tracks {
+            match fs::metadata(self.config.library_path.join(&track_path)).await {
+                Ok(metadata) => {
+                    track.size_bytes = metadata.len();
+                    result.push(track);
+                }
+                Err(_) => {
+                    if let Some(placement) = placements.get(&track_path) {
+                        track.size_bytes = placement.size_bytes;
+                        result.push(track);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// A track path relative to the library root, refusing anything outside it
+    fn relative_path(&self, track_path: &str) -> Option<PathBuf> {
+        let path = Path::new(track_path);
+        let relative = if path.is_absolute() {
+            path.strip_prefix(&self.config.library_path).ok()?
+        } else {
+            path
+        };
+
+        let normal = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        (normal && relative.components().next().is_some()).then(|| relative.to_path_buf())
+    }
+}
+
+fn placement_from_row(row: &sqlx::sqlite::SqliteRow) -> Placement {
+    let hot_path: String = row.get("hot_path");
+    Placement {
+        hot_path: PathBuf::from(hot_path),
+        size_bytes: row.get::<i64, _>("size_bytes").max(0) as u64,
+        source_mtime: row.get("source_mtime"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{NamedTempFile, TempDir};
+
+    fn track(path: &str, plays: u32, days_ago: i64, size_bytes: u64) -> TrackPopularity {
+        TrackPopularity {
+            track_path: path.to_string(),
+            plays,
+            last_played_at: Some(Utc::now() - ChronoDuration::days(days_ago)),
+            size_bytes,
+        }
+    }
+
+    #[test]
+    fn test_plan_placement() {
+        let config = TieringConfig {
+            hot_budget_bytes: 100,
+            promote_min_plays: 3,
+            demote_after: Duration::from_secs(90 * 86400),
+            ..TieringConfig::default()
+        };
+        let tracks = vec![
+            track("a.flac", 10, 1, 60),
+            track("b.flac", 5, 1, 50),
+            track("c.flac", 4, 2, 30),
+            track("d.flac", 1, 3, 10),
+            track("stale.flac", 0, 200, 5),
+        ];
+        let hot: HashSet<String> = ["d.flac", "stale.flac"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        let plan = plan_placement(&tracks, &hot, &config, Utc::now());
+        // b does not fit next to a, c does; d is still played so it stays
+        assert_eq!(plan.promote, ["a.flac", "c.flac"]);
+        assert_eq!(plan.demote, ["stale.flac"]);
+        assert_eq!(plan.hot_bytes, 100);
+
+        // Below the threshold nothing new is promoted
+        let quiet = vec![track("e.flac", 2, 1, 10)];
+        let plan = plan_placement(&quiet, &HashSet::new(), &config, Utc::now());
+        assert!(plan.promote.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_promote_and_locate() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let library = dir.path().join("library");
+        let hot = dir.path().join("hot");
+        fs::create_dir_all(library.join("Artist/Album"))
+            .await
+            .unwrap();
+        fs::write(library.join("Artist/Album/01.flac"), b"popular")
+            .await
+            .unwrap();
+        fs::write(library.join("Artist/Album/02.flac"), b"quiet")
+            .await
+            .unwrap();
+
+        let now = Utc::now().timestamp();
+        for (path, plays) in [("Artist/Album/01.flac", 3), ("Artist/Album/02.flac", 1)] {
+            for _ in 0..plays {
+                sqlx::query(
+                    "INSERT INTO listening_sessions (user_id, track_path, started_at) VALUES (1, ?, ?)",
+                )
+                .bind(path)
+                .bind(now)
+                .execute(database.pool())
+                .await
+                .unwrap();
+            }
+        }
+
+        let service = TieringService::new(
+            TieringConfig {
+                library_path: library.clone(),
+                hot_library_path: Some(hot.clone()),
+                ..TieringConfig::default()
+            },
+            database,
+        );
+
+        let report = service.run().await.unwrap();
+        assert_eq!(report.promoted, ["Artist/Album/01.flac"]);
+        assert_eq!(report.hot_bytes, 7);
+        let location = service.locate("Artist/Album/01.flac").await.unwrap();
+        assert_eq!(location.path, hot.join("Artist/Album/01.flac"));
+        assert_eq!(location.tier, Tier::Hot);
+        // Absolute library paths resolve the same way
+        let absolute = library.join("Artist/Album/01.flac");
+        let location = service.locate(&absolute.to_string_lossy()).await.unwrap();
+        assert_eq!(location.tier, Tier::Hot);
+        let location = service.locate("Artist/Album/02.flac").await.unwrap();
+        assert_eq!(location.path, library.join("Artist/Album/02.flac"));
+        assert_eq!(location.tier, Tier::Cold);
+        assert_eq!(service.locate("../etc/passwd").await, None);
+
+        // A lost hot copy falls back to the library, and is forgotten on the next run
+        fs::remove_file(hot.join("Artist/Album/01.flac"))
+            .await
+            .unwrap();
+        let location = service.locate("Artist/Album/01.flac").await.unwrap();
+        assert_eq!(location.path, absolute);
+        let report = service.run().await.unwrap();
+        assert_eq!(report.promoted, ["Artist/Album/01.flac"]);
+
+        // A library file replaced since promotion is served from the library
+        // until the next run copies it again
+        fs::write(&absolute, b"popular, remastered").await.unwrap();
+        let location = service.locate("Artist/Album/01.flac").await.unwrap();
+        assert_eq!(location.tier, Tier::Cold);
+        assert!(fs::metadata(hot.join("Artist/Album/01.flac"))
+            .await
+            .is_err());
+        let report = service.run().await.unwrap();
+        assert_eq!(report.promoted, ["Artist/Album/01.flac"]);
+        assert_eq!(report.hot_bytes, 19);
+        let location = service.locate("Artist/Album/01.flac").await.unwrap();
+        assert_eq!(location.tier, Tier::Hot);
+
+        service
+            .remember_track_path("song-1", "Artist/Album/01.flac")
+            .await;
+        assert_eq!(
+            service.track_path("song-1").await.as_deref(),
+            Some("Artist/Album/01.flac")
+        );
+        assert_eq!(service.track_path("song-2").await, None);
+
+        // A placement change or a vanished library file drops the cached path
+        service
+            .remember_track_path("song-2", "Artist/Album/02.flac")
+            .await;
+        service
+            .forget_track_paths(&HashSet::from(["Artist/Album/01.flac".to_string()]))
+            .await;
+        assert_eq!(service.track_path("song-1").await, None);
+        fs::remove_file(library.join("Artist/Album/02.flac"))
+            .await
+            .unwrap();
+        assert_eq!(service.track_path("song-2").await, None);
+        assert!(service.track_paths.lock().await.paths.is_empty());
+    }
+
+    #[test]
+    fn test_track_paths_are_bounded() {
+        let mut track_paths = TrackPaths::default();
+        for i in 0..TRACK_PATH_CAPACITY + 10 {
+            track_paths.insert(&format!("song-{}", i), &format!("{}.flac", i));
+        }
+        assert_eq!(track_paths.paths.len(), TRACK_PATH_CAPACITY);
+        assert_eq!(track_paths.order.len(), TRACK_PATH_CAPACITY);
+        assert!(!track_paths.paths.contains_key("song-9"));
+        assert!(track_paths.paths.contains_key("song-10"));
+    }
+}
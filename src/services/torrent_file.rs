@@ -0,0 +1,581 @@
+//! Torrent metainfo for StepheyBot Music
+//!
+//! Uploaded `.torrent` files and torrents fetched from indexer URLs are parsed
+//! locally, so a request knows its info hash before Transmission confirms it
+//! and torrents without any audio files are refused before they are added.
+
+use serde_bencode::value::Value;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::utils::is_audio_file;
+
+/// Larger files are not torrents worth parsing
+pub const MAX_TORRENT_SIZE: usize = 10 * 1024 * 1024;
+
+const MAX_REDIRECTS: usize = 5;
+
+/// Deeper lists and dictionaries are refused before they can exhaust the stack
+const MAX_BENCODE_DEPTH: usize = 64;
+
+/// Why a torrent was refused
+#[derive(Debug, thiserror::Error)]
+pub enum TorrentFileError {
+    #[error("Not a valid torrent file: {0}")]
+    Invalid(String),
+    #[error("Torrent '{name}' contains no audio files")]
+    NoAudioFiles { name: String },
+    #[error("Failed to fetch torrent: {0}")]
+    Fetch(String),
+}
+
+/// One file of a torrent
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TorrentFile {
+    pub path: String,
+    pub length: u64,
+}
+
+/// What StepheyBot needs to know about a torrent before adding it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TorrentMetainfo {
+    /// Lowercase hex SHA-1 of the bencoded info dictionary
+    pub info_hash: String,
+    pub name: String,
+    pub files: Vec<TorrentFile>,
+    pub total_size: u64,
+}
+
+impl TorrentMetainfo {
+    /// Parse a `.torrent` file
+    pub fn parse(bytes: &[u8]) -> Result<Self, TorrentFileError> {
+        if bytes.len() > MAX_TORRENT_SIZE {
+            return Err(TorrentFileError::Invalid(format!(
+                "{} bytes exceeds the {} byte limit",
+                bytes.len(),
+                MAX_TORRENT_SIZE
+            )));
+        }
+
+        if skip_value(bytes, 0, MAX_BENCODE_DEPTH).is_none() {
+            return Err(TorrentFileError::Invalid(format!(
+                "malformed bencode or nested more than {} levels deep",
+                MAX_BENCODE_DEPTH
+            )));
+        }
+        let root: Value = serde_bencode::from_bytes(bytes)
+            .map_err(|e| TorrentFileError::Invalid(e.to_string()))?;
+        let info = match &root {
+            Value::Dict(root) => root.get(b"info".as_slice()),
+            _ => None,
+        }
+        .ok_or_else(|| TorrentFileError::Invalid("missing info dictionary".to_string()))?;
+        let Value::Dict(fields) = info else {
+            return Err(TorrentFileError::Invalid(
+                "info is not a dictionary".to_string(),
+            ));
+        };
+
+        // The hash is over the info dictionary exactly as the file encodes
+        // it; re-encoding would drop unknown keys and reorder unsorted ones
+        let raw = raw_info(bytes)
+            .ok_or_else(|| TorrentFileError::Invalid("malformed info dictionary".to_string()))?;
+        let info_hash = hex_encode(&Sha1::digest(raw));
+
+        let name = text(fields, "name.utf-8")
+            .or_else(|| text(fields, "name"))
+            .unwrap_or_else(|| info_hash.clone());
+
+        let files = match fields.get(b"files".as_slice()) {
+            Some(Value::List(entries)) => entries
+                .iter()
+                .map(|entry| {
+                    let Value::Dict(entry) = entry else {
+                        return Err(TorrentFileError::Invalid(
+                            "file entry is not a dictionary".to_string(),
+                        ));
+                    };
+                    let path = path(entry, "path.utf-8")
+                        .or_else(|| path(entry, "path"))
+                        .ok_or_else(|| {
+                            TorrentFileError::Invalid("file entry without a path".to_string())
+                        })?;
+                    Ok(TorrentFile {
+                        path: format!("{}/{}", name, path),
+                        length: length(entry)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(_) => return Err(TorrentFileError::Invalid("files is not a list".to_string())),
+            None => vec![TorrentFile {
+                path: name.clone(),
+                length: length(fields)?,
+            }],
+        };
+
+        Ok(Self {
+            total_size: files.iter().map(|file| file.length).sum(),
+            info_hash,
+            name,
+            files,
+        })
+    }
+
+    /// Files with an audio extension
+    pub fn audio_files(&self) -> impl Iterator<Item = &TorrentFile> {
+        self.files
+            .iter()
+            .filter(|file| is_audio_file(Path::new(&file.path)))
+    }
+
+    /// Refuse torrents that would not add anything to the library
+    pub fn ensure_audio(&self) -> Result<(), TorrentFileError> {
+        if self.audio_files().next().is_none() {
+            return Err(TorrentFileError::NoAudioFiles {
+                name: self.name.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn text(fields: &HashMap<Vec<u8>, Value>, key: &str) -> Option<String> {
+    match fields.get(key.as_bytes()) {
+        Some(Value::Bytes(bytes)) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        _ => None,
+    }
+}
+
+fn path(fields: &HashMap<Vec<u8>, Value>, key: &str) -> Option<String> {
+    let Some(Value::List(parts)) = fields.get(key.as_bytes()) else {
+        return None;
+    };
+    let parts: Vec<String> = parts
+        .iter()
+        .filter_map(|part| match part {
+            Value::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn length(fields: &HashMap<Vec<u8>, Value>) -> Result<u64, TorrentFileError> {
+    match fields.get(b"length".as_slice()) {
+        Some(Value::Int(length)) if *length >= 0 => Ok(*length as u64),
+        _ => Err(TorrentFileError::Invalid(
+            "missing or negative file length".to_string(),
+        )),
+    }
+}
+
+/// The bytes of the top-level `info` value as they appear in the file
+fn raw_info(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.first() != Some(&b'd') {
+        return None;
+    }
+    let mut pos = 1;
+    while bytes.get(pos) != Some(&b'e') {
+        let key_end = skip_value(bytes, pos, MAX_BENCODE_DEPTH - 1)?;
+        let key = bytes.get(pos..key_end)?;
+        let value_end = skip_value(bytes, key_end, MAX_BENCODE_DEPTH - 1)?;
+        if key == b"4:info" {
+            return bytes.get(key_end..value_end);
+        }
+        pos = value_end;
+    }
+    None
+}
+
+/// Where the bencoded value starting at `pos` ends, or `None` if it is
+/// malformed or nests lists and dictionaries more than `depth` levels deep
+fn skip_value(bytes: &[u8], pos: usize, depth: usize) -> Option<usize> {
+    match *bytes.get(pos)? {
+        b'i' => Some(pos + bytes.get(pos..)?.iter().position(|&b| b == b'e')? + 1),
+        b'l' | b'd' => {
+            let depth = depth.checked_sub(1)?;
+            let mut pos = pos + 1;
+            while *bytes.get(pos)? != b'e' {
+                pos = skip_value(bytes, pos, depth)?;
+            }
+            Some(pos + 1)
+        }
+        b'0'..=b'9' => {
+            let colon = pos + bytes.get(pos..)?.iter().position(|&b| b == b':')?;
+            let length: usize = std::str::from_utf8(&bytes[pos..colon]).ok()?.parse().ok()?;
+            let end = colon.checked_add(1 + length)?;
+            (end <= bytes.len()).then_some(end)
+        }
+        _ => None,
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The info hash of a magnet link, as lowercase hex
+pub fn magnet_info_hash(magnet: &str) -> Option<String> {
+    let query = magnet.strip_prefix("magnet:?")?;
+    let hash = url::form_urlencoded::parse(query.as_bytes())
+        .filter(|(key, _)| key == "xt")
+        .find_map(|(_, value)| value.strip_prefix("urn:btih:").map(|hash| hash.to_string()))?;
+
+    match hash.len() {
+        40 if hash.chars().all(|c| c.is_ascii_hexdigit()) => Some(hash.to_lowercase()),
+        // Older clients put the hash in base32
+        32 => base32_decode(&hash).map(|bytes| hex_encode(&bytes)),
+        _ => None,
+    }
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut bits: u64 = 0;
+    let mut bit_count = 0;
+    let mut output = Vec::with_capacity(input.len() * 5 / 8);
+    for c in input.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c.to_ascii_uppercase())?;
+        bits = (bits << 5) | value as u64;
+        bit_count += 5;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            output.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(output)
+}
+
+/// What a torrent URL resolved to
+#[derive(Debug, Clone)]
+pub enum FetchedTorrent {
+    /// Indexers often redirect their download links to a magnet link
+    Magnet(String),
+    File {
+        metainfo: TorrentMetainfo,
+        bytes: Vec<u8>,
+    },
+}
+
+/// Download a torrent from an HTTP(S) URL, following redirects by hand so a
+/// redirect to a magnet link can be picked up
+///
+/// URLs come from users, so every hop has to resolve to a public address,
+/// except on `trusted_hosts` (the configured indexers, often on the local
+/// network). Each hop connects to the addresses that were checked.
+pub async fn fetch(
+    url: &str,
+    trusted_hosts: &[String],
+) -> Result<FetchedTorrent, TorrentFileError> {
+    let mut current =
+        url::Url::parse(url).map_err(|e| TorrentFileError::Fetch(format!("{}: {}", url, e)))?;
+    for _ in 0..=MAX_REDIRECTS {
+        if !matches!(current.scheme(), "http" | "https") {
+            return Err(TorrentFileError::Fetch(format!(
+                "unsupported URL scheme '{}'",
+                current.scheme()
+            )));
+        }
+
+        let client = client_for(&current, trusted_hosts).await?;
+        let mut response = client
+            .get(current.clone())
+            .send()
+            .await
+            .map_err(|e| TorrentFileError::Fetch(e.to_string()))?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| {
+                    TorrentFileError::Fetch("redirect without a location".to_string())
+                })?;
+            if location.starts_with("magnet:") {
+                return Ok(FetchedTorrent::Magnet(location.to_string()));
+            }
+            current = current
+                .join(location)
+                .map_err(|e| TorrentFileError::Fetch(e.to_string()))?;
+            continue;
+        }
+
+        if !response.status().is_success() {
+            return Err(TorrentFileError::Fetch(format!(
+                "{} returned {}",
+                current,
+                response.status()
+            )));
+        }
+
+        // Content-Length may be missing or wrong, so count while reading
+        let too_large = || TorrentFileError::Invalid("torrent exceeds the size limit".to_string());
+        if response
+            .content_length()
+            .is_some_and(|length| length as usize > MAX_TORRENT_SIZE)
+        {
+            return Err(too_large());
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| TorrentFileError::Fetch(e.to_string()))?
+        {
+            if bytes.len() + chunk.len() > MAX_TORRENT_SIZE {
+                return Err(too_large());
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        let metainfo = TorrentMetainfo::parse(&bytes)?;
+        return Ok(FetchedTorrent::File { metainfo, bytes });
+    }
+
+    Err(TorrentFileError::Fetch(format!(
+        "more than {} redirects",
+        MAX_REDIRECTS
+    )))
+}
+
+/// A client that may only reach `url`'s host at addresses that passed the check
+async fn client_for(
+    url: &url::Url,
+    trusted_hosts: &[String],
+) -> Result<reqwest::Client, TorrentFileError> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| TorrentFileError::Fetch(format!("{} has no host", url)))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|e| TorrentFileError::Fetch(format!("{}: {}", host, e)))?
+        .collect();
+
+    let trusted = trusted_hosts
+        .iter()
+        .any(|trusted| trusted.eq_ignore_ascii_case(host));
+    if addrs.is_empty() || (!trusted && !addrs.iter().all(|addr| is_public(addr.ip()))) {
+        return Err(TorrentFileError::Fetch(format!(
+            "{} does not resolve to a public address",
+            host
+        )));
+    }
+
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(Duration::from_secs(30))
+        .resolve_to_addrs(host, &addrs)
+        .build()
+        .map_err(|e| TorrentFileError::Fetch(e.to_string()))
+}
+
+/// Whether an address is on the public internet, rather than loopback, a
+/// private network, link-local or otherwise reserved
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address routes to: IPv4-mapped `::ffff:a.b.c.d`,
+/// IPv4-compatible `::a.b.c.d`, NAT64 `64:ff9b::/96` and 6to4 `2002::/16`
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return Some(v4);
+    }
+    let octets = ip.octets();
+    let low_bits = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    match ip.segments() {
+        // Also covers :: and ::1, which map to reserved 0.0.0.0/8
+        [0, 0, 0, 0, 0, 0, _, _] | [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(low_bits),
+        [0x2002, high, low, ..] => {
+            let [a, b] = high.to_be_bytes();
+            let [c, d] = low.to_be_bytes();
+            Some(Ipv4Addr::new(a, b, c, d))
+        }
+        _ => None,
+    }
+}
+
+/// Host names of URLs, for trusting the indexers' own download links
+pub fn url_hosts<'a>(urls: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    urls.into_iter()
+        .filter_map(|url| url::Url::parse(url).ok())
+        .filter_map(|url| url.host_str().map(str::to_string))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent(info: &str) -> Vec<u8> {
+        format!("d8:announce14:http://tracker4:info{}e", info).into_bytes()
+    }
+
+    #[test]
+    fn test_parse_metainfo() {
+        let info = "d5:filesld6:lengthi300e4:pathl7:01.flaceed6:lengthi20e4:pathl5:Scans9:cover.jpgeee4:name7:Album 112:piece lengthi16384e6:pieces0:e";
+        let bytes = torrent(info);
+        let metainfo = TorrentMetainfo::parse(&bytes).unwrap();
+
+        assert_eq!(
+            metainfo.info_hash,
+            hex_encode(&Sha1::digest(info.as_bytes()))
+        );
+        assert_eq!(metainfo.name, "Album 1");
+        assert_eq!(metainfo.total_size, 320);
+        assert_eq!(metainfo.files[1].path, "Album 1/Scans/cover.jpg");
+        assert_eq!(metainfo.audio_files().count(), 1);
+        assert!(metainfo.ensure_audio().is_ok());
+
+        let single = TorrentMetainfo::parse(&torrent(
+            "d6:lengthi99e4:name9:setup.exe12:piece lengthi16384e6:pieces0:e",
+        ))
+        .unwrap();
+        assert_eq!(single.files.len(), 1);
+        assert!(matches!(
+            single.ensure_audio(),
+            Err(TorrentFileError::NoAudioFiles { .. })
+        ));
+
+        assert!(TorrentMetainfo::parse(b"not bencode").is_err());
+        assert!(TorrentMetainfo::parse(b"d8:announce3:fooe").is_err());
+    }
+
+    #[test]
+    fn test_deep_nesting_is_refused() {
+        let nested = |depth: usize| {
+            torrent(&format!(
+                "d6:lengthi99e4:name7:01.flac12:piece lengthi16384e6:pieces0:7:x-extra{}{}e",
+                "l".repeat(depth),
+                "e".repeat(depth)
+            ))
+        };
+        assert!(TorrentMetainfo::parse(&nested(10)).is_ok());
+        for depth in [MAX_BENCODE_DEPTH, 100_000] {
+            assert!(matches!(
+                TorrentMetainfo::parse(&nested(depth)),
+                Err(TorrentFileError::Invalid(message)) if message.contains("nested")
+            ));
+        }
+    }
+
+    #[test]
+    fn test_info_hash_uses_raw_bytes() {
+        // Unsorted keys and an unknown key must be hashed as they are
+        let info = "d4:name7:Album 16:lengthi300e12:piece lengthi16384e6:pieces0:7:x-extrai1ee";
+        let metainfo = TorrentMetainfo::parse(&torrent(info)).unwrap();
+        assert_eq!(
+            metainfo.info_hash,
+            hex_encode(&Sha1::digest(info.as_bytes()))
+        );
+
+        // Keys after info don't matter
+        let bytes = format!("d4:info{}8:url-listle4:zzzz1:ze", info).into_bytes();
+        let metainfo = TorrentMetainfo::parse(&bytes).unwrap();
+        assert_eq!(
+            metainfo.info_hash,
+            hex_encode(&Sha1::digest(info.as_bytes()))
+        );
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b::192.168.0.1",
+            "2002:7f00:1::",
+            "2002:c0a8:0101::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "93.184.216.34",
+            "2606:4700::1111",
+            "64:ff9b::93.184.216.34",
+            "2002:5db8:d822::1",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_refuses_private_hosts() {
+        for url in [
+            "http://127.0.0.1:9/a.torrent",
+            "http://localhost:9/a.torrent",
+            "http://[::1]:9/a.torrent",
+            "http://169.254.169.254/latest/meta-data",
+        ] {
+            let result = fetch(url, &[]).await;
+            assert!(
+                matches!(&result, Err(TorrentFileError::Fetch(message)) if message.contains("public")),
+                "{}: {:?}",
+                url,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_magnet_info_hash() {
+        assert_eq!(
+            magnet_info_hash(
+                "magnet:?xt=urn:btih:C12FE1C06BBA254A9DC9F519B335AA7C1367A88A&dn=Album"
+            )
+            .as_deref(),
+            Some("c12fe1c06bba254a9dc9f519b335aa7c1367a88a")
+        );
+        assert_eq!(
+            magnet_info_hash("magnet:?xt=urn:btih:YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK").as_deref(),
+            Some("c12fe1c06bba254a9dc9f519b335aa7c1367a88a")
+        );
+        assert_eq!(magnet_info_hash("magnet:?dn=nothing"), None);
+        assert_eq!(magnet_info_hash("https://example.com/a.torrent"), None);
+    }
+}
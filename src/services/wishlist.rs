@@ -0,0 +1,1467 @@
+//! Wishlist autopilot for StepheyBot Music
+//!
+//! Users who opt in get music that external services think they would like
+//! proposed as download candidates, as long as it is not in the library yet.
+//! Candidates scoring above the user's threshold are queued automatically
+//! within a weekly budget, the rest wait for review, and every proposal and
+//! decision is written to an audit trail.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, Row};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+
+use crate::clients::lidarr::LidarrClient;
+use crate::clients::listenbrainz::{ListenBrainzClient, Recommendation};
+use crate::clients::musicbrainz::{MusicBrainzClient, Relation};
+use crate::clients::navidrome::NavidromeClient;
+use crate::database::Database;
+use crate::models::entities::{DownloadPriority, DownloadRequest};
+use crate::models::user::UserRole;
+use crate::services::download_service::DownloadService;
+use crate::services::quota::Requester;
+
+const WEEK_SECS: i64 = 7 * 86400;
+const MAX_SEED_ARTISTS: usize = 10;
+const MAX_PROPOSALS_PER_RUN: usize = 25;
+
+/// Actor recorded for decisions the autopilot makes on its own
+pub const AUTOPILOT_ACTOR: &str = "autopilot";
+
+/// What a candidate would acquire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateKind {
+    /// An artist, monitored through Lidarr
+    Artist,
+    /// A single recording, requested through the download service
+    Recording,
+}
+
+impl CandidateKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CandidateKind::Artist => "artist",
+            CandidateKind::Recording => "recording",
+        }
+    }
+}
+
+/// Where a suggestion came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateSource {
+    ListenBrainz,
+    MusicBrainz,
+}
+
+impl CandidateSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CandidateSource::ListenBrainz => "listenbrainz",
+            CandidateSource::MusicBrainz => "musicbrainz",
+        }
+    }
+}
+
+/// Review state of a candidate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateStatus {
+    Proposed,
+    Queued,
+    Rejected,
+    Failed,
+}
+
+impl CandidateStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CandidateStatus::Proposed => "proposed",
+            CandidateStatus::Queued => "queued",
+            CandidateStatus::Rejected => "rejected",
+            CandidateStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for CandidateStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            CandidateStatus::Proposed,
+            CandidateStatus::Queued,
+            CandidateStatus::Rejected,
+            CandidateStatus::Failed,
+        ]
+        .into_iter()
+        .find(|status| status.as_str() == s)
+        .ok_or_else(|| format!("Unknown wishlist status: {}", s))
+    }
+}
+
+/// Something a user might like, before it is stored as a candidate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub kind: CandidateKind,
+    pub source: CandidateSource,
+    pub artist_name: String,
+    pub artist_mbid: Option<String>,
+    pub title: Option<String>,
+    pub recording_mbid: Option<String>,
+    /// Between 0 and 1
+    pub score: f64,
+    pub reason: String,
+}
+
+impl Suggestion {
+    /// Key identifying the same suggestion across runs and sources
+    pub fn dedupe_key(&self) -> String {
+        match self.kind {
+            CandidateKind::Artist => format!(
+                "artist:{}",
+                self.artist_mbid
+                    .clone()
+                    .unwrap_or_else(|| self.artist_name.to_lowercase())
+            ),
+            CandidateKind::Recording => format!(
+                "recording:{}",
+                self.recording_mbid.clone().unwrap_or_else(|| format!(
+                    "{} - {}",
+                    self.artist_name.to_lowercase(),
+                    self.title.as_deref().unwrap_or_default().to_lowercase()
+                ))
+            ),
+        }
+    }
+}
+
+/// Turn ListenBrainz recommendations into suggestions, scaling scores so
+/// the best recommendation scores 1
+pub fn recommendation_suggestions(recommendations: &[Recommendation]) -> Vec<Suggestion> {
+    let best = recommendations
+        .iter()
+        .map(|r| r.score)
+        .fold(0.0_f64, f64::max);
+    if best <= 0.0 {
+        return Vec::new();
+    }
+
+    recommendations
+        .iter()
+        .filter(|r| !r.track_metadata.artist_name.is_empty())
+        .map(|r| {
+            let info = r.track_metadata.additional_info.as_ref();
+            Suggestion {
+                kind: CandidateKind::Recording,
+                source: CandidateSource::ListenBrainz,
+                artist_name: r.track_metadata.artist_name.clone(),
+                artist_mbid: info.and_then(|i| i.artist_mbid.clone()),
+                title: Some(r.track_metadata.track_name.clone()),
+                recording_mbid: Some(r.recording_mbid.clone()),
+                score: (r.score / best).clamp(0.0, 1.0),
+                reason: "Recommended by ListenBrainz from your listening history".to_string(),
+            }
+        })
+        .collect()
+}
+
+/// How strongly a MusicBrainz artist relationship suggests similar music
+pub fn relation_weight(relation_type: &str) -> f64 {
+    match relation_type {
+        "member of band" | "is person" => 0.9,
+        "collaboration" => 0.8,
+        "influenced by" => 0.7,
+        "supporting musician"
+        | "vocal supporting musician"
+        | "instrumental supporting musician" => 0.6,
+        "tribute" | "teacher" => 0.4,
+        // Family and personal relationships say little about the music
+        _ => 0.2,
+    }
+}
+
+/// Turn the artist relationships of a seed artist into suggestions
+pub fn relation_suggestions(
+    seed_name: &str,
+    seed_weight: f64,
+    relations: &[Relation],
+) -> Vec<Suggestion> {
+    relations
+        .iter()
+        .filter_map(|relation| {
+            let artist = relation.artist.as_ref()?;
+            Some(Suggestion {
+                kind: CandidateKind::Artist,
+                source: CandidateSource::MusicBrainz,
+                artist_name: artist.name.clone(),
+                artist_mbid: Some(artist.id.clone()),
+                title: None,
+                recording_mbid: None,
+                score: (seed_weight * relation_weight(&relation.relation_type)).clamp(0.0, 1.0),
+                reason: format!("Related to {} ({})", seed_name, relation.relation_type),
+            })
+        })
+        .collect()
+}
+
+/// Merge duplicate suggestions and order them best first; a suggestion that
+/// several sources agree on gets a small boost
+pub fn rank(suggestions: Vec<Suggestion>) -> Vec<Suggestion> {
+    let mut merged: HashMap<String, Suggestion> = HashMap::new();
+
+    for suggestion in suggestions {
+        match merged.get_mut(&suggestion.dedupe_key()) {
+            Some(existing) => {
+                let agreement = if existing.reason == suggestion.reason {
+                    0.0
+                } else {
+                    0.1
+                };
+                if suggestion.score > existing.score {
+                    existing.reason = format!("{}; {}", suggestion.reason, existing.reason);
+                    existing.score = suggestion.score;
+                } else if agreement > 0.0 {
+                    existing.reason = format!("{}; {}", existing.reason, suggestion.reason);
+                }
+                existing.score = (existing.score + agreement).min(1.0);
+            }
+            None => {
+                merged.insert(suggestion.dedupe_key(), suggestion);
+            }
+        }
+    }
+
+    let mut ranked: Vec<Suggestion> = merged.into_values().collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.artist_name.cmp(&b.artist_name))
+    });
+    ranked
+}
+
+/// A user's autopilot settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WishlistSettings {
+    pub user_id: String,
+    pub username: String,
+    pub role: UserRole,
+    pub enabled: bool,
+    /// Queue candidates above `min_score` without waiting for review
+    pub auto_queue: bool,
+    /// Automatic acquisitions allowed per rolling week
+    pub weekly_budget: u32,
+    pub min_score: f64,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Changes to a user's settings; unset fields are left alone
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SettingsUpdate {
+    pub enabled: Option<bool>,
+    pub auto_queue: Option<bool>,
+    pub weekly_budget: Option<u32>,
+    pub min_score: Option<f64>,
+}
+
+/// A stored suggestion and what became of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WishlistCandidate {
+    pub id: i64,
+    pub user_id: String,
+    pub kind: CandidateKind,
+    pub source: CandidateSource,
+    pub artist_name: String,
+    pub artist_mbid: Option<String>,
+    pub title: Option<String>,
+    pub recording_mbid: Option<String>,
+    pub score: f64,
+    pub reason: String,
+    pub status: CandidateStatus,
+    /// Download request ID, or `lidarr:artist:<id>` for Lidarr acquisitions
+    pub target: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+/// One entry of the audit trail
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub user_id: String,
+    pub candidate_id: Option<i64>,
+    pub action: String,
+    pub actor: String,
+    pub detail: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What one autopilot run did
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub user_id: String,
+    pub suggested: usize,
+    pub proposed: usize,
+    pub in_library: usize,
+    pub queued: usize,
+    pub failed: usize,
+    pub budget_remaining: u32,
+}
+
+/// Wishlist autopilot configuration
+#[derive(Debug, Clone)]
+pub struct WishlistConfig {
+    pub listenbrainz_url: String,
+    pub musicbrainz_user_agent: String,
+    pub navidrome_url: Option<String>,
+    pub navidrome_username: String,
+    pub navidrome_password: String,
+    pub lidarr_url: Option<String>,
+    pub lidarr_api_key: String,
+    pub lidarr_root_folder: String,
+    pub lidarr_quality_profile_id: u32,
+    /// Defaults for users who have not changed their settings
+    pub weekly_budget: u32,
+    pub min_score: f64,
+    pub run_interval: Duration,
+}
+
+impl Default for WishlistConfig {
+    fn default() -> Self {
+        Self {
+            listenbrainz_url: "https://api.listenbrainz.org".to_string(),
+            musicbrainz_user_agent: "StepheyBot-Music/1.0".to_string(),
+            navidrome_url: None,
+            navidrome_username: String::new(),
+            navidrome_password: String::new(),
+            lidarr_url: None,
+            lidarr_api_key: String::new(),
+            lidarr_root_folder: "/music".to_string(),
+            lidarr_quality_profile_id: 1,
+            weekly_budget: 5,
+            min_score: 0.6,
+            run_interval: Duration::from_secs(86400),
+        }
+    }
+}
+
+impl WishlistConfig {
+    /// Load the configuration, overriding defaults from `STEPHEYBOT__WISHLIST__*`
+    /// and the Lidarr settings
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(url) = var("STEPHEYBOT__LISTENBRAINZ__URL") {
+            config.listenbrainz_url = url;
+        }
+        if let Some(budget) =
+            var("STEPHEYBOT__WISHLIST__WEEKLY_BUDGET").and_then(|v| v.parse().ok())
+        {
+            config.weekly_budget = budget;
+        }
+        if let Some(score) = var("STEPHEYBOT__WISHLIST__MIN_SCORE")
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|score| (0.0..=1.0).contains(score))
+        {
+            config.min_score = score;
+        }
+        if let Some(hours) = var("STEPHEYBOT__WISHLIST__INTERVAL_HOURS")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|hours| *hours > 0)
+        {
+            config.run_interval = Duration::from_secs(hours * 3600);
+        }
+
+        config.lidarr_url = var("STEPHEYBOT__LIDARR__URL").or_else(|| var("LIDARR_URL"));
+        config.lidarr_api_key = var("STEPHEYBOT__LIDARR__API_KEY")
+            .or_else(|| var("LIDARR_API_KEY"))
+            .unwrap_or_default();
+        if let Some(root) = var("STEPHEYBOT__LIDARR__ROOT_FOLDER") {
+            config.lidarr_root_folder = root;
+        }
+        if let Some(profile) =
+            var("STEPHEYBOT__LIDARR__QUALITY_PROFILE_ID").and_then(|v| v.parse().ok())
+        {
+            config.lidarr_quality_profile_id = profile;
+        }
+
+        config
+    }
+}
+
+/// Proposes and acquires music users are likely to enjoy
+pub struct WishlistService {
+    config: WishlistConfig,
+    database: Arc<Database>,
+    downloads: Arc<DownloadService>,
+    listenbrainz: ListenBrainzClient,
+    musicbrainz: MusicBrainzClient,
+    navidrome: Option<NavidromeClient>,
+    lidarr: Option<LidarrClient>,
+}
+
+impl WishlistService {
+    /// Create a new wishlist service
+    pub fn new(
+        config: WishlistConfig,
+        database: Arc<Database>,
+        downloads: Arc<DownloadService>,
+    ) -> Result<Self> {
+        let listenbrainz = ListenBrainzClient::new(&config.listenbrainz_url, None)?;
+        let musicbrainz = MusicBrainzClient::new(&config.musicbrainz_user_agent)?;
+
+        // Without Navidrome only Lidarr can tell what is already in the library
+        let navidrome = config.navidrome_url.as_deref().and_then(|url| {
+            NavidromeClient::new(url, &config.navidrome_username, &config.navidrome_password)
+                .map_err(|e| warn!("Wishlist library checks limited: {}", e))
+                .ok()
+        });
+        let lidarr = config
+            .lidarr_url
+            .as_deref()
+            .filter(|_| !config.lidarr_api_key.is_empty())
+            .and_then(|url| {
+                LidarrClient::new(url, &config.lidarr_api_key)
+                    .map_err(|e| warn!("Wishlist artist acquisition disabled: {}", e))
+                    .ok()
+            });
+
+        Ok(Self {
+            config,
+            database,
+            downloads,
+            listenbrainz,
+            musicbrainz,
+            navidrome,
+            lidarr,
+        })
+    }
+
+    /// Run the autopilot periodically for every user who opted in
+    pub fn start(self: &Arc<Self>) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = interval(service.config.run_interval);
+            // The first tick fires immediately; give the other services time to start
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                service.run_all().await;
+            }
+        });
+    }
+
+    /// Run the autopilot for every user who opted in
+    pub async fn run_all(&self) {
+        let users = match self.scheduled_requesters().await {
+            Ok(users) => users,
+            Err(e) => {
+                warn!("Failed to load wishlist users: {}", e);
+                return;
+            }
+        };
+
+        for requester in users {
+            match self.run_for_user(&requester).await {
+                Ok(summary) => info!(
+                    "Wishlist autopilot for {}: {} proposed, {} queued",
+                    requester.user_id, summary.proposed, summary.queued
+                ),
+                Err(e) => warn!("Wishlist autopilot failed for {}: {}", requester.user_id, e),
+            }
+        }
+    }
+
+    /// Who scheduled runs act as: opted-in users that still exist and are
+    /// active. Roles come from the login token rather than the users table,
+    /// so without one nobody is trusted with admin rights.
+    async fn scheduled_requesters(&self) -> Result<Vec<Requester>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT w.user_id, u.username, w.role
+            FROM wishlist_settings w
+            JOIN users u ON CAST(u.id AS TEXT) = w.user_id
+            WHERE w.enabled = 1 AND u.is_active = 1
+            "#,
+        )
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Requester {
+                user_id: row.get("user_id"),
+                username: row.get("username"),
+                role: match row.try_get("role").unwrap_or(UserRole::Guest) {
+                    UserRole::Admin => UserRole::User,
+                    role => role,
+                },
+            })
+            .collect())
+    }
+
+    /// Get a user's settings, falling back to the defaults (disabled)
+    pub async fn settings(&self, requester: &Requester) -> Result<WishlistSettings> {
+        let row = sqlx::query("SELECT * FROM wishlist_settings WHERE user_id = ?")
+            .bind(&requester.user_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+
+        Ok(match row {
+            Some(row) => row_to_settings(&row),
+            None => WishlistSettings {
+                user_id: requester.user_id.clone(),
+                username: requester.username.clone(),
+                role: requester.role,
+                enabled: false,
+                auto_queue: true,
+                weekly_budget: self.config.weekly_budget,
+                min_score: self.config.min_score,
+                last_run_at: None,
+                updated_at: Utc::now(),
+            },
+        })
+    }
+
+    /// Change a user's settings, recording the change in the audit trail
+    pub async fn update_settings(
+        &self,
+        requester: &Requester,
+        update: &SettingsUpdate,
+    ) -> Result<WishlistSettings> {
+        let mut settings = self.settings(requester).await?;
+        settings.username = requester.username.clone();
+        settings.role = requester.role;
+        if let Some(enabled) = update.enabled {
+            settings.enabled = enabled;
+        }
+        if let Some(auto_queue) = update.auto_queue {
+            settings.auto_queue = auto_queue;
+        }
+        if let Some(budget) = update.weekly_budget {
+            // Only admins may raise their budget above the configured default
+            settings.weekly_budget = if requester.is_admin() {
+                budget
+            } else {
+                budget.min(self.config.weekly_budget)
+            };
+        }
+        if let Some(score) = update.min_score {
+            if !(0.0..=1.0).contains(&score) {
+                anyhow::bail!("min_score must be between 0 and 1");
+            }
+            settings.min_score = score;
+        }
+        settings.updated_at = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO wishlist_settings (
+                user_id, username, role, enabled, auto_queue, weekly_budget, min_score, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET
+                username = excluded.username,
+                role = excluded.role,
+                enabled = excluded.enabled,
+                auto_queue = excluded.auto_queue,
+                weekly_budget = excluded.weekly_budget,
+                min_score = excluded.min_score,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&settings.user_id)
+        .bind(&settings.username)
+        .bind(settings.role)
+        .bind(settings.enabled)
+        .bind(settings.auto_queue)
+        .bind(settings.weekly_budget)
+        .bind(settings.min_score)
+        .bind(settings.updated_at.timestamp())
+        .execute(self.database.pool())
+        .await?;
+
+        self.record(
+            &settings.user_id,
+            None,
+            "settings",
+            &requester.username,
+            &format!(
+                "enabled={}, auto_queue={}, weekly_budget={}, min_score={:.2}",
+                settings.enabled, settings.auto_queue, settings.weekly_budget, settings.min_score
+            ),
+        )
+        .await?;
+
+        Ok(settings)
+    }
+
+    /// Gather suggestions for a user, propose the ones not in the library and
+    /// queue the best within the weekly budget, requesting as `requester`
+    pub async fn run_for_user(&self, requester: &Requester) -> Result<RunSummary> {
+        let user_id = requester.user_id.as_str();
+        let row = sqlx::query("SELECT * FROM wishlist_settings WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+        let mut settings = match row {
+            Some(row) => row_to_settings(&row),
+            None => anyhow::bail!("Wishlist autopilot is not enabled for {}", user_id),
+        };
+        if !settings.enabled {
+            anyhow::bail!("Wishlist autopilot is not enabled for {}", user_id);
+        }
+        // A budget raised while the user was an admin does not outlive the role
+        if !requester.is_admin() {
+            settings.weekly_budget = settings.weekly_budget.min(self.config.weekly_budget);
+        }
+
+        let suggestions = self.gather(user_id).await?;
+        let mut summary = RunSummary {
+            user_id: user_id.to_string(),
+            suggested: suggestions.len(),
+            ..Default::default()
+        };
+
+        let known_artists = self.lidarr_artist_ids().await;
+        for suggestion in suggestions {
+            if summary.proposed >= MAX_PROPOSALS_PER_RUN {
+                break;
+            }
+            if self.is_known(user_id, &suggestion).await? {
+                continue;
+            }
+            if self.in_library(&suggestion, &known_artists).await {
+                summary.in_library += 1;
+                continue;
+            }
+            if self.propose(user_id, &suggestion).await?.is_some() {
+                summary.proposed += 1;
+            }
+        }
+
+        summary.budget_remaining = self.budget_remaining(&settings).await?;
+        if settings.auto_queue {
+            let candidates = self
+                .candidates(user_id, Some(CandidateStatus::Proposed), 500)
+                .await?;
+            for candidate in candidates
+                .into_iter()
+                .filter(|c| c.score >= settings.min_score)
+            {
+                if summary.budget_remaining == 0 {
+                    break;
+                }
+                let detail = format!(
+                    "Automatically queued (score {:.2}): {}",
+                    candidate.score, candidate.reason
+                );
+                match self
+                    .acquire(&candidate, requester, AUTOPILOT_ACTOR, &detail)
+                    .await
+                {
+                    Ok(_) => {
+                        summary.queued += 1;
+                        summary.budget_remaining -= 1;
+                    }
+                    Err(_) => summary.failed += 1,
+                }
+            }
+        }
+
+        sqlx::query("UPDATE wishlist_settings SET last_run_at = ? WHERE user_id = ?")
+            .bind(Utc::now().timestamp())
+            .bind(user_id)
+            .execute(self.database.pool())
+            .await?;
+
+        Ok(summary)
+    }
+
+    /// List a user's candidates, best first
+    pub async fn candidates(
+        &self,
+        user_id: &str,
+        status: Option<CandidateStatus>,
+        limit: u32,
+    ) -> Result<Vec<WishlistCandidate>> {
+        let rows = match status {
+            Some(status) => {
+                sqlx::query(
+                    "SELECT * FROM wishlist_candidates WHERE user_id = ? AND status = ? ORDER BY score DESC, id ASC LIMIT ?",
+                )
+                .bind(user_id)
+                .bind(status.as_str())
+                .bind(limit.min(500))
+                .fetch_all(self.database.pool())
+                .await?
+            }
+            None => {
+                sqlx::query(
+                    "SELECT * FROM wishlist_candidates WHERE user_id = ? ORDER BY created_at DESC, id DESC LIMIT ?",
+                )
+                .bind(user_id)
+                .bind(limit.min(500))
+                .fetch_all(self.database.pool())
+                .await?
+            }
+        };
+
+        Ok(rows.iter().map(row_to_candidate).collect())
+    }
+
+    /// Queue a proposed candidate the user picked on the review screen;
+    /// manual picks do not use up the autopilot budget
+    pub async fn approve(
+        &self,
+        requester: &Requester,
+        candidate_id: i64,
+    ) -> Result<WishlistCandidate> {
+        let candidate = self.proposed(&requester.user_id, candidate_id).await?;
+        let detail = format!("Approved on review: {}", candidate.reason);
+        self.acquire(&candidate, requester, &requester.username, &detail)
+            .await?;
+        self.candidate(candidate_id).await
+    }
+
+    /// Reject a proposed candidate so it is never proposed again
+    pub async fn reject(
+        &self,
+        requester: &Requester,
+        candidate_id: i64,
+        reason: Option<&str>,
+    ) -> Result<WishlistCandidate> {
+        self.proposed(&requester.user_id, candidate_id).await?;
+        self.set_status(candidate_id, CandidateStatus::Rejected, None)
+            .await?;
+        self.record(
+            &requester.user_id,
+            Some(candidate_id),
+            "rejected",
+            &requester.username,
+            reason.unwrap_or("Rejected on review"),
+        )
+        .await?;
+        self.candidate(candidate_id).await
+    }
+
+    /// A user's audit trail, newest first
+    pub async fn audit(&self, user_id: &str, limit: u32) -> Result<Vec<AuditEntry>> {
+        let rows = sqlx::query(
+            "SELECT * FROM wishlist_audit WHERE user_id = ? ORDER BY created_at DESC, id DESC LIMIT ?",
+        )
+        .bind(user_id)
+        .bind(limit.min(500))
+        .fetch_all(self.database.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| AuditEntry {
+                id: row.get("id"),
+                user_id: row.get("user_id"),
+                candidate_id: row.get("candidate_id"),
+                action: row.get("action"),
+                actor: row.get("actor"),
+                detail: row.get("detail"),
+                created_at: DateTime::from_timestamp(row.get("created_at"), 0)
+                    .unwrap_or_else(Utc::now),
+            })
+            .collect())
+    }
+
+    /// Automatic acquisitions left in the user's rolling week
+    pub async fn budget_remaining(&self, settings: &WishlistSettings) -> Result<u32> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) AS used FROM wishlist_audit
+            WHERE user_id = ? AND action = 'queued' AND actor = ? AND created_at >= ?
+            "#,
+        )
+        .bind(&settings.user_id)
+        .bind(AUTOPILOT_ACTOR)
+        .bind(Utc::now().timestamp() - WEEK_SECS)
+        .fetch_one(self.database.pool())
+        .await?;
+
+        let used = row.get::<i64, _>("used") as u32;
+        Ok(settings.weekly_budget.saturating_sub(used))
+    }
+
+    /// Collect and rank suggestions from every source
+    async fn gather(&self, user_id: &str) -> Result<Vec<Suggestion>> {
+        let mut suggestions = Vec::new();
+        let mut seeds: Vec<(String, String, f64)> = Vec::new();
+
+        if let Some(lb_user) = self.listenbrainz_user(user_id).await? {
+            match self.listenbrainz.get_recommendations(&lb_user).await {
+                Ok(recommendations) => {
+                    suggestions.extend(recommendation_suggestions(&recommendations))
+                }
+                Err(e) => warn!("Failed to get recommendations for {}: {}", lb_user, e),
+            }
+
+            // The user's most played artists seed the similar-artist search
+            match self
+                .listenbrainz
+                .get_user_stats(&lb_user, Some("month"))
+                .await
+            {
+                Ok(stats) => {
+                    let top = stats.artists.first().map_or(1, |a| a.listen_count.max(1));
+                    seeds.extend(stats.artists.iter().filter_map(|artist| {
+                        let mbid = artist.artist_mbid.clone()?;
+                        let weight = artist.listen_count as f64 / top as f64;
+                        Some((mbid, artist.artist_name.clone(), weight))
+                    }));
+                }
+                Err(e) => warn!("Failed to get listening stats for {}: {}", lb_user, e),
+            }
+        }
+
+        // Artists the user explicitly asked for are the strongest seeds
+        let requested = sqlx::query(
+            "SELECT foreign_artist_id, artist_name FROM lidarr_artist_requests WHERE user_id = ? ORDER BY requested_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(self.database.pool())
+        .await?;
+        seeds.splice(
+            0..0,
+            requested
+                .iter()
+                .map(|row| (row.get("foreign_artist_id"), row.get("artist_name"), 1.0)),
+        );
+
+        let mut seen = HashSet::new();
+        seeds.retain(|(mbid, _, _)| seen.insert(mbid.clone()));
+        seeds.truncate(MAX_SEED_ARTISTS);
+
+        for (mbid, name, weight) in &seeds {
+            match self.musicbrainz.get_artist_relations(mbid).await {
+                Ok(relations) => suggestions.extend(
+                    relation_suggestions(name, *weight, &relations)
+                        .into_iter()
+                        .filter(|s| !s.artist_mbid.as_ref().is_some_and(|id| seen.contains(id))),
+                ),
+                Err(e) => warn!("Failed to get relations for {}: {}", name, e),
+            }
+        }
+
+        Ok(rank(suggestions))
+    }
+
+    /// The ListenBrainz account a user linked, if any
+    async fn listenbrainz_user(&self, user_id: &str) -> Result<Option<String>> {
+        let row = sqlx::query(
+            r#"
+            SELECT service_user_id FROM user_integrations
+            WHERE user_id = ? AND service_name = 'listenbrainz' AND enabled = 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(self.database.pool())
+        .await?;
+
+        Ok(row.and_then(|row| row.get::<Option<String>, _>("service_user_id")))
+    }
+
+    /// MusicBrainz IDs of the artists Lidarr already manages
+    async fn lidarr_artist_ids(&self) -> HashSet<String> {
+        let Some(lidarr) = &self.lidarr else {
+            return HashSet::new();
+        };
+        match lidarr.get_artists().await {
+            Ok(artists) => artists
+                .into_iter()
+                .filter_map(|artist| artist.foreign_artist_id)
+                .collect(),
+            Err(e) => {
+                warn!("Failed to load Lidarr artists: {}", e);
+                HashSet::new()
+            }
+        }
+    }
+
+    /// Whether the suggestion was proposed to the user before, whatever
+    /// became of it
+    async fn is_known(&self, user_id: &str, suggestion: &Suggestion) -> Result<bool> {
+        let row =
+            sqlx::query("SELECT 1 FROM wishlist_candidates WHERE user_id = ? AND dedupe_key = ?")
+                .bind(user_id)
+                .bind(suggestion.dedupe_key())
+                .fetch_optional(self.database.pool())
+                .await?;
+        Ok(row.is_some())
+    }
+
+    /// Whether the suggested music is already in the library
+    async fn in_library(&self, suggestion: &Suggestion, lidarr_artists: &HashSet<String>) -> bool {
+        if suggestion.kind == CandidateKind::Artist
+            && suggestion
+                .artist_mbid
+                .as_ref()
+                .is_some_and(|mbid| lidarr_artists.contains(mbid))
+        {
+            return true;
+        }
+
+        let Some(navidrome) = &self.navidrome else {
+            return false;
+        };
+
+        match suggestion.kind {
+            CandidateKind::Artist => navidrome
+                .search(&suggestion.artist_name, Some(5), Some(0), Some(0))
+                .await
+                .map(|result| {
+                    result
+                        .artist
+                        .unwrap_or_default()
+                        .iter()
+                        .any(|artist| artist.name.eq_ignore_ascii_case(&suggestion.artist_name))
+                })
+                .unwrap_or(false),
+            CandidateKind::Recording => {
+                let title = suggestion.title.as_deref().unwrap_or_default();
+                let query = format!("{} {}", suggestion.artist_name, title);
+                navidrome
+                    .search(&query, Some(0), Some(0), Some(10))
+                    .await
+                    .map(|result| {
+                        result.song.unwrap_or_default().iter().any(|song| {
+                            song.title.eq_ignore_ascii_case(title)
+                                && song.artist.as_deref().is_some_and(|artist| {
+                                    artist.eq_ignore_ascii_case(&suggestion.artist_name)
+                                })
+                        })
+                    })
+                    .unwrap_or(false)
+            }
+        }
+    }
+
+    /// Store a suggestion as a proposed candidate, unless it already exists
+    async fn propose(&self, user_id: &str, suggestion: &Suggestion) -> Result<Option<i64>> {
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO wishlist_candidates (
+                user_id, dedupe_key, kind, source, artist_name, artist_mbid, title,
+                recording_mbid, score, reason, status, created_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(user_id)
+        .bind(suggestion.dedupe_key())
+        .bind(suggestion.kind.as_str())
+        .bind(suggestion.source.as_str())
+        .bind(&suggestion.artist_name)
+        .bind(&suggestion.artist_mbid)
+        .bind(&suggestion.title)
+        .bind(&suggestion.recording_mbid)
+        .bind(suggestion.score)
+        .bind(&suggestion.reason)
+        .bind(CandidateStatus::Proposed.as_str())
+        .bind(Utc::now().timestamp())
+        .execute(self.database.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let id = result.last_insert_rowid();
+        self.record(
+            user_id,
+            Some(id),
+            "proposed",
+            AUTOPILOT_ACTOR,
+            &format!("Score {:.2}: {}", suggestion.score, suggestion.reason),
+        )
+        .await?;
+        Ok(Some(id))
+    }
+
+    /// Request a candidate through the download service or Lidarr and record
+    /// the outcome
+    async fn acquire(
+        &self,
+        candidate: &WishlistCandidate,
+        requester: &Requester,
+        actor: &str,
+        detail: &str,
+    ) -> Result<String> {
+        match self.request(candidate, requester).await {
+            Ok((target, note)) => {
+                self.set_status(candidate.id, CandidateStatus::Queued, Some(&target))
+                    .await?;
+                self.record(
+                    &candidate.user_id,
+                    Some(candidate.id),
+                    "queued",
+                    actor,
+                    &format!("{} ({})", detail, note),
+                )
+                .await?;
+                Ok(target)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to acquire wishlist candidate {}: {}",
+                    candidate.id, e
+                );
+                self.set_status(candidate.id, CandidateStatus::Failed, None)
+                    .await?;
+                self.record(
+                    &candidate.user_id,
+                    Some(candidate.id),
+                    "failed",
+                    actor,
+                    &e.to_string(),
+                )
+                .await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Hand a candidate to whatever can acquire it, returning the target and
+    /// a note for the audit trail
+    async fn request(
+        &self,
+        candidate: &WishlistCandidate,
+        requester: &Requester,
+    ) -> Result<(String, String)> {
+        // Single recordings go through the download service when it can
+        // find releases itself; everything else goes to Lidarr
+        if let (CandidateKind::Recording, Some(title)) = (candidate.kind, &candidate.title) {
+            if self.downloads.can_search_indexers() {
+                let mut request = DownloadRequest::new(
+                    requester.user_id.clone(),
+                    candidate.artist_name.clone(),
+                    title.clone(),
+                );
+                // Wishlist downloads are background work and make way for streaming
+                request.priority = DownloadPriority::Low;
+                let request = self.downloads.submit_download(request, requester).await?;
+                let note = format!("download request {} {}", request.id, request.status);
+                return Ok((request.id, note));
+            }
+        }
+
+        let Some(lidarr) = &self.lidarr else {
+            anyhow::bail!("Neither indexers nor Lidarr are configured");
+        };
+
+        let existing = match &candidate.artist_mbid {
+            Some(mbid) => lidarr.find_artist_by_mbid(mbid).await?,
+            None => None,
+        };
+        let artist = match existing {
+            Some(artist) => artist,
+            None => {
+                let found = lidarr
+                    .search_artist(&candidate.artist_name)
+                    .await?
+                    .into_iter()
+                    .find(|artist| {
+                        candidate.artist_mbid.is_none()
+                            || artist.foreign_artist_id == candidate.artist_mbid
+                    })
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Lidarr does not know {}", candidate.artist_name)
+                    })?;
+                let artist = lidarr.create_artist_for_monitoring(
+                    &found.artist_name,
+                    found.foreign_artist_id.as_deref(),
+                    self.config.lidarr_quality_profile_id,
+                    &self.config.lidarr_root_folder,
+                    false,
+                );
+                lidarr.add_artist(&artist).await?
+            }
+        };
+
+        let artist_id = artist
+            .id
+            .ok_or_else(|| anyhow::anyhow!("Lidarr returned no ID for {}", artist.artist_name))?;
+        lidarr.search_artist_releases(artist_id).await?;
+
+        // Lidarr tells the user through its webhook when albums land
+        if let Some(mbid) = &artist.foreign_artist_id {
+            sqlx::query(
+                r#"
+                INSERT INTO lidarr_artist_requests (foreign_artist_id, artist_name, user_id, requested_at)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(foreign_artist_id, user_id) DO NOTHING
+                "#,
+            )
+            .bind(mbid)
+            .bind(&artist.artist_name)
+            .bind(&requester.user_id)
+            .bind(Utc::now().timestamp())
+            .execute(self.database.pool())
+            .await?;
+        }
+
+        Ok((
+            format!("lidarr:artist:{}", artist_id),
+            format!("monitoring {} in Lidarr", artist.artist_name),
+        ))
+    }
+
+    /// Load a candidate that belongs to the user and is still up for review
+    async fn proposed(&self, user_id: &str, candidate_id: i64) -> Result<WishlistCandidate> {
+        let candidate = self.candidate(candidate_id).await?;
+        if candidate.user_id != user_id {
+            anyhow::bail!("Wishlist candidate {} not found", candidate_id);
+        }
+        if candidate.status != CandidateStatus::Proposed {
+            anyhow::bail!(
+                "Wishlist candidate {} is {}, not proposed",
+                candidate_id,
+                candidate.status.as_str()
+            );
+        }
+        Ok(candidate)
+    }
+
+    async fn candidate(&self, candidate_id: i64) -> Result<WishlistCandidate> {
+        let row = sqlx::query("SELECT * FROM wishlist_candidates WHERE id = ?")
+            .bind(candidate_id)
+            .fetch_optional(self.database.pool())
+            .await?;
+
+        row.as_ref()
+            .map(row_to_candidate)
+            .ok_or_else(|| anyhow::anyhow!("Wishlist candidate {} not found", candidate_id))
+    }
+
+    async fn set_status(
+        &self,
+        candidate_id: i64,
+        status: CandidateStatus,
+        target: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE wishlist_candidates SET status = ?, target = COALESCE(?, target), decided_at = ? WHERE id = ?",
+        )
+        .bind(status.as_str())
+        .bind(target)
+        .bind(Utc::now().timestamp())
+        .bind(candidate_id)
+        .execute(self.database.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Append to the audit trail
+    async fn record(
+        &self,
+        user_id: &str,
+        candidate_id: Option<i64>,
+        action: &str,
+        actor: &str,
+        detail: &str,
+    ) -> Result<()> {
+        debug!(
+            "Wishlist {} for {} by {}: {}",
+            action, user_id, actor, detail
+        );
+        sqlx::query(
+            r#"
+            INSERT INTO wishlist_audit (user_id, candidate_id, action, actor, detail, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(user_id)
+        .bind(candidate_id)
+        .bind(action)
+        .bind(actor)
+        .bind(detail)
+        .bind(Utc::now().timestamp())
+        .execute(self.database.pool())
+        .await?;
+        Ok(())
+    }
+}
+
+fn row_to_settings(row: &SqliteRow) -> WishlistSettings {
+    WishlistSettings {
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        role: row.try_get("role").unwrap_or(UserRole::Guest),
+        enabled: row.get("enabled"),
+        auto_queue: row.get("auto_queue"),
+        weekly_budget: row.get::<i64, _>("weekly_budget") as u32,
+        min_score: row.get("min_score"),
+        last_run_at: row
+            .get::<Option<i64>, _>("last_run_at")
+            .and_then(|t| DateTime::from_timestamp(t, 0)),
+        updated_at: DateTime::from_timestamp(row.get("updated_at"), 0).unwrap_or_else(Utc::now),
+    }
+}
+
+fn row_to_candidate(row: &SqliteRow) -> WishlistCandidate {
+    WishlistCandidate {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        kind: if row.get::<String, _>("kind") == "artist" {
+            CandidateKind::Artist
+        } else {
+            CandidateKind::Recording
+        },
+        source: if row.get::<String, _>("source") == "musicbrainz" {
+            CandidateSource::MusicBrainz
+        } else {
+            CandidateSource::ListenBrainz
+        },
+        artist_name: row.get("artist_name"),
+        artist_mbid: row.get("artist_mbid"),
+        title: row.get("title"),
+        recording_mbid: row.get("recording_mbid"),
+        score: row.get("score"),
+        reason: row.get("reason"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .unwrap_or(CandidateStatus::Proposed),
+        target: row.get("target"),
+        created_at: DateTime::from_timestamp(row.get("created_at"), 0).unwrap_or_else(Utc::now),
+        decided_at: row
+            .get::<Option<i64>, _>("decided_at")
+            .and_then(|t| DateTime::from_timestamp(t, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::listenbrainz::{AdditionalInfo, TrackMetadata};
+    use crate::clients::musicbrainz::Artist;
+    use crate::services::download_service::DownloadConfig;
+    use tempfile::NamedTempFile;
+
+    fn recommendation(mbid: &str, artist: &str, title: &str, score: f64) -> Recommendation {
+        Recommendation {
+            recording_mbid: mbid.to_string(),
+            score,
+            track_metadata: TrackMetadata {
+                artist_name: artist.to_string(),
+                track_name: title.to_string(),
+                release_name: None,
+                additional_info: Some(AdditionalInfo {
+                    duration_ms: None,
+                    recording_mbid: Some(mbid.to_string()),
+                    artist_mbid: None,
+                    release_mbid: None,
+                }),
+            },
+        }
+    }
+
+    fn related(relation_type: &str, mbid: &str, name: &str) -> Relation {
+        let artist: Artist = serde_json::from_value(serde_json::json!({
+            "id": mbid,
+            "name": name
+        }))
+        .unwrap();
+        Relation {
+            relation_type: relation_type.to_string(),
+            direction: None,
+            target: None,
+            artist: Some(artist),
+            release: None,
+            recording: None,
+            url: None,
+            begin: None,
+            end: None,
+            ended: None,
+            attributes: None,
+        }
+    }
+
+    #[test]
+    fn test_rank_suggestions() {
+        let mut suggestions = recommendation_suggestions(&[
+            recommendation("rec-1", "Artist A", "One", 4.0),
+            recommendation("rec-2", "Artist B", "Two", 2.0),
+        ]);
+        assert_eq!(suggestions[0].score, 1.0);
+        assert_eq!(suggestions[1].score, 0.5);
+
+        let relations = [
+            related("member of band", "art-c", "Artist C"),
+            related("married", "art-d", "Artist D"),
+        ];
+        suggestions.extend(relation_suggestions("Seed", 0.5, &relations));
+        suggestions.extend(relation_suggestions("Other Seed", 1.0, &relations[..1]));
+
+        let ranked = rank(suggestions);
+        assert_eq!(ranked.len(), 4);
+        assert_eq!(ranked[0].artist_name, "Artist A");
+        // Two seeds agreeing on Artist C boost it above its best single score
+        let c = ranked.iter().find(|s| s.artist_name == "Artist C").unwrap();
+        assert!((c.score - 1.0).abs() < 1e-9);
+        assert!(c.reason.contains("Seed") && c.reason.contains("Other Seed"));
+        assert_eq!(ranked.last().unwrap().artist_name, "Artist D");
+        assert_eq!(ranked.last().unwrap().dedupe_key(), "artist:art-d");
+    }
+
+    #[tokio::test]
+    async fn test_review_and_budget() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let downloads =
+            Arc::new(DownloadService::new(DownloadConfig::default(), database.clone()).unwrap());
+        let service = WishlistService::new(WishlistConfig::default(), database, downloads).unwrap();
+
+        let alice = Requester {
+            user_id: "7".to_string(),
+            username: "alice".to_string(),
+            role: UserRole::User,
+        };
+        assert!(!service.settings(&alice).await.unwrap().enabled);
+        assert!(service.run_for_user(&alice).await.is_err());
+
+        let capped = service
+            .update_settings(
+                &alice,
+                &SettingsUpdate {
+                    weekly_budget: Some(u32::MAX),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(capped.weekly_budget, 5);
+
+        let settings = service
+            .update_settings(
+                &alice,
+                &SettingsUpdate {
+                    enabled: Some(true),
+                    weekly_budget: Some(2),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(settings.enabled);
+        assert_eq!(service.budget_remaining(&settings).await.unwrap(), 2);
+
+        let suggestions = recommendation_suggestions(&[
+            recommendation("rec-1", "Artist A", "One", 1.0),
+            recommendation("rec-2", "Artist B", "Two", 0.8),
+        ]);
+        let first = service
+            .propose("7", &suggestions[0])
+            .await
+            .unwrap()
+            .unwrap();
+        let second = service
+            .propose("7", &suggestions[1])
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(service
+            .propose("7", &suggestions[0])
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service.is_known("7", &suggestions[0]).await.unwrap());
+
+        let rejected = service
+            .reject(&alice, second, Some("Not my style"))
+            .await
+            .unwrap();
+        assert_eq!(rejected.status, CandidateStatus::Rejected);
+        assert!(service.reject(&alice, second, None).await.is_err());
+
+        // Nothing can acquire it without indexers or Lidarr
+        assert!(service.approve(&alice, first).await.is_err());
+        let failed = service.candidate(first).await.unwrap();
+        assert_eq!(failed.status, CandidateStatus::Failed);
+
+        let audit = service.audit("7", 10).await.unwrap();
+        let actions: Vec<&str> = audit.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(
+            actions,
+            ["failed", "rejected", "proposed", "proposed", "settings", "settings"]
+        );
+        assert_eq!(audit[1].detail, "Not my style");
+
+        service
+            .record("7", Some(first), "queued", AUTOPILOT_ACTOR, "test")
+            .await
+            .unwrap();
+        assert_eq!(service.budget_remaining(&settings).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_requesters() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db_url = format!("sqlite:{}", temp_file.path().display());
+        let database = Arc::new(Database::new(&db_url).await.unwrap());
+        database.migrate().await.unwrap();
+        let downloads =
+            Arc::new(DownloadService::new(DownloadConfig::default(), database.clone()).unwrap());
+        let service =
+            WishlistService::new(WishlistConfig::default(), database.clone(), downloads).unwrap();
+
+        for (id, name, active) in [(101, "former-admin", true), (102, "inactive", false)] {
+            sqlx::query(
+                "INSERT INTO users (id, keycloak_id, username, email, is_active) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(id)
+            .bind(format!("kc-{}", id))
+            .bind(name)
+            .bind(format!("{}@example.com", name))
+            .bind(active)
+            .execute(database.pool())
+            .await
+            .unwrap();
+        }
+        // User 103 opted in but has since been deleted
+        for (id, role) in [
+            ("101", UserRole::Admin),
+            ("102", UserRole::User),
+            ("103", UserRole::User),
+        ] {
+            let requester = Requester {
+                user_id: id.to_string(),
+                username: format!("user-{}", id),
+                role,
+            };
+            service
+                .update_settings(
+                    &requester,
+                    &SettingsUpdate {
+                        enabled: Some(true),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        let users = service.scheduled_requesters().await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user_id, "101");
+        assert_eq!(users[0].username, "former-admin");
+        assert_eq!(users[0].role, UserRole::User);
+    }
+}
@@ -265,6 +265,26 @@ pub fn hash_string(input: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+/// SHA-256 of a file, read in chunks rather than all at once
+pub async fn sha256_file(path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {:?} for hashing", path))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
 /// Generate a random alphanumeric string
 pub fn generate_random_string(length: usize) -> String {
     use rand::Rng;